@@ -0,0 +1,145 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::AuthError;
+
+/// Party acting on behalf of the subject (RFC 8693, section 4.1).
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Actor {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
+}
+
+/// Confirmation claim binding the token to the key of a DPoP proof (RFC 9449, section 6).
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Confirmation {
+    pub jkt: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    Access,
+    Refresh,
+}
+
+/// Claims of a flatline access token. Roles are kept as strings, so services do not break
+/// when flatline introduces new roles.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Claims {
+    pub iss: String,
+    pub sub: Uuid,
+    pub aud: String,
+    pub exp: i64,
+    pub nbf: i64,
+    pub iat: i64,
+    pub jti: Uuid,
+    pub typ: TokenType,
+    pub username: String,
+    #[serde(deserialize_with = "roles::deserialize")]
+    pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ver: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "scope")]
+    pub scope: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<Uuid>,
+}
+
+impl Claims {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_ref()
+            .is_some_and(|scopes| scopes.iter().any(|s| s == scope))
+    }
+}
+
+/// Claims verified by the [`AuthLayer`](crate::AuthLayer) in front of the handler.
+impl<S> FromRequestParts<S> for Claims
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Claims>()
+            .cloned()
+            .ok_or(AuthError::Unauthorized)
+    }
+}
+
+/// Tokens issued before roles were typed carry them as a comma separated string.
+mod roles {
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RolesClaim {
+        List(Vec<String>),
+        Legacy(String),
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match RolesClaim::deserialize(deserializer)? {
+            RolesClaim::List(roles) => roles,
+            RolesClaim::Legacy(roles) => roles
+                .split(',')
+                .map(|role| role.trim().to_lowercase())
+                .filter(|role| !role.is_empty())
+                .collect(),
+        })
+    }
+}
+
+/// The scope claim is a space delimited string on the wire (RFC 8693, section 4.2).
+mod scope {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ScopeClaim {
+        List(Vec<String>),
+        Delimited(String),
+    }
+
+    pub fn serialize<S>(scope: &Option<Vec<String>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match scope {
+            Some(scope) => serializer.serialize_str(&scope.join(" ")),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(
+            Option::<ScopeClaim>::deserialize(deserializer)?.map(|scope| match scope {
+                ScopeClaim::List(scopes) => scopes,
+                ScopeClaim::Delimited(scopes) => {
+                    scopes.split_whitespace().map(str::to_owned).collect()
+                }
+            }),
+        )
+    }
+}
@@ -0,0 +1,62 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("unauthorized")]
+    Unauthorized,
+    #[error("forbidden")]
+    Forbidden,
+    #[error("token is invalid")]
+    TokenInvalid,
+    #[error("token has expired")]
+    TokenExpired,
+    #[error("token has been revoked")]
+    TokenRevoked,
+    /// The keys or the revocation state of the token could not be fetched.
+    #[error("token verification failed: {0}")]
+    Unavailable(String),
+}
+
+impl AuthError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AuthError::Forbidden => StatusCode::FORBIDDEN,
+            AuthError::TokenInvalid => StatusCode::UNAUTHORIZED,
+            AuthError::TokenExpired => StatusCode::UNAUTHORIZED,
+            AuthError::TokenRevoked => StatusCode::UNAUTHORIZED,
+            AuthError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+/// Responds with the same envelope as the flatline API.
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let message = match self {
+            AuthError::Unavailable(_) => {
+                tracing::error!("{}", self);
+                "something went wrong".to_owned()
+            }
+            _ => self.to_string(),
+        };
+
+        (
+            status,
+            Json(json!({
+                "success": false,
+                "code": status.as_u16(),
+                "api_version": "v1",
+                "message": message,
+                "payload": null,
+            })),
+        )
+            .into_response()
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::Body,
+    http::Request,
+    response::{IntoResponse, Response},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{error::AuthError, verifier::Verifier};
+
+/// Verifies the bearer token of every request and makes its [`Claims`](crate::Claims)
+/// available to the handlers, responding with 401 if the token is missing or invalid and
+/// with 403 if the subject lacks a required role.
+#[derive(Clone)]
+pub struct AuthLayer {
+    verifier: Verifier,
+    roles: Arc<[String]>,
+}
+
+impl AuthLayer {
+    pub fn new(verifier: Verifier) -> Self {
+        Self {
+            verifier,
+            roles: Arc::new([]),
+        }
+    }
+
+    /// Requires the subject to have the role, on top of the roles required already.
+    pub fn require_role(mut self, role: &str) -> Self {
+        let mut roles = self.roles.to_vec();
+        roles.push(role.to_owned());
+        self.roles = roles.into();
+        self
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            verifier: self.verifier.clone(),
+            roles: Arc::clone(&self.roles),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    verifier: Verifier,
+    roles: Arc<[String]>,
+}
+
+impl<S> Service<Request<Body>> for AuthService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        // The clone might not be ready, so the ready service is taken instead.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let verifier = self.verifier.clone();
+        let roles = Arc::clone(&self.roles);
+
+        Box::pin(async move {
+            let claims = match verifier.verify_request(req.headers()).await {
+                Ok(claims) => claims,
+                Err(e) => return Ok(e.into_response()),
+            };
+
+            if !roles.iter().all(|role| claims.has_role(role)) {
+                return Ok(AuthError::Forbidden.into_response());
+            }
+
+            req.extensions_mut().insert(claims);
+            inner.call(req).await
+        })
+    }
+}
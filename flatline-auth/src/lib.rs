@@ -0,0 +1,34 @@
+//! Verification of flatline access tokens for downstream axum services, with the same
+//! semantics as the `auth_guard` of flatline. Tokens are verified with the shared access
+//! token secret, or with [`Verifier::jwks`] if flatline signs them with its OIDC key.
+//!
+//! ```no_run
+//! use axum::{routing::get, Router};
+//! use flatline_auth::{AuthLayer, Claims, Revocation, Verifier};
+//!
+//! async fn hello(claims: Claims) -> String {
+//!     format!("Hello, {}", claims.username)
+//! }
+//!
+//! let verifier = Verifier::hmac("http://127.0.0.1:8080", "flatline", "jwt_access_secret")
+//!     .revocation(Revocation::introspection(
+//!         "http://127.0.0.1:8080/oauth/introspect",
+//!         "client_id",
+//!         "client_secret",
+//!     ))
+//!     .build();
+//!
+//! let app: Router = Router::new()
+//!     .route("/hello", get(hello))
+//!     .layer(AuthLayer::new(verifier).require_role("admin"));
+//! ```
+
+mod claims;
+mod error;
+mod layer;
+mod verifier;
+
+pub use claims::{Actor, Claims, Confirmation, TokenType};
+pub use error::AuthError;
+pub use layer::{AuthLayer, AuthService};
+pub use verifier::{Revocation, Verifier, VerifierBuilder};
@@ -0,0 +1,400 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::http::{header, HeaderMap};
+use jsonwebtoken::{
+    jwk::{AlgorithmParameters, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    claims::{Claims, TokenType},
+    error::AuthError,
+};
+
+struct CachedJwks {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+enum KeySource {
+    Hmac(DecodingKey),
+    Jwks {
+        url: String,
+        ttl: Duration,
+        min_refresh: Duration,
+        cache: RwLock<Option<CachedJwks>>,
+    },
+}
+
+enum RevocationKind {
+    #[cfg(feature = "redis")]
+    Redis {
+        client: redis::Client,
+        conn: tokio::sync::Mutex<Option<redis::aio::MultiplexedConnection>>,
+    },
+    Introspection {
+        url: String,
+        client_id: String,
+        client_secret: String,
+    },
+}
+
+/// Source of truth for tokens revoked before they expire. Without it, revoked tokens are
+/// accepted until they expire.
+pub struct Revocation(RevocationKind);
+
+impl Revocation {
+    /// Checks the token blacklist and the cached token versions in the Redis instance of
+    /// flatline. Token versions missing from the cache are not checked.
+    #[cfg(feature = "redis")]
+    pub fn redis(uri: &str) -> redis::RedisResult<Self> {
+        Ok(Self(RevocationKind::Redis {
+            client: redis::Client::open(uri)?,
+            conn: tokio::sync::Mutex::new(None),
+        }))
+    }
+
+    /// Asks the introspection endpoint of flatline (`/oauth/introspect`) whether the token is
+    /// active, authenticating as the given confidential client.
+    pub fn introspection(url: &str, client_id: &str, client_secret: &str) -> Self {
+        Self(RevocationKind::Introspection {
+            url: url.to_owned(),
+            client_id: client_id.to_owned(),
+            client_secret: client_secret.to_owned(),
+        })
+    }
+}
+
+pub struct VerifierBuilder {
+    issuer: String,
+    audience: String,
+    leeway: u64,
+    keys: KeySource,
+    revocation: Option<Revocation>,
+    timeout: Duration,
+}
+
+impl VerifierBuilder {
+    /// Leeway in seconds for the expiration and not-before claims, 30 by default.
+    pub fn leeway(mut self, leeway: u64) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// How long fetched keys are used before they are fetched again, 5 minutes by default.
+    pub fn jwks_cache_ttl(mut self, ttl: Duration) -> Self {
+        if let KeySource::Jwks { ttl: ref mut t, .. } = self.keys {
+            *t = ttl;
+        }
+        self
+    }
+
+    /// How often the keys are fetched again at most when a token is signed with an unknown
+    /// key, 30 seconds by default.
+    pub fn jwks_min_refresh(mut self, interval: Duration) -> Self {
+        if let KeySource::Jwks {
+            ref mut min_refresh,
+            ..
+        } = self.keys
+        {
+            *min_refresh = interval;
+        }
+        self
+    }
+
+    pub fn revocation(mut self, revocation: Revocation) -> Self {
+        self.revocation = Some(revocation);
+        self
+    }
+
+    /// Timeout of requests for keys and introspection, 10 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn build(self) -> Verifier {
+        let http = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .unwrap_or_default();
+
+        Verifier {
+            inner: Arc::new(Inner {
+                issuer: self.issuer,
+                audience: self.audience,
+                leeway: self.leeway,
+                keys: self.keys,
+                revocation: self.revocation.map(|r| r.0),
+                http,
+            }),
+        }
+    }
+}
+
+struct Inner {
+    issuer: String,
+    audience: String,
+    leeway: u64,
+    keys: KeySource,
+    revocation: Option<RevocationKind>,
+    http: reqwest::Client,
+}
+
+/// Verifies flatline access tokens the same way flatline does: the signature, the issuer,
+/// the audience, the expiration and the token type, and optionally the revocation state.
+/// DPoP bound tokens are rejected, as their proofs are not verified.
+#[derive(Clone)]
+pub struct Verifier {
+    inner: Arc<Inner>,
+}
+
+impl Verifier {
+    fn builder(issuer: &str, audience: &str, keys: KeySource) -> VerifierBuilder {
+        VerifierBuilder {
+            issuer: issuer.trim_end_matches('/').to_owned(),
+            audience: audience.to_owned(),
+            leeway: 30,
+            keys,
+            revocation: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Verifies tokens signed with the shared access token secret (`JWT_ACCESS_SECRET`).
+    pub fn hmac(issuer: &str, audience: &str, secret: &str) -> VerifierBuilder {
+        Self::builder(
+            issuer,
+            audience,
+            KeySource::Hmac(DecodingKey::from_secret(secret.as_bytes())),
+        )
+    }
+
+    /// Verifies tokens signed with the asymmetric keys published at the JWKS URL, i.e.
+    /// `{issuer}/.well-known/jwks.json` when flatline signs access tokens with its OIDC
+    /// signing key (`JWT_ACCESS_ES256`). No secret is shared with the service then.
+    pub fn jwks(issuer: &str, audience: &str, url: &str) -> VerifierBuilder {
+        Self::builder(
+            issuer,
+            audience,
+            KeySource::Jwks {
+                url: url.to_owned(),
+                ttl: Duration::from_secs(300),
+                min_refresh: Duration::from_secs(30),
+                cache: RwLock::new(None),
+            },
+        )
+    }
+
+    /// Verifies the bearer token in the authorization header.
+    pub async fn verify_request(&self, headers: &HeaderMap) -> Result<Claims, AuthError> {
+        let (scheme, token) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split_once(' '))
+            .ok_or(AuthError::Unauthorized)?;
+
+        if scheme != "Bearer" {
+            return Err(AuthError::TokenInvalid);
+        }
+
+        self.verify(token).await
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<Claims, AuthError> {
+        let inner = &self.inner;
+        let header = jsonwebtoken::decode_header(token).map_err(|_| AuthError::TokenInvalid)?;
+        let (key, algorithm) = match &inner.keys {
+            KeySource::Hmac(key) => (key.clone(), Algorithm::HS256),
+            KeySource::Jwks { .. } => (
+                self.find_key(header.kid.as_deref(), header.alg).await?,
+                header.alg,
+            ),
+        };
+
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = inner.leeway;
+        validation.validate_nbf = true;
+        validation.set_issuer(&[&inner.issuer]);
+        validation.set_audience(&[&inner.audience]);
+        validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &key, &validation)
+            .map_err(|err| match err.kind() {
+                jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+                _ => AuthError::TokenInvalid,
+            })?
+            .claims;
+
+        if claims.typ != TokenType::Access || claims.cnf.is_some() {
+            return Err(AuthError::TokenInvalid);
+        }
+
+        if let Some(revocation) = &inner.revocation {
+            if !self.is_active(revocation, token, &claims).await? {
+                return Err(AuthError::TokenRevoked);
+            }
+        }
+
+        Ok(claims)
+    }
+
+    async fn fetch_jwks(&self, url: &str) -> Result<JwkSet, AuthError> {
+        let unavailable = |e: reqwest::Error| AuthError::Unavailable(e.to_string());
+        let body = self
+            .inner
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(unavailable)?
+            .text()
+            .await
+            .map_err(unavailable)?;
+
+        serde_json::from_str(&body).map_err(|e| AuthError::Unavailable(e.to_string()))
+    }
+
+    /// Finds the key the token is signed with, refetching the keys when they are stale or
+    /// the key is unknown, e.g. after a key rotation. Symmetric keys are never accepted.
+    async fn find_key(
+        &self,
+        kid: Option<&str>,
+        algorithm: Algorithm,
+    ) -> Result<DecodingKey, AuthError> {
+        let KeySource::Jwks {
+            url,
+            ttl,
+            min_refresh,
+            cache,
+        } = &self.inner.keys
+        else {
+            return Err(AuthError::TokenInvalid);
+        };
+
+        let lookup = |keys: &JwkSet| {
+            let jwk = match kid {
+                Some(kid) => keys.find(kid),
+                None if keys.keys.len() == 1 => keys.keys.first(),
+                None => None,
+            }?;
+
+            let algorithm_matches = jwk
+                .common
+                .key_algorithm
+                .is_none_or(|alg| alg.to_string().parse() == Ok(algorithm));
+            if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)) || !algorithm_matches {
+                return None;
+            }
+
+            DecodingKey::from_jwk(jwk).ok()
+        };
+
+        let refresh = {
+            let cached = cache.read().await;
+            match cached.as_ref() {
+                Some(cached) if cached.fetched_at.elapsed() < *ttl => match lookup(&cached.keys) {
+                    Some(key) => return Ok(key),
+                    None => cached.fetched_at.elapsed() >= *min_refresh,
+                },
+                _ => true,
+            }
+        };
+
+        let mut cached = cache.write().await;
+        if refresh {
+            match self.fetch_jwks(url).await {
+                Ok(keys) => {
+                    *cached = Some(CachedJwks {
+                        keys,
+                        fetched_at: Instant::now(),
+                    })
+                }
+                // Stale keys are still better than failing every request while the
+                // issuer is unreachable.
+                Err(e) if cached.is_some() => tracing::warn!("using stale keys: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        cached
+            .as_ref()
+            .and_then(|cached| lookup(&cached.keys))
+            .ok_or(AuthError::TokenInvalid)
+    }
+
+    #[cfg_attr(not(feature = "redis"), allow(unused_variables))]
+    async fn is_active(
+        &self,
+        revocation: &RevocationKind,
+        token: &str,
+        claims: &Claims,
+    ) -> Result<bool, AuthError> {
+        match revocation {
+            #[cfg(feature = "redis")]
+            RevocationKind::Redis { client, conn } => {
+                let unavailable = |e: redis::RedisError| AuthError::Unavailable(e.to_string());
+                let mut connection = {
+                    let mut conn = conn.lock().await;
+                    match conn.as_ref() {
+                        Some(connection) => connection.clone(),
+                        None => {
+                            let connection = client
+                                .get_multiplexed_async_connection()
+                                .await
+                                .map_err(unavailable)?;
+                            conn.insert(connection).clone()
+                        }
+                    }
+                };
+
+                let result: redis::RedisResult<(bool, Option<i64>)> = redis::pipe()
+                    .cmd("EXISTS")
+                    .arg(format!("token:{}", claims.jti))
+                    .cmd("GET")
+                    .arg(format!("tokenversion:{}", claims.sub))
+                    .query_async(&mut connection)
+                    .await;
+
+                match result {
+                    Ok((blacklisted, version)) => Ok(!blacklisted
+                        && (claims.ver.is_none() || version.is_none() || version == claims.ver)),
+                    Err(e) => {
+                        // Connect again on the next request, the connection might be broken.
+                        conn.lock().await.take();
+                        Err(unavailable(e))
+                    }
+                }
+            }
+            RevocationKind::Introspection {
+                url,
+                client_id,
+                client_secret,
+            } => {
+                let unavailable = |e: reqwest::Error| AuthError::Unavailable(e.to_string());
+                let body = self
+                    .inner
+                    .http
+                    .post(url)
+                    .basic_auth(client_id, Some(client_secret))
+                    .form(&[("token", token), ("token_type_hint", "access_token")])
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .map_err(unavailable)?
+                    .text()
+                    .await
+                    .map_err(unavailable)?;
+
+                let introspection: serde_json::Value = serde_json::from_str(&body)
+                    .map_err(|e| AuthError::Unavailable(e.to_string()))?;
+                Ok(introspection["active"].as_bool().unwrap_or(false))
+            }
+        }
+    }
+}
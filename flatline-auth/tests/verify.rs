@@ -0,0 +1,334 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use flatline::{
+    config::Config,
+    models::user::{Role, User},
+    services::{
+        jwt::{self, Claims as FlatlineClaims, TokenContext, TokenType},
+        oidc::SigningKey,
+    },
+};
+use flatline_auth::{AuthError, AuthLayer, Claims, Revocation, Verifier};
+use serde_json::json;
+use tower::ServiceExt;
+
+fn hmac_verifier(config: &Config) -> Verifier {
+    Verifier::hmac(
+        &config.issuer(),
+        &config.jwt_audience,
+        &config.jwt_access_secret,
+    )
+    .build()
+}
+
+async fn serve(router: Router) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    addr
+}
+
+/// Access and refresh token of the user, issued the way flatline issues them at login.
+fn token_pair(user: &User, config: &Config) -> (String, String) {
+    let (signing_key, _) = SigningKey::generate().unwrap();
+    let (access_token, refresh_token, _) =
+        jwt::pairs_from_user(user, &TokenContext::default(), config, &signing_key).unwrap();
+    (access_token, refresh_token)
+}
+
+fn access_claims(user: &User, config: &Config) -> FlatlineClaims {
+    FlatlineClaims::from_user(
+        user,
+        TokenType::Access,
+        config,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+#[tokio::test]
+async fn verify_flatline_tokens() {
+    let config = Config::default();
+    let verifier = hmac_verifier(&config);
+    let user = User::new("alice", "hash", &[Role::User, Role::Admin]);
+    let (access_token, refresh_token) = token_pair(&user, &config);
+
+    let claims = verifier.verify(&access_token).await.unwrap();
+    assert_eq!(claims.sub, user.id);
+    assert_eq!(claims.username, "alice");
+    assert_eq!(claims.roles, vec!["user", "admin"]);
+    assert!(claims.has_role("admin"));
+
+    assert!(matches!(
+        verifier.verify(&refresh_token).await,
+        Err(AuthError::TokenInvalid)
+    ));
+
+    let other_audience = Verifier::hmac(&config.issuer(), "billing", &config.jwt_access_secret)
+        .build()
+        .verify(&access_token)
+        .await;
+    assert!(matches!(other_audience, Err(AuthError::TokenInvalid)));
+
+    let expired = FlatlineClaims {
+        exp: chrono::Utc::now().timestamp() - 120,
+        ..access_claims(&user, &config)
+    };
+    let expired = jwt::generate_token(&expired, &config.jwt_access_secret).unwrap();
+    assert!(matches!(
+        verifier.verify(&expired).await,
+        Err(AuthError::TokenExpired)
+    ));
+}
+
+#[tokio::test]
+async fn layer_requires_token_and_roles() {
+    let config = Config::default();
+    let user = User::new("alice", "hash", &[Role::User]);
+    let (token, _) = token_pair(&user, &config);
+
+    let hello = Router::new().route(
+        "/",
+        get(|claims: Claims| async move { format!("Hello, {}", claims.username) }),
+    );
+    let app = hello.clone().layer(AuthLayer::new(hmac_verifier(&config)));
+    let admin_app = hello.layer(AuthLayer::new(hmac_verifier(&config)).require_role("admin"));
+
+    let request = |token: Option<&str>| {
+        let builder = Request::builder().uri("/");
+        match token {
+            Some(token) => builder.header(header::AUTHORIZATION, format!("Bearer {}", token)),
+            None => builder,
+        }
+        .body(Body::empty())
+        .unwrap()
+    };
+
+    let res = app.clone().oneshot(request(None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = app.clone().oneshot(request(Some("invalid"))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = app.oneshot(request(Some(&token))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"Hello, alice");
+
+    let res = admin_app.oneshot(request(Some(&token))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn verify_with_jwks() {
+    let config = Config {
+        jwt_access_es256: true,
+        ..Config::default()
+    };
+    let (key, _) = SigningKey::generate().unwrap();
+    let (next_key, _) = SigningKey::generate().unwrap();
+    let jwks = Arc::new(RwLock::new(key.jwks()));
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let down = Arc::new(AtomicBool::new(false));
+    let addr = serve(Router::new().route(
+        "/.well-known/jwks.json",
+        get({
+            let (jwks, fetches, down) = (jwks.clone(), fetches.clone(), down.clone());
+            move || async move {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                if down.load(Ordering::SeqCst) {
+                    return Err(StatusCode::SERVICE_UNAVAILABLE);
+                }
+                Ok(Json(serde_json::to_value(&*jwks.read().unwrap()).unwrap()))
+            }
+        }),
+    ))
+    .await;
+    let verifier = |min_refresh: Duration| {
+        Verifier::jwks(
+            &config.issuer(),
+            &config.jwt_audience,
+            &format!("http://{}/.well-known/jwks.json", addr),
+        )
+        .jwks_min_refresh(min_refresh)
+        .build()
+    };
+    let issue = |user: &User, key: &SigningKey| {
+        jwt::pairs_from_user(user, &TokenContext::default(), &config, key)
+            .unwrap()
+            .0
+    };
+
+    let user = User::new("alice", "hash", &[Role::User]);
+    let token = issue(&user, &key);
+    let jwks_verifier = verifier(Duration::from_secs(30));
+    assert_eq!(jwks_verifier.verify(&token).await.unwrap().sub, user.id);
+    assert_eq!(jwks_verifier.verify(&token).await.unwrap().sub, user.id);
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+    // Tokens signed with a shared secret must not verify against published keys.
+    let hmac_token = jwt::generate_token(&access_claims(&user, &config), "secret").unwrap();
+    assert!(matches!(
+        jwks_verifier.verify(&hmac_token).await,
+        Err(AuthError::TokenInvalid)
+    ));
+
+    // Keys are not refetched for unknown keys more often than the minimum refresh interval.
+    *jwks.write().unwrap() = next_key.jwks();
+    let next_token = issue(&user, &next_key);
+    assert!(matches!(
+        jwks_verifier.verify(&next_token).await,
+        Err(AuthError::TokenInvalid)
+    ));
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+    // After a key rotation the keys are refetched for the unknown key.
+    *jwks.write().unwrap() = key.jwks();
+    let rotating_verifier = verifier(Duration::ZERO);
+    assert!(rotating_verifier.verify(&token).await.is_ok());
+    *jwks.write().unwrap() = next_key.jwks();
+    assert!(rotating_verifier.verify(&next_token).await.is_ok());
+    assert_eq!(fetches.load(Ordering::SeqCst), 3);
+    assert!(matches!(
+        rotating_verifier
+            .verify(&issue(&user, &SigningKey::generate().unwrap().0))
+            .await,
+        Err(AuthError::TokenInvalid)
+    ));
+
+    // Stale keys are used while the issuer is unreachable.
+    down.store(true, Ordering::SeqCst);
+    let expiring_verifier = Verifier::jwks(
+        &config.issuer(),
+        &config.jwt_audience,
+        &format!("http://{}/.well-known/jwks.json", addr),
+    )
+    .jwks_cache_ttl(Duration::ZERO)
+    .build();
+    assert!(matches!(
+        expiring_verifier.verify(&next_token).await,
+        Err(AuthError::Unavailable(_))
+    ));
+    down.store(false, Ordering::SeqCst);
+    assert!(expiring_verifier.verify(&next_token).await.is_ok());
+    down.store(true, Ordering::SeqCst);
+    assert!(expiring_verifier.verify(&next_token).await.is_ok());
+}
+
+#[tokio::test]
+async fn revoked_by_introspection() {
+    let config = Config::default();
+    let user = User::new("alice", "hash", &[Role::User]);
+    let (token, _) = token_pair(&user, &config);
+
+    let addr = serve(
+        Router::new()
+            .route(
+                "/active",
+                post(|| async { Json(json!({ "active": true })) }),
+            )
+            .route(
+                "/inactive",
+                post(|| async { Json(json!({ "active": false })) }),
+            ),
+    )
+    .await;
+
+    let verifier = |path: &str| {
+        Verifier::hmac(
+            &config.issuer(),
+            &config.jwt_audience,
+            &config.jwt_access_secret,
+        )
+        .revocation(Revocation::introspection(
+            &format!("http://{}{}", addr, path),
+            "client",
+            "secret",
+        ))
+        .build()
+    };
+
+    assert!(verifier("/active").verify(&token).await.is_ok());
+    assert!(matches!(
+        verifier("/inactive").verify(&token).await,
+        Err(AuthError::TokenRevoked)
+    ));
+    assert!(matches!(
+        verifier("/missing").verify(&token).await,
+        Err(AuthError::Unavailable(_))
+    ));
+}
+
+#[cfg(feature = "redis")]
+#[tokio::test]
+#[ignore = "requires Redis"]
+async fn revoked_in_redis() {
+    use flatline::database::redis::RedisCache;
+
+    let redis_host = std::env::var("REDIS_HOST").expect("REDIS_HOST should be set");
+    let config = Config {
+        redis_host,
+        ..Config::default()
+    };
+    let cache = RedisCache::new(config.redis_uri()).await.unwrap();
+    let verifier = |uri: &str| {
+        Verifier::hmac(
+            &config.issuer(),
+            &config.jwt_audience,
+            &config.jwt_access_secret,
+        )
+        .revocation(Revocation::redis(uri).unwrap())
+        .build()
+    };
+    let redis_verifier = verifier(&config.redis_uri());
+
+    let user = User::new("alice", "hash", &[Role::User]);
+    let (token, _) = token_pair(&user, &config);
+
+    // Versions missing from the cache are not checked.
+    assert!(redis_verifier.verify(&token).await.is_ok());
+    cache
+        .token_versions()
+        .store(user.id, user.token_version, 60)
+        .await
+        .unwrap();
+    assert!(redis_verifier.verify(&token).await.is_ok());
+
+    cache
+        .token_versions()
+        .store(user.id, user.token_version + 1, 60)
+        .await
+        .unwrap();
+    assert!(matches!(
+        redis_verifier.verify(&token).await,
+        Err(AuthError::TokenRevoked)
+    ));
+
+    let user = User::new("bob", "hash", &[Role::User]);
+    let (token, _) = token_pair(&user, &config);
+    let jti = redis_verifier.verify(&token).await.unwrap().jti;
+    cache.tokens().blacklist(jti, 60).await.unwrap();
+    assert!(matches!(
+        redis_verifier.verify(&token).await,
+        Err(AuthError::TokenRevoked)
+    ));
+
+    assert!(matches!(
+        verifier("redis://127.0.0.1:1").verify(&token).await,
+        Err(AuthError::Unavailable(_))
+    ));
+}
@@ -0,0 +1,284 @@
+use std::time::{Duration, Instant};
+
+use reqwest::{header, Method, RequestBuilder};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    error::{Error, Result},
+    types::{
+        ApiResponse, ApiVersion, AuthPayload, IssuedToken, LoginTokens, Page, PasswordPayload,
+        RefreshPayload, RefreshedTokens, UserDto, UserQuery,
+    },
+};
+
+/// Access tokens are refreshed this long before they expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+struct Session {
+    access_token: String,
+    refresh_token: String,
+    expires_at: Instant,
+}
+
+fn expires_at(token: &IssuedToken) -> Instant {
+    Instant::now() + Duration::from_secs(token.expires_in.max(0) as u64)
+}
+
+/// The json feature of reqwest is not enabled, so bodies are serialized here.
+fn with_json<B: Serialize + ?Sized>(request: RequestBuilder, body: &B) -> Result<RequestBuilder> {
+    Ok(request
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(body)?))
+}
+
+/// Deserializes the payload, or the field of the payload if there is a key.
+fn parse_payload<T: DeserializeOwned>(response: ApiResponse, key: Option<&str>) -> Result<T> {
+    let mut payload = response.payload.unwrap_or_default();
+    if let Some(key) = key {
+        payload = payload[key].take();
+    }
+
+    Ok(serde_json::from_value(payload)?)
+}
+
+/// Client of the flatline API. After logging in, requests are sent with the access token of
+/// the session, which is refreshed automatically with the refresh token.
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    version: ApiVersion,
+    session: Mutex<Option<Session>>,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            version: ApiVersion::V1,
+            session: Mutex::new(None),
+        }
+    }
+
+    pub fn with_api_version(mut self, version: ApiVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http.request(
+            method,
+            format!("{}/api/{}{}", self.base_url, self.version, path),
+        )
+    }
+
+    /// Sends the request, turning error responses of the API into [`Error::Api`].
+    async fn execute(&self, request: RequestBuilder) -> Result<ApiResponse> {
+        let res = request.send().await?;
+        let status = res.status();
+        let body = res.text().await?;
+
+        let response: ApiResponse = match serde_json::from_str(&body) {
+            Ok(response) => response,
+            // Rejections of the framework, e.g. malformed bodies, have no envelope.
+            Err(_) if !status.is_success() => {
+                return Err(Error::Api {
+                    code: status.as_u16(),
+                    message: body,
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // Some endpoints respond with an error status, but without clearing the success flag.
+        if !response.success || !status.is_success() {
+            return Err(Error::Api {
+                code: response.code,
+                message: response.message,
+            });
+        }
+
+        Ok(response)
+    }
+
+    async fn refresh_session(&self, session: &mut Session) -> Result<()> {
+        let response = self
+            .execute(with_json(
+                self.request(Method::POST, "/auth/refresh"),
+                &RefreshPayload {
+                    refresh_token: session.refresh_token.clone(),
+                },
+            )?)
+            .await?;
+        let tokens: RefreshedTokens = parse_payload(response, None)?;
+
+        session.expires_at = expires_at(&tokens.jwt_access);
+        session.access_token = tokens.jwt_access.token;
+
+        Ok(())
+    }
+
+    /// Access token of the session, refreshed first if it is about to expire.
+    pub async fn access_token(&self) -> Result<String> {
+        let mut session = self.session.lock().await;
+        let session = session.as_mut().ok_or(Error::NotAuthenticated)?;
+
+        if session.expires_at <= Instant::now() + REFRESH_MARGIN {
+            self.refresh_session(session).await?;
+        }
+
+        Ok(session.access_token.clone())
+    }
+
+    /// Exchanges the refresh token of the session for a new access token.
+    pub async fn refresh(&self) -> Result<()> {
+        let mut session = self.session.lock().await;
+        let session = session.as_mut().ok_or(Error::NotAuthenticated)?;
+        self.refresh_session(session).await
+    }
+
+    /// Sends an authenticated request to the path under `/api/{version}`. Requests rejected
+    /// with 401, e.g. because the access token was revoked, are retried once after refreshing.
+    pub async fn send<B: Serialize + ?Sized>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<ApiResponse> {
+        self.authorized(|token| {
+            let request = self.request(method.clone(), path).bearer_auth(token);
+            match body {
+                Some(body) => with_json(request, body),
+                None => Ok(request),
+            }
+        })
+        .await
+    }
+
+    /// Executes the request built with the access token of the session, see [`Client::send`].
+    async fn authorized<F>(&self, build: F) -> Result<ApiResponse>
+    where
+        F: Fn(&str) -> Result<RequestBuilder>,
+    {
+        let token = self.access_token().await?;
+        match self.execute(build(&token)?).await {
+            Err(Error::Api { code: 401, .. }) => {
+                self.refresh().await?;
+                let token = self.access_token().await?;
+                self.execute(build(&token)?).await
+            }
+            res => res,
+        }
+    }
+
+    pub async fn register(&self, payload: &AuthPayload) -> Result<UserDto> {
+        let response = self
+            .execute(with_json(
+                self.request(Method::POST, "/auth/register"),
+                payload,
+            )?)
+            .await?;
+        parse_payload(response, Some("user"))
+    }
+
+    /// Logs in, replacing the current session.
+    pub async fn login(&self, payload: &AuthPayload) -> Result<LoginTokens> {
+        let response = self
+            .execute(with_json(
+                self.request(Method::POST, "/auth/login"),
+                payload,
+            )?)
+            .await?;
+        let tokens: LoginTokens = parse_payload(response, None)?;
+
+        *self.session.lock().await = Some(Session {
+            access_token: tokens.jwt_access.token.clone(),
+            refresh_token: tokens.jwt_refresh.token.clone(),
+            expires_at: expires_at(&tokens.jwt_access),
+        });
+
+        Ok(tokens)
+    }
+
+    /// Logs in to the global namespace with the username and password.
+    pub async fn login_with_password(&self, username: &str, password: &str) -> Result<LoginTokens> {
+        self.login(&AuthPayload {
+            username: username.to_owned(),
+            password: password.to_owned(),
+            email: None,
+            organization: None,
+            invitation: None,
+        })
+        .await
+    }
+
+    /// Revokes the session. It is forgotten even if the request fails.
+    pub async fn logout(&self) -> Result<()> {
+        let token = self.access_token().await;
+        let session = self
+            .session
+            .lock()
+            .await
+            .take()
+            .ok_or(Error::NotAuthenticated)?;
+        let token = token?;
+
+        self.execute(with_json(
+            self.request(Method::POST, "/auth/logout")
+                .bearer_auth(token),
+            &RefreshPayload {
+                refresh_token: session.refresh_token,
+            },
+        )?)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn change_password(&self, current_password: &str, new_password: &str) -> Result<()> {
+        let payload = PasswordPayload {
+            current_password: current_password.to_owned(),
+            new_password: new_password.to_owned(),
+        };
+        self.send(Method::POST, "/auth/password", Some(&payload))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn user(&self, id: Uuid) -> Result<UserDto> {
+        let response = self
+            .send::<()>(Method::GET, &format!("/users/{}", id), None)
+            .await?;
+        parse_payload(response, Some("user"))
+    }
+
+    /// Lists the users of the global namespace, or of the organization in the query.
+    /// Requires an admin.
+    pub async fn users(&self, query: &UserQuery) -> Result<Page<UserDto>> {
+        let response = self
+            .authorized(|token| {
+                Ok(self
+                    .request(Method::GET, "/users")
+                    .query(query)
+                    .bearer_auth(token))
+            })
+            .await?;
+        let mut payload = response.payload.unwrap_or_default();
+
+        Ok(Page {
+            items: serde_json::from_value(payload["users"].take())?,
+            next_cursor: serde_json::from_value(payload["next_cursor"].take())?,
+        })
+    }
+
+    /// Deletes expired refresh tokens, returning how many were deleted. Requires an admin.
+    pub async fn delete_expired_tokens(&self) -> Result<u64> {
+        let response = self
+            .send::<()>(Method::GET, "/maintenance/delete-expired-jwt", None)
+            .await?;
+        parse_payload(response, Some("deleted_count"))
+    }
+}
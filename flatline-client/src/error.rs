@@ -0,0 +1,24 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("invalid response: {0}")]
+    Decode(#[from] serde_json::Error),
+    /// The API responded with an error, e.g. 401 for an invalid token.
+    #[error("{message} ({code})")]
+    Api { code: u16, message: String },
+    #[error("not logged in")]
+    NotAuthenticated,
+}
+
+impl Error {
+    /// Status code of the API error, `None` for other errors.
+    pub fn code(&self) -> Option<u16> {
+        match self {
+            Error::Api { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,27 @@
+//! Typed client for the flatline API. Requests and responses mirror the types of the server,
+//! without depending on the server crate.
+//!
+//! ```no_run
+//! # async fn example() -> flatline_client::Result<()> {
+//! use flatline_client::Client;
+//!
+//! let client = Client::new("http://127.0.0.1:8080");
+//! client.login_with_password("admin", "password").await?;
+//! let deleted = client.delete_expired_tokens().await?;
+//! println!("deleted {} expired tokens", deleted);
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+mod types;
+
+pub use client::Client;
+pub use error::{Error, Result};
+pub use reqwest::Method;
+pub use types::{
+    ApiResponse, ApiVersion, AuthPayload, AuthSource, IssuedToken, LoginTokens, Page,
+    PasswordPayload, RefreshPayload, RefreshedTokens, Role, SortOrder, UserDto, UserQuery,
+    UserSort,
+};
@@ -0,0 +1,150 @@
+//! Requests and responses of the flatline API. They mirror the types of the server, without
+//! depending on it, and are checked against them in the tests.
+
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl Display for ApiVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiVersion::V1 => write!(f, "v1"),
+            ApiVersion::V2 => write!(f, "v2"),
+        }
+    }
+}
+
+/// Envelope of every response of the API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiResponse {
+    pub success: bool,
+    pub code: u16,
+    pub api_version: String,
+    pub message: String,
+    pub payload: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Admin,
+}
+
+/// Where the password of the user is verified.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthSource {
+    #[default]
+    Local,
+    Ldap,
+    Oidc,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserDto {
+    pub id: Uuid,
+    pub username: String,
+    pub roles: Vec<Role>,
+    pub email: Option<String>,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub disabled_reason: Option<String>,
+    pub auth_source: AuthSource,
+    pub org_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    CreatedAt,
+    Username,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Filters, sorting and the page to fetch when listing users.
+/// `created_after` is inclusive, while `created_before` is exclusive.
+/// Only users of the global namespace are listed, unless `org_id` is given.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct UserQuery {
+    pub org_id: Option<Uuid>,
+    pub username_prefix: Option<String>,
+    pub role: Option<Role>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub disabled: Option<bool>,
+    #[serde(default)]
+    pub sort: UserSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthPayload {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Slug of the organization to log in to, the global namespace is used without it.
+    #[serde(default)]
+    pub organization: Option<String>,
+    /// Invitation token, required to register when registration is invite-only.
+    #[serde(default)]
+    pub invitation: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefreshPayload {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PasswordPayload {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// Token issued by the login and refresh endpoints.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IssuedToken {
+    pub token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginTokens {
+    pub jwt_access: IssuedToken,
+    pub jwt_refresh: IssuedToken,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefreshedTokens {
+    pub jwt_access: IssuedToken,
+}
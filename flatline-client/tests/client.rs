@@ -0,0 +1,117 @@
+//! Runs the client against the real router backed by the mock database. Redis is required
+//! at `REDIS_HOST`, so the tests are ignored by default.
+
+use std::net::SocketAddr;
+
+use flatline::{config::Config, database::DatabaseVariant, routes};
+use flatline_client::{AuthPayload, Client, Error, Role, UserQuery};
+use uuid::Uuid;
+
+async fn spawn_server(config: Config) -> String {
+    let state = flatline::init_state(config).await.unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            routes::create_routes(state).into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap()
+    });
+
+    format!("http://{}", addr)
+}
+
+fn config() -> Config {
+    Config {
+        database_variant: DatabaseVariant::Mock,
+        redis_host: std::env::var("REDIS_HOST").expect("REDIS_HOST should be set"),
+        ..Config::default()
+    }
+}
+
+fn payload(username: &str, password: &str) -> AuthPayload {
+    AuthPayload {
+        username: username.to_owned(),
+        password: password.to_owned(),
+        email: None,
+        organization: None,
+        invitation: None,
+    }
+}
+
+#[tokio::test]
+#[ignore = "requires Redis"]
+async fn register_login_and_refresh() {
+    // Access tokens expiring within the refresh margin are refreshed before every request.
+    let client = Client::new(
+        &spawn_server(Config {
+            jwt_access_expiration: 10,
+            ..config()
+        })
+        .await,
+    );
+
+    let user = client
+        .register(&payload("alice", "password"))
+        .await
+        .unwrap();
+    assert_eq!(user.username, "alice");
+    assert_eq!(user.roles, vec![Role::User]);
+
+    let tokens = client
+        .login_with_password("alice", "password")
+        .await
+        .unwrap();
+    assert_eq!(tokens.jwt_access.token_type, "Bearer");
+    assert_eq!(tokens.jwt_access.expires_in, 10);
+
+    let refreshed = client.access_token().await.unwrap();
+    assert_ne!(refreshed, tokens.jwt_access.token);
+
+    client
+        .change_password("password", "new password")
+        .await
+        .unwrap();
+
+    // Changing the password revokes the tokens of the session.
+    client
+        .login_with_password("alice", "new password")
+        .await
+        .unwrap();
+    assert_eq!(client.user(user.id).await.unwrap().username, "alice");
+    // Listing users requires an admin.
+    let err = client.users(&UserQuery::default()).await.unwrap_err();
+    assert_eq!(err.code(), Some(403));
+}
+
+#[tokio::test]
+#[ignore = "requires Redis"]
+async fn api_errors() {
+    let client = Client::new(&spawn_server(config()).await);
+    client.register(&payload("bob", "password")).await.unwrap();
+
+    assert!(matches!(
+        client.access_token().await,
+        Err(Error::NotAuthenticated)
+    ));
+
+    let err = client
+        .login_with_password("bob", "wrong")
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(401));
+
+    client.login_with_password("bob", "password").await.unwrap();
+    let err = client.user(Uuid::new_v4()).await.unwrap_err();
+    assert_eq!(err.code(), Some(403));
+    let err = client.delete_expired_tokens().await.unwrap_err();
+    assert_eq!(err.code(), Some(403));
+
+    client.logout().await.unwrap();
+    assert!(matches!(
+        client.delete_expired_tokens().await,
+        Err(Error::NotAuthenticated)
+    ));
+}
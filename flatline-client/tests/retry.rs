@@ -0,0 +1,151 @@
+//! Runs the client against a stub of the API, which responds with the types of the server,
+//! so no Redis is required.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use flatline::{
+    error::ApiError,
+    models::user::{
+        Role as ServerRole, User, UserDto as ServerUserDto, UserQuery as ServerUserQuery,
+    },
+    routes::{
+        auth::{IssuedToken, LoginTokens, RefreshPayload, RefreshedTokens},
+        ApiResponse,
+    },
+    services::auth::AuthError,
+};
+use flatline_client::{Client, Role, UserQuery};
+use serde_json::json;
+
+#[derive(Default)]
+struct Stub {
+    refreshes: AtomicUsize,
+    requests: AtomicUsize,
+    user: Option<ServerUserDto>,
+}
+
+fn issued(token: &str) -> IssuedToken {
+    IssuedToken {
+        token: token.to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: 900,
+    }
+}
+
+fn ok(payload: serde_json::Value) -> ApiResponse {
+    ApiResponse::builder().with_payload(payload).build()
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+async fn login() -> ApiResponse {
+    let tokens = LoginTokens {
+        jwt_access: issued("revoked"),
+        jwt_refresh: issued("refresh"),
+    };
+    ok(serde_json::to_value(tokens).unwrap())
+}
+
+async fn refresh(
+    State(stub): State<Arc<Stub>>,
+    Json(payload): Json<RefreshPayload>,
+) -> Result<ApiResponse, ApiError> {
+    stub.refreshes.fetch_add(1, Ordering::SeqCst);
+    if payload.refresh_token != "refresh" {
+        return Err(AuthError::TokenInvalid.into());
+    }
+
+    let tokens = RefreshedTokens {
+        jwt_access: issued("access"),
+    };
+    Ok(ok(serde_json::to_value(tokens).unwrap()))
+}
+
+/// Accepts only the refreshed access token, as if the one issued at login was revoked.
+async fn users(
+    State(stub): State<Arc<Stub>>,
+    headers: HeaderMap,
+    Query(query): Query<ServerUserQuery>,
+) -> Result<ApiResponse, ApiError> {
+    stub.requests.fetch_add(1, Ordering::SeqCst);
+    if bearer(&headers) != Some("access") {
+        return Err(AuthError::TokenInvalid.into());
+    }
+
+    assert_eq!(query.role, Some(ServerRole::Admin));
+    assert_eq!(query.limit, Some(1));
+    Ok(ok(json!({ "users": [stub.user], "next_cursor": "next" })))
+}
+
+/// Rejects every token, so the request fails even after refreshing.
+async fn rejected(State(stub): State<Arc<Stub>>) -> Result<ApiResponse, ApiError> {
+    stub.requests.fetch_add(1, Ordering::SeqCst);
+    Err(AuthError::TokenRevoked.into())
+}
+
+async fn spawn_stub(stub: Arc<Stub>) -> String {
+    let router = Router::new()
+        .route("/api/v1/auth/login", post(login))
+        .route("/api/v1/auth/refresh", post(refresh))
+        .route("/api/v1/users", get(users))
+        .route("/api/v1/maintenance/delete-expired-jwt", get(rejected))
+        .with_state(stub);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn refresh_and_retry_on_unauthorized() {
+    let user = User::new("alice", "hash", &[ServerRole::User, ServerRole::Admin]);
+    let stub = Arc::new(Stub {
+        user: Some(ServerUserDto::from(&user)),
+        ..Stub::default()
+    });
+    let client = Client::new(&spawn_stub(Arc::clone(&stub)).await);
+    client
+        .login_with_password("alice", "password")
+        .await
+        .unwrap();
+
+    let query = UserQuery {
+        role: Some(Role::Admin),
+        limit: Some(1),
+        ..UserQuery::default()
+    };
+    let page = client.users(&query).await.unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].id, user.id);
+    assert_eq!(page.items[0].roles, vec![Role::User, Role::Admin]);
+    assert_eq!(page.next_cursor.as_deref(), Some("next"));
+    assert_eq!(stub.refreshes.load(Ordering::SeqCst), 1);
+    assert_eq!(stub.requests.load(Ordering::SeqCst), 2);
+
+    // The refreshed token is used from now on.
+    client.users(&query).await.unwrap();
+    assert_eq!(stub.refreshes.load(Ordering::SeqCst), 1);
+    assert_eq!(stub.requests.load(Ordering::SeqCst), 3);
+
+    // Requests rejected after refreshing are not retried again.
+    let err = client.delete_expired_tokens().await.unwrap_err();
+    assert_eq!(err.code(), Some(StatusCode::UNAUTHORIZED.as_u16()));
+    assert_eq!(stub.refreshes.load(Ordering::SeqCst), 2);
+    assert_eq!(stub.requests.load(Ordering::SeqCst), 5);
+}
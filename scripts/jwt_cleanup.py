@@ -12,7 +12,7 @@ def login(base_url, username, password):
             headers={"Content-Type": "application/json"}
         )
         response.raise_for_status()
-        return response.json().get("payload").get("access_token")
+        return response.json().get("payload").get("jwt_access").get("token")
     except requests.exceptions.RequestException as e:
         print(f"login failed: {e}")
         return None
@@ -4,10 +4,78 @@ use valuable::Valuable;
 
 use crate::database::DatabaseVariant;
 
+/// Upstream OpenID Connect provider users can sign in with. The endpoints are discovered
+/// from the issuer.
 #[derive(Clone, Debug, Serialize, Deserialize, Valuable)]
+pub struct UpstreamProvider {
+    /// Identifies the provider in the URLs and in the linked identities.
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default = "default_upstream_scope")]
+    pub scope: String,
+}
+
+fn default_upstream_scope() -> String {
+    "openid email profile".to_string()
+}
+
+/// Who can create an account with `POST /auth/register`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RegistrationMode {
+    #[default]
+    Open,
+    /// Registering requires an invitation created by an admin.
+    InviteOnly,
+    Closed,
+}
+
+impl std::str::FromStr for RegistrationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Self::Open),
+            "invite_only" => Ok(Self::InviteOnly),
+            "closed" => Ok(Self::Closed),
+            _ => Err(format!("registration mode ({}) not recognized", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for RegistrationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistrationMode::Open => write!(f, "open"),
+            RegistrationMode::InviteOnly => write!(f, "invite_only"),
+            RegistrationMode::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+impl Valuable for RegistrationMode {
+    fn as_value(&self) -> valuable::Value<'_> {
+        match self {
+            RegistrationMode::Open => valuable::Value::String("open"),
+            RegistrationMode::InviteOnly => valuable::Value::String("invite_only"),
+            RegistrationMode::Closed => valuable::Value::String("closed"),
+        }
+    }
+
+    fn visit(&self, visit: &mut dyn valuable::Visit) {
+        visit.visit_value(self.as_value());
+    }
+}
+
+/// Fields missing from a config file fall back to [`Config::default`], so config files
+/// written before a field was added keep working.
+#[derive(Clone, Debug, Serialize, Deserialize, Valuable)]
+#[serde(default)]
 pub struct Config {
     pub api_host: String,
     pub api_port: u16,
+    pub issuer_url: String,
 
     #[serde(
         deserialize_with = "serde_from_str::deserialize",
@@ -29,8 +97,59 @@ pub struct Config {
 
     pub jwt_access_expiration: i64,
     pub jwt_refresh_expiration: i64,
+    pub jwt_audience: String,
+    pub jwt_leeway: u64,
+    /// Tokens issued before the `iss`, `aud`, `nbf` and `typ` claims were introduced are
+    /// accepted until this unix timestamp, never if it is 0.
+    pub jwt_legacy_until: i64,
+    pub impersonation_token_expiration: i64,
 
     pub user_session_limit: usize,
+    pub token_version_cache_ttl: i64,
+    pub group_roles_cache_ttl: i64,
+    pub deleted_user_retention: i64,
+    pub audit_log_retention: i64,
+
+    pub oauth_code_expiration: i64,
+    pub oauth_device_code_expiration: i64,
+    pub oauth_device_poll_interval: i64,
+    pub dpop_proof_lifetime: i64,
+    pub oidc_signing_key: String,
+    /// Access tokens are signed with the OIDC signing key instead of the access token secret,
+    /// so services can verify them with the published public key.
+    pub jwt_access_es256: bool,
+
+    pub webhook_poll_interval: u64,
+    pub webhook_request_timeout: u64,
+    pub webhook_max_attempts: i32,
+    pub webhook_retry_base_delay: i64,
+
+    pub scim_token: String,
+
+    pub ldap_url: String,
+    pub ldap_user_dn: String,
+    pub ldap_group_base_dn: String,
+    pub ldap_group_roles: String,
+    pub ldap_timeout: u64,
+
+    pub oidc_upstream_providers: Vec<UpstreamProvider>,
+    pub federated_login_expiration: i64,
+
+    pub org_invitation_expiration: i64,
+
+    #[serde(
+        deserialize_with = "serde_from_str::deserialize",
+        serialize_with = "serde_from_str::serialize"
+    )]
+    pub registration_mode: RegistrationMode,
+    pub invitation_expiration: i64,
+    /// Users of LDAP and upstream providers are created on their first login even if the
+    /// registration mode is not open.
+    pub external_registration: bool,
+
+    pub policy_file: String,
+
+    pub session_cookie: String,
 }
 
 impl Default for Config {
@@ -38,6 +157,7 @@ impl Default for Config {
         Self {
             api_host: "127.0.0.1".to_string(),
             api_port: 8080,
+            issuer_url: "http://127.0.0.1:8080".to_string(),
             database_variant: DatabaseVariant::Postgres,
             database_host: "127.0.0.1".to_string(),
             database_port: 5432,
@@ -51,7 +171,39 @@ impl Default for Config {
             jwt_refresh_secret: "jwt_refresh_secret".to_string(),
             jwt_access_expiration: 900,
             jwt_refresh_expiration: 2592000,
+            jwt_audience: "flatline".to_string(),
+            jwt_leeway: 30,
+            jwt_legacy_until: 0,
+            impersonation_token_expiration: 300,
             user_session_limit: 5,
+            token_version_cache_ttl: 300,
+            group_roles_cache_ttl: 300,
+            deleted_user_retention: 2592000,
+            audit_log_retention: 7776000,
+            oauth_code_expiration: 60,
+            oauth_device_code_expiration: 600,
+            oauth_device_poll_interval: 5,
+            dpop_proof_lifetime: 60,
+            oidc_signing_key: String::new(),
+            jwt_access_es256: false,
+            webhook_poll_interval: 5,
+            webhook_request_timeout: 10,
+            webhook_max_attempts: 8,
+            webhook_retry_base_delay: 30,
+            scim_token: String::new(),
+            ldap_url: String::new(),
+            ldap_user_dn: "uid={username},ou=people,dc=example,dc=org".to_string(),
+            ldap_group_base_dn: String::new(),
+            ldap_group_roles: String::new(),
+            ldap_timeout: 5,
+            oidc_upstream_providers: Vec::new(),
+            federated_login_expiration: 600,
+            org_invitation_expiration: 604800,
+            registration_mode: RegistrationMode::Open,
+            invitation_expiration: 604800,
+            external_registration: false,
+            policy_file: String::new(),
+            session_cookie: String::new(),
         }
     }
 }
@@ -63,6 +215,7 @@ impl Config {
             .expect("API_PORT should be set")
             .parse()
             .expect("API_PORT should be of type u16");
+        let issuer_url = std::env::var("ISSUER_URL").expect("ISSUER_URL should be set");
 
         let database_variant: DatabaseVariant = std::env::var("DATABASE_VARIANT")
             .expect("DATABASE_VARIANT should be set")
@@ -101,15 +254,139 @@ impl Config {
             .expect("JWT_REFRESH_EXPIRATION should be set")
             .parse::<i64>()
             .expect("JWT_REFRESH_EXPIRATION should be of type i64");
+        let jwt_audience = std::env::var("JWT_AUDIENCE").expect("JWT_AUDIENCE should be set");
+        let jwt_leeway = std::env::var("JWT_LEEWAY")
+            .expect("JWT_LEEWAY should be set")
+            .parse::<u64>()
+            .expect("JWT_LEEWAY should be of type u64");
+        let jwt_legacy_until = std::env::var("JWT_LEGACY_UNTIL")
+            .map(|v| {
+                v.parse::<i64>()
+                    .expect("JWT_LEGACY_UNTIL should be of type i64")
+            })
+            .unwrap_or_default();
+        let impersonation_token_expiration = std::env::var("IMPERSONATION_TOKEN_EXPIRATION")
+            .expect("IMPERSONATION_TOKEN_EXPIRATION should be set")
+            .parse::<i64>()
+            .expect("IMPERSONATION_TOKEN_EXPIRATION should be of type i64");
 
         let user_session_limit = std::env::var("USER_SESSION_LIMIT")
             .expect("USER_SESSION_LIMIT should be set")
             .parse::<usize>()
             .expect("USER_SESSION_LIMIT should be a numeric type");
+        let token_version_cache_ttl = std::env::var("TOKEN_VERSION_CACHE_TTL")
+            .expect("TOKEN_VERSION_CACHE_TTL should be set")
+            .parse::<i64>()
+            .expect("TOKEN_VERSION_CACHE_TTL should be of type i64");
+        let deleted_user_retention = std::env::var("DELETED_USER_RETENTION")
+            .expect("DELETED_USER_RETENTION should be set")
+            .parse::<i64>()
+            .expect("DELETED_USER_RETENTION should be of type i64");
+        let audit_log_retention = std::env::var("AUDIT_LOG_RETENTION")
+            .expect("AUDIT_LOG_RETENTION should be set")
+            .parse::<i64>()
+            .expect("AUDIT_LOG_RETENTION should be of type i64");
+
+        let oauth_code_expiration = std::env::var("OAUTH_CODE_EXPIRATION")
+            .expect("OAUTH_CODE_EXPIRATION should be set")
+            .parse::<i64>()
+            .expect("OAUTH_CODE_EXPIRATION should be of type i64");
+        let oauth_device_code_expiration = std::env::var("OAUTH_DEVICE_CODE_EXPIRATION")
+            .expect("OAUTH_DEVICE_CODE_EXPIRATION should be set")
+            .parse::<i64>()
+            .expect("OAUTH_DEVICE_CODE_EXPIRATION should be of type i64");
+        let oauth_device_poll_interval = std::env::var("OAUTH_DEVICE_POLL_INTERVAL")
+            .expect("OAUTH_DEVICE_POLL_INTERVAL should be set")
+            .parse::<i64>()
+            .expect("OAUTH_DEVICE_POLL_INTERVAL should be of type i64");
+
+        let dpop_proof_lifetime = std::env::var("DPOP_PROOF_LIFETIME")
+            .expect("DPOP_PROOF_LIFETIME should be set")
+            .parse::<i64>()
+            .expect("DPOP_PROOF_LIFETIME should be of type i64");
+
+        let oidc_signing_key = std::env::var("OIDC_SIGNING_KEY").unwrap_or_default();
+        let jwt_access_es256 = std::env::var("JWT_ACCESS_ES256")
+            .map(|v| {
+                v.parse::<bool>()
+                    .expect("JWT_ACCESS_ES256 should be of type bool")
+            })
+            .unwrap_or_default();
+
+        let webhook_poll_interval = std::env::var("WEBHOOK_POLL_INTERVAL")
+            .expect("WEBHOOK_POLL_INTERVAL should be set")
+            .parse::<u64>()
+            .expect("WEBHOOK_POLL_INTERVAL should be of type u64");
+        let webhook_request_timeout = std::env::var("WEBHOOK_REQUEST_TIMEOUT")
+            .expect("WEBHOOK_REQUEST_TIMEOUT should be set")
+            .parse::<u64>()
+            .expect("WEBHOOK_REQUEST_TIMEOUT should be of type u64");
+        let webhook_max_attempts = std::env::var("WEBHOOK_MAX_ATTEMPTS")
+            .expect("WEBHOOK_MAX_ATTEMPTS should be set")
+            .parse::<i32>()
+            .expect("WEBHOOK_MAX_ATTEMPTS should be of type i32");
+        let webhook_retry_base_delay = std::env::var("WEBHOOK_RETRY_BASE_DELAY")
+            .expect("WEBHOOK_RETRY_BASE_DELAY should be set")
+            .parse::<i64>()
+            .expect("WEBHOOK_RETRY_BASE_DELAY should be of type i64");
+
+        let scim_token = std::env::var("SCIM_TOKEN").unwrap_or_default();
+
+        let ldap_url = std::env::var("LDAP_URL").unwrap_or_default();
+        let ldap_user_dn = std::env::var("LDAP_USER_DN").unwrap_or_default();
+        let ldap_group_base_dn = std::env::var("LDAP_GROUP_BASE_DN").unwrap_or_default();
+        let ldap_group_roles = std::env::var("LDAP_GROUP_ROLES").unwrap_or_default();
+        let ldap_timeout = std::env::var("LDAP_TIMEOUT")
+            .expect("LDAP_TIMEOUT should be set")
+            .parse::<u64>()
+            .expect("LDAP_TIMEOUT should be of type u64");
+
+        let oidc_upstream_providers = std::env::var("OIDC_UPSTREAM_PROVIDERS")
+            .map(|v| {
+                serde_json::from_str(&v)
+                    .expect("OIDC_UPSTREAM_PROVIDERS should be a JSON array of providers")
+            })
+            .unwrap_or_default();
+        let federated_login_expiration = std::env::var("FEDERATED_LOGIN_EXPIRATION")
+            .expect("FEDERATED_LOGIN_EXPIRATION should be set")
+            .parse::<i64>()
+            .expect("FEDERATED_LOGIN_EXPIRATION should be of type i64");
+
+        let org_invitation_expiration = std::env::var("ORG_INVITATION_EXPIRATION")
+            .expect("ORG_INVITATION_EXPIRATION should be set")
+            .parse::<i64>()
+            .expect("ORG_INVITATION_EXPIRATION should be of type i64");
+
+        let group_roles_cache_ttl = std::env::var("GROUP_ROLES_CACHE_TTL")
+            .expect("GROUP_ROLES_CACHE_TTL should be set")
+            .parse::<i64>()
+            .expect("GROUP_ROLES_CACHE_TTL should be of type i64");
+
+        let registration_mode = std::env::var("REGISTRATION_MODE")
+            .map(|v| {
+                v.parse()
+                    .expect("REGISTRATION_MODE should be one of open, invite_only, closed")
+            })
+            .unwrap_or_default();
+        let invitation_expiration = std::env::var("INVITATION_EXPIRATION")
+            .expect("INVITATION_EXPIRATION should be set")
+            .parse::<i64>()
+            .expect("INVITATION_EXPIRATION should be of type i64");
+        let external_registration = std::env::var("EXTERNAL_REGISTRATION")
+            .map(|v| {
+                v.parse::<bool>()
+                    .expect("EXTERNAL_REGISTRATION should be of type bool")
+            })
+            .unwrap_or_default();
+
+        let policy_file = std::env::var("POLICY_FILE").unwrap_or_default();
+
+        let session_cookie = std::env::var("SESSION_COOKIE").unwrap_or_default();
 
         Config {
             api_host,
             api_port,
+            issuer_url,
 
             database_variant,
             database_host,
@@ -127,8 +404,49 @@ impl Config {
 
             jwt_access_expiration,
             jwt_refresh_expiration,
+            jwt_audience,
+            jwt_leeway,
+            jwt_legacy_until,
+            impersonation_token_expiration,
 
             user_session_limit,
+            token_version_cache_ttl,
+            group_roles_cache_ttl,
+            deleted_user_retention,
+            audit_log_retention,
+
+            oauth_code_expiration,
+            oauth_device_code_expiration,
+            oauth_device_poll_interval,
+            dpop_proof_lifetime,
+            oidc_signing_key,
+            jwt_access_es256,
+
+            webhook_poll_interval,
+            webhook_request_timeout,
+            webhook_max_attempts,
+            webhook_retry_base_delay,
+
+            scim_token,
+
+            ldap_url,
+            ldap_user_dn,
+            ldap_group_base_dn,
+            ldap_group_roles,
+            ldap_timeout,
+
+            oidc_upstream_providers,
+            federated_login_expiration,
+
+            org_invitation_expiration,
+
+            registration_mode,
+            invitation_expiration,
+            external_registration,
+
+            policy_file,
+
+            session_cookie,
         }
     }
 
@@ -143,6 +461,7 @@ impl Config {
         Config {
             api_host: self.api_host.clone(),
             api_port: self.api_port,
+            issuer_url: self.issuer_url.clone(),
 
             database_variant: self.database_variant.clone(),
             database_host: self.database_host.clone(),
@@ -159,11 +478,64 @@ impl Config {
             jwt_refresh_secret: "<redacted>".to_string(),
             jwt_access_expiration: self.jwt_access_expiration,
             jwt_refresh_expiration: self.jwt_refresh_expiration,
+            jwt_audience: self.jwt_audience.clone(),
+            jwt_leeway: self.jwt_leeway,
+            jwt_legacy_until: self.jwt_legacy_until,
+            impersonation_token_expiration: self.impersonation_token_expiration,
 
             user_session_limit: self.user_session_limit,
+            token_version_cache_ttl: self.token_version_cache_ttl,
+            group_roles_cache_ttl: self.group_roles_cache_ttl,
+            deleted_user_retention: self.deleted_user_retention,
+            audit_log_retention: self.audit_log_retention,
+
+            oauth_code_expiration: self.oauth_code_expiration,
+            oauth_device_code_expiration: self.oauth_device_code_expiration,
+            oauth_device_poll_interval: self.oauth_device_poll_interval,
+            dpop_proof_lifetime: self.dpop_proof_lifetime,
+            oidc_signing_key: "<redacted>".to_string(),
+            jwt_access_es256: self.jwt_access_es256,
+
+            webhook_poll_interval: self.webhook_poll_interval,
+            webhook_request_timeout: self.webhook_request_timeout,
+            webhook_max_attempts: self.webhook_max_attempts,
+            webhook_retry_base_delay: self.webhook_retry_base_delay,
+
+            scim_token: "<redacted>".to_string(),
+
+            ldap_url: self.ldap_url.clone(),
+            ldap_user_dn: self.ldap_user_dn.clone(),
+            ldap_group_base_dn: self.ldap_group_base_dn.clone(),
+            ldap_group_roles: self.ldap_group_roles.clone(),
+            ldap_timeout: self.ldap_timeout,
+
+            oidc_upstream_providers: self
+                .oidc_upstream_providers
+                .iter()
+                .map(|provider| UpstreamProvider {
+                    client_secret: "<redacted>".to_string(),
+                    ..provider.clone()
+                })
+                .collect(),
+            federated_login_expiration: self.federated_login_expiration,
+
+            org_invitation_expiration: self.org_invitation_expiration,
+
+            registration_mode: self.registration_mode,
+            invitation_expiration: self.invitation_expiration,
+            external_registration: self.external_registration,
+
+            policy_file: self.policy_file.clone(),
+
+            session_cookie: self.session_cookie.clone(),
         }
     }
 
+    /// Whether users of LDAP and upstream providers can be created on their first login.
+    pub fn allows_external_registration(&self) -> bool {
+        self.registration_mode == RegistrationMode::Open || self.external_registration
+    }
+
     pub fn socket_addr(&self) -> SocketAddr {
         format!("{}:{}", self.api_host, self.api_port)
             .parse()
@@ -175,6 +547,11 @@ impl Config {
             })
     }
 
+    /// Public base URL of this flatline instance, without a trailing slash.
+    pub fn issuer(&self) -> String {
+        self.issuer_url.trim_end_matches('/').to_string()
+    }
+
     pub fn database_uri(&self) -> String {
         match self.database_variant {
             DatabaseVariant::Postgres | DatabaseVariant::MySql => format!(
@@ -221,3 +598,26 @@ mod serde_from_str {
         serializer.serialize_str(&value.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let config: Config = serde_json::from_value(json!({
+            "api_host": "0.0.0.0",
+            "database_variant": "mock",
+            "redis_host": "127.0.0.1",
+            "user_session_limit": 3
+        }))
+        .unwrap();
+
+        assert_eq!(config.api_host, "0.0.0.0");
+        assert!(matches!(config.database_variant, DatabaseVariant::Mock));
+        assert_eq!(config.user_session_limit, 3);
+        assert_eq!(config.jwt_audience, Config::default().jwt_audience);
+        assert_eq!(config.registration_mode, RegistrationMode::Open);
+    }
+}
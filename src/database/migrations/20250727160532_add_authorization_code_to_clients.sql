@@ -0,0 +1,6 @@
+-- Add migration script here
+
+ALTER TABLE clients
+    ALTER COLUMN secret_hash DROP NOT NULL,
+    ADD COLUMN redirect_uris TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN grant_types TEXT[] NOT NULL DEFAULT '{client_credentials}'
@@ -0,0 +1,5 @@
+-- Add migration script here
+
+ALTER TABLE users
+    ADD COLUMN email VARCHAR(320),
+    ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE
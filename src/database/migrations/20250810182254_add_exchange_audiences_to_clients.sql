@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE clients
+    ADD COLUMN exchange_audiences TEXT[] NOT NULL DEFAULT '{}'
@@ -0,0 +1,11 @@
+-- Add migration script here
+
+CREATE TYPE user_role AS ENUM ('user', 'admin');
+
+ALTER TABLE users
+    ALTER COLUMN roles DROP DEFAULT,
+    ALTER COLUMN roles TYPE user_role[]
+        USING string_to_array(replace(roles, ' ', ''), ',')::user_role[],
+    ALTER COLUMN roles SET DEFAULT '{user}';
+
+CREATE INDEX users_roles_idx ON users USING GIN (roles);
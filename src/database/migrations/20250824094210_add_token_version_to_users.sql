@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE users
+    ADD COLUMN token_version BIGINT NOT NULL DEFAULT 0
@@ -0,0 +1,12 @@
+-- Add migration script here
+
+ALTER TABLE users
+    ADD COLUMN disabled_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN disabled_reason TEXT,
+    ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE,
+    DROP CONSTRAINT users_username_key;
+
+-- Usernames of soft deleted users can be taken again.
+CREATE UNIQUE INDEX users_username_key ON users (username) WHERE deleted_at IS NULL;
+
+CREATE INDEX users_deleted_at_idx ON users (deleted_at) WHERE deleted_at IS NOT NULL;
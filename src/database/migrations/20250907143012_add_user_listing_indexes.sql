@@ -0,0 +1,10 @@
+-- Add migration script here
+
+-- Keyset pagination, with the id breaking ties between equal sort keys.
+CREATE INDEX users_created_at_id_idx ON users (created_at, id) WHERE deleted_at IS NULL;
+CREATE INDEX users_username_id_idx ON users (username, id) WHERE deleted_at IS NULL;
+
+-- Prefix matching with LIKE needs a pattern index regardless of the database collation.
+CREATE INDEX users_username_pattern_idx ON users (username varchar_pattern_ops) WHERE deleted_at IS NULL;
+
+CREATE INDEX users_disabled_at_idx ON users (disabled_at) WHERE disabled_at IS NOT NULL;
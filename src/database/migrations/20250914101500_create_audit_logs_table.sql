@@ -0,0 +1,12 @@
+-- Add migration script here
+
+CREATE TABLE audit_logs (
+    id UUID PRIMARY KEY,
+    event_type VARCHAR(64) NOT NULL,
+    actor_id UUID,
+    target_id UUID,
+    metadata JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_logs_target_id_idx ON audit_logs (target_id, created_at);
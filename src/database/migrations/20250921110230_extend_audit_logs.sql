@@ -0,0 +1,23 @@
+-- Add migration script here
+
+CREATE TYPE audit_outcome AS ENUM ('success', 'failure');
+
+ALTER TABLE audit_logs
+    ADD COLUMN ip_address VARCHAR(45),
+    ADD COLUMN user_agent TEXT,
+    ADD COLUMN outcome audit_outcome NOT NULL DEFAULT 'success';
+
+CREATE INDEX audit_logs_created_at_id_idx ON audit_logs (created_at, id);
+CREATE INDEX audit_logs_actor_id_idx ON audit_logs (actor_id, created_at);
+CREATE INDEX audit_logs_event_type_idx ON audit_logs (event_type, created_at);
+
+-- Entries can only be appended, and removed once they are past the retention period.
+CREATE FUNCTION audit_logs_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_logs is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_logs_append_only
+    BEFORE UPDATE ON audit_logs
+    FOR EACH ROW EXECUTE FUNCTION audit_logs_append_only();
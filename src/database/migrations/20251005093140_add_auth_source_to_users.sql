@@ -0,0 +1,7 @@
+-- Add migration script here
+
+CREATE TYPE auth_source AS ENUM ('local', 'ldap');
+
+-- The source of a user never changes, users of external directories are created on their first login.
+ALTER TABLE users
+    ADD COLUMN auth_source auth_source NOT NULL DEFAULT 'local';
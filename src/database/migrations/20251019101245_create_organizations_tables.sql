@@ -0,0 +1,43 @@
+-- Add migration script here
+
+CREATE TABLE organizations (
+    id UUID PRIMARY KEY,
+    slug VARCHAR(64) NOT NULL UNIQUE,
+    name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TYPE org_role AS ENUM ('member', 'admin');
+
+CREATE TABLE org_memberships (
+    org_id UUID NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role org_role NOT NULL DEFAULT 'member',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (org_id, user_id)
+);
+
+CREATE INDEX org_memberships_user_id_idx ON org_memberships (user_id);
+
+CREATE TABLE org_invitations (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    email TEXT,
+    role org_role NOT NULL DEFAULT 'member',
+    token_hash TEXT NOT NULL UNIQUE,
+    invited_by UUID REFERENCES users (id) ON DELETE SET NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX org_invitations_org_id_idx ON org_invitations (org_id, created_at);
+
+-- Users of an organization live in its own namespace, users without one in the global namespace.
+ALTER TABLE users
+    ADD COLUMN org_id UUID REFERENCES organizations (id);
+
+DROP INDEX users_username_key;
+CREATE UNIQUE INDEX users_username_key ON users (username) WHERE deleted_at IS NULL AND org_id IS NULL;
+CREATE UNIQUE INDEX users_org_username_key ON users (org_id, username) WHERE deleted_at IS NULL AND org_id IS NOT NULL;
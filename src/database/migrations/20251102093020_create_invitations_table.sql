@@ -0,0 +1,15 @@
+-- Add migration script here
+
+CREATE TABLE invitations (
+    id UUID PRIMARY KEY,
+    email TEXT,
+    roles user_role[] NOT NULL DEFAULT '{user}',
+    token_hash TEXT NOT NULL UNIQUE,
+    created_by UUID REFERENCES users (id) ON DELETE SET NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    used_by UUID,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX invitations_created_at_idx ON invitations (created_at);
@@ -4,12 +4,29 @@ use std::{
 };
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 use crate::{
-    database::RefreshTokenRepository,
+    database::{
+        AuditLogRepository, ClientRepository, GroupMemberRepository, GroupRepository,
+        InvitationRepository, MembershipRepository, OrgInvitationRepository,
+        OrganizationRepository, RefreshTokenRepository, UserIdentityRepository,
+        WebhookOutboxRepository, WebhookRepository,
+    },
     error::ApiError,
-    models::{refresh_token::RefreshToken, user::User},
+    models::{
+        audit_log::{AuditLog, AuditLogQuery},
+        client::Client,
+        group::{Group, GroupMember},
+        invitation::Invitation,
+        organization::{Membership, OrgInvitation, Organization},
+        pagination::{Page, SortOrder},
+        refresh_token::RefreshToken,
+        user::{Role, User, UserCursor, UserQuery},
+        user_identity::UserIdentity,
+        webhook::{WebhookDelivery, WebhookMessage, WebhookStatus, WebhookSubscription},
+    },
 };
 
 use super::{Database, UserRepository};
@@ -18,6 +35,18 @@ use super::{Database, UserRepository};
 pub struct MockDatabase {
     users: Arc<RwLock<HashMap<Uuid, User>>>,
     refresh_tokens: Arc<RwLock<HashMap<Uuid, RefreshToken>>>,
+    clients: Arc<RwLock<HashMap<Uuid, Client>>>,
+    audit_logs: Arc<RwLock<Vec<AuditLog>>>,
+    webhooks: Arc<RwLock<HashMap<Uuid, WebhookSubscription>>>,
+    webhook_outbox: Arc<RwLock<HashMap<Uuid, WebhookMessage>>>,
+    webhook_deliveries: Arc<RwLock<Vec<WebhookDelivery>>>,
+    user_identities: Arc<RwLock<HashMap<Uuid, UserIdentity>>>,
+    organizations: Arc<RwLock<HashMap<Uuid, Organization>>>,
+    memberships: Arc<RwLock<HashMap<(Uuid, Uuid), Membership>>>,
+    org_invitations: Arc<RwLock<HashMap<Uuid, OrgInvitation>>>,
+    groups: Arc<RwLock<HashMap<Uuid, Group>>>,
+    group_members: Arc<RwLock<HashMap<(Uuid, Uuid), GroupMember>>>,
+    invitations: Arc<RwLock<HashMap<Uuid, Invitation>>>,
 }
 
 impl MockDatabase {
@@ -25,6 +54,18 @@ impl MockDatabase {
         Arc::new(Self {
             users: Arc::new(RwLock::new(HashMap::new())),
             refresh_tokens: Arc::new(RwLock::new(HashMap::new())),
+            clients: Arc::new(RwLock::new(HashMap::new())),
+            audit_logs: Arc::new(RwLock::new(Vec::new())),
+            webhooks: Arc::new(RwLock::new(HashMap::new())),
+            webhook_outbox: Arc::new(RwLock::new(HashMap::new())),
+            webhook_deliveries: Arc::new(RwLock::new(Vec::new())),
+            user_identities: Arc::new(RwLock::new(HashMap::new())),
+            organizations: Arc::new(RwLock::new(HashMap::new())),
+            memberships: Arc::new(RwLock::new(HashMap::new())),
+            org_invitations: Arc::new(RwLock::new(HashMap::new())),
+            groups: Arc::new(RwLock::new(HashMap::new())),
+            group_members: Arc::new(RwLock::new(HashMap::new())),
+            invitations: Arc::new(RwLock::new(HashMap::new())),
         })
     }
 }
@@ -43,13 +84,57 @@ impl Database for MockDatabase {
     fn refresh_tokens(&self) -> &dyn RefreshTokenRepository {
         self
     }
+
+    fn clients(&self) -> &dyn ClientRepository {
+        self
+    }
+
+    fn audit_logs(&self) -> &dyn AuditLogRepository {
+        self
+    }
+
+    fn webhooks(&self) -> &dyn WebhookRepository {
+        self
+    }
+
+    fn webhook_outbox(&self) -> &dyn WebhookOutboxRepository {
+        self
+    }
+
+    fn user_identities(&self) -> &dyn UserIdentityRepository {
+        self
+    }
+
+    fn organizations(&self) -> &dyn OrganizationRepository {
+        self
+    }
+
+    fn memberships(&self) -> &dyn MembershipRepository {
+        self
+    }
+
+    fn org_invitations(&self) -> &dyn OrgInvitationRepository {
+        self
+    }
+
+    fn groups(&self) -> &dyn GroupRepository {
+        self
+    }
+
+    fn group_members(&self) -> &dyn GroupMemberRepository {
+        self
+    }
+
+    fn invitations(&self) -> &dyn InvitationRepository {
+        self
+    }
 }
 
 #[async_trait]
 impl UserRepository for MockDatabase {
     async fn create(&self, user: User) -> Result<User, ApiError> {
         for u in self.users.read().unwrap().values() {
-            if u.username == user.username {
+            if u.username == user.username && u.org_id == user.org_id && u.deleted_at.is_none() {
                 return Err(ApiError::Internal(anyhow::Error::msg(
                     "username already exists",
                 )));
@@ -61,12 +146,25 @@ impl UserRepository for MockDatabase {
     }
 
     async fn find_all(&self) -> Result<Vec<User>, ApiError> {
-        let users: Vec<User> = self.users.read().unwrap().values().cloned().collect();
+        let users: Vec<User> = self
+            .users
+            .read()
+            .unwrap()
+            .values()
+            .filter(|user| user.deleted_at.is_none())
+            .cloned()
+            .collect();
         Ok(users)
     }
 
     async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, ApiError> {
-        let user = self.users.read().unwrap().get(&id).cloned();
+        let user = self
+            .users
+            .read()
+            .unwrap()
+            .get(&id)
+            .filter(|user| user.deleted_at.is_none())
+            .cloned();
         Ok(user)
     }
 
@@ -76,12 +174,169 @@ impl UserRepository for MockDatabase {
             .read()
             .unwrap()
             .values()
-            .find(|user| user.username == username)
+            .find(|user| {
+                user.username == username && user.org_id.is_none() && user.deleted_at.is_none()
+            })
             .cloned();
 
         Ok(user)
     }
 
+    async fn find_by_org_username(
+        &self,
+        org_id: Uuid,
+        username: &str,
+    ) -> Result<Option<User>, ApiError> {
+        let user = self
+            .users
+            .read()
+            .unwrap()
+            .values()
+            .find(|user| {
+                user.org_id == Some(org_id)
+                    && user.username == username
+                    && user.deleted_at.is_none()
+            })
+            .cloned();
+
+        Ok(user)
+    }
+
+    async fn find_by_role(&self, role: Role) -> Result<Vec<User>, ApiError> {
+        let groups = self.groups.read().unwrap();
+        let group_members: Vec<Uuid> = self
+            .group_members
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|(group_id, _)| {
+                groups
+                    .get(group_id)
+                    .is_some_and(|g| g.roles.contains(&role))
+            })
+            .map(|(_, user_id)| *user_id)
+            .collect();
+
+        let users = self
+            .users
+            .read()
+            .unwrap()
+            .values()
+            .filter(|user| user.has_role(role) || group_members.contains(&user.id))
+            .filter(|user| user.deleted_at.is_none())
+            .cloned()
+            .collect();
+
+        Ok(users)
+    }
+
+    async fn find_page(&self, query: &UserQuery) -> Result<Page<User>, ApiError> {
+        let cursor = query.decode_cursor()?;
+        let limit = query.page_size();
+
+        let mut users: Vec<User> = self
+            .users
+            .read()
+            .unwrap()
+            .values()
+            .filter(|user| user.deleted_at.is_none())
+            .filter(|user| user.org_id == query.org_id)
+            .filter(|user| {
+                query
+                    .username_prefix
+                    .as_deref()
+                    .is_none_or(|prefix| user.username.starts_with(prefix))
+            })
+            .filter(|user| query.role.is_none_or(|role| user.has_role(role)))
+            .filter(|user| query.created_after.is_none_or(|t| user.created_at >= t))
+            .filter(|user| query.created_before.is_none_or(|t| user.created_at < t))
+            .filter(|user| query.disabled.is_none_or(|d| user.is_disabled() == d))
+            .cloned()
+            .collect();
+
+        let key = |user: &User| UserCursor::for_user(user, query.sort);
+        let ordered = |a: &UserCursor, b: &UserCursor| match query.order {
+            SortOrder::Asc => a.cmp(b),
+            SortOrder::Desc => b.cmp(a),
+        };
+
+        if let Some(cursor) = &cursor {
+            users.retain(|user| ordered(&key(user), cursor).is_gt());
+        }
+        users.sort_by(|a, b| ordered(&key(a), &key(b)));
+        users.truncate(limit + 1);
+
+        Ok(Page::from_items(users, limit, |user| {
+            query.encode_cursor(user)
+        }))
+    }
+
+    async fn count(&self) -> Result<u64, ApiError> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .values()
+            .filter(|user| user.deleted_at.is_none() && user.org_id.is_none())
+            .count() as u64)
+    }
+
+    async fn update(&self, user: User) -> Result<Option<User>, ApiError> {
+        let mut users = self.users.write().unwrap();
+        let Some(existing) = users
+            .get_mut(&user.id)
+            .filter(|user| user.deleted_at.is_none())
+        else {
+            return Ok(None);
+        };
+
+        *existing = User {
+            token_version: existing.token_version,
+            deleted_at: existing.deleted_at,
+            auth_source: existing.auth_source,
+            org_id: existing.org_id,
+            created_at: existing.created_at,
+            updated_at: chrono::Utc::now(),
+            ..user
+        };
+
+        Ok(Some(existing.clone()))
+    }
+
+    async fn increment_token_version(&self, id: Uuid) -> Result<Option<i64>, ApiError> {
+        let mut users = self.users.write().unwrap();
+        let version = users
+            .get_mut(&id)
+            .filter(|user| user.deleted_at.is_none())
+            .map(|user| {
+                user.token_version += 1;
+                user.token_version
+            });
+
+        Ok(version)
+    }
+
+    async fn soft_delete(&self, id: Uuid) -> Result<Option<User>, ApiError> {
+        let mut users = self.users.write().unwrap();
+        let user = users
+            .get_mut(&id)
+            .filter(|user| user.deleted_at.is_none())
+            .map(|user| {
+                let now = chrono::Utc::now();
+                user.deleted_at = Some(now);
+                user.updated_at = now;
+                user.clone()
+            });
+
+        Ok(user)
+    }
+
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<u64, ApiError> {
+        let mut users = self.users.write().unwrap();
+        let original_count = users.len();
+
+        users.retain(|_, user| user.deleted_at.is_none_or(|deleted_at| deleted_at > before));
+        Ok((original_count - users.len()) as u64)
+    }
+
     async fn delete_all(&self) -> Result<u64, ApiError> {
         let mut users = self.users.write().unwrap();
         let deleted_count = users.len();
@@ -115,6 +370,14 @@ impl RefreshTokenRepository for MockDatabase {
         Ok(tokens.remove_entry(&jti).map(|(_, tok)| tok))
     }
 
+    async fn delete_by_sub(&self, sub: Uuid) -> Result<u64, ApiError> {
+        let mut tokens = self.refresh_tokens.write().unwrap();
+        let original_count = tokens.len();
+
+        tokens.retain(|_, tok| tok.sub != sub);
+        Ok((original_count - tokens.len()) as u64)
+    }
+
     async fn find_by_sub(&self, sub: Uuid) -> Result<Vec<RefreshToken>, ApiError> {
         let tokens: Vec<RefreshToken> = self
             .refresh_tokens
@@ -133,3 +396,646 @@ impl RefreshTokenRepository for MockDatabase {
         return Ok(token);
     }
 }
+
+#[async_trait]
+impl ClientRepository for MockDatabase {
+    async fn create(&self, client: Client) -> Result<Client, ApiError> {
+        for c in self.clients.read().unwrap().values() {
+            if c.client_id == client.client_id {
+                return Err(ApiError::Internal(anyhow::Error::msg(
+                    "client_id already exists",
+                )));
+            }
+        }
+
+        self.clients
+            .write()
+            .unwrap()
+            .insert(client.id, client.clone());
+        Ok(client)
+    }
+
+    async fn find_all(&self) -> Result<Vec<Client>, ApiError> {
+        let clients: Vec<Client> = self.clients.read().unwrap().values().cloned().collect();
+        Ok(clients)
+    }
+
+    async fn find_by_client_id(&self, client_id: &str) -> Result<Option<Client>, ApiError> {
+        let client = self
+            .clients
+            .read()
+            .unwrap()
+            .values()
+            .find(|client| client.client_id == client_id)
+            .cloned();
+
+        Ok(client)
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Client>, ApiError> {
+        let mut clients = self.clients.write().unwrap();
+        Ok(clients.remove(&id))
+    }
+}
+
+#[async_trait]
+impl AuditLogRepository for MockDatabase {
+    async fn create(&self, audit_log: AuditLog) -> Result<AuditLog, ApiError> {
+        self.audit_logs.write().unwrap().push(audit_log.clone());
+        Ok(audit_log)
+    }
+
+    async fn find_page(&self, query: &AuditLogQuery) -> Result<Page<AuditLog>, ApiError> {
+        let cursor = query.decode_cursor()?;
+        let limit = query.page_size();
+
+        let key = |log: &AuditLog| (log.created_at, log.id);
+        let ordered = |a: &AuditLog, b: (DateTime<Utc>, Uuid)| match query.order {
+            SortOrder::Asc => key(a).cmp(&b),
+            SortOrder::Desc => b.cmp(&key(a)),
+        };
+
+        let mut logs: Vec<AuditLog> = self
+            .audit_logs
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|log| {
+                query
+                    .event_type
+                    .as_ref()
+                    .is_none_or(|t| *t == log.event_type)
+            })
+            .filter(|log| query.actor_id.is_none_or(|id| log.actor_id == Some(id)))
+            .filter(|log| query.target_id.is_none_or(|id| log.target_id == Some(id)))
+            .filter(|log| query.outcome.is_none_or(|o| log.outcome == o))
+            .filter(|log| {
+                query
+                    .ip_address
+                    .as_ref()
+                    .is_none_or(|ip| log.ip_address.as_ref() == Some(ip))
+            })
+            .filter(|log| {
+                query
+                    .user_agent
+                    .as_ref()
+                    .is_none_or(|ua| log.user_agent.as_ref() == Some(ua))
+            })
+            .filter(|log| query.created_after.is_none_or(|t| log.created_at >= t))
+            .filter(|log| query.created_before.is_none_or(|t| log.created_at < t))
+            .filter(|log| {
+                cursor
+                    .as_ref()
+                    .is_none_or(|c| ordered(log, (c.created_at, c.id)).is_gt())
+            })
+            .cloned()
+            .collect();
+
+        logs.sort_by(|a, b| ordered(a, key(b)));
+        logs.truncate(limit + 1);
+
+        Ok(Page::from_items(logs, limit, |log| {
+            query.encode_cursor(log)
+        }))
+    }
+
+    async fn delete_older_than(&self, before: DateTime<Utc>) -> Result<u64, ApiError> {
+        let mut logs = self.audit_logs.write().unwrap();
+        let original_count = logs.len();
+
+        logs.retain(|log| log.created_at > before);
+        Ok((original_count - logs.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for MockDatabase {
+    async fn create(
+        &self,
+        subscription: WebhookSubscription,
+    ) -> Result<WebhookSubscription, ApiError> {
+        self.webhooks
+            .write()
+            .unwrap()
+            .insert(subscription.id, subscription.clone());
+        Ok(subscription)
+    }
+
+    async fn find_all(&self) -> Result<Vec<WebhookSubscription>, ApiError> {
+        let mut subscriptions: Vec<WebhookSubscription> =
+            self.webhooks.read().unwrap().values().cloned().collect();
+        subscriptions.sort_by_key(|s| s.created_at);
+        Ok(subscriptions)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<WebhookSubscription>, ApiError> {
+        Ok(self.webhooks.read().unwrap().get(&id).cloned())
+    }
+
+    async fn find_by_event_type(
+        &self,
+        event_type: &str,
+    ) -> Result<Vec<WebhookSubscription>, ApiError> {
+        Ok(self
+            .webhooks
+            .read()
+            .unwrap()
+            .values()
+            .filter(|s| s.subscribes_to(event_type))
+            .cloned()
+            .collect())
+    }
+
+    async fn update(
+        &self,
+        subscription: WebhookSubscription,
+    ) -> Result<Option<WebhookSubscription>, ApiError> {
+        let mut webhooks = self.webhooks.write().unwrap();
+        let Some(existing) = webhooks.get_mut(&subscription.id) else {
+            return Ok(None);
+        };
+
+        *existing = WebhookSubscription {
+            created_at: existing.created_at,
+            updated_at: Utc::now(),
+            ..subscription
+        };
+        Ok(Some(existing.clone()))
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<WebhookSubscription>, ApiError> {
+        let subscription = self.webhooks.write().unwrap().remove(&id);
+        if subscription.is_some() {
+            self.webhook_outbox
+                .write()
+                .unwrap()
+                .retain(|_, m| m.subscription_id != id);
+            self.webhook_deliveries
+                .write()
+                .unwrap()
+                .retain(|d| d.subscription_id != id);
+        }
+
+        Ok(subscription)
+    }
+}
+
+#[async_trait]
+impl WebhookOutboxRepository for MockDatabase {
+    async fn enqueue(&self, messages: Vec<WebhookMessage>) -> Result<u64, ApiError> {
+        let mut outbox = self.webhook_outbox.write().unwrap();
+        let count = messages.len() as u64;
+        for message in messages {
+            outbox.insert(message.id, message);
+        }
+
+        Ok(count)
+    }
+
+    async fn claim_due(
+        &self,
+        lease_until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<WebhookMessage>, ApiError> {
+        let now = Utc::now();
+        let mut outbox = self.webhook_outbox.write().unwrap();
+
+        let mut due: Vec<&mut WebhookMessage> = outbox
+            .values_mut()
+            .filter(|m| m.status == WebhookStatus::Pending && m.next_attempt_at <= now)
+            .collect();
+        due.sort_by_key(|m| m.next_attempt_at);
+
+        Ok(due
+            .into_iter()
+            .take(limit)
+            .map(|m| {
+                m.next_attempt_at = lease_until;
+                m.updated_at = now;
+                m.clone()
+            })
+            .collect())
+    }
+
+    async fn update(&self, message: WebhookMessage) -> Result<Option<WebhookMessage>, ApiError> {
+        let mut outbox = self.webhook_outbox.write().unwrap();
+        let Some(existing) = outbox.get_mut(&message.id) else {
+            return Ok(None);
+        };
+
+        existing.status = message.status;
+        existing.attempts = message.attempts;
+        existing.next_attempt_at = message.next_attempt_at;
+        existing.updated_at = Utc::now();
+        Ok(Some(existing.clone()))
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<WebhookMessage>, ApiError> {
+        Ok(self.webhook_outbox.read().unwrap().get(&id).cloned())
+    }
+
+    async fn create_delivery(
+        &self,
+        delivery: WebhookDelivery,
+    ) -> Result<WebhookDelivery, ApiError> {
+        self.webhook_deliveries
+            .write()
+            .unwrap()
+            .push(delivery.clone());
+        Ok(delivery)
+    }
+
+    async fn find_deliveries(
+        &self,
+        subscription_id: Uuid,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, ApiError> {
+        let mut deliveries: Vec<WebhookDelivery> = self
+            .webhook_deliveries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|d| d.subscription_id == subscription_id)
+            .cloned()
+            .collect();
+        deliveries.sort_by_key(|d| std::cmp::Reverse((d.created_at, d.id)));
+        deliveries.truncate(limit);
+
+        Ok(deliveries)
+    }
+}
+
+#[async_trait]
+impl UserIdentityRepository for MockDatabase {
+    async fn create(&self, identity: UserIdentity) -> Result<UserIdentity, ApiError> {
+        let mut identities = self.user_identities.write().unwrap();
+        if identities
+            .values()
+            .any(|i| i.provider == identity.provider && i.subject == identity.subject)
+        {
+            return Err(ApiError::Internal(anyhow::Error::msg(
+                "identity already exists",
+            )));
+        }
+
+        identities.insert(identity.id, identity.clone());
+        Ok(identity)
+    }
+
+    async fn find_by_subject(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<UserIdentity>, ApiError> {
+        Ok(self
+            .user_identities
+            .read()
+            .unwrap()
+            .values()
+            .find(|i| i.provider == provider && i.subject == subject)
+            .cloned())
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, ApiError> {
+        let mut identities: Vec<UserIdentity> = self
+            .user_identities
+            .read()
+            .unwrap()
+            .values()
+            .filter(|i| i.user_id == user_id)
+            .cloned()
+            .collect();
+        identities.sort_by_key(|i| i.created_at);
+        Ok(identities)
+    }
+
+    async fn update(&self, identity: UserIdentity) -> Result<Option<UserIdentity>, ApiError> {
+        let mut identities = self.user_identities.write().unwrap();
+        let Some(existing) = identities.get_mut(&identity.id) else {
+            return Ok(None);
+        };
+
+        existing.email = identity.email;
+        existing.last_login_at = identity.last_login_at;
+        Ok(Some(existing.clone()))
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<UserIdentity>, ApiError> {
+        Ok(self.user_identities.write().unwrap().remove(&id))
+    }
+}
+
+#[async_trait]
+impl OrganizationRepository for MockDatabase {
+    async fn create(&self, organization: Organization) -> Result<Organization, ApiError> {
+        let mut organizations = self.organizations.write().unwrap();
+        if organizations.values().any(|o| o.slug == organization.slug) {
+            return Err(ApiError::Internal(anyhow::Error::msg(
+                "organization slug already exists",
+            )));
+        }
+
+        organizations.insert(organization.id, organization.clone());
+        Ok(organization)
+    }
+
+    async fn find_all(&self) -> Result<Vec<Organization>, ApiError> {
+        let mut organizations: Vec<Organization> = self
+            .organizations
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        organizations.sort_by(|a, b| a.slug.cmp(&b.slug));
+        Ok(organizations)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Organization>, ApiError> {
+        Ok(self.organizations.read().unwrap().get(&id).cloned())
+    }
+
+    async fn find_by_slug(&self, slug: &str) -> Result<Option<Organization>, ApiError> {
+        Ok(self
+            .organizations
+            .read()
+            .unwrap()
+            .values()
+            .find(|o| o.slug == slug)
+            .cloned())
+    }
+
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Organization>, ApiError> {
+        let memberships = self.memberships.read().unwrap();
+        let mut organizations: Vec<Organization> = self
+            .organizations
+            .read()
+            .unwrap()
+            .values()
+            .filter(|o| memberships.contains_key(&(o.id, user_id)))
+            .cloned()
+            .collect();
+        organizations.sort_by(|a, b| a.slug.cmp(&b.slug));
+        Ok(organizations)
+    }
+}
+
+#[async_trait]
+impl MembershipRepository for MockDatabase {
+    async fn upsert(&self, membership: Membership) -> Result<Membership, ApiError> {
+        let mut memberships = self.memberships.write().unwrap();
+        let key = (membership.org_id, membership.user_id);
+        let saved = match memberships.get(&key) {
+            Some(existing) => Membership {
+                created_at: existing.created_at,
+                updated_at: Utc::now(),
+                ..membership
+            },
+            None => membership,
+        };
+
+        memberships.insert(key, saved.clone());
+        Ok(saved)
+    }
+
+    async fn find(&self, org_id: Uuid, user_id: Uuid) -> Result<Option<Membership>, ApiError> {
+        Ok(self
+            .memberships
+            .read()
+            .unwrap()
+            .get(&(org_id, user_id))
+            .cloned())
+    }
+
+    async fn find_by_org_id(&self, org_id: Uuid) -> Result<Vec<Membership>, ApiError> {
+        let mut memberships: Vec<Membership> = self
+            .memberships
+            .read()
+            .unwrap()
+            .values()
+            .filter(|m| m.org_id == org_id)
+            .cloned()
+            .collect();
+        memberships.sort_by_key(|m| m.created_at);
+        Ok(memberships)
+    }
+
+    async fn delete(&self, org_id: Uuid, user_id: Uuid) -> Result<Option<Membership>, ApiError> {
+        Ok(self.memberships.write().unwrap().remove(&(org_id, user_id)))
+    }
+}
+
+#[async_trait]
+impl OrgInvitationRepository for MockDatabase {
+    async fn create(&self, invitation: OrgInvitation) -> Result<OrgInvitation, ApiError> {
+        self.org_invitations
+            .write()
+            .unwrap()
+            .insert(invitation.id, invitation.clone());
+        Ok(invitation)
+    }
+
+    async fn find_by_token_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<OrgInvitation>, ApiError> {
+        Ok(self
+            .org_invitations
+            .read()
+            .unwrap()
+            .values()
+            .find(|i| i.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn find_by_org_id(&self, org_id: Uuid) -> Result<Vec<OrgInvitation>, ApiError> {
+        let mut invitations: Vec<OrgInvitation> = self
+            .org_invitations
+            .read()
+            .unwrap()
+            .values()
+            .filter(|i| i.org_id == org_id)
+            .cloned()
+            .collect();
+        invitations.sort_by_key(|i| i.created_at);
+        Ok(invitations)
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<OrgInvitation>, ApiError> {
+        Ok(self.org_invitations.write().unwrap().remove(&id))
+    }
+}
+
+#[async_trait]
+impl GroupRepository for MockDatabase {
+    async fn create(&self, group: Group) -> Result<Group, ApiError> {
+        let mut groups = self.groups.write().unwrap();
+        if groups.values().any(|g| g.name == group.name) {
+            return Err(ApiError::Internal(anyhow::Error::msg(
+                "group name already exists",
+            )));
+        }
+
+        groups.insert(group.id, group.clone());
+        Ok(group)
+    }
+
+    async fn find_all(&self) -> Result<Vec<Group>, ApiError> {
+        let mut groups: Vec<Group> = self.groups.read().unwrap().values().cloned().collect();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(groups)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Group>, ApiError> {
+        Ok(self.groups.read().unwrap().get(&id).cloned())
+    }
+
+    async fn find_by_name(&self, name: &str) -> Result<Option<Group>, ApiError> {
+        Ok(self
+            .groups
+            .read()
+            .unwrap()
+            .values()
+            .find(|g| g.name == name)
+            .cloned())
+    }
+
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Group>, ApiError> {
+        let members = self.group_members.read().unwrap();
+        let mut groups: Vec<Group> = self
+            .groups
+            .read()
+            .unwrap()
+            .values()
+            .filter(|g| members.contains_key(&(g.id, user_id)))
+            .cloned()
+            .collect();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(groups)
+    }
+
+    async fn update(&self, group: Group) -> Result<Option<Group>, ApiError> {
+        let mut groups = self.groups.write().unwrap();
+        if groups
+            .values()
+            .any(|g| g.name == group.name && g.id != group.id)
+        {
+            return Err(ApiError::Internal(anyhow::Error::msg(
+                "group name already exists",
+            )));
+        }
+
+        let Some(existing) = groups.get_mut(&group.id) else {
+            return Ok(None);
+        };
+        *existing = Group {
+            created_at: existing.created_at,
+            updated_at: Utc::now(),
+            ..group
+        };
+        Ok(Some(existing.clone()))
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Group>, ApiError> {
+        let deleted = self.groups.write().unwrap().remove(&id);
+        if deleted.is_some() {
+            self.group_members
+                .write()
+                .unwrap()
+                .retain(|(group_id, _), _| *group_id != id);
+        }
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl GroupMemberRepository for MockDatabase {
+    async fn create(&self, member: GroupMember) -> Result<Option<GroupMember>, ApiError> {
+        let mut members = self.group_members.write().unwrap();
+        let key = (member.group_id, member.user_id);
+        if members.contains_key(&key) {
+            return Ok(None);
+        }
+
+        members.insert(key, member.clone());
+        Ok(Some(member))
+    }
+
+    async fn find_by_group_id(&self, group_id: Uuid) -> Result<Vec<GroupMember>, ApiError> {
+        let mut members: Vec<GroupMember> = self
+            .group_members
+            .read()
+            .unwrap()
+            .values()
+            .filter(|m| m.group_id == group_id)
+            .cloned()
+            .collect();
+        members.sort_by_key(|m| m.created_at);
+        Ok(members)
+    }
+
+    async fn delete(&self, group_id: Uuid, user_id: Uuid) -> Result<Option<GroupMember>, ApiError> {
+        Ok(self
+            .group_members
+            .write()
+            .unwrap()
+            .remove(&(group_id, user_id)))
+    }
+}
+
+#[async_trait]
+impl InvitationRepository for MockDatabase {
+    async fn create(&self, invitation: Invitation) -> Result<Invitation, ApiError> {
+        self.invitations
+            .write()
+            .unwrap()
+            .insert(invitation.id, invitation.clone());
+        Ok(invitation)
+    }
+
+    async fn find_all(&self) -> Result<Vec<Invitation>, ApiError> {
+        let mut invitations: Vec<Invitation> =
+            self.invitations.read().unwrap().values().cloned().collect();
+        invitations.sort_by_key(|i| std::cmp::Reverse(i.created_at));
+        Ok(invitations)
+    }
+
+    async fn find_by_token_hash(&self, token_hash: &str) -> Result<Option<Invitation>, ApiError> {
+        Ok(self
+            .invitations
+            .read()
+            .unwrap()
+            .values()
+            .find(|i| i.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, ApiError> {
+        let mut invitations = self.invitations.write().unwrap();
+        let Some(invitation) = invitations.get_mut(&id).filter(|i| i.used_at.is_none()) else {
+            return Ok(None);
+        };
+
+        invitation.used_at = Some(Utc::now());
+        invitation.used_by = Some(user_id);
+        Ok(Some(invitation.clone()))
+    }
+
+    async fn release(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, ApiError> {
+        let mut invitations = self.invitations.write().unwrap();
+        let Some(invitation) = invitations
+            .get_mut(&id)
+            .filter(|i| i.used_by == Some(user_id))
+        else {
+            return Ok(None);
+        };
+
+        invitation.used_at = None;
+        invitation.used_by = None;
+        Ok(Some(invitation.clone()))
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Invitation>, ApiError> {
+        Ok(self.invitations.write().unwrap().remove(&id))
+    }
+}
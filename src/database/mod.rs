@@ -1,12 +1,24 @@
 use std::str::FromStr;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
     error::ApiError,
-    models::{refresh_token::RefreshToken, user::User},
+    models::{
+        audit_log::{AuditLog, AuditLogQuery},
+        client::Client,
+        group::{Group, GroupMember},
+        invitation::Invitation,
+        organization::{Membership, OrgInvitation, Organization},
+        pagination::Page,
+        refresh_token::RefreshToken,
+        user::{Role, User, UserQuery},
+        user_identity::UserIdentity,
+        webhook::{WebhookDelivery, WebhookMessage, WebhookSubscription},
+    },
 };
 
 pub mod mock;
@@ -66,6 +78,17 @@ pub trait Database: Send + Sync {
     async fn migrate(&self) -> Result<(), ApiError>;
     fn users(&self) -> &dyn UserRepository;
     fn refresh_tokens(&self) -> &dyn RefreshTokenRepository;
+    fn clients(&self) -> &dyn ClientRepository;
+    fn audit_logs(&self) -> &dyn AuditLogRepository;
+    fn webhooks(&self) -> &dyn WebhookRepository;
+    fn webhook_outbox(&self) -> &dyn WebhookOutboxRepository;
+    fn user_identities(&self) -> &dyn UserIdentityRepository;
+    fn organizations(&self) -> &dyn OrganizationRepository;
+    fn memberships(&self) -> &dyn MembershipRepository;
+    fn org_invitations(&self) -> &dyn OrgInvitationRepository;
+    fn groups(&self) -> &dyn GroupRepository;
+    fn group_members(&self) -> &dyn GroupMemberRepository;
+    fn invitations(&self) -> &dyn InvitationRepository;
 }
 
 #[async_trait]
@@ -73,7 +96,23 @@ pub trait UserRepository {
     async fn create(&self, user: User) -> Result<User, ApiError>;
     async fn find_all(&self) -> Result<Vec<User>, ApiError>;
     async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, ApiError>;
+    /// Finds the user in the global namespace, i.e. a user without an organization.
     async fn find_by_username(&self, username: &str) -> Result<Option<User>, ApiError>;
+    async fn find_by_org_username(
+        &self,
+        org_id: Uuid,
+        username: &str,
+    ) -> Result<Option<User>, ApiError>;
+    /// Finds the users holding the role, either directly or through a group.
+    async fn find_by_role(&self, role: Role) -> Result<Vec<User>, ApiError>;
+    async fn find_page(&self, query: &UserQuery) -> Result<Page<User>, ApiError>;
+    /// Number of users in the global namespace, i.e. those listed by `find_page` without
+    /// an organization.
+    async fn count(&self) -> Result<u64, ApiError>;
+    async fn update(&self, user: User) -> Result<Option<User>, ApiError>;
+    async fn increment_token_version(&self, id: Uuid) -> Result<Option<i64>, ApiError>;
+    async fn soft_delete(&self, id: Uuid) -> Result<Option<User>, ApiError>;
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<u64, ApiError>;
     async fn delete_all(&self) -> Result<u64, ApiError>;
 }
 
@@ -82,14 +121,148 @@ pub trait RefreshTokenRepository {
     async fn create(&self, refresh_token: RefreshToken) -> Result<RefreshToken, ApiError>;
     async fn delete_expired(&self) -> Result<u64, ApiError>;
     async fn delete_by_jti(&self, jti: Uuid) -> Result<Option<RefreshToken>, ApiError>;
+    async fn delete_by_sub(&self, sub: Uuid) -> Result<u64, ApiError>;
     async fn find_by_sub(&self, sub: Uuid) -> Result<Vec<RefreshToken>, ApiError>;
     async fn find_by_jti(&self, jti: Uuid) -> Result<Option<RefreshToken>, ApiError>;
 }
 
+#[async_trait]
+pub trait ClientRepository {
+    async fn create(&self, client: Client) -> Result<Client, ApiError>;
+    async fn find_all(&self) -> Result<Vec<Client>, ApiError>;
+    async fn find_by_client_id(&self, client_id: &str) -> Result<Option<Client>, ApiError>;
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Client>, ApiError>;
+}
+
+#[async_trait]
+pub trait AuditLogRepository {
+    async fn create(&self, audit_log: AuditLog) -> Result<AuditLog, ApiError>;
+    async fn find_page(&self, query: &AuditLogQuery) -> Result<Page<AuditLog>, ApiError>;
+    async fn delete_older_than(&self, before: DateTime<Utc>) -> Result<u64, ApiError>;
+}
+
+#[async_trait]
+pub trait WebhookRepository {
+    async fn create(
+        &self,
+        subscription: WebhookSubscription,
+    ) -> Result<WebhookSubscription, ApiError>;
+    async fn find_all(&self) -> Result<Vec<WebhookSubscription>, ApiError>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<WebhookSubscription>, ApiError>;
+    async fn find_by_event_type(
+        &self,
+        event_type: &str,
+    ) -> Result<Vec<WebhookSubscription>, ApiError>;
+    async fn update(
+        &self,
+        subscription: WebhookSubscription,
+    ) -> Result<Option<WebhookSubscription>, ApiError>;
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<WebhookSubscription>, ApiError>;
+}
+
+#[async_trait]
+pub trait WebhookOutboxRepository {
+    async fn enqueue(&self, messages: Vec<WebhookMessage>) -> Result<u64, ApiError>;
+    /// Takes up to `limit` pending messages which are due, and postpones them until
+    /// `lease_until`, so that they are not picked up again while being delivered.
+    async fn claim_due(
+        &self,
+        lease_until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<WebhookMessage>, ApiError>;
+    async fn update(&self, message: WebhookMessage) -> Result<Option<WebhookMessage>, ApiError>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<WebhookMessage>, ApiError>;
+    async fn create_delivery(&self, delivery: WebhookDelivery)
+        -> Result<WebhookDelivery, ApiError>;
+    async fn find_deliveries(
+        &self,
+        subscription_id: Uuid,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, ApiError>;
+}
+
+#[async_trait]
+pub trait UserIdentityRepository {
+    async fn create(&self, identity: UserIdentity) -> Result<UserIdentity, ApiError>;
+    async fn find_by_subject(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<UserIdentity>, ApiError>;
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, ApiError>;
+    async fn update(&self, identity: UserIdentity) -> Result<Option<UserIdentity>, ApiError>;
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<UserIdentity>, ApiError>;
+}
+
+#[async_trait]
+pub trait OrganizationRepository {
+    async fn create(&self, organization: Organization) -> Result<Organization, ApiError>;
+    async fn find_all(&self) -> Result<Vec<Organization>, ApiError>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Organization>, ApiError>;
+    async fn find_by_slug(&self, slug: &str) -> Result<Option<Organization>, ApiError>;
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Organization>, ApiError>;
+}
+
+#[async_trait]
+pub trait MembershipRepository {
+    /// Adds the member, or changes the role of an existing member.
+    async fn upsert(&self, membership: Membership) -> Result<Membership, ApiError>;
+    async fn find(&self, org_id: Uuid, user_id: Uuid) -> Result<Option<Membership>, ApiError>;
+    async fn find_by_org_id(&self, org_id: Uuid) -> Result<Vec<Membership>, ApiError>;
+    async fn delete(&self, org_id: Uuid, user_id: Uuid) -> Result<Option<Membership>, ApiError>;
+}
+
+#[async_trait]
+pub trait OrgInvitationRepository {
+    async fn create(&self, invitation: OrgInvitation) -> Result<OrgInvitation, ApiError>;
+    async fn find_by_token_hash(&self, token_hash: &str)
+        -> Result<Option<OrgInvitation>, ApiError>;
+    async fn find_by_org_id(&self, org_id: Uuid) -> Result<Vec<OrgInvitation>, ApiError>;
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<OrgInvitation>, ApiError>;
+}
+
+#[async_trait]
+pub trait GroupRepository {
+    async fn create(&self, group: Group) -> Result<Group, ApiError>;
+    async fn find_all(&self) -> Result<Vec<Group>, ApiError>;
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Group>, ApiError>;
+    async fn find_by_name(&self, name: &str) -> Result<Option<Group>, ApiError>;
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Group>, ApiError>;
+    async fn update(&self, group: Group) -> Result<Option<Group>, ApiError>;
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Group>, ApiError>;
+}
+
+#[async_trait]
+pub trait GroupMemberRepository {
+    /// Adds the member, returning `None` if the user already is a member of the group.
+    async fn create(&self, member: GroupMember) -> Result<Option<GroupMember>, ApiError>;
+    async fn find_by_group_id(&self, group_id: Uuid) -> Result<Vec<GroupMember>, ApiError>;
+    async fn delete(&self, group_id: Uuid, user_id: Uuid) -> Result<Option<GroupMember>, ApiError>;
+}
+
+#[async_trait]
+pub trait InvitationRepository {
+    async fn create(&self, invitation: Invitation) -> Result<Invitation, ApiError>;
+    async fn find_all(&self) -> Result<Vec<Invitation>, ApiError>;
+    async fn find_by_token_hash(&self, token_hash: &str) -> Result<Option<Invitation>, ApiError>;
+    /// Marks the invitation as used by the user, returning `None` if it was already used.
+    async fn mark_used(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, ApiError>;
+    /// Undoes [`InvitationRepository::mark_used`] by the user, so that the invitation can be used again.
+    async fn release(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, ApiError>;
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Invitation>, ApiError>;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{database::mock::MockDatabase, models::user::Role};
+    use crate::{
+        database::mock::MockDatabase,
+        models::{
+            audit_log::{event_type, AuditOutcome},
+            pagination::SortOrder,
+            user::UserSort,
+        },
+    };
 
     #[tokio::test]
     async fn create_user() {
@@ -149,7 +322,7 @@ mod tests {
             assert!(user_res.is_ok());
         }
 
-        let users_res = db.find_all().await;
+        let users_res = db.users().find_all().await;
         assert!(users_res.is_ok());
 
         let mut users = users_res.unwrap();
@@ -161,6 +334,114 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn find_users_page() {
+        let db = MockDatabase::new();
+
+        for i in 0..5 {
+            let mut user = User::new(&format!("test_user_{}", i), "test_password", &[Role::User]);
+            if i % 2 == 0 {
+                user = user.disable(None);
+            }
+            db.users().create(user).await.unwrap();
+        }
+        let _ = db
+            .users()
+            .create(User::new(
+                "another_user",
+                "test_password",
+                &[Role::User, Role::Admin],
+            ))
+            .await;
+        let org_id = Uuid::new_v4();
+        let tenant_user = db
+            .users()
+            .create(User::new("test_tenant", "test_password", &[Role::Admin]).with_org(org_id))
+            .await
+            .unwrap();
+
+        let mut query = UserQuery {
+            username_prefix: Some("test_".to_owned()),
+            sort: UserSort::Username,
+            order: SortOrder::Desc,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut usernames = Vec::new();
+        loop {
+            let page = db.users().find_page(&query).await.unwrap();
+            assert!(page.items.len() <= 2);
+            usernames.extend(page.items.into_iter().map(|user| user.username));
+
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(
+            usernames,
+            (0..5)
+                .rev()
+                .map(|i| format!("test_user_{}", i))
+                .collect::<Vec<_>>()
+        );
+
+        let query = UserQuery {
+            disabled: Some(false),
+            role: Some(Role::User),
+            ..Default::default()
+        };
+        let page = db.users().find_page(&query).await.unwrap();
+        assert_eq!(page.items.len(), 3);
+        assert!(page.next_cursor.is_none());
+
+        let query = UserQuery {
+            role: Some(Role::Admin),
+            ..Default::default()
+        };
+        let page = db.users().find_page(&query).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].username, "another_user");
+        assert_eq!(db.users().count().await.unwrap(), 6);
+
+        let query = UserQuery {
+            org_id: Some(org_id),
+            ..Default::default()
+        };
+        let page = db.users().find_page(&query).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, tenant_user.id);
+    }
+
+    #[tokio::test]
+    async fn find_users_page_cursor_sort_mismatch() {
+        let db = MockDatabase::new();
+        for i in 0..2 {
+            let _ = db
+                .users()
+                .create(User::new(
+                    &format!("test_user_{}", i),
+                    "test_password",
+                    &[Role::User],
+                ))
+                .await;
+        }
+
+        let query = UserQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = db.users().find_page(&query).await.unwrap();
+
+        let query = UserQuery {
+            sort: UserSort::Username,
+            cursor: page.next_cursor,
+            ..query
+        };
+        let page_res = db.users().find_page(&query).await;
+        assert!(matches!(page_res, Err(ApiError::BadRequest(_))));
+    }
+
     #[tokio::test]
     async fn find_user_by_id_not_found() {
         let db = MockDatabase::new();
@@ -179,7 +460,7 @@ mod tests {
         assert!(user_create_res.is_ok());
 
         let user_create = user_create_res.unwrap();
-        let user_res = db.find_by_id(user_create.id).await;
+        let user_res = db.users().find_by_id(user_create.id).await;
         assert!(user_res.is_ok());
 
         let user_opt = user_res.unwrap();
@@ -190,6 +471,152 @@ mod tests {
         assert_eq!(user.username, user_create.username);
     }
 
+    #[tokio::test]
+    async fn find_users_by_role() {
+        let db = MockDatabase::new();
+        let _ = db
+            .users()
+            .create(User::new("test_user", "test_password", &[Role::User]))
+            .await;
+        let _ = db
+            .users()
+            .create(User::new(
+                "test_admin",
+                "test_password",
+                &[Role::User, Role::Admin],
+            ))
+            .await;
+
+        let admins_res = db.users().find_by_role(Role::Admin).await;
+        assert!(admins_res.is_ok());
+
+        let admins = admins_res.unwrap();
+        assert_eq!(admins.len(), 1);
+        assert_eq!(admins[0].username, "test_admin");
+
+        let users = db.users().find_by_role(Role::User).await.unwrap();
+        assert_eq!(users.len(), 2);
+
+        // Roles granted by groups count as well.
+        let group = db
+            .groups()
+            .create(Group::new("ops", None, &[Role::Admin]))
+            .await
+            .unwrap();
+        let test_user = db
+            .users()
+            .find_by_username("test_user")
+            .await
+            .unwrap()
+            .unwrap();
+        db.group_members()
+            .create(GroupMember::new(group.id, test_user.id))
+            .await
+            .unwrap();
+
+        let admins = db.users().find_by_role(Role::Admin).await.unwrap();
+        assert_eq!(admins.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn update_user() {
+        let db = MockDatabase::new();
+        let user = db
+            .users()
+            .create(User::new("test_user", "test_password", &[Role::User]))
+            .await
+            .unwrap();
+
+        let updated_res = db
+            .users()
+            .update(User {
+                roles: vec![Role::User, Role::Admin],
+                token_version: 42,
+                ..user.clone()
+            })
+            .await;
+        assert!(updated_res.is_ok());
+
+        let updated = updated_res.unwrap().unwrap();
+        assert!(updated.has_role(Role::Admin));
+        assert_eq!(updated.token_version, user.token_version);
+
+        let missing = User::new("missing_user", "test_password", &[Role::User]);
+        assert!(db.users().update(missing).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn increment_user_token_version() {
+        let db = MockDatabase::new();
+        let user = db
+            .users()
+            .create(User::new("test_user", "test_password", &[Role::User]))
+            .await
+            .unwrap();
+
+        let version = db.users().increment_token_version(user.id).await;
+        assert_eq!(version.unwrap(), Some(user.token_version + 1));
+
+        let version = db.users().increment_token_version(Uuid::new_v4()).await;
+        assert_eq!(version.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn soft_delete_user() {
+        let db = MockDatabase::new();
+        let user = db
+            .users()
+            .create(User::new("test_user", "test_password", &[Role::User]))
+            .await
+            .unwrap();
+
+        let deleted_res = db.users().soft_delete(user.id).await;
+        assert!(deleted_res.unwrap().is_some_and(|u| u.deleted_at.is_some()));
+
+        assert!(db.users().find_by_id(user.id).await.unwrap().is_none());
+        assert!(db
+            .users()
+            .find_by_username("test_user")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db.users().find_all().await.unwrap().is_empty());
+        assert_eq!(db.users().count().await.unwrap(), 0);
+        assert!(db.users().soft_delete(user.id).await.unwrap().is_none());
+
+        // The username of a soft deleted user can be taken again.
+        let another_user_res = db
+            .users()
+            .create(User::new("test_user", "test_password", &[Role::User]))
+            .await;
+        assert!(another_user_res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn purge_deleted_users() {
+        let db = MockDatabase::new();
+        let user = db
+            .users()
+            .create(User::new("test_user", "test_password", &[Role::User]))
+            .await
+            .unwrap();
+        let _ = db
+            .users()
+            .create(User::new("another_user", "test_password", &[Role::User]))
+            .await;
+        let _ = db.users().soft_delete(user.id).await;
+
+        let purged_count = db
+            .users()
+            .purge_deleted(Utc::now() - chrono::Duration::days(1))
+            .await;
+        assert_eq!(purged_count.unwrap(), 0);
+
+        let purged_count = db.users().purge_deleted(Utc::now()).await;
+        assert_eq!(purged_count.unwrap(), 1);
+        assert_eq!(db.users().find_all().await.unwrap().len(), 1);
+    }
+
     #[tokio::test]
     async fn delete_all_users() {
         let db = MockDatabase::new();
@@ -211,4 +638,71 @@ mod tests {
         assert!(deleted_count_res.is_ok());
         assert_eq!(deleted_count_res.unwrap(), users_ctr);
     }
+
+    #[tokio::test]
+    async fn find_audit_logs_page() {
+        let db = MockDatabase::new();
+        let (actor_id, target_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        for _ in 0..3 {
+            let log_res = db
+                .audit_logs()
+                .create(
+                    AuditLog::new(event_type::IMPERSONATION, Some(actor_id), Some(target_id))
+                        .with_metadata(serde_json::json!({ "jti": Uuid::new_v4() })),
+                )
+                .await;
+            assert!(log_res.is_ok());
+        }
+        let _ = db
+            .audit_logs()
+            .create(AuditLog::new(event_type::LOGIN, None, Some(target_id)).failed())
+            .await;
+
+        let mut query = AuditLogQuery {
+            event_type: Some(event_type::IMPERSONATION.to_owned()),
+            target_id: Some(target_id),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = db.audit_logs().find_page(&query).await.unwrap();
+        assert_eq!(page.items.len(), 2);
+        assert!(page.items[0].created_at >= page.items[1].created_at);
+        assert_eq!(page.items[0].actor_id, Some(actor_id));
+
+        query.cursor = page.next_cursor;
+        let page = db.audit_logs().find_page(&query).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert!(page.next_cursor.is_none());
+
+        let query = AuditLogQuery {
+            outcome: Some(AuditOutcome::Failure),
+            ..Default::default()
+        };
+        let page = db.audit_logs().find_page(&query).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].event_type, event_type::LOGIN);
+    }
+
+    #[tokio::test]
+    async fn delete_old_audit_logs() {
+        let db = MockDatabase::new();
+        let _ = db
+            .audit_logs()
+            .create(AuditLog {
+                created_at: Utc::now() - chrono::Duration::days(2),
+                ..AuditLog::new(event_type::LOGIN, None, None)
+            })
+            .await;
+        let _ = db
+            .audit_logs()
+            .create(AuditLog::new(event_type::LOGIN, None, None))
+            .await;
+
+        let deleted_count = db
+            .audit_logs()
+            .delete_older_than(Utc::now() - chrono::Duration::days(1))
+            .await;
+        assert_eq!(deleted_count.unwrap(), 1);
+    }
 }
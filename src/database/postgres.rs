@@ -1,14 +1,31 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
 use crate::{
     config::Config,
-    database::RefreshTokenRepository,
+    database::{
+        AuditLogRepository, ClientRepository, GroupMemberRepository, GroupRepository,
+        InvitationRepository, MembershipRepository, OrgInvitationRepository,
+        OrganizationRepository, RefreshTokenRepository, UserIdentityRepository,
+        WebhookOutboxRepository, WebhookRepository,
+    },
     error::ApiError,
-    models::{refresh_token::RefreshToken, user::User},
+    models::{
+        audit_log::{AuditLog, AuditLogQuery},
+        client::Client,
+        group::{Group, GroupMember},
+        invitation::Invitation,
+        organization::{Membership, OrgInvitation, Organization},
+        pagination::{Page, SortOrder},
+        refresh_token::RefreshToken,
+        user::{Role, User, UserCursor, UserQuery, UserSort},
+        user_identity::UserIdentity,
+        webhook::{WebhookDelivery, WebhookMessage, WebhookSubscription},
+    },
 };
 
 use super::{Database, UserRepository};
@@ -46,6 +63,58 @@ impl Database for PostgresDatabase {
     fn refresh_tokens(&self) -> &dyn RefreshTokenRepository {
         self
     }
+
+    fn clients(&self) -> &dyn ClientRepository {
+        self
+    }
+
+    fn audit_logs(&self) -> &dyn AuditLogRepository {
+        self
+    }
+
+    fn webhooks(&self) -> &dyn WebhookRepository {
+        self
+    }
+
+    fn webhook_outbox(&self) -> &dyn WebhookOutboxRepository {
+        self
+    }
+
+    fn user_identities(&self) -> &dyn UserIdentityRepository {
+        self
+    }
+
+    fn organizations(&self) -> &dyn OrganizationRepository {
+        self
+    }
+
+    fn memberships(&self) -> &dyn MembershipRepository {
+        self
+    }
+
+    fn org_invitations(&self) -> &dyn OrgInvitationRepository {
+        self
+    }
+
+    fn groups(&self) -> &dyn GroupRepository {
+        self
+    }
+
+    fn group_members(&self) -> &dyn GroupMemberRepository {
+        self
+    }
+
+    fn invitations(&self) -> &dyn InvitationRepository {
+        self
+    }
+}
+
+/// Escapes the wildcards of a `LIKE` pattern, so that the value is matched literally.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
 
 #[async_trait]
@@ -55,15 +124,24 @@ impl UserRepository for PostgresDatabase {
 
         let created_user = sqlx::query_as::<_, User>(
             r#"
-            INSERT INTO users (id, username, password_hash, roles, created_at, updated_at) 
-            VALUES ($1, $2, $3, $4, $5, $6)
-            RETURNING id, username, password_hash, roles, created_at, updated_at
+            INSERT INTO users (id, username, password_hash, roles, email, email_verified, token_version,
+                disabled_at, disabled_reason, auth_source, org_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            RETURNING id, username, password_hash, roles, email, email_verified, token_version,
+                disabled_at, disabled_reason, deleted_at, auth_source, org_id, created_at, updated_at
             "#,
         )
         .bind(user.id)
         .bind(user.username)
         .bind(user.password_hash)
         .bind(user.roles)
+        .bind(user.email)
+        .bind(user.email_verified)
+        .bind(user.token_version)
+        .bind(user.disabled_at)
+        .bind(user.disabled_reason)
+        .bind(user.auth_source)
+        .bind(user.org_id)
         .bind(user.created_at)
         .bind(user.updated_at)
         .fetch_one(&mut *tx)
@@ -74,7 +152,7 @@ impl UserRepository for PostgresDatabase {
     }
 
     async fn find_all(&self) -> Result<Vec<User>, ApiError> {
-        let users = sqlx::query_as::<_, User>("SELECT * FROM users")
+        let users = sqlx::query_as::<_, User>("SELECT * FROM users WHERE deleted_at IS NULL")
             .fetch_all(&self.pool)
             .await?;
 
@@ -82,23 +160,228 @@ impl UserRepository for PostgresDatabase {
     }
 
     async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, ApiError> {
-        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
-            .bind(id)
-            .fetch_optional(&self.pool)
-            .await?;
+        let user =
+            sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
 
         Ok(user)
     }
 
     async fn find_by_username(&self, username: &str) -> Result<Option<User>, ApiError> {
-        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = $1")
-            .bind(username)
-            .fetch_optional(&self.pool)
-            .await?;
+        let user = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE username = $1 AND org_id IS NULL AND deleted_at IS NULL",
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn find_by_org_username(
+        &self,
+        org_id: Uuid,
+        username: &str,
+    ) -> Result<Option<User>, ApiError> {
+        let user = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE org_id = $1 AND username = $2 AND deleted_at IS NULL",
+        )
+        .bind(org_id)
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
 
         Ok(user)
     }
 
+    async fn find_by_role(&self, role: Role) -> Result<Vec<User>, ApiError> {
+        let users = sqlx::query_as::<_, User>(
+            r#"
+            SELECT * FROM users
+            WHERE deleted_at IS NULL AND (
+                $1 = ANY(roles) OR id IN (
+                    SELECT group_members.user_id FROM group_members
+                    JOIN groups ON groups.id = group_members.group_id
+                    WHERE $1 = ANY(groups.roles)
+                )
+            )
+            "#,
+        )
+        .bind(role)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    async fn find_page(&self, query: &UserQuery) -> Result<Page<User>, ApiError> {
+        let cursor = query.decode_cursor()?;
+        let limit = query.page_size();
+
+        let mut builder =
+            QueryBuilder::<Postgres>::new("SELECT * FROM users WHERE deleted_at IS NULL");
+
+        match query.org_id {
+            Some(org_id) => builder.push(" AND org_id = ").push_bind(org_id),
+            None => builder.push(" AND org_id IS NULL"),
+        };
+        if let Some(prefix) = query.username_prefix.as_deref() {
+            builder
+                .push(" AND username LIKE ")
+                .push_bind(format!("{}%", escape_like(prefix)));
+        }
+        if let Some(role) = query.role {
+            builder.push(" AND ").push_bind(role).push(" = ANY(roles)");
+        }
+        if let Some(created_after) = query.created_after {
+            builder.push(" AND created_at >= ").push_bind(created_after);
+        }
+        if let Some(created_before) = query.created_before {
+            builder.push(" AND created_at < ").push_bind(created_before);
+        }
+        match query.disabled {
+            Some(true) => builder.push(" AND disabled_at IS NOT NULL"),
+            Some(false) => builder.push(" AND disabled_at IS NULL"),
+            None => &mut builder,
+        };
+
+        // Keyset pagination, i.e. continue right after the last user of the previous page.
+        let op = match query.order {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        };
+        match cursor {
+            Some(UserCursor::CreatedAt { created_at, id }) => {
+                builder
+                    .push(format!(" AND (created_at, id) {} (", op))
+                    .push_bind(created_at)
+                    .push(", ")
+                    .push_bind(id)
+                    .push(")");
+            }
+            Some(UserCursor::Username { username, id }) => {
+                builder
+                    .push(format!(" AND (username, id) {} (", op))
+                    .push_bind(username)
+                    .push(", ")
+                    .push_bind(id)
+                    .push(")");
+            }
+            None => {}
+        }
+
+        let column = match query.sort {
+            UserSort::CreatedAt => "created_at",
+            UserSort::Username => "username",
+        };
+        let order = query.order.as_sql();
+        builder
+            .push(format!(
+                " ORDER BY {} {}, id {} LIMIT ",
+                column, order, order
+            ))
+            .push_bind(limit as i64 + 1);
+
+        let users = builder
+            .build_query_as::<User>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(Page::from_items(users, limit, |user| {
+            query.encode_cursor(user)
+        }))
+    }
+
+    async fn count(&self) -> Result<u64, ApiError> {
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM users WHERE deleted_at IS NULL AND org_id IS NULL",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count as u64)
+    }
+
+    async fn update(&self, user: User) -> Result<Option<User>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated_user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users
+            SET username = $2, password_hash = $3, roles = $4, email = $5, email_verified = $6,
+                disabled_at = $7, disabled_reason = $8, updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(user.id)
+        .bind(user.username)
+        .bind(user.password_hash)
+        .bind(user.roles)
+        .bind(user.email)
+        .bind(user.email_verified)
+        .bind(user.disabled_at)
+        .bind(user.disabled_reason)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(updated_user)
+    }
+
+    async fn increment_token_version(&self, id: Uuid) -> Result<Option<i64>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let version = sqlx::query_scalar::<_, i64>(
+            r#"
+            UPDATE users
+            SET token_version = token_version + 1
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING token_version
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(version)
+    }
+
+    async fn soft_delete(&self, id: Uuid) -> Result<Option<User>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users
+            SET deleted_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(deleted_user)
+    }
+
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<u64, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_count = sqlx::query("DELETE FROM users WHERE deleted_at <= $1")
+            .bind(before)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(deleted_count)
+    }
+
     async fn delete_all(&self) -> Result<u64, ApiError> {
         let mut tx = self.pool.begin().await?;
 
@@ -168,6 +451,19 @@ impl RefreshTokenRepository for PostgresDatabase {
         Ok(tok)
     }
 
+    async fn delete_by_sub(&self, sub: Uuid) -> Result<u64, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_count = sqlx::query("DELETE FROM refresh_tokens WHERE sub = $1")
+            .bind(sub)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(deleted_count)
+    }
+
     async fn find_by_sub(&self, sub: Uuid) -> Result<Vec<RefreshToken>, ApiError> {
         let tokens =
             sqlx::query_as::<_, RefreshToken>("SELECT * FROM refresh_tokens WHERE sub = $1")
@@ -188,3 +484,998 @@ impl RefreshTokenRepository for PostgresDatabase {
         return Ok(token);
     }
 }
+
+#[async_trait]
+impl ClientRepository for PostgresDatabase {
+    async fn create(&self, client: Client) -> Result<Client, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_client = sqlx::query_as::<_, Client>(
+            r#"
+            INSERT INTO clients (id, client_id, secret_hash, scopes, redirect_uris, grant_types, exchange_audiences, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id, client_id, secret_hash, scopes, redirect_uris, grant_types, exchange_audiences, created_at, updated_at
+            "#,
+        )
+        .bind(client.id)
+        .bind(client.client_id)
+        .bind(client.secret_hash)
+        .bind(client.scopes)
+        .bind(client.redirect_uris)
+        .bind(client.grant_types)
+        .bind(client.exchange_audiences)
+        .bind(client.created_at)
+        .bind(client.updated_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_client)
+    }
+
+    async fn find_all(&self) -> Result<Vec<Client>, ApiError> {
+        let clients = sqlx::query_as::<_, Client>("SELECT * FROM clients")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(clients)
+    }
+
+    async fn find_by_client_id(&self, client_id: &str) -> Result<Option<Client>, ApiError> {
+        let client = sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(client)
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Client>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let client = sqlx::query_as::<_, Client>(
+            r#"
+            DELETE FROM clients
+            WHERE id = $1
+            RETURNING id, client_id, secret_hash, scopes, redirect_uris, grant_types, exchange_audiences, created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl AuditLogRepository for PostgresDatabase {
+    async fn create(&self, audit_log: AuditLog) -> Result<AuditLog, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_log = sqlx::query_as::<_, AuditLog>(
+            r#"
+            INSERT INTO audit_logs (id, event_type, actor_id, target_id, ip_address, user_agent,
+                outcome, metadata, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id, event_type, actor_id, target_id, ip_address, user_agent, outcome,
+                metadata, created_at
+            "#,
+        )
+        .bind(audit_log.id)
+        .bind(audit_log.event_type)
+        .bind(audit_log.actor_id)
+        .bind(audit_log.target_id)
+        .bind(audit_log.ip_address)
+        .bind(audit_log.user_agent)
+        .bind(audit_log.outcome)
+        .bind(audit_log.metadata)
+        .bind(audit_log.created_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_log)
+    }
+
+    async fn find_page(&self, query: &AuditLogQuery) -> Result<Page<AuditLog>, ApiError> {
+        let cursor = query.decode_cursor()?;
+        let limit = query.page_size();
+
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM audit_logs WHERE TRUE");
+
+        if let Some(event_type) = query.event_type.as_deref() {
+            builder.push(" AND event_type = ").push_bind(event_type);
+        }
+        if let Some(actor_id) = query.actor_id {
+            builder.push(" AND actor_id = ").push_bind(actor_id);
+        }
+        if let Some(target_id) = query.target_id {
+            builder.push(" AND target_id = ").push_bind(target_id);
+        }
+        if let Some(outcome) = query.outcome {
+            builder.push(" AND outcome = ").push_bind(outcome);
+        }
+        if let Some(ip_address) = query.ip_address.as_deref() {
+            builder.push(" AND ip_address = ").push_bind(ip_address);
+        }
+        if let Some(user_agent) = query.user_agent.as_deref() {
+            builder.push(" AND user_agent = ").push_bind(user_agent);
+        }
+        if let Some(created_after) = query.created_after {
+            builder.push(" AND created_at >= ").push_bind(created_after);
+        }
+        if let Some(created_before) = query.created_before {
+            builder.push(" AND created_at < ").push_bind(created_before);
+        }
+
+        let order = query.order.as_sql();
+        if let Some(cursor) = cursor {
+            let op = match query.order {
+                SortOrder::Asc => ">",
+                SortOrder::Desc => "<",
+            };
+            builder
+                .push(format!(" AND (created_at, id) {} (", op))
+                .push_bind(cursor.created_at)
+                .push(", ")
+                .push_bind(cursor.id)
+                .push(")");
+        }
+
+        builder
+            .push(format!(
+                " ORDER BY created_at {}, id {} LIMIT ",
+                order, order
+            ))
+            .push_bind(limit as i64 + 1);
+
+        let logs = builder
+            .build_query_as::<AuditLog>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(Page::from_items(logs, limit, |log| {
+            query.encode_cursor(log)
+        }))
+    }
+
+    async fn delete_older_than(&self, before: DateTime<Utc>) -> Result<u64, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_count = sqlx::query("DELETE FROM audit_logs WHERE created_at <= $1")
+            .bind(before)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(deleted_count)
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for PostgresDatabase {
+    async fn create(
+        &self,
+        subscription: WebhookSubscription,
+    ) -> Result<WebhookSubscription, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_subscription = sqlx::query_as::<_, WebhookSubscription>(
+            r#"
+            INSERT INTO webhook_subscriptions (id, url, secret, event_types, active, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, url, secret, event_types, active, created_at, updated_at
+            "#,
+        )
+        .bind(subscription.id)
+        .bind(subscription.url)
+        .bind(subscription.secret)
+        .bind(subscription.event_types)
+        .bind(subscription.active)
+        .bind(subscription.created_at)
+        .bind(subscription.updated_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_subscription)
+    }
+
+    async fn find_all(&self) -> Result<Vec<WebhookSubscription>, ApiError> {
+        let subscriptions = sqlx::query_as::<_, WebhookSubscription>(
+            "SELECT * FROM webhook_subscriptions ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<WebhookSubscription>, ApiError> {
+        let subscription = sqlx::query_as::<_, WebhookSubscription>(
+            "SELECT * FROM webhook_subscriptions WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(subscription)
+    }
+
+    async fn find_by_event_type(
+        &self,
+        event_type: &str,
+    ) -> Result<Vec<WebhookSubscription>, ApiError> {
+        let subscriptions = sqlx::query_as::<_, WebhookSubscription>(
+            "SELECT * FROM webhook_subscriptions WHERE active AND $1 = ANY(event_types)",
+        )
+        .bind(event_type)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    async fn update(
+        &self,
+        subscription: WebhookSubscription,
+    ) -> Result<Option<WebhookSubscription>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated_subscription = sqlx::query_as::<_, WebhookSubscription>(
+            r#"
+            UPDATE webhook_subscriptions
+            SET url = $2, secret = $3, event_types = $4, active = $5, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, url, secret, event_types, active, created_at, updated_at
+            "#,
+        )
+        .bind(subscription.id)
+        .bind(subscription.url)
+        .bind(subscription.secret)
+        .bind(subscription.event_types)
+        .bind(subscription.active)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(updated_subscription)
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<WebhookSubscription>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let subscription = sqlx::query_as::<_, WebhookSubscription>(
+            r#"
+            DELETE FROM webhook_subscriptions
+            WHERE id = $1
+            RETURNING id, url, secret, event_types, active, created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(subscription)
+    }
+}
+
+#[async_trait]
+impl WebhookOutboxRepository for PostgresDatabase {
+    async fn enqueue(&self, messages: Vec<WebhookMessage>) -> Result<u64, ApiError> {
+        if messages.is_empty() {
+            return Ok(0);
+        }
+
+        let mut builder = QueryBuilder::<Postgres>::new(
+            "INSERT INTO webhook_outbox (id, subscription_id, event_type, payload, status, \
+             attempts, next_attempt_at, created_at, updated_at) ",
+        );
+        builder.push_values(messages, |mut b, message| {
+            b.push_bind(message.id)
+                .push_bind(message.subscription_id)
+                .push_bind(message.event_type)
+                .push_bind(message.payload)
+                .push_bind(message.status)
+                .push_bind(message.attempts)
+                .push_bind(message.next_attempt_at)
+                .push_bind(message.created_at)
+                .push_bind(message.updated_at);
+        });
+
+        let mut tx = self.pool.begin().await?;
+        let res = builder.build().execute(&mut *tx).await?;
+
+        tx.commit().await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn claim_due(
+        &self,
+        lease_until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<WebhookMessage>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let messages = sqlx::query_as::<_, WebhookMessage>(
+            r#"
+            UPDATE webhook_outbox
+            SET next_attempt_at = $1, updated_at = NOW()
+            WHERE id IN (
+                SELECT id FROM webhook_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, subscription_id, event_type, payload, status, attempts,
+                next_attempt_at, created_at, updated_at
+            "#,
+        )
+        .bind(lease_until)
+        .bind(limit as i64)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(messages)
+    }
+
+    async fn update(&self, message: WebhookMessage) -> Result<Option<WebhookMessage>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated_message = sqlx::query_as::<_, WebhookMessage>(
+            r#"
+            UPDATE webhook_outbox
+            SET status = $2, attempts = $3, next_attempt_at = $4, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, subscription_id, event_type, payload, status, attempts,
+                next_attempt_at, created_at, updated_at
+            "#,
+        )
+        .bind(message.id)
+        .bind(message.status)
+        .bind(message.attempts)
+        .bind(message.next_attempt_at)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(updated_message)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<WebhookMessage>, ApiError> {
+        let message =
+            sqlx::query_as::<_, WebhookMessage>("SELECT * FROM webhook_outbox WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(message)
+    }
+
+    async fn create_delivery(
+        &self,
+        delivery: WebhookDelivery,
+    ) -> Result<WebhookDelivery, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_delivery = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            INSERT INTO webhook_deliveries (id, message_id, subscription_id, attempt, status_code,
+                error, duration_ms, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, message_id, subscription_id, attempt, status_code, error, duration_ms,
+                created_at
+            "#,
+        )
+        .bind(delivery.id)
+        .bind(delivery.message_id)
+        .bind(delivery.subscription_id)
+        .bind(delivery.attempt)
+        .bind(delivery.status_code)
+        .bind(delivery.error)
+        .bind(delivery.duration_ms)
+        .bind(delivery.created_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_delivery)
+    }
+
+    async fn find_deliveries(
+        &self,
+        subscription_id: Uuid,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, ApiError> {
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE subscription_id = $1
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+}
+
+#[async_trait]
+impl UserIdentityRepository for PostgresDatabase {
+    async fn create(&self, identity: UserIdentity) -> Result<UserIdentity, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_identity = sqlx::query_as::<_, UserIdentity>(
+            r#"
+            INSERT INTO user_identities (id, user_id, provider, subject, email, created_at, last_login_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, user_id, provider, subject, email, created_at, last_login_at
+            "#,
+        )
+        .bind(identity.id)
+        .bind(identity.user_id)
+        .bind(identity.provider)
+        .bind(identity.subject)
+        .bind(identity.email)
+        .bind(identity.created_at)
+        .bind(identity.last_login_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_identity)
+    }
+
+    async fn find_by_subject(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<UserIdentity>, ApiError> {
+        let identity = sqlx::query_as::<_, UserIdentity>(
+            "SELECT * FROM user_identities WHERE provider = $1 AND subject = $2",
+        )
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(identity)
+    }
+
+    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, ApiError> {
+        let identities = sqlx::query_as::<_, UserIdentity>(
+            "SELECT * FROM user_identities WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(identities)
+    }
+
+    async fn update(&self, identity: UserIdentity) -> Result<Option<UserIdentity>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated_identity = sqlx::query_as::<_, UserIdentity>(
+            r#"
+            UPDATE user_identities
+            SET email = $2, last_login_at = $3
+            WHERE id = $1
+            RETURNING id, user_id, provider, subject, email, created_at, last_login_at
+            "#,
+        )
+        .bind(identity.id)
+        .bind(identity.email)
+        .bind(identity.last_login_at)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(updated_identity)
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<UserIdentity>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_identity = sqlx::query_as::<_, UserIdentity>(
+            r#"
+            DELETE FROM user_identities
+            WHERE id = $1
+            RETURNING id, user_id, provider, subject, email, created_at, last_login_at
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(deleted_identity)
+    }
+}
+
+#[async_trait]
+impl OrganizationRepository for PostgresDatabase {
+    async fn create(&self, organization: Organization) -> Result<Organization, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_organization = sqlx::query_as::<_, Organization>(
+            r#"
+            INSERT INTO organizations (id, slug, name, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, slug, name, created_at, updated_at
+            "#,
+        )
+        .bind(organization.id)
+        .bind(organization.slug)
+        .bind(organization.name)
+        .bind(organization.created_at)
+        .bind(organization.updated_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_organization)
+    }
+
+    async fn find_all(&self) -> Result<Vec<Organization>, ApiError> {
+        let organizations =
+            sqlx::query_as::<_, Organization>("SELECT * FROM organizations ORDER BY slug")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(organizations)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Organization>, ApiError> {
+        let organization =
+            sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(organization)
+    }
+
+    async fn find_by_slug(&self, slug: &str) -> Result<Option<Organization>, ApiError> {
+        let organization =
+            sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE slug = $1")
+                .bind(slug)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(organization)
+    }
+
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Organization>, ApiError> {
+        let organizations = sqlx::query_as::<_, Organization>(
+            r#"
+            SELECT o.* FROM organizations o
+            JOIN org_memberships m ON m.org_id = o.id
+            WHERE m.user_id = $1
+            ORDER BY o.slug
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(organizations)
+    }
+}
+
+#[async_trait]
+impl MembershipRepository for PostgresDatabase {
+    async fn upsert(&self, membership: Membership) -> Result<Membership, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let saved_membership = sqlx::query_as::<_, Membership>(
+            r#"
+            INSERT INTO org_memberships (org_id, user_id, role, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (org_id, user_id) DO UPDATE SET role = EXCLUDED.role, updated_at = NOW()
+            RETURNING org_id, user_id, role, created_at, updated_at
+            "#,
+        )
+        .bind(membership.org_id)
+        .bind(membership.user_id)
+        .bind(membership.role)
+        .bind(membership.created_at)
+        .bind(membership.updated_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(saved_membership)
+    }
+
+    async fn find(&self, org_id: Uuid, user_id: Uuid) -> Result<Option<Membership>, ApiError> {
+        let membership = sqlx::query_as::<_, Membership>(
+            "SELECT * FROM org_memberships WHERE org_id = $1 AND user_id = $2",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(membership)
+    }
+
+    async fn find_by_org_id(&self, org_id: Uuid) -> Result<Vec<Membership>, ApiError> {
+        let memberships = sqlx::query_as::<_, Membership>(
+            "SELECT * FROM org_memberships WHERE org_id = $1 ORDER BY created_at",
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(memberships)
+    }
+
+    async fn delete(&self, org_id: Uuid, user_id: Uuid) -> Result<Option<Membership>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_membership = sqlx::query_as::<_, Membership>(
+            r#"
+            DELETE FROM org_memberships
+            WHERE org_id = $1 AND user_id = $2
+            RETURNING org_id, user_id, role, created_at, updated_at
+            "#,
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(deleted_membership)
+    }
+}
+
+#[async_trait]
+impl OrgInvitationRepository for PostgresDatabase {
+    async fn create(&self, invitation: OrgInvitation) -> Result<OrgInvitation, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_invitation = sqlx::query_as::<_, OrgInvitation>(
+            r#"
+            INSERT INTO org_invitations (id, org_id, email, role, token_hash, invited_by, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, org_id, email, role, token_hash, invited_by, expires_at, created_at
+            "#,
+        )
+        .bind(invitation.id)
+        .bind(invitation.org_id)
+        .bind(invitation.email)
+        .bind(invitation.role)
+        .bind(invitation.token_hash)
+        .bind(invitation.invited_by)
+        .bind(invitation.expires_at)
+        .bind(invitation.created_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_invitation)
+    }
+
+    async fn find_by_token_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<OrgInvitation>, ApiError> {
+        let invitation = sqlx::query_as::<_, OrgInvitation>(
+            "SELECT * FROM org_invitations WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    async fn find_by_org_id(&self, org_id: Uuid) -> Result<Vec<OrgInvitation>, ApiError> {
+        let invitations = sqlx::query_as::<_, OrgInvitation>(
+            "SELECT * FROM org_invitations WHERE org_id = $1 ORDER BY created_at",
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(invitations)
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<OrgInvitation>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_invitation = sqlx::query_as::<_, OrgInvitation>(
+            r#"
+            DELETE FROM org_invitations
+            WHERE id = $1
+            RETURNING id, org_id, email, role, token_hash, invited_by, expires_at, created_at
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(deleted_invitation)
+    }
+}
+
+#[async_trait]
+impl GroupRepository for PostgresDatabase {
+    async fn create(&self, group: Group) -> Result<Group, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_group = sqlx::query_as::<_, Group>(
+            r#"
+            INSERT INTO groups (id, name, description, roles, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, name, description, roles, created_at, updated_at
+            "#,
+        )
+        .bind(group.id)
+        .bind(group.name)
+        .bind(group.description)
+        .bind(group.roles)
+        .bind(group.created_at)
+        .bind(group.updated_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_group)
+    }
+
+    async fn find_all(&self) -> Result<Vec<Group>, ApiError> {
+        let groups = sqlx::query_as::<_, Group>("SELECT * FROM groups ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(groups)
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Group>, ApiError> {
+        let group = sqlx::query_as::<_, Group>("SELECT * FROM groups WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(group)
+    }
+
+    async fn find_by_name(&self, name: &str) -> Result<Option<Group>, ApiError> {
+        let group = sqlx::query_as::<_, Group>("SELECT * FROM groups WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(group)
+    }
+
+    async fn find_by_member(&self, user_id: Uuid) -> Result<Vec<Group>, ApiError> {
+        let groups = sqlx::query_as::<_, Group>(
+            r#"
+            SELECT g.* FROM groups g
+            JOIN group_members m ON m.group_id = g.id
+            WHERE m.user_id = $1
+            ORDER BY g.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(groups)
+    }
+
+    async fn update(&self, group: Group) -> Result<Option<Group>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated_group = sqlx::query_as::<_, Group>(
+            r#"
+            UPDATE groups
+            SET name = $2, description = $3, roles = $4, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, name, description, roles, created_at, updated_at
+            "#,
+        )
+        .bind(group.id)
+        .bind(group.name)
+        .bind(group.description)
+        .bind(group.roles)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(updated_group)
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Group>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_group = sqlx::query_as::<_, Group>(
+            r#"
+            DELETE FROM groups
+            WHERE id = $1
+            RETURNING id, name, description, roles, created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(deleted_group)
+    }
+}
+
+#[async_trait]
+impl GroupMemberRepository for PostgresDatabase {
+    async fn create(&self, member: GroupMember) -> Result<Option<GroupMember>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_member = sqlx::query_as::<_, GroupMember>(
+            r#"
+            INSERT INTO group_members (group_id, user_id, created_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (group_id, user_id) DO NOTHING
+            RETURNING group_id, user_id, created_at
+            "#,
+        )
+        .bind(member.group_id)
+        .bind(member.user_id)
+        .bind(member.created_at)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_member)
+    }
+
+    async fn find_by_group_id(&self, group_id: Uuid) -> Result<Vec<GroupMember>, ApiError> {
+        let members = sqlx::query_as::<_, GroupMember>(
+            "SELECT * FROM group_members WHERE group_id = $1 ORDER BY created_at",
+        )
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(members)
+    }
+
+    async fn delete(&self, group_id: Uuid, user_id: Uuid) -> Result<Option<GroupMember>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_member = sqlx::query_as::<_, GroupMember>(
+            r#"
+            DELETE FROM group_members
+            WHERE group_id = $1 AND user_id = $2
+            RETURNING group_id, user_id, created_at
+            "#,
+        )
+        .bind(group_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(deleted_member)
+    }
+}
+
+#[async_trait]
+impl InvitationRepository for PostgresDatabase {
+    async fn create(&self, invitation: Invitation) -> Result<Invitation, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let created_invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            INSERT INTO invitations (id, email, roles, token_hash, created_by, expires_at, used_at, used_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id, email, roles, token_hash, created_by, expires_at, used_at, used_by, created_at
+            "#,
+        )
+        .bind(invitation.id)
+        .bind(invitation.email)
+        .bind(invitation.roles)
+        .bind(invitation.token_hash)
+        .bind(invitation.created_by)
+        .bind(invitation.expires_at)
+        .bind(invitation.used_at)
+        .bind(invitation.used_by)
+        .bind(invitation.created_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(created_invitation)
+    }
+
+    async fn find_all(&self) -> Result<Vec<Invitation>, ApiError> {
+        let invitations =
+            sqlx::query_as::<_, Invitation>("SELECT * FROM invitations ORDER BY created_at DESC")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(invitations)
+    }
+
+    async fn find_by_token_hash(&self, token_hash: &str) -> Result<Option<Invitation>, ApiError> {
+        let invitation =
+            sqlx::query_as::<_, Invitation>("SELECT * FROM invitations WHERE token_hash = $1")
+                .bind(token_hash)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(invitation)
+    }
+
+    async fn mark_used(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let used_invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            UPDATE invitations
+            SET used_at = NOW(), used_by = $2
+            WHERE id = $1 AND used_at IS NULL
+            RETURNING id, email, roles, token_hash, created_by, expires_at, used_at, used_by, created_at
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(used_invitation)
+    }
+
+    async fn release(&self, id: Uuid, user_id: Uuid) -> Result<Option<Invitation>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let released_invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            UPDATE invitations
+            SET used_at = NULL, used_by = NULL
+            WHERE id = $1 AND used_by = $2
+            RETURNING id, email, roles, token_hash, created_by, expires_at, used_at, used_by, created_at
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(released_invitation)
+    }
+
+    async fn delete_by_id(&self, id: Uuid) -> Result<Option<Invitation>, ApiError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted_invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            DELETE FROM invitations
+            WHERE id = $1
+            RETURNING id, email, roles, token_hash, created_by, expires_at, used_at, used_by, created_at
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(deleted_invitation)
+    }
+}
@@ -4,6 +4,13 @@ use redis::aio::MultiplexedConnection;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use crate::models::{
+    authorization_code::AuthorizationCode,
+    device_authorization::{DeviceAuthorization, DevicePolling},
+    user::Role,
+    user_identity::FederatedLogin,
+};
+
 #[derive(Clone)]
 pub struct RedisCache {
     conn: Arc<Mutex<MultiplexedConnection>>,
@@ -24,6 +31,50 @@ impl RedisCache {
             prefix: "token:",
         }
     }
+
+    pub fn token_versions(&self) -> TokenVersionCache {
+        TokenVersionCache {
+            conn: self.conn.clone(),
+            prefix: "tokenversion:",
+        }
+    }
+
+    pub fn group_roles(&self) -> GroupRoleCache {
+        GroupRoleCache {
+            conn: self.conn.clone(),
+            prefix: "grouproles:",
+        }
+    }
+
+    pub fn authorization_codes(&self) -> AuthorizationCodeStore {
+        AuthorizationCodeStore {
+            conn: self.conn.clone(),
+            prefix: "authcode:",
+        }
+    }
+
+    pub fn dpop_proofs(&self) -> DpopReplayCache {
+        DpopReplayCache {
+            conn: self.conn.clone(),
+            prefix: "dpop:",
+        }
+    }
+
+    pub fn device_codes(&self) -> DeviceCodeStore {
+        DeviceCodeStore {
+            conn: self.conn.clone(),
+            prefix: "devicecode:",
+            user_code_prefix: "usercode:",
+            polling_prefix: "devicepoll:",
+        }
+    }
+
+    pub fn federated_logins(&self) -> FederatedLoginStore {
+        FederatedLoginStore {
+            conn: self.conn.clone(),
+            prefix: "federated:",
+        }
+    }
 }
 
 pub struct TokenBlacklist {
@@ -50,3 +101,325 @@ impl TokenBlacklist {
         redis::cmd("EXISTS").arg(&key).query_async(&mut *conn).await
     }
 }
+
+pub struct TokenVersionCache {
+    conn: Arc<Mutex<MultiplexedConnection>>,
+    prefix: &'static str,
+}
+
+impl TokenVersionCache {
+    pub async fn find(&self, user_id: Uuid) -> redis::RedisResult<Option<i64>> {
+        let key = format!("{}{}", self.prefix, user_id);
+        let mut conn = self.conn.lock().await;
+        redis::cmd("GET").arg(&key).query_async(&mut *conn).await
+    }
+
+    /// Caches the version read from the database. Never overwrites a cached value, which
+    /// might have been set by a concurrent [`TokenVersionCache::store`] with a newer version.
+    pub async fn fill(&self, user_id: Uuid, version: i64, exp: i64) -> redis::RedisResult<()> {
+        let key = format!("{}{}", self.prefix, user_id);
+        let mut conn = self.conn.lock().await;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(version)
+            .arg("NX")
+            .arg("EX")
+            .arg(exp)
+            .query_async(&mut *conn)
+            .await
+    }
+
+    pub async fn store(&self, user_id: Uuid, version: i64, exp: i64) -> redis::RedisResult<()> {
+        let key = format!("{}{}", self.prefix, user_id);
+        let mut conn = self.conn.lock().await;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(version)
+            .arg("EX")
+            .arg(exp)
+            .query_async(&mut *conn)
+            .await
+    }
+}
+
+pub struct GroupRoleCache {
+    conn: Arc<Mutex<MultiplexedConnection>>,
+    prefix: &'static str,
+}
+
+impl GroupRoleCache {
+    pub async fn find(&self, user_id: Uuid) -> redis::RedisResult<Option<Vec<Role>>> {
+        let key = format!("{}{}", self.prefix, user_id);
+        let mut conn = self.conn.lock().await;
+        let value: Option<String> = redis::cmd("GET").arg(&key).query_async(&mut *conn).await?;
+
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+
+    pub async fn store(&self, user_id: Uuid, roles: &[Role], exp: i64) -> redis::RedisResult<()> {
+        let key = format!("{}{}", self.prefix, user_id);
+        let value = to_json(&roles)?;
+
+        let mut conn = self.conn.lock().await;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(value)
+            .arg("EX")
+            .arg(exp)
+            .query_async(&mut *conn)
+            .await
+    }
+
+    /// Drops the cached roles of the users, e.g. after their groups changed.
+    pub async fn invalidate(&self, user_ids: &[Uuid]) -> redis::RedisResult<()> {
+        if user_ids.is_empty() {
+            return Ok(());
+        }
+
+        let keys: Vec<String> = user_ids
+            .iter()
+            .map(|id| format!("{}{}", self.prefix, id))
+            .collect();
+        let mut conn = self.conn.lock().await;
+        redis::cmd("DEL").arg(&keys).query_async(&mut *conn).await
+    }
+}
+
+pub struct DpopReplayCache {
+    conn: Arc<Mutex<MultiplexedConnection>>,
+    prefix: &'static str,
+}
+
+impl DpopReplayCache {
+    /// Records the proof identifier, returning `false` if the proof was already used.
+    pub async fn insert(&self, jti: &str, exp: i64) -> redis::RedisResult<bool> {
+        let key = format!("{}{}", self.prefix, jti);
+        let mut conn = self.conn.lock().await;
+        let res: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg("used")
+            .arg("NX")
+            .arg("EX")
+            .arg(exp)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(res.is_some())
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> redis::RedisResult<String> {
+    serde_json::to_string(value).map_err(|e| {
+        redis::RedisError::from((
+            redis::ErrorKind::TypeError,
+            "value serialization failed",
+            e.to_string(),
+        ))
+    })
+}
+
+pub struct AuthorizationCodeStore {
+    conn: Arc<Mutex<MultiplexedConnection>>,
+    prefix: &'static str,
+}
+
+impl AuthorizationCodeStore {
+    pub async fn store(
+        &self,
+        code: &str,
+        authorization_code: &AuthorizationCode,
+        exp: i64,
+    ) -> redis::RedisResult<()> {
+        let key = format!("{}{}", self.prefix, code);
+        let value = to_json(authorization_code)?;
+
+        let mut conn = self.conn.lock().await;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(value)
+            .arg("EX")
+            .arg(exp)
+            .query_async(&mut *conn)
+            .await
+    }
+
+    /// Fetches and removes the authorization code, so that every code can be redeemed only once.
+    pub async fn take(&self, code: &str) -> redis::RedisResult<Option<AuthorizationCode>> {
+        let key = format!("{}{}", self.prefix, code);
+        let mut conn = self.conn.lock().await;
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(&key)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+}
+
+pub struct DeviceCodeStore {
+    conn: Arc<Mutex<MultiplexedConnection>>,
+    prefix: &'static str,
+    user_code_prefix: &'static str,
+    polling_prefix: &'static str,
+}
+
+impl DeviceCodeStore {
+    /// Stores the device authorization along with a user code index pointing back to the device code.
+    pub async fn store(
+        &self,
+        device_code: &str,
+        authorization: &DeviceAuthorization,
+        exp: i64,
+    ) -> redis::RedisResult<()> {
+        let key = format!("{}{}", self.prefix, device_code);
+        let user_code_key = format!("{}{}", self.user_code_prefix, authorization.user_code);
+        let value = to_json(authorization)?;
+
+        let mut conn = self.conn.lock().await;
+        redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&key)
+            .arg(value)
+            .arg("EX")
+            .arg(exp)
+            .ignore()
+            .cmd("SET")
+            .arg(&user_code_key)
+            .arg(device_code)
+            .arg("EX")
+            .arg(exp)
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+    }
+
+    /// Overwrites an existing device authorization without extending its expiration time.
+    /// Only the decision of the resource owner is written, polls go to [`DeviceCodeStore::store_polling`].
+    pub async fn update(
+        &self,
+        device_code: &str,
+        authorization: &DeviceAuthorization,
+    ) -> redis::RedisResult<()> {
+        let key = format!("{}{}", self.prefix, device_code);
+        let value = to_json(authorization)?;
+
+        let mut conn = self.conn.lock().await;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(value)
+            .arg("XX")
+            .arg("KEEPTTL")
+            .query_async(&mut *conn)
+            .await
+    }
+
+    pub async fn find(&self, device_code: &str) -> redis::RedisResult<Option<DeviceAuthorization>> {
+        let key = format!("{}{}", self.prefix, device_code);
+        let mut conn = self.conn.lock().await;
+        let value: Option<String> = redis::cmd("GET").arg(&key).query_async(&mut *conn).await?;
+
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+
+    pub async fn find_polling(
+        &self,
+        device_code: &str,
+    ) -> redis::RedisResult<Option<DevicePolling>> {
+        let key = format!("{}{}", self.polling_prefix, device_code);
+        let mut conn = self.conn.lock().await;
+        let value: Option<String> = redis::cmd("GET").arg(&key).query_async(&mut *conn).await?;
+
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+
+    pub async fn store_polling(
+        &self,
+        device_code: &str,
+        polling: &DevicePolling,
+        exp: i64,
+    ) -> redis::RedisResult<()> {
+        let key = format!("{}{}", self.polling_prefix, device_code);
+        let value = to_json(polling)?;
+
+        let mut conn = self.conn.lock().await;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(value)
+            .arg("EX")
+            .arg(exp)
+            .query_async(&mut *conn)
+            .await
+    }
+
+    pub async fn find_device_code(&self, user_code: &str) -> redis::RedisResult<Option<String>> {
+        let key = format!("{}{}", self.user_code_prefix, user_code);
+        let mut conn = self.conn.lock().await;
+        redis::cmd("GET").arg(&key).query_async(&mut *conn).await
+    }
+
+    /// Fetches and removes the device authorization along with its user code and polling
+    /// state, so that only one of concurrent polls can redeem an approved device code.
+    pub async fn take(&self, device_code: &str) -> redis::RedisResult<Option<DeviceAuthorization>> {
+        let key = format!("{}{}", self.prefix, device_code);
+        let mut conn = self.conn.lock().await;
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(&key)
+            .query_async(&mut *conn)
+            .await?;
+
+        let Some(authorization) =
+            value.and_then(|v| serde_json::from_str::<DeviceAuthorization>(&v).ok())
+        else {
+            return Ok(None);
+        };
+
+        let user_code_key = format!("{}{}", self.user_code_prefix, authorization.user_code);
+        let polling_key = format!("{}{}", self.polling_prefix, device_code);
+        redis::cmd("DEL")
+            .arg(&user_code_key)
+            .arg(&polling_key)
+            .query_async::<()>(&mut *conn)
+            .await?;
+
+        Ok(Some(authorization))
+    }
+}
+
+pub struct FederatedLoginStore {
+    conn: Arc<Mutex<MultiplexedConnection>>,
+    prefix: &'static str,
+}
+
+impl FederatedLoginStore {
+    pub async fn store(
+        &self,
+        state: &str,
+        login: &FederatedLogin,
+        exp: i64,
+    ) -> redis::RedisResult<()> {
+        let key = format!("{}{}", self.prefix, state);
+        let value = to_json(login)?;
+
+        let mut conn = self.conn.lock().await;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(value)
+            .arg("EX")
+            .arg(exp)
+            .query_async(&mut *conn)
+            .await
+    }
+
+    /// Fetches and removes the pending login, so that every `state` can be used only once.
+    pub async fn take(&self, state: &str) -> redis::RedisResult<Option<FederatedLogin>> {
+        let key = format!("{}{}", self.prefix, state);
+        let mut conn = self.conn.lock().await;
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(&key)
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+    }
+}
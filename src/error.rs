@@ -1,6 +1,9 @@
-use axum::{extract::rejection::PathRejection, http::StatusCode, response::IntoResponse};
+use axum::{extract::rejection::PathRejection, http::StatusCode, response::IntoResponse, Json};
 
-use crate::{routes::ApiResponse, services::auth::AuthError};
+use crate::{
+    routes::ApiResponse,
+    services::{auth::AuthError, oauth::OAuthError},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ApiError {
@@ -10,6 +13,8 @@ pub enum ApiError {
     NotFound(String),
     #[error("authentication error: {0}")]
     Auth(#[from] AuthError),
+    #[error("oauth error: {0}")]
+    OAuth(#[from] OAuthError),
     #[error("internal server error: {0}")]
     Internal(#[from] anyhow::Error),
 }
@@ -20,6 +25,18 @@ impl IntoResponse for ApiError {
             ApiError::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             ApiError::NotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
             ApiError::Auth(ref auth_err) => (auth_err.status_code(), self.to_string()),
+            ApiError::OAuth(oauth_err) => {
+                // OAuth endpoints respond with the RFC 6749 (section 5.2) error format
+                // instead of the regular API response envelope.
+                return (
+                    oauth_err.status_code(),
+                    Json(serde_json::json!({
+                        "error": oauth_err.error_code(),
+                        "error_description": oauth_err.to_string(),
+                    })),
+                )
+                    .into_response();
+            }
             ApiError::Internal(error) => {
                 tracing::error!("{}", error);
                 (
@@ -1,8 +1,10 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use crate::database::{postgres::PostgresDatabase, redis::RedisCache};
 use config::Config;
 use database::{mock::MockDatabase, Database};
+use models::policy::PolicySet;
+use services::{auth::AuthProvider, oidc::SigningKey};
 
 pub mod config;
 pub mod database;
@@ -16,6 +18,9 @@ pub struct ApiState {
     db: Arc<dyn Database>,
     redis: RedisCache,
     config: Config,
+    signing_key: Arc<SigningKey>,
+    auth_providers: Vec<Arc<dyn AuthProvider>>,
+    policies: Arc<PolicySet>,
 }
 
 pub async fn init_database(cfg: &Config) -> anyhow::Result<Arc<dyn Database>> {
@@ -39,17 +44,60 @@ async fn ctrl_c() {
     tracing::info!("Ctrl-C signal received, shutting down...");
 }
 
-pub async fn run(config: Config) -> anyhow::Result<()> {
+/// Connects to the database and Redis and loads the keys, providers and policies.
+pub async fn init_state(config: Config) -> anyhow::Result<Arc<ApiState>> {
     let db = init_database(&config).await?;
     let redis = RedisCache::new(config.redis_uri()).await?;
-    let state = Arc::new(ApiState { db, redis, config });
+    let signing_key = Arc::new(SigningKey::from_config(&config)?);
+    let auth_providers = services::auth::providers_from_config(&config)?;
+    let policies = Arc::new(services::authz::policies_from_config(&config)?);
+
+    Ok(Arc::new(ApiState {
+        db,
+        redis,
+        config,
+        signing_key,
+        auth_providers,
+        policies,
+    }))
+}
+
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    let state = init_state(config).await?;
+
+    let webhook_dispatcher =
+        services::webhooks::spawn_dispatcher(Arc::clone(&state.db), state.config.clone());
 
     let listener = tokio::net::TcpListener::bind(state.config.socket_addr()).await?;
     tracing::info!("Listening on: {}", listener.local_addr()?);
 
-    axum::serve(listener, routes::create_routes(state))
-        .with_graceful_shutdown(ctrl_c())
-        .await?;
+    axum::serve(
+        listener,
+        routes::create_routes(state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(ctrl_c())
+    .await?;
+
+    webhook_dispatcher.abort();
 
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::*;
+
+    /// State backed by the mock database and the Redis at `REDIS_HOST`. Tests using it are
+    /// ignored by default and run with `cargo test -- --ignored`.
+    pub async fn state(config: Config) -> Arc<ApiState> {
+        let redis_host =
+            std::env::var("REDIS_HOST").expect("REDIS_HOST should be set for tests using Redis");
+
+        let config = Config {
+            database_variant: database::DatabaseVariant::Mock,
+            redis_host,
+            ..config
+        };
+        init_state(config).await.expect("state should initialize")
+    }
+}
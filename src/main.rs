@@ -6,7 +6,7 @@ use flatline::{
     config::Config,
     init_database,
     models::user::{Role, User},
-    services::auth::hash_string,
+    services::{self, auth::hash_string, oauth::grant_type, oidc::SigningKey},
 };
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -23,6 +23,8 @@ struct Args {
 enum Command {
     /// Initialize flatline configuration in the home directory
     Init,
+    /// Generate a signing key for OpenID Connect ID tokens
+    Keygen,
     /// Execute administrative commands
     Exec {
         #[command(subcommand)]
@@ -57,12 +59,33 @@ enum ExecCommand {
         #[arg(short, long)]
         password: String,
     },
+    /// Create an OAuth client for a service account
+    CreateClient {
+        /// Client identifier used in the client credentials grant
+        #[arg(short, long)]
+        client_id: String,
+        /// Scopes the client is allowed to request
+        #[arg(short, long, value_delimiter = ',')]
+        scopes: Vec<String>,
+    },
     /// Delete expired JWT refresh tokens
     DelExpJwt {
         /// Confirmation flag (required)
         #[arg(long)]
         confirm: bool,
     },
+    /// Permanently delete users soft deleted longer than the retention period ago
+    PurgeDeletedUsers {
+        /// Confirmation flag (required)
+        #[arg(long)]
+        confirm: bool,
+    },
+    /// Delete audit log entries older than the retention period
+    PurgeAuditLogs {
+        /// Confirmation flag (required)
+        #[arg(long)]
+        confirm: bool,
+    },
 }
 
 impl ExecCommand {
@@ -77,6 +100,21 @@ impl ExecCommand {
 
                 Ok(())
             }
+            ExecCommand::CreateClient { client_id, scopes } => {
+                let (_, client_secret) = services::oauth::create_client(
+                    db.as_ref(),
+                    client_id,
+                    true,
+                    scopes,
+                    &[],
+                    &[grant_type::CLIENT_CREDENTIALS.to_owned()],
+                    &[],
+                )
+                .await?;
+                println!("client_secret: {}", client_secret.unwrap_or_default());
+
+                Ok(())
+            }
             ExecCommand::DelExpJwt { confirm } => {
                 if !confirm {
                     return Err(anyhow!("Confirmation is required for this command"));
@@ -85,6 +123,30 @@ impl ExecCommand {
                 let deleted_count = db.refresh_tokens().delete_expired().await?;
                 tracing::info!("Deleted ({}) expired JWT refresh tokens", deleted_count);
 
+                Ok(())
+            }
+            ExecCommand::PurgeDeletedUsers { confirm } => {
+                if !confirm {
+                    return Err(anyhow!("Confirmation is required for this command"));
+                }
+
+                let before =
+                    chrono::Utc::now() - chrono::Duration::seconds(config.deleted_user_retention);
+                let purged_count = db.users().purge_deleted(before).await?;
+                tracing::info!("Purged ({}) deleted users", purged_count);
+
+                Ok(())
+            }
+            ExecCommand::PurgeAuditLogs { confirm } => {
+                if !confirm {
+                    return Err(anyhow!("Confirmation is required for this command"));
+                }
+
+                let before =
+                    chrono::Utc::now() - chrono::Duration::seconds(config.audit_log_retention);
+                let deleted_count = db.audit_logs().delete_older_than(before).await?;
+                tracing::info!("Deleted ({}) expired audit log entries", deleted_count);
+
                 Ok(())
             }
         }
@@ -130,6 +192,11 @@ async fn parse_cli(args: Args) -> anyhow::Result<Option<Config>> {
             tracing::info!("Configuration directory initialized in '~/.{}'. Make sure to fill out the 'config.json' file.", env!("CARGO_PKG_NAME"));
             Ok(None)
         }
+        Some(Command::Keygen) => {
+            let (_, encoded_key) = SigningKey::generate()?;
+            println!("{}", encoded_key);
+            Ok(None)
+        }
         Some(Command::Exec { command }) => {
             let config = choose_config()?;
             command.handle_exec_command(config).await?;
@@ -0,0 +1,168 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    error::ApiError,
+    models::pagination::{self, SortOrder},
+};
+
+pub mod event_type {
+    pub const REGISTER: &str = "register";
+    pub const LOGIN: &str = "login";
+    pub const LOGOUT: &str = "logout";
+    pub const REFRESH: &str = "refresh";
+    pub const SESSION_REVOKED: &str = "session_revoked";
+    pub const PASSWORD_CHANGED: &str = "password_changed";
+    pub const ROLES_CHANGED: &str = "roles_changed";
+    pub const FORCE_LOGOUT: &str = "force_logout";
+    pub const USER_DISABLED: &str = "user_disabled";
+    pub const USER_ENABLED: &str = "user_enabled";
+    pub const USER_DELETED: &str = "user_deleted";
+    pub const USERS_DELETED: &str = "users_deleted";
+    pub const IMPERSONATION: &str = "impersonation";
+    pub const IDENTITY_LINKED: &str = "identity_linked";
+    pub const IDENTITY_UNLINKED: &str = "identity_unlinked";
+    pub const ORG_CREATED: &str = "org_created";
+    pub const ORG_MEMBER_ADDED: &str = "org_member_added";
+    pub const ORG_MEMBER_REMOVED: &str = "org_member_removed";
+    pub const ORG_ROLE_CHANGED: &str = "org_role_changed";
+    pub const GROUP_CREATED: &str = "group_created";
+    pub const GROUP_UPDATED: &str = "group_updated";
+    pub const GROUP_DELETED: &str = "group_deleted";
+    pub const GROUP_MEMBER_ADDED: &str = "group_member_added";
+    pub const GROUP_MEMBER_REMOVED: &str = "group_member_removed";
+    pub const INVITATION_CREATED: &str = "invitation_created";
+    pub const INVITATION_REVOKED: &str = "invitation_revoked";
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "audit_outcome", rename_all = "lowercase")]
+pub enum AuditOutcome {
+    #[default]
+    Success,
+    Failure,
+}
+
+/// Network details of the request that caused the event.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct AuditLog {
+    pub id: Uuid,
+    pub event_type: String,
+    pub actor_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub outcome: AuditOutcome,
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditLog {
+    pub fn new(event_type: &str, actor_id: Option<Uuid>, target_id: Option<Uuid>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            event_type: event_type.to_owned(),
+            actor_id,
+            target_id,
+            ip_address: None,
+            user_agent: None,
+            outcome: AuditOutcome::Success,
+            metadata: serde_json::Value::Object(Default::default()),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> AuditLog {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_client(mut self, client: &ClientInfo) -> AuditLog {
+        self.ip_address = client.ip_address.clone();
+        self.user_agent = client.user_agent.clone();
+        self
+    }
+
+    pub fn failed(mut self) -> AuditLog {
+        self.outcome = AuditOutcome::Failure;
+        self
+    }
+}
+
+/// Position after the last entry of a page. Entries are ordered by their creation time,
+/// with the id breaking ties.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AuditLogCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+/// Filters and the page to fetch when querying the audit log.
+/// `created_after` is inclusive, while `created_before` is exclusive.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AuditLogQuery {
+    pub event_type: Option<String>,
+    pub actor_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub outcome: Option<AuditOutcome>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(default = "default_order")]
+    pub order: SortOrder,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+/// The most recent events are usually the most interesting ones.
+fn default_order() -> SortOrder {
+    SortOrder::Desc
+}
+
+impl Default for AuditLogQuery {
+    fn default() -> Self {
+        Self {
+            event_type: None,
+            actor_id: None,
+            target_id: None,
+            outcome: None,
+            ip_address: None,
+            user_agent: None,
+            created_after: None,
+            created_before: None,
+            order: default_order(),
+            limit: None,
+            cursor: None,
+        }
+    }
+}
+
+impl AuditLogQuery {
+    pub fn page_size(&self) -> usize {
+        pagination::page_size(self.limit)
+    }
+
+    pub fn decode_cursor(&self) -> Result<Option<AuditLogCursor>, ApiError> {
+        self.cursor
+            .as_deref()
+            .map(pagination::decode_cursor)
+            .transpose()
+    }
+
+    pub fn encode_cursor(&self, audit_log: &AuditLog) -> String {
+        pagination::encode_cursor(&AuditLogCursor {
+            created_at: audit_log.created_at,
+            id: audit_log.id,
+        })
+    }
+}
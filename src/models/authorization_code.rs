@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AuthorizationCode {
+    pub client_id: String,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+    pub auth_time: i64,
+}
+
+impl AuthorizationCode {
+    pub fn new(
+        client_id: &str,
+        user_id: Uuid,
+        redirect_uri: &str,
+        scope: &str,
+        code_challenge: &str,
+        nonce: Option<&str>,
+        auth_time: i64,
+    ) -> Self {
+        Self {
+            client_id: client_id.to_owned(),
+            user_id,
+            redirect_uri: redirect_uri.to_owned(),
+            scope: scope.to_owned(),
+            code_challenge: code_challenge.to_owned(),
+            nonce: nonce.map(str::to_owned),
+            auth_time,
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct Client {
+    pub id: Uuid,
+    pub client_id: String,
+    pub secret_hash: Option<String>,
+    pub scopes: Vec<String>,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub exchange_audiences: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Client {
+    /// Creates a new client. Clients without a secret hash are public clients
+    /// (e.g. SPAs or native apps) which can only use grants protected by PKCE.
+    pub fn new(
+        client_id: &str,
+        secret_hash: Option<&str>,
+        scopes: &[String],
+        redirect_uris: &[String],
+        grant_types: &[String],
+    ) -> Client {
+        let now = Utc::now();
+        Client {
+            id: Uuid::new_v4(),
+            client_id: client_id.to_owned(),
+            secret_hash: secret_hash.map(str::to_owned),
+            scopes: scopes.to_vec(),
+            redirect_uris: redirect_uris.to_vec(),
+            grant_types: grant_types.to_vec(),
+            exchange_audiences: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Sets the audiences the client is allowed to request in a token exchange.
+    pub fn with_exchange_audiences(mut self, exchange_audiences: &[String]) -> Client {
+        self.exchange_audiences = exchange_audiences.to_vec();
+        self
+    }
+
+    pub fn is_confidential(&self) -> bool {
+        self.secret_hash.is_some()
+    }
+
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    pub fn allows_grant(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|g| g == grant_type)
+    }
+
+    pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|uri| uri == redirect_uri)
+    }
+
+    pub fn allows_audience(&self, audience: &str) -> bool {
+        self.exchange_audiences.iter().any(|aud| aud == audience)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientDto {
+    pub id: Uuid,
+    pub client_id: String,
+    pub confidential: bool,
+    pub scopes: Vec<String>,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub exchange_audiences: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Client> for ClientDto {
+    fn from(client: Client) -> Self {
+        Self {
+            id: client.id,
+            confidential: client.is_confidential(),
+            client_id: client.client_id,
+            scopes: client.scopes,
+            redirect_uris: client.redirect_uris,
+            grant_types: client.grant_types,
+            exchange_audiences: client.exchange_audiences,
+            created_at: client.created_at,
+            updated_at: client.updated_at,
+        }
+    }
+}
+
+impl From<&Client> for ClientDto {
+    fn from(client: &Client) -> Self {
+        Self {
+            id: client.id,
+            client_id: client.client_id.clone(),
+            confidential: client.is_confidential(),
+            scopes: client.scopes.clone(),
+            redirect_uris: client.redirect_uris.clone(),
+            grant_types: client.grant_types.clone(),
+            exchange_audiences: client.exchange_audiences.clone(),
+            created_at: client.created_at,
+            updated_at: client.updated_at,
+        }
+    }
+}
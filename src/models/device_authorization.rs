@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceAuthorizationStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DeviceAuthorization {
+    pub client_id: String,
+    pub user_code: String,
+    pub scope: String,
+    pub status: DeviceAuthorizationStatus,
+    pub user_id: Option<Uuid>,
+    pub auth_time: Option<i64>,
+    pub interval: i64,
+}
+
+/// Polling state of a device code. It is stored apart from the authorization, so that polls
+/// never overwrite the decision of the resource owner.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DevicePolling {
+    pub interval: i64,
+    pub last_polled_at: i64,
+}
+
+impl DeviceAuthorization {
+    pub fn new(client_id: &str, user_code: &str, scope: &str, interval: i64) -> Self {
+        Self {
+            client_id: client_id.to_owned(),
+            user_code: user_code.to_owned(),
+            scope: scope.to_owned(),
+            status: DeviceAuthorizationStatus::Pending,
+            user_id: None,
+            auth_time: None,
+            interval,
+        }
+    }
+
+    pub fn approve(&mut self, user_id: Uuid, auth_time: i64) {
+        self.status = DeviceAuthorizationStatus::Approved;
+        self.user_id = Some(user_id);
+        self.auth_time = Some(auth_time);
+    }
+
+    pub fn deny(&mut self) {
+        self.status = DeviceAuthorizationStatus::Denied;
+    }
+}
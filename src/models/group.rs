@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::models::user::Role;
+
+/// Named set of roles granted to every member of the group on top of their own roles.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, FromRow)]
+pub struct Group {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub roles: Vec<Role>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Group {
+    pub fn new(name: &str, description: Option<&str>, roles: &[Role]) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            name: name.to_owned(),
+            description: description.map(str::to_owned),
+            roles: roles.to_vec(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, FromRow)]
+pub struct GroupMember {
+    pub group_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl GroupMember {
+    pub fn new(group_id: Uuid, user_id: Uuid) -> Self {
+        Self {
+            group_id,
+            user_id,
+            created_at: Utc::now(),
+        }
+    }
+}
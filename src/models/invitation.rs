@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::models::user::Role;
+
+/// Invitation to register an account, required when registration is invite-only. Only the
+/// hash of the token is stored, the token itself is shown once when the invitation is created.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, FromRow)]
+pub struct Invitation {
+    pub id: Uuid,
+    /// Email the account is registered with, any email is accepted without it.
+    pub email: Option<String>,
+    /// Roles assigned to the account registered with the invitation.
+    pub roles: Vec<Role>,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub created_by: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub used_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Invitation {
+    pub fn new(
+        token_hash: &str,
+        email: Option<&str>,
+        roles: &[Role],
+        created_by: Option<Uuid>,
+        expires_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            email: email.map(str::to_owned),
+            roles: roles.to_vec(),
+            token_hash: token_hash.to_owned(),
+            created_by,
+            expires_at,
+            used_at: None,
+            used_by: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+
+    pub fn is_used(&self) -> bool {
+        self.used_at.is_some()
+    }
+}
@@ -1,2 +1,14 @@
+pub mod audit_log;
+pub mod authorization_code;
+pub mod client;
+pub mod device_authorization;
+pub mod group;
+pub mod invitation;
+pub mod organization;
+pub mod pagination;
+pub mod policy;
 pub mod refresh_token;
+pub mod scim;
 pub mod user;
+pub mod user_identity;
+pub mod webhook;
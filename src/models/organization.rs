@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::models::user::{User, UserDto};
+
+/// Tenant with its own namespace of usernames.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, FromRow)]
+pub struct Organization {
+    pub id: Uuid,
+    /// Identifies the organization when logging in, unique across all organizations.
+    pub slug: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Organization {
+    pub fn new(slug: &str, name: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            slug: slug.to_owned(),
+            name: name.to_owned(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Role of a member within a single organization, independent of the global roles.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "org_role", rename_all = "lowercase")]
+pub enum OrgRole {
+    #[default]
+    Member,
+    Admin,
+}
+
+impl std::fmt::Display for OrgRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrgRole::Member => write!(f, "member"),
+            OrgRole::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, FromRow)]
+pub struct Membership {
+    pub org_id: Uuid,
+    pub user_id: Uuid,
+    pub role: OrgRole,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Membership {
+    pub fn new(org_id: Uuid, user_id: Uuid, role: OrgRole) -> Self {
+        let now = Utc::now();
+        Self {
+            org_id,
+            user_id,
+            role,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemberDto {
+    pub user: UserDto,
+    pub role: OrgRole,
+    pub joined_at: DateTime<Utc>,
+}
+
+impl MemberDto {
+    pub fn new(user: &User, membership: &Membership) -> Self {
+        Self {
+            user: UserDto::from(user),
+            role: membership.role,
+            joined_at: membership.created_at,
+        }
+    }
+}
+
+/// Invitation to join an organization with a new account. Only the hash of the token is stored,
+/// the token itself is shown once when the invitation is created.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, FromRow)]
+pub struct OrgInvitation {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub email: Option<String>,
+    pub role: OrgRole,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub invited_by: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrgInvitation {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
@@ -0,0 +1,101 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::error::ApiError;
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// Clamps the requested page size to the allowed range.
+pub fn page_size(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+/// Cursors are opaque to clients, they only pass back the value from the previous page.
+pub fn encode_cursor<T: Serialize>(cursor: &T) -> String {
+    let json = serde_json::to_vec(cursor).expect("cursor should always serialize");
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Result<T, ApiError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| ApiError::BadRequest("cursor is invalid".to_owned()))
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page out of up to `limit + 1` items. The extra item is only fetched to tell
+    /// whether there is a next page, which then starts after the last returned item.
+    pub fn from_items(mut items: Vec<T>, limit: usize, cursor: impl Fn(&T) -> String) -> Self {
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(cursor)
+        } else {
+            None
+        };
+
+        Self { items, next_cursor }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_from_items() {
+        let page = Page::from_items(vec![1, 2, 3], 2, |i| i.to_string());
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor.as_deref(), Some("2"));
+
+        let page = Page::from_items(vec![1, 2], 2, |i| i.to_string());
+        assert_eq!(page.items, vec![1, 2]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn cursor_roundtrip() {
+        let cursor = encode_cursor(&("test_user", 42));
+        assert_eq!(
+            decode_cursor::<(String, i32)>(&cursor).unwrap(),
+            ("test_user".to_owned(), 42)
+        );
+
+        assert!(matches!(
+            decode_cursor::<(String, i32)>("not a cursor"),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::user::Role;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// Condition on the attributes of an authorization request. String operands starting with
+/// `$` refer to an attribute of the request, e.g. `$subject.sub` or `$resource.org_ids`,
+/// any other operand is a literal value.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    HasRole(Role),
+    HasScope(String),
+    /// The operand is present and not null.
+    Exists(Value),
+    Equals(Value, Value),
+    /// The first operand is an array containing the second one.
+    Contains(Value, Value),
+    /// Both operands are arrays with at least one element in common.
+    Intersects(Value, Value),
+    AllOf(Vec<Condition>),
+    AnyOf(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default)]
+    pub effect: Effect,
+    /// Actions the rule applies to, `*` matches any action.
+    pub actions: Vec<String>,
+    /// Resource types the rule applies to, `*` matches any type.
+    pub resources: Vec<String>,
+    /// Conditions that all have to hold for the rule to apply.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct PolicySet {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    /// Name of the rule the decision is based on, `None` if no rule applied.
+    pub rule: Option<String>,
+}
+
+fn matches(patterns: &[String], value: &str) -> bool {
+    patterns.iter().any(|p| p == "*" || p == value)
+}
+
+/// Resolves an operand against the attributes, missing attributes resolve to null.
+fn resolve<'a>(operand: &'a Value, attributes: &'a Value) -> &'a Value {
+    match operand.as_str().and_then(|s| s.strip_prefix('$')) {
+        Some(path) => path
+            .split('.')
+            .try_fold(attributes, |value, key| value.get(key))
+            .unwrap_or(&Value::Null),
+        None => operand,
+    }
+}
+
+impl Condition {
+    /// Evaluates the condition against the attributes, a document with the `subject`,
+    /// `resource` and `params` of the request. Null never equals anything, so conditions
+    /// on missing attributes do not hold.
+    pub fn holds(&self, attributes: &Value) -> bool {
+        let subject = &attributes["subject"];
+        match self {
+            Condition::HasRole(role) => subject["roles"]
+                .as_array()
+                .is_some_and(|roles| roles.contains(&serde_json::json!(role))),
+            Condition::HasScope(scope) => subject["scope"]
+                .as_str()
+                .is_some_and(|scopes| scopes.split(' ').any(|s| s == scope)),
+            Condition::Exists(a) => !resolve(a, attributes).is_null(),
+            Condition::Equals(a, b) => {
+                let (a, b) = (resolve(a, attributes), resolve(b, attributes));
+                !a.is_null() && a == b
+            }
+            Condition::Contains(a, b) => {
+                let b = resolve(b, attributes);
+                !b.is_null()
+                    && resolve(a, attributes)
+                        .as_array()
+                        .is_some_and(|values| values.contains(b))
+            }
+            Condition::Intersects(a, b) => {
+                match (
+                    resolve(a, attributes).as_array(),
+                    resolve(b, attributes).as_array(),
+                ) {
+                    (Some(a), Some(b)) => a.iter().any(|v| !v.is_null() && b.contains(v)),
+                    _ => false,
+                }
+            }
+            Condition::AllOf(conditions) => conditions.iter().all(|c| c.holds(attributes)),
+            Condition::AnyOf(conditions) => conditions.iter().any(|c| c.holds(attributes)),
+            Condition::Not(condition) => !condition.holds(attributes),
+        }
+    }
+}
+
+impl Rule {
+    pub fn applies(&self, action: &str, resource_type: &str, attributes: &Value) -> bool {
+        matches(&self.actions, action)
+            && matches(&self.resources, resource_type)
+            && self.conditions.iter().all(|c| c.holds(attributes))
+    }
+}
+
+impl PolicySet {
+    /// Denies unless an allow rule applies and no deny rule does.
+    pub fn evaluate(&self, action: &str, resource_type: &str, attributes: &Value) -> Decision {
+        let mut allowed_by = None;
+        for rule in &self.rules {
+            if !rule.applies(action, resource_type, attributes) {
+                continue;
+            }
+
+            match rule.effect {
+                Effect::Deny => {
+                    return Decision {
+                        allowed: false,
+                        rule: Some(rule.name.clone()),
+                    }
+                }
+                Effect::Allow => {
+                    allowed_by.get_or_insert_with(|| rule.name.clone());
+                }
+            }
+        }
+
+        Decision {
+            allowed: allowed_by.is_some(),
+            rule: allowed_by,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn policies() -> PolicySet {
+        serde_json::from_value(json!({
+            "rules": [
+                {
+                    "name": "admins",
+                    "actions": ["*"],
+                    "resources": ["*"],
+                    "conditions": [{ "has_role": "admin" }]
+                },
+                {
+                    "name": "own_or_same_org",
+                    "actions": ["read"],
+                    "resources": ["user"],
+                    "conditions": [{ "any_of": [
+                        { "equals": ["$subject.sub", "$params.id"] },
+                        { "intersects": ["$subject.org_ids", "$resource.org_ids"] }
+                    ]}]
+                },
+                {
+                    "name": "disabled",
+                    "effect": "deny",
+                    "actions": ["*"],
+                    "resources": ["user"],
+                    "conditions": [{ "equals": ["$resource.disabled", true] }]
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn attributes(subject: Value, resource: Value) -> Value {
+        json!({ "subject": subject, "resource": resource, "params": { "id": resource["id"] } })
+    }
+
+    #[test]
+    fn evaluate_policies() {
+        let policies = policies();
+        let admin = json!({ "sub": "a", "roles": ["admin"], "org_ids": [] });
+        let alice = json!({ "sub": "b", "roles": ["user"], "org_ids": ["org1"] });
+        let bob = json!({ "id": "c", "org_ids": ["org1", "org2"] });
+        let carol = json!({ "id": "d", "org_ids": [] });
+
+        let decision = policies.evaluate("read", "user", &attributes(alice.clone(), bob.clone()));
+        assert_eq!(
+            decision,
+            Decision {
+                allowed: true,
+                rule: Some("own_or_same_org".to_string())
+            }
+        );
+        assert!(
+            !policies
+                .evaluate("update", "user", &attributes(alice.clone(), bob.clone()))
+                .allowed
+        );
+        assert!(
+            !policies
+                .evaluate("read", "user", &attributes(alice.clone(), carol.clone()))
+                .allowed
+        );
+        assert!(
+            policies
+                .evaluate("read", "user", &attributes(alice, json!({ "id": "b" })))
+                .allowed
+        );
+        assert!(
+            policies
+                .evaluate("delete", "user", &attributes(admin.clone(), carol))
+                .allowed
+        );
+
+        let decision = policies.evaluate(
+            "read",
+            "user",
+            &attributes(admin, json!({ "id": "c", "disabled": true })),
+        );
+        assert!(!decision.allowed);
+        assert_eq!(decision.rule.as_deref(), Some("disabled"));
+    }
+
+    #[test]
+    fn missing_attributes_never_match() {
+        let condition = Condition::Equals(json!("$subject.org_id"), json!("$resource.org_id"));
+        assert!(!condition.holds(&json!({ "subject": {}, "resource": {} })));
+        assert!(
+            !Condition::Contains(json!("$resource.tags"), json!("$subject.missing"))
+                .holds(&json!({ "subject": {}, "resource": { "tags": [null] } }))
+        );
+        assert!(Condition::Not(Box::new(condition)).holds(&json!({})));
+        assert!(!Condition::Exists(json!("$subject.org_id")).holds(&json!({ "subject": {} })));
+        assert!(PolicySet::default()
+            .evaluate("read", "user", &json!({}))
+            .rule
+            .is_none());
+    }
+}
@@ -5,9 +5,14 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
-use crate::error::ApiError;
+use crate::{
+    error::ApiError,
+    models::pagination::{self, SortOrder},
+};
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
 pub enum Role {
     User,
     Admin,
@@ -46,27 +51,53 @@ impl Role {
             .join(",")
     }
 
-    pub fn to_vec(roles: &str) -> Vec<Self> {
+    /// Parses a comma separated list of roles, failing on the first unknown role.
+    pub fn to_vec(roles: &str) -> Result<Vec<Self>, ApiError> {
         roles
             .split(',')
-            .filter_map(|s| {
-                let trimmed = s.trim();
-                if trimmed.is_empty() {
-                    return None;
-                }
-
-                Role::from_str(trimmed).ok()
-            })
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Role::from_str)
             .collect()
     }
 }
 
+/// Where the password of the user is verified.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "auth_source", rename_all = "lowercase")]
+pub enum AuthSource {
+    #[default]
+    Local,
+    Ldap,
+    Oidc,
+}
+
+impl std::fmt::Display for AuthSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthSource::Local => write!(f, "local"),
+            AuthSource::Ldap => write!(f, "ldap"),
+            AuthSource::Oidc => write!(f, "oidc"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
     pub password_hash: String,
-    pub roles: String,
+    pub roles: Vec<Role>,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub token_version: i64,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub disabled_reason: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub auth_source: AuthSource,
+    /// Organization whose namespace holds the username, `None` for the global namespace.
+    pub org_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -78,14 +109,148 @@ impl User {
             id: Uuid::new_v4(),
             username: username.to_owned(),
             password_hash: password_hash.to_owned(),
-            roles: Role::from_vec(roles),
+            roles: roles.to_vec(),
+            email: None,
+            email_verified: false,
+            token_version: 0,
+            disabled_at: None,
+            disabled_reason: None,
+            deleted_at: None,
+            auth_source: AuthSource::Local,
+            org_id: None,
             created_at: now,
             updated_at: now,
         }
     }
 
+    pub fn with_email(mut self, email: &str) -> User {
+        self.email = Some(email.to_owned());
+        self
+    }
+
+    pub fn with_auth_source(mut self, auth_source: AuthSource) -> User {
+        self.auth_source = auth_source;
+        self
+    }
+
+    pub fn with_org(mut self, org_id: Uuid) -> User {
+        self.org_id = Some(org_id);
+        self
+    }
+
     pub fn has_role(&self, role: Role) -> bool {
-        self.roles.contains(&role.to_string())
+        self.roles.contains(&role)
+    }
+
+    /// Own roles of the user followed by the roles inherited from groups, without duplicates.
+    pub fn effective_roles(&self, group_roles: &[Role]) -> Vec<Role> {
+        let mut roles = self.roles.clone();
+        for role in group_roles {
+            if !roles.contains(role) {
+                roles.push(*role);
+            }
+        }
+        roles
+    }
+
+    pub fn disable(mut self, reason: Option<&str>) -> User {
+        self.disabled_at = Some(Utc::now());
+        self.disabled_reason = reason.map(str::to_owned);
+        self
+    }
+
+    pub fn enable(mut self) -> User {
+        self.disabled_at = None;
+        self.disabled_reason = None;
+        self
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    CreatedAt,
+    Username,
+}
+
+/// Position after the last user of a page, holding the value of the sort key and the id,
+/// which breaks ties between users with the same key. Cursors of the same kind are
+/// ordered the same way as the users they point at.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum UserCursor {
+    CreatedAt { created_at: DateTime<Utc>, id: Uuid },
+    Username { username: String, id: Uuid },
+}
+
+impl UserCursor {
+    pub fn for_user(user: &User, sort: UserSort) -> Self {
+        match sort {
+            UserSort::CreatedAt => UserCursor::CreatedAt {
+                created_at: user.created_at,
+                id: user.id,
+            },
+            UserSort::Username => UserCursor::Username {
+                username: user.username.clone(),
+                id: user.id,
+            },
+        }
+    }
+
+    pub fn sort(&self) -> UserSort {
+        match self {
+            UserCursor::CreatedAt { .. } => UserSort::CreatedAt,
+            UserCursor::Username { .. } => UserSort::Username,
+        }
+    }
+}
+
+/// Filters, sorting and the page to fetch when listing users.
+/// `created_after` is inclusive, while `created_before` is exclusive.
+/// Only users of the global namespace are listed, unless `org_id` is given.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct UserQuery {
+    pub org_id: Option<Uuid>,
+    pub username_prefix: Option<String>,
+    pub role: Option<Role>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub disabled: Option<bool>,
+    #[serde(default)]
+    pub sort: UserSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+impl UserQuery {
+    pub fn page_size(&self) -> usize {
+        pagination::page_size(self.limit)
+    }
+
+    pub fn decode_cursor(&self) -> Result<Option<UserCursor>, ApiError> {
+        let Some(cursor) = self.cursor.as_deref() else {
+            return Ok(None);
+        };
+
+        let cursor: UserCursor = pagination::decode_cursor(cursor)?;
+        if cursor.sort() != self.sort {
+            return Err(ApiError::BadRequest(
+                "cursor does not match the sort order".to_owned(),
+            ));
+        }
+
+        Ok(Some(cursor))
+    }
+
+    pub fn encode_cursor(&self, user: &User) -> String {
+        pagination::encode_cursor(&UserCursor::for_user(user, self.sort))
     }
 }
 
@@ -93,7 +258,12 @@ impl User {
 pub struct UserDto {
     pub id: Uuid,
     pub username: String,
-    pub roles: String,
+    pub roles: Vec<Role>,
+    pub email: Option<String>,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub disabled_reason: Option<String>,
+    pub auth_source: AuthSource,
+    pub org_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -104,6 +274,11 @@ impl From<User> for UserDto {
             id: user.id,
             username: user.username.clone(),
             roles: user.roles.clone(),
+            email: user.email.clone(),
+            disabled_at: user.disabled_at,
+            disabled_reason: user.disabled_reason.clone(),
+            auth_source: user.auth_source,
+            org_id: user.org_id,
             created_at: user.created_at,
             updated_at: user.updated_at,
         }
@@ -116,6 +291,11 @@ impl From<&User> for UserDto {
             id: user.id,
             username: user.username.clone(),
             roles: user.roles.clone(),
+            email: user.email.clone(),
+            disabled_at: user.disabled_at,
+            disabled_reason: user.disabled_reason.clone(),
+            auth_source: user.auth_source,
+            org_id: user.org_id,
             created_at: user.created_at,
             updated_at: user.updated_at,
         }
@@ -139,7 +319,14 @@ mod tests {
         let role_string = "user,admin";
         let roles = Role::to_vec(role_string);
 
-        assert_eq!(vec![Role::User, Role::Admin], roles);
+        assert_eq!(vec![Role::User, Role::Admin], roles.unwrap());
+    }
+
+    #[test]
+    fn roles_from_string_unknown_role() {
+        let roles = Role::to_vec("user,superuser");
+
+        assert!(matches!(roles, Err(ApiError::BadRequest(_))));
     }
 
     #[test]
@@ -149,4 +336,27 @@ mod tests {
         assert!(user.has_role(Role::User));
         assert!(!user.has_role(Role::Admin));
     }
+
+    #[test]
+    fn user_effective_roles() {
+        let user = User::new("test_user", "test_hash", &[Role::User]);
+
+        assert_eq!(user.effective_roles(&[]), vec![Role::User]);
+        assert_eq!(
+            user.effective_roles(&[Role::Admin, Role::User]),
+            vec![Role::User, Role::Admin]
+        );
+    }
+
+    #[test]
+    fn disable_and_enable_user() {
+        let user = User::new("test_user", "test_hash", &[Role::User]).disable(Some("spam"));
+
+        assert!(user.is_disabled());
+        assert_eq!(user.disabled_reason.as_deref(), Some("spam"));
+
+        let user = user.enable();
+        assert!(!user.is_disabled());
+        assert!(user.disabled_reason.is_none());
+    }
 }
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Account of a user at an upstream identity provider, identified by the issuer specific subject.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, FromRow)]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: DateTime<Utc>,
+}
+
+impl UserIdentity {
+    pub fn new(user_id: Uuid, provider: &str, subject: &str, email: Option<&str>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            provider: provider.to_owned(),
+            subject: subject.to_owned(),
+            email: email.map(str::to_owned),
+            created_at: now,
+            last_login_at: now,
+        }
+    }
+}
+
+/// Pending authorization request sent to an upstream provider, looked up by its `state`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FederatedLogin {
+    pub provider: String,
+    pub redirect_uri: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    /// User the identity is linked to, instead of logging in.
+    pub link_user_id: Option<Uuid>,
+}
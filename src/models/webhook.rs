@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub mod event_type {
+    pub const USER_REGISTERED: &str = "user.registered";
+    pub const USER_DISABLED: &str = "user.disabled";
+    pub const USER_ENABLED: &str = "user.enabled";
+    pub const USER_DELETED: &str = "user.deleted";
+    pub const USER_ROLES_CHANGED: &str = "user.roles_changed";
+    pub const SESSION_NEW_DEVICE: &str = "session.new_device";
+
+    pub const ALL: &[&str] = &[
+        USER_REGISTERED,
+        USER_DISABLED,
+        USER_ENABLED,
+        USER_DELETED,
+        USER_ROLES_CHANGED,
+        SESSION_NEW_DEVICE,
+    ];
+
+    pub fn is_known(event_type: &str) -> bool {
+        ALL.contains(&event_type)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+    pub event_types: Vec<String>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookSubscription {
+    /// Creates an active subscription. The secret is kept in plain text, since it is needed
+    /// to sign every payload sent to the subscriber.
+    pub fn new(url: &str, secret: &str, event_types: &[String]) -> WebhookSubscription {
+        let now = Utc::now();
+        WebhookSubscription {
+            id: Uuid::new_v4(),
+            url: url.to_owned(),
+            secret: secret.to_owned(),
+            event_types: event_types.to_vec(),
+            active: true,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn subscribes_to(&self, event_type: &str) -> bool {
+        self.active && self.event_types.iter().any(|e| e == event_type)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WebhookSubscriptionDto {
+    pub id: Uuid,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<WebhookSubscription> for WebhookSubscriptionDto {
+    fn from(subscription: WebhookSubscription) -> Self {
+        Self {
+            id: subscription.id,
+            url: subscription.url,
+            event_types: subscription.event_types,
+            active: subscription.active,
+            created_at: subscription.created_at,
+            updated_at: subscription.updated_at,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "webhook_status", rename_all = "lowercase")]
+pub enum WebhookStatus {
+    #[default]
+    Pending,
+    Delivered,
+    Failed,
+}
+
+/// Event waiting in the outbox to be delivered to a single subscription.
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct WebhookMessage {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: WebhookStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookMessage {
+    pub fn new(
+        subscription_id: Uuid,
+        event_type: &str,
+        payload: serde_json::Value,
+    ) -> WebhookMessage {
+        let now = Utc::now();
+        WebhookMessage {
+            id: Uuid::new_v4(),
+            subscription_id,
+            event_type: event_type.to_owned(),
+            payload,
+            status: WebhookStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Result of a single delivery attempt.
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub subscription_id: Uuid,
+    pub attempt: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookDelivery {
+    pub fn is_success(&self) -> bool {
+        self.status_code
+            .is_some_and(|code| (200..300).contains(&code))
+    }
+}
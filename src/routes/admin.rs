@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    routing::{get, post},
+    Extension, Router,
+};
+
+use crate::{
+    error::ApiError,
+    models::{
+        audit_log::{AuditLogQuery, ClientInfo},
+        user::Role,
+    },
+    routes::{
+        extractors::{ApiVersion, VerIdParams},
+        ApiResponse,
+    },
+    services::{self, auth::AuthError, jwt::Claims},
+    ApiState,
+};
+
+async fn impersonate(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    let (access_token, impersonation_claims) =
+        services::admin::impersonate(&state, &claims, id, &client).await?;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message(&format!(
+            "Impersonating user ({})",
+            impersonation_claims.username
+        ))
+        .with_payload(serde_json::json!({
+            "jwt_access": {
+                "token": access_token,
+                "token_type": "Bearer",
+                "expires_in": impersonation_claims.exp - impersonation_claims.iat,
+            },
+        }))
+        .build()
+        .as_ok()
+}
+
+async fn get_audit_logs(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    query: Result<Query<AuditLogQuery>, QueryRejection>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) || claims.is_delegated() {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+    let page = state.db.audit_logs().find_page(&query).await?;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message(&format!("found {} audit log entries", page.items.len()))
+        .with_payload(serde_json::json!({
+            "audit_logs": page.items,
+            "next_cursor": page.next_cursor,
+        }))
+        .build()
+        .as_ok()
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    let protected_routes = Router::new()
+        .route("/impersonate/{id}", post(impersonate))
+        .route("/audit-logs", get(get_audit_logs))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)));
+
+    Router::new().merge(protected_routes).with_state(state)
+}
@@ -1,39 +1,120 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{rejection::QueryRejection, OriginalUri, Query, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    response::IntoResponse,
     routing::{get, post},
     Extension, Json, Router,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{
     error::ApiError,
-    models::user::{Role, UserDto},
+    models::{
+        audit_log::{event_type, AuditLog, ClientInfo},
+        user::{Role, UserDto},
+        webhook,
+    },
     routes::{extractors::ApiVersion, ApiResponse},
     services::{self, auth::AuthError, jwt::Claims},
     ApiState,
 };
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AuthPayload {
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Slug of the organization to log in to, the global namespace is used without it.
+    #[serde(default)]
+    pub organization: Option<String>,
+    /// Invitation token, required to register when registration is invite-only.
+    #[serde(default)]
+    pub invitation: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct VerifyQuery {
+    /// Role the subject is required to have.
+    #[serde(default)]
+    pub role: Option<Role>,
+    /// Scope the token is required to have.
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RefreshPayload {
     pub refresh_token: String,
 }
 
+/// Token issued by the login and refresh endpoints.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IssuedToken {
+    pub token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginTokens {
+    pub jwt_access: IssuedToken,
+    pub jwt_refresh: IssuedToken,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefreshedTokens {
+    pub jwt_access: IssuedToken,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PasswordPayload {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DevicePayload {
+    pub user_code: String,
+    pub approve: bool,
+}
+
 async fn register(
     State(state): State<Arc<ApiState>>,
     version: ApiVersion,
+    client: ClientInfo,
     Json(payload): Json<AuthPayload>,
 ) -> Result<ApiResponse, ApiError> {
-    let new_user = services::users::create_user(&state, payload, &[Role::User]).await?;
+    let username = payload.username.clone();
+    let (new_user, invitation) = match services::invitations::register(&state, payload).await {
+        Ok(registered) => registered,
+        Err(e) => {
+            let mut metadata = services::audit::failure_metadata(&e);
+            metadata["username"] = json!(username);
+            services::audit::record(
+                &state,
+                AuditLog::new(event_type::REGISTER, None, None)
+                    .with_client(&client)
+                    .with_metadata(metadata)
+                    .failed(),
+            )
+            .await;
+            return Err(e);
+        }
+    };
+
+    let mut audit_log = AuditLog::new(event_type::REGISTER, Some(new_user.id), Some(new_user.id))
+        .with_client(&client);
+    if let Some(invitation) = invitation {
+        audit_log = audit_log.with_metadata(json!({ "invitation_id": invitation.id }));
+    }
+    services::audit::record(&state, audit_log).await;
+    services::webhooks::publish_user_event(&state, webhook::event_type::USER_REGISTERED, &new_user)
+        .await;
+
     let user_dto = UserDto::from(&new_user);
 
     ApiResponse::builder()
@@ -49,10 +130,58 @@ async fn register(
 async fn login(
     State(state): State<Arc<ApiState>>,
     version: ApiVersion,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    client: ClientInfo,
     Json(payload): Json<AuthPayload>,
 ) -> Result<ApiResponse, ApiError> {
-    let (access_token, refresh_token, deleted_token) =
-        services::auth::login(&state, payload).await?;
+    let jkt =
+        services::dpop::verify_request(&state, &headers, &Method::POST, uri.path(), None).await?;
+
+    let username = payload.username.clone();
+    let (user, access_token, refresh_token, deleted_token) =
+        match services::auth::login(&state, payload, jkt.as_deref()).await {
+            Ok(res) => res,
+            Err(e) => {
+                let mut metadata = services::audit::failure_metadata(&e);
+                metadata["username"] = json!(username);
+                services::audit::record(
+                    &state,
+                    AuditLog::new(event_type::LOGIN, None, None)
+                        .with_client(&client)
+                        .with_metadata(metadata)
+                        .failed(),
+                )
+                .await;
+                return Err(e);
+            }
+        };
+
+    // Checked before the login is recorded, otherwise every device would already be known.
+    // Lookup errors are treated as a known device, they must not fail the login.
+    let new_device = matches!(
+        services::audit::is_known_device(&state, user.id, &client).await,
+        Ok(false)
+    );
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::LOGIN, Some(user.id), Some(user.id)).with_client(&client),
+    )
+    .await;
+
+    if new_device {
+        services::webhooks::publish(
+            &state,
+            webhook::event_type::SESSION_NEW_DEVICE,
+            json!({
+                "user": UserDto::from(&user),
+                "ip_address": client.ip_address,
+                "user_agent": client.user_agent,
+            }),
+        )
+        .await;
+    }
 
     let msg = if let Some(token) = deleted_token {
         format!(
@@ -68,16 +197,16 @@ async fn login(
         .with_code(StatusCode::OK)
         .with_api_version(version)
         .with_message(&msg)
-        .with_payload(json!({
-            "jwt_access": {
-                "token": access_token,
-                "token_type": "Bearer",
-                "expires_in": state.config.jwt_access_expiration,
+        .with_payload(json!(LoginTokens {
+            jwt_access: IssuedToken {
+                token: access_token,
+                token_type: services::oauth::access_token_type(jkt.as_deref()).to_owned(),
+                expires_in: state.config.jwt_access_expiration,
             },
-            "jwt_refresh": {
-                "token": refresh_token,
-                "token_type": "Refresh",
-                "expires_in": state.config.jwt_refresh_expiration,
+            jwt_refresh: IssuedToken {
+                token: refresh_token,
+                token_type: "Refresh".to_owned(),
+                expires_in: state.config.jwt_refresh_expiration,
             },
         }))
         .build()
@@ -87,20 +216,46 @@ async fn login(
 async fn refresh(
     State(state): State<Arc<ApiState>>,
     version: ApiVersion,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    client: ClientInfo,
     Json(payload): Json<RefreshPayload>,
 ) -> Result<ApiResponse, ApiError> {
-    let access_token = services::auth::refresh(&state, &payload.refresh_token).await?;
+    let jkt =
+        services::dpop::verify_request(&state, &headers, &Method::POST, uri.path(), None).await?;
+
+    let (access_token, claims) =
+        match services::auth::refresh(&state, &payload.refresh_token, jkt.as_deref()).await {
+            Ok(res) => res,
+            Err(e) => {
+                services::audit::record(
+                    &state,
+                    AuditLog::new(event_type::REFRESH, None, None)
+                        .with_client(&client)
+                        .with_metadata(services::audit::failure_metadata(&e))
+                        .failed(),
+                )
+                .await;
+                return Err(e);
+            }
+        };
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::REFRESH, Some(claims.sub), Some(claims.sub)).with_client(&client),
+    )
+    .await;
 
     ApiResponse::builder()
         .with_success(true)
         .with_code(StatusCode::OK)
         .with_api_version(version)
         .with_message("Issued new access token")
-        .with_payload(json!({
-            "jwt_access": {
-                "token": access_token,
-                "token_type": "Bearer",
-                "expires_in": state.config.jwt_access_expiration,
+        .with_payload(json!(RefreshedTokens {
+            jwt_access: IssuedToken {
+                token: access_token,
+                token_type: services::oauth::access_token_type(jkt.as_deref()).to_owned(),
+                expires_in: state.config.jwt_access_expiration,
             },
         }))
         .build()
@@ -111,9 +266,20 @@ async fn logout(
     State(state): State<Arc<ApiState>>,
     version: ApiVersion,
     Extension(claims): Extension<Claims>,
+    client: ClientInfo,
     Json(payload): Json<RefreshPayload>,
 ) -> Result<ApiResponse, ApiError> {
     let res = services::auth::logout(&state, &payload.refresh_token, claims.jti).await?;
+
+    if let Some(jti) = res {
+        services::audit::record(
+            &state,
+            AuditLog::new(event_type::LOGOUT, Some(claims.sub), Some(claims.sub))
+                .with_client(&client)
+                .with_metadata(json!({ "session_id": jti })),
+        )
+        .await;
+    }
     let mut builder = ApiResponse::builder().with_api_version(version);
 
     builder = if let Some(jti) = res {
@@ -130,6 +296,92 @@ async fn logout(
     builder.build().as_ok()
 }
 
+async fn change_password(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<PasswordPayload>,
+) -> Result<ApiResponse, ApiError> {
+    // Neither OAuth clients nor impersonating admins may take over the account.
+    claims.ensure_interactive_user()?;
+
+    let res = services::users::change_password(
+        &state,
+        claims.sub,
+        &payload.current_password,
+        &payload.new_password,
+    )
+    .await;
+
+    let audit_log = AuditLog::new(
+        event_type::PASSWORD_CHANGED,
+        Some(claims.sub),
+        Some(claims.sub),
+    )
+    .with_client(&client);
+    match res {
+        Ok(_) => services::audit::record(&state, audit_log).await,
+        Err(e) => {
+            services::audit::record(
+                &state,
+                audit_log
+                    .with_metadata(services::audit::failure_metadata(&e))
+                    .failed(),
+            )
+            .await;
+            return Err(e);
+        }
+    }
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("Password changed. All sessions have been revoked.")
+        .build()
+        .as_ok()
+}
+
+async fn device(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<DevicePayload>,
+) -> Result<ApiResponse, ApiError> {
+    // Delegated tokens must not be able to grant access to other clients.
+    claims.ensure_interactive_user()?;
+
+    let user = state
+        .db
+        .users()
+        .find_by_id(claims.sub)
+        .await?
+        .ok_or(AuthError::Unauthorized)?;
+
+    let authorization = services::oauth::decide_device_authorization(
+        &state,
+        &payload.user_code,
+        &user,
+        payload.approve,
+    )
+    .await?;
+
+    let msg = if payload.approve {
+        "Device authorization approved"
+    } else {
+        "Device authorization denied"
+    };
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message(msg)
+        .with_payload(json!({
+            "client_id": authorization.client_id,
+            "scope": authorization.scope,
+        }))
+        .build()
+        .as_ok()
+}
+
 async fn protected(
     Extension(claims): Extension<Claims>,
     version: ApiVersion,
@@ -146,7 +398,7 @@ async fn admin(
     Extension(claims): Extension<Claims>,
     version: ApiVersion,
 ) -> Result<ApiResponse, ApiError> {
-    if !claims.admin {
+    if !claims.has_role(Role::Admin) {
         return Err(AuthError::Forbidden.into());
     }
 
@@ -157,14 +409,102 @@ async fn admin(
         .as_ok()
 }
 
+const X_AUTH_USER: HeaderName = HeaderName::from_static("x-auth-user");
+const X_AUTH_USER_ID: HeaderName = HeaderName::from_static("x-auth-user-id");
+const X_AUTH_ROLES: HeaderName = HeaderName::from_static("x-auth-roles");
+
+/// First of the headers set by the proxy.
+fn forwarded<'a>(headers: &'a HeaderMap, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name))
+        .and_then(|v| v.to_str().ok())
+}
+
+/// Forward auth endpoint for reverse proxies. The original method and URI, which DPoP proofs
+/// are bound to, are taken from the X-Forwarded-Method and X-Forwarded-Uri headers (Traefik)
+/// or the X-Original-Method and X-Original-URI headers (nginx).
+async fn verify(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    query: Result<Query<VerifyQuery>, QueryRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+
+    // Browsers cannot attach the authorization header, so the access token of their
+    // session is read from the configured cookie instead.
+    let (scheme, access_token) = match services::auth::authorization(&headers) {
+        Some(authorization) => authorization,
+        None if !state.config.session_cookie.is_empty() => (
+            "Bearer",
+            services::auth::cookie(&headers, &state.config.session_cookie)
+                .ok_or(AuthError::Unauthorized)?,
+        ),
+        None => return Err(AuthError::Unauthorized.into()),
+    };
+
+    let method = forwarded(&headers, &["x-forwarded-method", "x-original-method"])
+        .and_then(|m| m.parse().ok())
+        .unwrap_or(method);
+    let path = forwarded(&headers, &["x-forwarded-uri", "x-original-uri"])
+        .unwrap_or_else(|| uri.path())
+        .split('?')
+        .next()
+        .unwrap_or_default();
+
+    let claims =
+        services::auth::authenticate(&state, scheme, access_token, &headers, &method, path).await?;
+
+    if query.role.is_some_and(|role| !claims.has_role(role))
+        || query
+            .scope
+            .as_deref()
+            .is_some_and(|scope| !claims.has_scope(scope))
+    {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let roles: Vec<String> = claims.roles.iter().map(Role::to_string).collect();
+    let response = ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Token valid")
+        .with_payload(json!({
+            "user_id": claims.sub,
+            "username": claims.username,
+            "roles": claims.roles,
+        }))
+        .build();
+
+    // Usernames may contain characters not allowed in headers, proxies can rely on the
+    // user id when the username is left out.
+    let username = HeaderValue::try_from(claims.username).ok();
+
+    Ok((
+        [
+            (X_AUTH_USER_ID, claims.sub.to_string()),
+            (X_AUTH_ROLES, roles.join(",")),
+        ],
+        username.map(|username| [(X_AUTH_USER, username)]),
+        response,
+    ))
+}
+
 pub fn create_routes(state: Arc<ApiState>) -> Router {
     let public_routes = Router::new()
         .route("/register", post(register))
         .route("/login", post(login))
-        .route("/refresh", post(refresh));
+        .route("/refresh", post(refresh))
+        .route("/verify", get(verify));
 
     let protected_routes = Router::new()
         .route("/logout", post(logout))
+        .route("/password", post(change_password))
+        .route("/device", post(device))
         .route("/protected", get(protected))
         .route("/admin", get(admin))
         .layer(axum::middleware::from_fn(services::auth::auth_guard))
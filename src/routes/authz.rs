@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, routing::post, Extension, Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    error::ApiError,
+    routes::{extractors::ApiVersion, ApiResponse},
+    services::{
+        self,
+        auth::AuthError,
+        authz::Resource,
+        jwt::Claims,
+        oauth::{find_active_claims, TokenTypeHint},
+    },
+    ApiState,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CheckPayload {
+    pub action: String,
+    pub resource: Resource,
+    /// Access token of the subject, the caller is the subject without it.
+    #[serde(default)]
+    pub subject_token: Option<String>,
+}
+
+async fn check(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CheckPayload>,
+) -> Result<ApiResponse, ApiError> {
+    if payload.action.is_empty() || payload.resource.kind.is_empty() {
+        return Err(ApiError::BadRequest(
+            "action and resource type are required".to_string(),
+        ));
+    }
+
+    let subject = match payload.subject_token.as_deref() {
+        Some(token) => find_active_claims(&state, token, TokenTypeHint::AccessToken)
+            .await?
+            .ok_or(AuthError::TokenInvalid)?,
+        None => claims,
+    };
+
+    let decision = services::authz::authorize(
+        state.db.as_ref(),
+        &state.policies,
+        &subject,
+        &payload.action,
+        &payload.resource,
+        json!({ "id": payload.resource.id }),
+    )
+    .await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message(if decision.allowed {
+            "Access allowed"
+        } else {
+            "Access denied"
+        })
+        .with_payload(json!({ "subject": subject.sub, "decision": decision }))
+        .build()
+        .as_ok()
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    Router::new()
+        .route("/check", post(check))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)))
+        .with_state(state)
+}
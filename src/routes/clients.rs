@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+
+use crate::{
+    error::ApiError,
+    models::{client::ClientDto, user::Role},
+    routes::{
+        extractors::{ApiVersion, VerIdParams},
+        ApiResponse,
+    },
+    services::{self, auth::AuthError, jwt::Claims, oauth::grant_type},
+    ApiState,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClientPayload {
+    pub client_id: String,
+    #[serde(default = "default_confidential")]
+    pub confidential: bool,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default = "default_grant_types")]
+    pub grant_types: Vec<String>,
+    #[serde(default)]
+    pub exchange_audiences: Vec<String>,
+}
+
+fn default_confidential() -> bool {
+    true
+}
+
+fn default_grant_types() -> Vec<String> {
+    vec![grant_type::CLIENT_CREDENTIALS.to_owned()]
+}
+
+async fn create_client(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<ClientPayload>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let (client, client_secret) = services::oauth::create_client(
+        state.db.as_ref(),
+        &payload.client_id,
+        payload.confidential,
+        &payload.scopes,
+        &payload.redirect_uris,
+        &payload.grant_types,
+        &payload.exchange_audiences,
+    )
+    .await?;
+    let client_dto = ClientDto::from(client);
+
+    ApiResponse::builder()
+        .with_code(StatusCode::CREATED)
+        .with_api_version(version)
+        .with_message("client created")
+        .with_payload(serde_json::json!({
+            "client": client_dto,
+            "client_secret": client_secret,
+        }))
+        .build()
+        .as_ok()
+}
+
+async fn get_all_clients(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let clients: Vec<ClientDto> = state
+        .db
+        .clients()
+        .find_all()
+        .await?
+        .iter()
+        .map(ClientDto::from)
+        .collect();
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message(&format!("found {} clients", clients.len()))
+        .with_payload(serde_json::json!({ "clients": clients }))
+        .build()
+        .as_ok()
+}
+
+async fn delete_client(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let Some(client) = state.db.clients().delete_by_id(id).await? else {
+        return ApiResponse::builder()
+            .with_success(false)
+            .with_api_version(version)
+            .with_message("client not found")
+            .with_code(StatusCode::NOT_FOUND)
+            .build()
+            .as_ok();
+    };
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("client deleted")
+        .with_payload(serde_json::json!({ "client": ClientDto::from(client) }))
+        .build()
+        .as_ok()
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    let protected_routes = Router::new()
+        .route("/", get(get_all_clients))
+        .route("/", post(create_client))
+        .route("/{id}", delete(delete_client))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)));
+
+    Router::new().merge(protected_routes).with_state(state)
+}
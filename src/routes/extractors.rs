@@ -1,15 +1,15 @@
-use std::{collections::HashMap, fmt::Display, str::FromStr};
+use std::{collections::HashMap, fmt::Display, net::SocketAddr, str::FromStr};
 
 use axum::{
-    extract::{FromRequestParts, Path},
-    http::request::Parts,
+    extract::{ConnectInfo, FromRequestParts, Path},
+    http::{header, request::Parts},
     RequestPartsExt,
 };
 use serde::de::Error;
 use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::error::ApiError;
+use crate::{error::ApiError, models::audit_log::ClientInfo};
 
 #[derive(Debug)]
 pub enum ApiVersion {
@@ -97,3 +97,114 @@ where
         Ok(VerIdParams { version, id })
     }
 }
+
+/// Path parameters of routes addressing a member of a resource, e.g. a user within a group.
+pub struct VerIdMemberParams {
+    pub version: ApiVersion,
+    pub id: Uuid,
+    pub member_id: Uuid,
+}
+
+impl<S> FromRequestParts<S> for VerIdMemberParams
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path((version, id, member_id)): Path<(ApiVersion, Uuid, Uuid)> =
+            Path::from_request_parts(parts, state)
+                .await
+                .map_err(|rejection| {
+                    let msg = format!("{}", rejection);
+                    if msg.contains("version (") && msg.contains(") not supported") {
+                        ApiError::from(rejection)
+                    } else {
+                        ApiError::BadRequest(format!("invalid path parameters: {}", rejection))
+                    }
+                })?;
+
+        Ok(VerIdMemberParams {
+            version,
+            id,
+            member_id,
+        })
+    }
+}
+
+/// Path parameters of the organization routes, `id` is present on routes of a single resource.
+pub struct OrgParams {
+    pub version: ApiVersion,
+    pub org_id: Uuid,
+    pub id: Option<Uuid>,
+}
+
+impl OrgParams {
+    /// Id of the resource within the organization, for routes with an `{id}` parameter.
+    pub fn resource_id(&self) -> Result<Uuid, ApiError> {
+        self.id
+            .ok_or_else(|| ApiError::BadRequest("id param missing".to_string()))
+    }
+}
+
+impl<S> FromRequestParts<S> for OrgParams
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let params: Path<HashMap<String, String>> = parts
+            .extract()
+            .await
+            .map_err(|e| ApiError::BadRequest(format!("path rejection error: {}", e)))?;
+
+        let parse_id = |name: &str| {
+            params
+                .get(name)
+                .map(|id| {
+                    Uuid::parse_str(id).map_err(|e| {
+                        ApiError::BadRequest(format!("invalid path parameters: {}: {}", name, e))
+                    })
+                })
+                .transpose()
+        };
+
+        let version = params
+            .get("version")
+            .ok_or_else(|| ApiError::BadRequest("version param missing".to_string()))
+            .and_then(|v| ApiVersion::from_str(v))?;
+        let org_id = parse_id("org_id")?
+            .ok_or_else(|| ApiError::BadRequest("org_id param missing".to_string()))?;
+
+        Ok(OrgParams {
+            version,
+            org_id,
+            id: parse_id("id")?,
+        })
+    }
+}
+
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip_address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+
+        Ok(ClientInfo {
+            ip_address,
+            user_agent,
+        })
+    }
+}
@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, post},
+    Extension, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    error::ApiError,
+    models::{
+        audit_log::{event_type, AuditLog, ClientInfo},
+        user::UserDto,
+        webhook,
+    },
+    routes::{
+        extractors::{ApiVersion, VerIdParams},
+        ApiResponse,
+    },
+    services::{self, auth::AuthError, federation::FederatedOutcome, jwt::Claims},
+    ApiState,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CallbackQuery {
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub error_description: Option<String>,
+}
+
+async fn providers(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+) -> Result<ApiResponse, ApiError> {
+    let providers: Vec<_> = state
+        .config
+        .oidc_upstream_providers
+        .iter()
+        .map(|provider| {
+            json!({
+                "name": provider.name,
+                "issuer": provider.issuer,
+                "authorize_uri": format!(
+                    "{}/api/{}/auth/federated/{}/authorize",
+                    state.config.issuer(),
+                    version,
+                    provider.name
+                ),
+            })
+        })
+        .collect();
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Identity providers")
+        .with_payload(json!({ "providers": providers }))
+        .build()
+        .as_ok()
+}
+
+async fn authorize(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Path((_, provider)): Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    let location = services::federation::start(&state, &version, &provider, None).await?;
+    Ok(Redirect::to(&location).into_response())
+}
+
+async fn link(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Path((_, provider)): Path<(String, String)>,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    // A linked identity logs in as the user, so neither OAuth clients nor impersonating
+    // admins may link one.
+    claims.ensure_interactive_user()?;
+
+    let authorization_url =
+        services::federation::start(&state, &version, &provider, Some(claims.sub)).await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Continue at the identity provider to link the identity")
+        .with_payload(json!({ "authorization_url": authorization_url }))
+        .build()
+        .as_ok()
+}
+
+async fn callback(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Path((_, provider)): Path<(String, String)>,
+    client: ClientInfo,
+    Query(query): Query<CallbackQuery>,
+) -> Result<ApiResponse, ApiError> {
+    let res = match (&query.error, &query.code, &query.state) {
+        (Some(error), _, _) => Err(ApiError::BadRequest(format!(
+            "login at {} failed: {}",
+            provider,
+            query.error_description.as_deref().unwrap_or(error)
+        ))),
+        (None, Some(code), Some(login_state)) => {
+            services::federation::complete(&state, &provider, code, login_state).await
+        }
+        _ => Err(ApiError::BadRequest(
+            "code and state parameters are required".to_string(),
+        )),
+    };
+
+    let outcome = match res {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            let mut metadata = services::audit::failure_metadata(&e);
+            metadata["provider"] = json!(provider);
+            services::audit::record(
+                &state,
+                AuditLog::new(event_type::LOGIN, None, None)
+                    .with_client(&client)
+                    .with_metadata(metadata)
+                    .failed(),
+            )
+            .await;
+            return Err(e);
+        }
+    };
+
+    let (federated, access_token, refresh_token, deleted_token) = match outcome {
+        FederatedOutcome::Linked(identity) => {
+            services::audit::record(
+                &state,
+                AuditLog::new(
+                    event_type::IDENTITY_LINKED,
+                    Some(identity.user_id),
+                    Some(identity.user_id),
+                )
+                .with_client(&client)
+                .with_metadata(json!({ "provider": provider, "identity_id": identity.id })),
+            )
+            .await;
+
+            return ApiResponse::builder()
+                .with_success(true)
+                .with_code(StatusCode::OK)
+                .with_api_version(version)
+                .with_message("Identity linked")
+                .with_payload(json!({ "identity": identity }))
+                .build()
+                .as_ok();
+        }
+        FederatedOutcome::Login {
+            federated,
+            access_token,
+            refresh_token,
+            deleted_token,
+        } => (federated, access_token, refresh_token, deleted_token),
+    };
+
+    let user = &federated.user;
+    if federated.created {
+        services::audit::record(
+            &state,
+            AuditLog::new(event_type::REGISTER, Some(user.id), Some(user.id))
+                .with_client(&client)
+                .with_metadata(json!({ "provider": provider })),
+        )
+        .await;
+        services::webhooks::publish_user_event(&state, webhook::event_type::USER_REGISTERED, user)
+            .await;
+    }
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::LOGIN, Some(user.id), Some(user.id))
+            .with_client(&client)
+            .with_metadata(json!({ "provider": provider })),
+    )
+    .await;
+
+    let msg = if let Some(token) = deleted_token {
+        format!(
+            "Login successful. Oldest session ({}) revoked due to user session limit.",
+            token.jti
+        )
+    } else {
+        String::from("Login successful.")
+    };
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message(&msg)
+        .with_payload(json!({
+            "user": UserDto::from(user),
+            "jwt_access": {
+                "token": access_token,
+                "token_type": services::oauth::access_token_type(None),
+                "expires_in": state.config.jwt_access_expiration,
+            },
+            "jwt_refresh": {
+                "token": refresh_token,
+                "token_type": "Refresh",
+                "expires_in": state.config.jwt_refresh_expiration,
+            },
+        }))
+        .build()
+        .as_ok()
+}
+
+async fn identities(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    let identities = state
+        .db
+        .user_identities()
+        .find_by_user_id(claims.sub)
+        .await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Linked identities")
+        .with_payload(json!({ "identities": identities }))
+        .build()
+        .as_ok()
+}
+
+async fn unlink(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    claims.ensure_interactive_user()?;
+
+    let user = state
+        .db
+        .users()
+        .find_by_id(claims.sub)
+        .await?
+        .ok_or(AuthError::Unauthorized)?;
+    let identity = services::federation::unlink_identity(state.db.as_ref(), &user, id).await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::IDENTITY_UNLINKED, Some(user.id), Some(user.id))
+            .with_client(&client)
+            .with_metadata(json!({ "provider": identity.provider, "identity_id": identity.id })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Identity unlinked")
+        .with_payload(json!({ "identity": identity }))
+        .build()
+        .as_ok()
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    let public_routes = Router::new()
+        .route("/", get(providers))
+        .route("/{provider}/authorize", get(authorize))
+        .route("/{provider}/callback", get(callback));
+
+    let protected_routes = Router::new()
+        .route("/{provider}/link", post(link))
+        .route("/identities", get(identities))
+        .route("/identities/{id}", delete(unlink))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)));
+
+    Router::new()
+        .merge(public_routes)
+        .merge(protected_routes)
+        .with_state(state)
+}
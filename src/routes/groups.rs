@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, put},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    error::ApiError,
+    models::{
+        audit_log::{event_type, AuditLog, ClientInfo},
+        user::{Role, UserDto},
+    },
+    routes::{
+        extractors::{ApiVersion, VerIdMemberParams, VerIdParams},
+        ApiResponse,
+    },
+    services::{self, auth::AuthError, jwt::Claims},
+    ApiState,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GroupPayload {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+}
+
+fn require_admin(claims: &Claims) -> Result<(), ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    Ok(())
+}
+
+async fn list_groups(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+    let groups = state.db.groups().find_all().await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Groups")
+        .with_payload(json!({ "groups": groups }))
+        .build()
+        .as_ok()
+}
+
+async fn create_group(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<GroupPayload>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+    let group = services::groups::create_group(
+        state.db.as_ref(),
+        &payload.name,
+        payload.description.as_deref(),
+        &payload.roles,
+    )
+    .await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::GROUP_CREATED, Some(claims.sub), None)
+            .with_client(&client)
+            .with_metadata(
+                json!({ "group_id": group.id, "name": group.name, "roles": group.roles }),
+            ),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::CREATED)
+        .with_api_version(version)
+        .with_message("Group created")
+        .with_payload(json!({ "group": group }))
+        .build()
+        .as_ok()
+}
+
+async fn get_group(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+    let group = services::groups::find_group(state.db.as_ref(), id).await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Group found")
+        .with_payload(json!({ "group": group }))
+        .build()
+        .as_ok()
+}
+
+async fn update_group(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<GroupPayload>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+    let (old, group) = services::groups::update_group(
+        state.db.as_ref(),
+        id,
+        &payload.name,
+        payload.description.as_deref(),
+        &payload.roles,
+    )
+    .await?;
+
+    if old.roles != group.roles {
+        let member_ids = services::groups::member_ids(state.db.as_ref(), id).await?;
+        services::groups::sync_members(
+            &state,
+            &member_ids,
+            services::groups::drops_roles(&old, &group),
+        )
+        .await?;
+    }
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::GROUP_UPDATED, Some(claims.sub), None)
+            .with_client(&client)
+            .with_metadata(
+                json!({ "group_id": group.id, "name": group.name, "roles": group.roles }),
+            ),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Group updated")
+        .with_payload(json!({ "group": group }))
+        .build()
+        .as_ok()
+}
+
+async fn delete_group(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+    let (group, member_ids) = services::groups::delete_group(state.db.as_ref(), id).await?;
+    services::groups::sync_members(&state, &member_ids, !group.roles.is_empty()).await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::GROUP_DELETED, Some(claims.sub), None)
+            .with_client(&client)
+            .with_metadata(json!({ "group_id": group.id, "name": group.name })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Group deleted")
+        .with_payload(json!({ "group": group }))
+        .build()
+        .as_ok()
+}
+
+async fn list_members(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+    let members: Vec<UserDto> = services::groups::list_members(state.db.as_ref(), id)
+        .await?
+        .into_iter()
+        .map(UserDto::from)
+        .collect();
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Group members")
+        .with_payload(json!({ "members": members }))
+        .build()
+        .as_ok()
+}
+
+async fn add_member(
+    State(state): State<Arc<ApiState>>,
+    VerIdMemberParams {
+        version,
+        id,
+        member_id,
+    }: VerIdMemberParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+    let (group, member) = services::groups::add_member(state.db.as_ref(), id, member_id).await?;
+    services::groups::sync_members(&state, &[member_id], false).await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(
+            event_type::GROUP_MEMBER_ADDED,
+            Some(claims.sub),
+            Some(member_id),
+        )
+        .with_client(&client)
+        .with_metadata(json!({ "group_id": group.id, "name": group.name })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::CREATED)
+        .with_api_version(version)
+        .with_message("Member added")
+        .with_payload(json!({ "member": member }))
+        .build()
+        .as_ok()
+}
+
+async fn remove_member(
+    State(state): State<Arc<ApiState>>,
+    VerIdMemberParams {
+        version,
+        id,
+        member_id,
+    }: VerIdMemberParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+    let (group, member) = services::groups::remove_member(state.db.as_ref(), id, member_id).await?;
+    services::groups::sync_members(&state, &[member_id], !group.roles.is_empty()).await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(
+            event_type::GROUP_MEMBER_REMOVED,
+            Some(claims.sub),
+            Some(member_id),
+        )
+        .with_client(&client)
+        .with_metadata(json!({ "group_id": group.id, "name": group.name })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Member removed")
+        .with_payload(json!({ "member": member }))
+        .build()
+        .as_ok()
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    Router::new()
+        .route("/", get(list_groups).post(create_group))
+        .route(
+            "/{id}",
+            get(get_group).put(update_group).delete(delete_group),
+        )
+        .route("/{id}/members", get(list_members))
+        .route(
+            "/{id}/members/{user_id}",
+            put(add_member).delete(remove_member),
+        )
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)))
+        .with_state(state)
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    error::ApiError,
+    models::{
+        audit_log::{event_type, AuditLog, ClientInfo},
+        user::Role,
+    },
+    routes::{
+        extractors::{ApiVersion, VerIdParams},
+        ApiResponse,
+    },
+    services::{self, auth::AuthError, jwt::Claims},
+    ApiState,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct InvitationPayload {
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Roles of the registered account, the user role is assigned without them.
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    /// Lifetime of the invitation in seconds, the configured lifetime is used without it.
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+}
+
+async fn list_invitations(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let invitations = state.db.invitations().find_all().await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Invitations")
+        .with_payload(json!({ "invitations": invitations }))
+        .build()
+        .as_ok()
+}
+
+async fn create_invitation(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<InvitationPayload>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let (invitation, token) = services::invitations::create_invitation(
+        state.db.as_ref(),
+        claims.sub,
+        payload.email.as_deref(),
+        &payload.roles,
+        payload
+            .expires_in
+            .unwrap_or(state.config.invitation_expiration),
+    )
+    .await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::INVITATION_CREATED, Some(claims.sub), None)
+            .with_client(&client)
+            .with_metadata(json!({ "invitation_id": invitation.id, "roles": invitation.roles })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::CREATED)
+        .with_api_version(version)
+        .with_message("Invitation created. The token is shown only once.")
+        .with_payload(json!({ "invitation": invitation, "token": token }))
+        .build()
+        .as_ok()
+}
+
+async fn revoke_invitation(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let invitation = services::invitations::revoke_invitation(state.db.as_ref(), id).await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::INVITATION_REVOKED, Some(claims.sub), None)
+            .with_client(&client)
+            .with_metadata(json!({ "invitation_id": invitation.id })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Invitation revoked")
+        .with_payload(json!({ "invitation": invitation }))
+        .build()
+        .as_ok()
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    Router::new()
+        .route("/", get(list_invitations).post(create_invitation))
+        .route("/{id}", delete(revoke_invitation))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)))
+        .with_state(state)
+}
@@ -4,6 +4,7 @@ use axum::{extract::State, routing::get, Extension, Router};
 
 use crate::{
     error::ApiError,
+    models::user::Role,
     routes::{extractors::ApiVersion, ApiResponse},
     services::{self, auth::AuthError, jwt::Claims},
     ApiState,
@@ -14,7 +15,7 @@ async fn delete_expired_jwt(
     version: ApiVersion,
     Extension(claims): Extension<Claims>,
 ) -> Result<ApiResponse, ApiError> {
-    if !claims.admin {
+    if !claims.has_role(Role::Admin) {
         return Err(AuthError::Forbidden.into());
     }
 
@@ -28,9 +29,49 @@ async fn delete_expired_jwt(
         .as_ok()
 }
 
+async fn purge_deleted_users(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let purged_count = services::users::purge_deleted_users(&state).await?;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("purged deleted users")
+        .with_payload(serde_json::json!({"purged_count": purged_count}))
+        .build()
+        .as_ok()
+}
+
+async fn purge_audit_logs(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let deleted_count = services::audit::purge_expired(&state).await?;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("purged expired audit log entries")
+        .with_payload(serde_json::json!({"deleted_count": deleted_count}))
+        .build()
+        .as_ok()
+}
+
 pub fn create_routes(state: Arc<ApiState>) -> Router {
     let protected_routes = Router::new()
         .route("/delete-expired-jwt", get(delete_expired_jwt))
+        .route("/purge-deleted-users", get(purge_deleted_users))
+        .route("/purge-audit-logs", get(purge_audit_logs))
         .layer(axum::middleware::from_fn(services::auth::auth_guard))
         .layer(Extension(state.clone()));
 
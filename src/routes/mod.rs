@@ -12,10 +12,21 @@ use tracing::Level;
 
 use crate::{error::ApiError, routes::extractors::ApiVersion, ApiState};
 
+pub mod admin;
 pub mod auth;
+pub mod authz;
+pub mod clients;
 pub mod extractors;
+pub mod federation;
+pub mod groups;
+pub mod invitations;
 pub mod maintenance;
+pub mod oauth;
+pub mod oidc;
+pub mod organizations;
+pub mod scim;
 pub mod users;
+pub mod webhooks;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiResponse {
@@ -144,6 +155,14 @@ pub fn create_routes(state: Arc<ApiState>) -> Router {
             "/api/{version}/auth",
             auth::create_routes(Arc::clone(&state)),
         )
+        .nest(
+            "/api/{version}/auth/federated",
+            federation::create_routes(Arc::clone(&state)),
+        )
+        .nest(
+            "/api/{version}/admin",
+            admin::create_routes(Arc::clone(&state)),
+        )
         .nest(
             "/api/{version}/maintenance",
             maintenance::create_routes(Arc::clone(&state)),
@@ -152,6 +171,33 @@ pub fn create_routes(state: Arc<ApiState>) -> Router {
             "/api/{version}/users",
             users::create_routes(Arc::clone(&state)),
         )
+        .nest(
+            "/api/{version}/authz",
+            authz::create_routes(Arc::clone(&state)),
+        )
+        .nest(
+            "/api/{version}/clients",
+            clients::create_routes(Arc::clone(&state)),
+        )
+        .nest(
+            "/api/{version}/groups",
+            groups::create_routes(Arc::clone(&state)),
+        )
+        .nest(
+            "/api/{version}/invitations",
+            invitations::create_routes(Arc::clone(&state)),
+        )
+        .nest(
+            "/api/{version}/orgs",
+            organizations::create_routes(Arc::clone(&state)),
+        )
+        .nest(
+            "/api/{version}/webhooks",
+            webhooks::create_routes(Arc::clone(&state)),
+        )
+        .nest("/scim/v2", scim::create_routes(Arc::clone(&state)))
+        .nest("/oauth", oauth::create_routes(Arc::clone(&state)))
+        .merge(oidc::create_routes(Arc::clone(&state)))
         .route("/api/{version}/health", get(health_check))
         .fallback(fallback_handler)
         .layer(
@@ -0,0 +1,541 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::FormRejection, OriginalUri, Query, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use serde::Deserialize;
+
+use crate::{
+    error::ApiError,
+    models::device_authorization::DeviceAuthorization,
+    services::{
+        self,
+        auth::AuthError,
+        oauth::{
+            grant_type, AuthorizationRequest, ClientCredentials, OAuthError, TokenExchangeRequest,
+        },
+    },
+    ApiState,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub device_code: Option<String>,
+    pub subject_token: Option<String>,
+    pub subject_token_type: Option<String>,
+    pub audience: Option<String>,
+    pub requested_token_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeviceAuthorizationRequest {
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeviceQuery {
+    pub user_code: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeviceForm {
+    pub user_code: String,
+    pub username: String,
+    pub password: String,
+    pub decision: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TokenLookupRequest {
+    pub token: Option<String>,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AuthorizationForm {
+    #[serde(flatten)]
+    pub request: AuthorizationRequest,
+    pub username: String,
+    pub password: String,
+    pub decision: String,
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+fn hidden_input(name: &str, value: Option<&str>) -> String {
+    value
+        .map(|v| {
+            format!(
+                r#"<input type="hidden" name="{}" value="{}">"#,
+                name,
+                escape_html(v)
+            )
+        })
+        .unwrap_or_default()
+}
+
+fn render_page(status: StatusCode, title: &str, body: &str) -> Response {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title} - {name}</title>
+</head>
+<body>
+<main>
+<h1>{title}</h1>
+{body}
+</main>
+</body>
+</html>"#,
+        title = escape_html(title),
+        name = env!("CARGO_PKG_NAME"),
+        body = body,
+    );
+
+    (
+        status,
+        [
+            (header::X_FRAME_OPTIONS, "DENY"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        Html(html),
+    )
+        .into_response()
+}
+
+fn error_page(message: &str) -> Response {
+    render_page(
+        StatusCode::BAD_REQUEST,
+        "Authorization error",
+        &format!("<p>{}</p>", escape_html(message)),
+    )
+}
+
+fn login_page(
+    request: &AuthorizationRequest,
+    client_id: &str,
+    scope: &str,
+    error: Option<&str>,
+) -> Response {
+    let status = if error.is_some() {
+        StatusCode::UNAUTHORIZED
+    } else {
+        StatusCode::OK
+    };
+
+    let scopes: String = scope
+        .split_whitespace()
+        .map(|s| format!("<li>{}</li>", escape_html(s)))
+        .collect();
+
+    let hidden: String = [
+        ("response_type", request.response_type.as_deref()),
+        ("client_id", request.client_id.as_deref()),
+        ("redirect_uri", request.redirect_uri.as_deref()),
+        ("scope", request.scope.as_deref()),
+        ("state", request.state.as_deref()),
+        ("code_challenge", request.code_challenge.as_deref()),
+        (
+            "code_challenge_method",
+            request.code_challenge_method.as_deref(),
+        ),
+        ("nonce", request.nonce.as_deref()),
+    ]
+    .iter()
+    .map(|(name, value)| hidden_input(name, *value))
+    .collect();
+
+    let body = format!(
+        r#"<p><strong>{client_id}</strong> is requesting access to your account.</p>
+<ul>{scopes}</ul>
+{error}
+<form method="post" action="authorize">
+{hidden}
+<label>Username <input type="text" name="username" autocomplete="username" required></label>
+<label>Password <input type="password" name="password" autocomplete="current-password" required></label>
+<button type="submit" name="decision" value="allow">Allow</button>
+<button type="submit" name="decision" value="deny" formnovalidate>Deny</button>
+</form>"#,
+        client_id = escape_html(client_id),
+        scopes = scopes,
+        error = error
+            .map(|e| format!(r#"<p role="alert">{}</p>"#, escape_html(e)))
+            .unwrap_or_default(),
+        hidden = hidden,
+    );
+
+    render_page(status, "Sign in", &body)
+}
+
+fn device_page(
+    user_code: Option<&str>,
+    authorization: Option<&DeviceAuthorization>,
+    error: Option<&str>,
+) -> Response {
+    let status = if error.is_some() {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::OK
+    };
+
+    let request = authorization
+        .map(|authorization| {
+            let scopes: String = authorization
+                .scope
+                .split_whitespace()
+                .map(|s| format!("<li>{}</li>", escape_html(s)))
+                .collect();
+            format!(
+                "<p><strong>{}</strong> is requesting access to your account.</p>\n<ul>{}</ul>",
+                escape_html(&authorization.client_id),
+                scopes
+            )
+        })
+        .unwrap_or_else(|| "<p>Enter the code displayed on your device.</p>".to_owned());
+
+    let body = format!(
+        r#"{request}
+{error}
+<form method="post" action="device">
+<label>Code <input type="text" name="user_code" value="{user_code}" autocomplete="off" required></label>
+<label>Username <input type="text" name="username" autocomplete="username" required></label>
+<label>Password <input type="password" name="password" autocomplete="current-password" required></label>
+<button type="submit" name="decision" value="allow">Allow</button>
+<button type="submit" name="decision" value="deny">Deny</button>
+</form>"#,
+        request = request,
+        error = error
+            .map(|e| format!(r#"<p role="alert">{}</p>"#, escape_html(e)))
+            .unwrap_or_default(),
+        user_code = escape_html(user_code.unwrap_or_default()),
+    );
+
+    render_page(status, "Connect a device", &body)
+}
+
+fn redirect_with_error(
+    redirect_uri: &str,
+    err: &OAuthError,
+    state: Option<&str>,
+) -> Result<Response, ApiError> {
+    let description = err.to_string();
+    let mut params = vec![
+        ("error", err.error_code()),
+        ("error_description", description.as_str()),
+    ];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+
+    let location = services::oauth::authorization_redirect(redirect_uri, &params)?;
+    Ok(Redirect::to(&location).into_response())
+}
+
+async fn authorize_page(
+    State(state): State<Arc<ApiState>>,
+    Query(request): Query<AuthorizationRequest>,
+) -> Result<Response, ApiError> {
+    let (client, redirect_uri) =
+        match services::oauth::resolve_authorization_client(&state, &request).await {
+            Ok(res) => res,
+            Err(ApiError::BadRequest(msg)) => return Ok(error_page(&msg)),
+            Err(e) => return Err(e),
+        };
+
+    match services::oauth::validate_authorization(client, redirect_uri.clone(), &request) {
+        Ok(authorization) => Ok(login_page(
+            &request,
+            &authorization.client.client_id,
+            &authorization.scope,
+            None,
+        )),
+        Err(err) => redirect_with_error(&redirect_uri, &err, request.state.as_deref()),
+    }
+}
+
+async fn authorize(
+    State(state): State<Arc<ApiState>>,
+    Form(form): Form<AuthorizationForm>,
+) -> Result<Response, ApiError> {
+    let request = &form.request;
+    let (client, redirect_uri) =
+        match services::oauth::resolve_authorization_client(&state, request).await {
+            Ok(res) => res,
+            Err(ApiError::BadRequest(msg)) => return Ok(error_page(&msg)),
+            Err(e) => return Err(e),
+        };
+
+    let authorization =
+        match services::oauth::validate_authorization(client, redirect_uri.clone(), request) {
+            Ok(authorization) => authorization,
+            Err(err) => return redirect_with_error(&redirect_uri, &err, request.state.as_deref()),
+        };
+
+    if form.decision != "allow" {
+        return redirect_with_error(
+            &redirect_uri,
+            &OAuthError::AccessDenied,
+            request.state.as_deref(),
+        );
+    }
+
+    let user =
+        match services::auth::verify_credentials(&state, &form.username, &form.password).await {
+            Ok(user) => user,
+            Err(ApiError::Auth(AuthError::InvalidCredentials)) => {
+                return Ok(login_page(
+                    request,
+                    &authorization.client.client_id,
+                    &authorization.scope,
+                    Some("Invalid username or password."),
+                ))
+            }
+            Err(ApiError::Auth(AuthError::AccountDisabled)) => {
+                return Ok(login_page(
+                    request,
+                    &authorization.client.client_id,
+                    &authorization.scope,
+                    Some("This account has been disabled."),
+                ))
+            }
+            Err(e) => return Err(e),
+        };
+
+    let code = services::oauth::issue_authorization_code(&state, &authorization, &user).await?;
+
+    let mut params = vec![("code", code.as_str())];
+    if let Some(state) = request.state.as_deref() {
+        params.push(("state", state));
+    }
+
+    let location = services::oauth::authorization_redirect(&redirect_uri, &params)?;
+    Ok(Redirect::to(&location).into_response())
+}
+
+async fn device_authorization(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    payload: Result<Form<DeviceAuthorizationRequest>, FormRejection>,
+) -> Result<Response, ApiError> {
+    let Form(payload) = payload.map_err(|e| OAuthError::InvalidRequest(e.body_text()))?;
+    let credentials =
+        ClientCredentials::from_request(&headers, payload.client_id, payload.client_secret)?;
+    let client = services::oauth::authenticate_client(&state, &credentials).await?;
+
+    let device_authorization =
+        services::oauth::device_authorization(&state, &client, payload.scope.as_deref()).await?;
+
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(device_authorization),
+    )
+        .into_response())
+}
+
+async fn device_verification_page(
+    State(state): State<Arc<ApiState>>,
+    Query(query): Query<DeviceQuery>,
+) -> Result<Response, ApiError> {
+    let Some(user_code) = query.user_code.as_deref() else {
+        return Ok(device_page(None, None, None));
+    };
+
+    match services::oauth::find_device_authorization(&state, user_code).await {
+        Ok((_, authorization)) => Ok(device_page(Some(user_code), Some(&authorization), None)),
+        Err(ApiError::BadRequest(msg)) => Ok(device_page(Some(user_code), None, Some(&msg))),
+        Err(e) => Err(e),
+    }
+}
+
+async fn device_verification(
+    State(state): State<Arc<ApiState>>,
+    Form(form): Form<DeviceForm>,
+) -> Result<Response, ApiError> {
+    let user =
+        match services::auth::verify_credentials(&state, &form.username, &form.password).await {
+            Ok(user) => user,
+            Err(ApiError::Auth(AuthError::InvalidCredentials)) => {
+                return Ok(device_page(
+                    Some(&form.user_code),
+                    None,
+                    Some("Invalid username or password."),
+                ))
+            }
+            Err(ApiError::Auth(AuthError::AccountDisabled)) => {
+                return Ok(device_page(
+                    Some(&form.user_code),
+                    None,
+                    Some("This account has been disabled."),
+                ))
+            }
+            Err(e) => return Err(e),
+        };
+
+    let approve = form.decision == "allow";
+    match services::oauth::decide_device_authorization(&state, &form.user_code, &user, approve)
+        .await
+    {
+        Ok(_) if approve => Ok(render_page(
+            StatusCode::OK,
+            "Device connected",
+            "<p>You can now return to your device.</p>",
+        )),
+        Ok(_) => Ok(render_page(
+            StatusCode::OK,
+            "Request denied",
+            "<p>The device was not granted access to your account.</p>",
+        )),
+        Err(ApiError::BadRequest(msg)) => Ok(device_page(Some(&form.user_code), None, Some(&msg))),
+        Err(e) => Err(e),
+    }
+}
+
+async fn token(
+    State(state): State<Arc<ApiState>>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    payload: Result<Form<TokenRequest>, FormRejection>,
+) -> Result<Response, ApiError> {
+    let Form(payload) = payload.map_err(|e| OAuthError::InvalidRequest(e.body_text()))?;
+    let credentials =
+        ClientCredentials::from_request(&headers, payload.client_id, payload.client_secret)?;
+    let client = services::oauth::authenticate_client(&state, &credentials).await?;
+
+    let jkt = services::dpop::verify_request(&state, &headers, &Method::POST, uri.path(), None)
+        .await
+        .map_err(|e| match e {
+            ApiError::Auth(AuthError::InvalidDpopProof) => OAuthError::InvalidDpopProof.into(),
+            e => e,
+        })?;
+    let jkt = jkt.as_deref();
+
+    let token_response = match payload.grant_type.as_str() {
+        grant_type::CLIENT_CREDENTIALS => {
+            services::oauth::client_credentials_grant(
+                &state,
+                &client,
+                payload.scope.as_deref(),
+                jkt,
+            )
+            .await?
+        }
+        grant_type::AUTHORIZATION_CODE => {
+            services::oauth::authorization_code_grant(
+                &state,
+                &client,
+                payload.code.as_deref(),
+                payload.redirect_uri.as_deref(),
+                payload.code_verifier.as_deref(),
+                jkt,
+            )
+            .await?
+        }
+        grant_type::DEVICE_CODE => {
+            services::oauth::device_code_grant(&state, &client, payload.device_code.as_deref(), jkt)
+                .await?
+        }
+        grant_type::TOKEN_EXCHANGE => {
+            let request = TokenExchangeRequest {
+                subject_token: payload.subject_token.as_deref(),
+                subject_token_type: payload.subject_token_type.as_deref(),
+                audience: payload.audience.as_deref(),
+                scope: payload.scope.as_deref(),
+                requested_token_type: payload.requested_token_type.as_deref(),
+            };
+            services::oauth::token_exchange_grant(&state, &client, request, jkt).await?
+        }
+        grant_type::REFRESH_TOKEN => {
+            services::oauth::refresh_token_grant(
+                &state,
+                &client,
+                payload.refresh_token.as_deref(),
+                jkt,
+            )
+            .await?
+        }
+        grant_type => return Err(OAuthError::UnsupportedGrantType(grant_type.to_owned()).into()),
+    };
+
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(token_response)).into_response())
+}
+
+async fn introspect(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    payload: Result<Form<TokenLookupRequest>, FormRejection>,
+) -> Result<Response, ApiError> {
+    let Form(payload) = payload.map_err(|e| OAuthError::InvalidRequest(e.body_text()))?;
+    let credentials =
+        ClientCredentials::from_request(&headers, payload.client_id, payload.client_secret)?;
+    let client = services::oauth::authenticate_client(&state, &credentials).await?;
+
+    let introspection = services::oauth::introspect(
+        &state,
+        &client,
+        payload.token.as_deref(),
+        payload.token_type_hint.as_deref(),
+    )
+    .await?;
+
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(introspection)).into_response())
+}
+
+async fn revoke(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    payload: Result<Form<TokenLookupRequest>, FormRejection>,
+) -> Result<Response, ApiError> {
+    let Form(payload) = payload.map_err(|e| OAuthError::InvalidRequest(e.body_text()))?;
+    let credentials =
+        ClientCredentials::from_request(&headers, payload.client_id, payload.client_secret)?;
+    let client = services::oauth::authenticate_client(&state, &credentials).await?;
+
+    services::oauth::revoke(
+        &state,
+        &client,
+        payload.token.as_deref(),
+        payload.token_type_hint.as_deref(),
+    )
+    .await?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    Router::new()
+        .route("/authorize", get(authorize_page))
+        .route("/authorize", post(authorize))
+        .route("/token", post(token))
+        .route("/device_authorization", post(device_authorization))
+        .route("/device", get(device_verification_page))
+        .route("/device", post(device_verification))
+        .route("/introspect", post(introspect))
+        .route("/revoke", post(revoke))
+        .with_state(state)
+}
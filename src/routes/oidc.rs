@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State, http::header, response::IntoResponse, routing::get, Extension, Json, Router,
+};
+
+use crate::{
+    error::ApiError,
+    services::{self, jwt::Claims},
+    ApiState,
+};
+
+async fn openid_configuration(State(state): State<Arc<ApiState>>) -> impl IntoResponse {
+    Json(services::oidc::discovery_document(&state.config))
+}
+
+async fn jwks(State(state): State<Arc<ApiState>>) -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "public, max-age=3600")],
+        Json(state.signing_key.jwks()),
+    )
+}
+
+async fn userinfo(
+    State(state): State<Arc<ApiState>>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse, ApiError> {
+    let info = services::oidc::find_user_info(&state, &claims).await?;
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(info)))
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    let public_routes = Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(openid_configuration),
+        )
+        .route("/.well-known/jwks.json", get(jwks));
+
+    let protected_routes = Router::new()
+        .route("/userinfo", get(userinfo).post(userinfo))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)));
+
+    Router::new()
+        .merge(public_routes)
+        .merge(protected_routes)
+        .with_state(state)
+}
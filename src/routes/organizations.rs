@@ -0,0 +1,449 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    error::ApiError,
+    models::{
+        audit_log::{event_type, AuditLog, ClientInfo},
+        organization::{MemberDto, OrgRole},
+        user::{Role, UserDto},
+        webhook,
+    },
+    routes::{
+        extractors::{ApiVersion, OrgParams},
+        ApiResponse,
+    },
+    services::{
+        self,
+        auth::AuthError,
+        jwt::Claims,
+        organizations::{self as orgs, NewMember},
+    },
+    ApiState,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OrganizationPayload {
+    pub slug: String,
+    pub name: String,
+    /// Global user who becomes the first admin of the organization.
+    #[serde(default)]
+    pub owner_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MemberPayload {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RolePayload {
+    pub role: OrgRole,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct InvitationPayload {
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub role: OrgRole,
+    /// Lifetime of the invitation in seconds, the configured lifetime is used without it.
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AcceptInvitationPayload {
+    pub token: String,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+async fn list_organizations(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    let organizations = orgs::list_organizations(state.db.as_ref(), &claims).await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(version)
+        .with_message("Organizations")
+        .with_payload(json!({ "organizations": organizations }))
+        .build()
+        .as_ok()
+}
+
+async fn create_organization(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<OrganizationPayload>,
+) -> Result<ApiResponse, ApiError> {
+    claims.ensure_interactive_user()?;
+    if !claims.has_role(Role::Admin) || claims.org_id.is_some() {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let organization = orgs::create_organization(
+        state.db.as_ref(),
+        &payload.slug,
+        &payload.name,
+        payload.owner_id,
+    )
+    .await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::ORG_CREATED, Some(claims.sub), payload.owner_id)
+            .with_client(&client)
+            .with_metadata(json!({ "org_id": organization.id, "slug": organization.slug })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::CREATED)
+        .with_api_version(version)
+        .with_message("Organization created")
+        .with_payload(json!({ "organization": organization }))
+        .build()
+        .as_ok()
+}
+
+async fn get_organization(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Member).await?;
+    let organization = orgs::find_organization(state.db.as_ref(), params.org_id).await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(params.version)
+        .with_message("Organization found")
+        .with_payload(json!({ "organization": organization }))
+        .build()
+        .as_ok()
+}
+
+async fn list_members(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Admin).await?;
+    let members = orgs::list_members(state.db.as_ref(), params.org_id).await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(params.version)
+        .with_message("Members")
+        .with_payload(json!({ "members": members }))
+        .build()
+        .as_ok()
+}
+
+async fn create_member(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<MemberPayload>,
+) -> Result<ApiResponse, ApiError> {
+    claims.ensure_interactive_user()?;
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Admin).await?;
+    orgs::find_organization(state.db.as_ref(), params.org_id).await?;
+
+    let (user, membership) = orgs::create_member(
+        state.db.as_ref(),
+        params.org_id,
+        NewMember {
+            username: payload.username,
+            password: payload.password,
+            email: payload.email,
+            role: payload.role,
+        },
+    )
+    .await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(
+            event_type::ORG_MEMBER_ADDED,
+            Some(claims.sub),
+            Some(user.id),
+        )
+        .with_client(&client)
+        .with_metadata(json!({ "org_id": params.org_id, "role": membership.role })),
+    )
+    .await;
+    services::webhooks::publish_user_event(&state, webhook::event_type::USER_REGISTERED, &user)
+        .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::CREATED)
+        .with_api_version(params.version)
+        .with_message("Member created")
+        .with_payload(json!({ "member": MemberDto::new(&user, &membership) }))
+        .build()
+        .as_ok()
+}
+
+async fn get_member(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Admin).await?;
+    let (user, membership) =
+        orgs::find_member(state.db.as_ref(), params.org_id, params.resource_id()?).await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(params.version)
+        .with_message("Member found")
+        .with_payload(json!({ "member": MemberDto::new(&user, &membership) }))
+        .build()
+        .as_ok()
+}
+
+async fn set_member_role(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<RolePayload>,
+) -> Result<ApiResponse, ApiError> {
+    claims.ensure_interactive_user()?;
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Admin).await?;
+    orgs::find_organization(state.db.as_ref(), params.org_id).await?;
+
+    let (user, membership) = orgs::set_member_role(
+        state.db.as_ref(),
+        &claims,
+        params.org_id,
+        params.resource_id()?,
+        payload.role,
+    )
+    .await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(
+            event_type::ORG_ROLE_CHANGED,
+            Some(claims.sub),
+            Some(user.id),
+        )
+        .with_client(&client)
+        .with_metadata(json!({ "org_id": params.org_id, "role": membership.role })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(params.version)
+        .with_message("Member role updated")
+        .with_payload(json!({ "member": MemberDto::new(&user, &membership) }))
+        .build()
+        .as_ok()
+}
+
+async fn remove_member(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    claims.ensure_interactive_user()?;
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Admin).await?;
+    let user_id = params.resource_id()?;
+    orgs::remove_member(&state, params.org_id, user_id).await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(
+            event_type::ORG_MEMBER_REMOVED,
+            Some(claims.sub),
+            Some(user_id),
+        )
+        .with_client(&client)
+        .with_metadata(json!({ "org_id": params.org_id })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(params.version)
+        .with_message("Member removed")
+        .build()
+        .as_ok()
+}
+
+async fn list_invitations(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Admin).await?;
+    let invitations = state
+        .db
+        .org_invitations()
+        .find_by_org_id(params.org_id)
+        .await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(params.version)
+        .with_message("Invitations")
+        .with_payload(json!({ "invitations": invitations }))
+        .build()
+        .as_ok()
+}
+
+async fn create_invitation(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<InvitationPayload>,
+) -> Result<ApiResponse, ApiError> {
+    claims.ensure_interactive_user()?;
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Admin).await?;
+    orgs::find_organization(state.db.as_ref(), params.org_id).await?;
+
+    let expires_in = payload
+        .expires_in
+        .unwrap_or(state.config.org_invitation_expiration);
+    if expires_in <= 0 {
+        return Err(ApiError::BadRequest(
+            "expires_in has to be positive".to_string(),
+        ));
+    }
+
+    let (invitation, token) = orgs::create_invitation(
+        state.db.as_ref(),
+        params.org_id,
+        claims.sub,
+        payload.email.as_deref(),
+        payload.role,
+        expires_in,
+    )
+    .await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::CREATED)
+        .with_api_version(params.version)
+        .with_message("Invitation created. The token is shown only once.")
+        .with_payload(json!({ "invitation": invitation, "token": token }))
+        .build()
+        .as_ok()
+}
+
+async fn revoke_invitation(
+    State(state): State<Arc<ApiState>>,
+    params: OrgParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    claims.ensure_interactive_user()?;
+    orgs::authorize(state.db.as_ref(), &claims, params.org_id, OrgRole::Admin).await?;
+    let invitation =
+        orgs::revoke_invitation(state.db.as_ref(), params.org_id, params.resource_id()?).await?;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::OK)
+        .with_api_version(params.version)
+        .with_message("Invitation revoked")
+        .with_payload(json!({ "invitation": invitation }))
+        .build()
+        .as_ok()
+}
+
+async fn accept_invitation(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    client: ClientInfo,
+    Json(payload): Json<AcceptInvitationPayload>,
+) -> Result<ApiResponse, ApiError> {
+    let (organization, user, membership) = orgs::accept_invitation(
+        state.db.as_ref(),
+        &payload.token,
+        &payload.username,
+        &payload.password,
+        payload.email.as_deref(),
+    )
+    .await?;
+
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::REGISTER, Some(user.id), Some(user.id))
+            .with_client(&client)
+            .with_metadata(json!({ "org_id": organization.id, "role": membership.role })),
+    )
+    .await;
+    services::webhooks::publish_user_event(&state, webhook::event_type::USER_REGISTERED, &user)
+        .await;
+
+    ApiResponse::builder()
+        .with_success(true)
+        .with_code(StatusCode::CREATED)
+        .with_api_version(version)
+        .with_message("User created")
+        .with_payload(json!({ "organization": organization, "user": UserDto::from(&user) }))
+        .build()
+        .as_ok()
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    let public_routes = Router::new().route("/invitations/accept", post(accept_invitation));
+
+    let protected_routes = Router::new()
+        .route("/", get(list_organizations).post(create_organization))
+        .route("/{org_id}", get(get_organization))
+        .route("/{org_id}/users", get(list_members).post(create_member))
+        .route(
+            "/{org_id}/users/{id}",
+            get(get_member).delete(remove_member),
+        )
+        .route("/{org_id}/users/{id}/role", put(set_member_role))
+        .route(
+            "/{org_id}/invitations",
+            get(list_invitations).post(create_invitation),
+        )
+        .route("/{org_id}/invitations/{id}", delete(revoke_invitation))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)));
+
+    Router::new()
+        .merge(public_routes)
+        .merge(protected_routes)
+        .with_state(state)
+}
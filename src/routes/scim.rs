@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Path, Query, State,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use serde::Serialize;
+
+use crate::{
+    models::{
+        audit_log::ClientInfo,
+        pagination::MAX_PAGE_SIZE,
+        scim::{
+            schema, ScimGroup, ScimListQuery, ScimMeta, ScimPatchRequest, ScimUser, CONTENT_TYPE,
+        },
+    },
+    services::{self, scim::ScimError},
+    ApiState,
+};
+
+fn scim_response<T: Serialize>(status: StatusCode, body: &T, meta: Option<&ScimMeta>) -> Response {
+    let mut res = (status, Json(body)).into_response();
+    res.headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+    if let Some(meta) = meta {
+        if let Ok(etag) = HeaderValue::from_str(&meta.version) {
+            res.headers_mut().insert(header::ETAG, etag);
+        }
+        if status == StatusCode::CREATED {
+            if let Ok(location) = HeaderValue::from_str(&meta.location) {
+                res.headers_mut().insert(header::LOCATION, location);
+            }
+        }
+    }
+
+    res
+}
+
+/// Responds with `304 Not Modified` if the client already has the current version.
+fn resource_response<T: Serialize>(
+    headers: &HeaderMap,
+    body: &T,
+    meta: Option<&ScimMeta>,
+) -> Response {
+    let version = meta.map(|m| m.version.as_str()).unwrap_or_default();
+    let not_modified = header_value(headers, header::IF_NONE_MATCH)
+        .is_some_and(|etags| services::scim::check_version(Some(etags), version).is_ok());
+
+    if not_modified {
+        let mut res = StatusCode::NOT_MODIFIED.into_response();
+        if let Ok(etag) = HeaderValue::from_str(version) {
+            res.headers_mut().insert(header::ETAG, etag);
+        }
+        return res;
+    }
+
+    scim_response(StatusCode::OK, body, meta)
+}
+
+fn header_value(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn json_body<T>(body: Result<Json<T>, JsonRejection>) -> Result<T, ScimError> {
+    body.map(|Json(body)| body)
+        .map_err(|e| ScimError::InvalidSyntax(e.body_text()))
+}
+
+fn list_query(
+    query: Result<Query<ScimListQuery>, QueryRejection>,
+) -> Result<ScimListQuery, ScimError> {
+    query
+        .map(|Query(query)| query)
+        .map_err(|e| ScimError::InvalidValue(e.body_text()))
+}
+
+async fn service_provider_config() -> Response {
+    let body = serde_json::json!({
+        "schemas": [schema::SERVICE_PROVIDER_CONFIG],
+        "patch": { "supported": true },
+        "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+        "filter": { "supported": true, "maxResults": MAX_PAGE_SIZE },
+        "changePassword": { "supported": true },
+        "sort": { "supported": false },
+        "etag": { "supported": true },
+        "authenticationSchemes": [{
+            "type": "oauthbearertoken",
+            "name": "Bearer Token",
+            "description": "Authentication with the configured SCIM bearer token",
+        }],
+    });
+
+    scim_response(StatusCode::OK, &body, None)
+}
+
+async fn list_users(
+    State(state): State<Arc<ApiState>>,
+    query: Result<Query<ScimListQuery>, QueryRejection>,
+) -> Result<Response, ScimError> {
+    let res = services::scim::list_users(
+        state.db.as_ref(),
+        &state.config.issuer(),
+        &list_query(query)?,
+    )
+    .await?;
+    Ok(scim_response(StatusCode::OK, &res, None))
+}
+
+async fn create_user(
+    State(state): State<Arc<ApiState>>,
+    client: ClientInfo,
+    body: Result<Json<ScimUser>, JsonRejection>,
+) -> Result<Response, ScimError> {
+    let user = services::scim::create_user(&state, &client, json_body(body)?).await?;
+    Ok(scim_response(
+        StatusCode::CREATED,
+        &user,
+        user.meta.as_ref(),
+    ))
+}
+
+async fn get_user(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ScimError> {
+    let user = services::scim::get_user(&state, &id).await?;
+    Ok(resource_response(&headers, &user, user.meta.as_ref()))
+}
+
+async fn replace_user(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    client: ClientInfo,
+    body: Result<Json<ScimUser>, JsonRejection>,
+) -> Result<Response, ScimError> {
+    let if_match = header_value(&headers, header::IF_MATCH);
+    let user =
+        services::scim::replace_user(&state, &client, &id, if_match, json_body(body)?).await?;
+    Ok(scim_response(StatusCode::OK, &user, user.meta.as_ref()))
+}
+
+async fn patch_user(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    client: ClientInfo,
+    body: Result<Json<ScimPatchRequest>, JsonRejection>,
+) -> Result<Response, ScimError> {
+    let if_match = header_value(&headers, header::IF_MATCH);
+    let user = services::scim::patch_user(&state, &client, &id, if_match, json_body(body)?).await?;
+    Ok(scim_response(StatusCode::OK, &user, user.meta.as_ref()))
+}
+
+async fn delete_user(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    client: ClientInfo,
+) -> Result<Response, ScimError> {
+    let if_match = header_value(&headers, header::IF_MATCH);
+    services::scim::delete_user(&state, &client, &id, if_match).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn list_groups(
+    State(state): State<Arc<ApiState>>,
+    query: Result<Query<ScimListQuery>, QueryRejection>,
+) -> Result<Response, ScimError> {
+    let res = services::scim::list_groups(&state, &list_query(query)?).await?;
+    Ok(scim_response(StatusCode::OK, &res, None))
+}
+
+async fn create_group(
+    State(state): State<Arc<ApiState>>,
+    client: ClientInfo,
+    body: Result<Json<ScimGroup>, JsonRejection>,
+) -> Result<Response, ScimError> {
+    let group = services::scim::create_group(&state, &client, json_body(body)?).await?;
+    Ok(scim_response(
+        StatusCode::CREATED,
+        &group,
+        group.meta.as_ref(),
+    ))
+}
+
+async fn get_group(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ScimError> {
+    let group = services::scim::get_group(&state, &id).await?;
+    Ok(resource_response(&headers, &group, group.meta.as_ref()))
+}
+
+async fn replace_group(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    client: ClientInfo,
+    body: Result<Json<ScimGroup>, JsonRejection>,
+) -> Result<Response, ScimError> {
+    let if_match = header_value(&headers, header::IF_MATCH);
+    let group =
+        services::scim::replace_group(&state, &client, &id, if_match, json_body(body)?).await?;
+    Ok(scim_response(StatusCode::OK, &group, group.meta.as_ref()))
+}
+
+async fn patch_group(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    client: ClientInfo,
+    body: Result<Json<ScimPatchRequest>, JsonRejection>,
+) -> Result<Response, ScimError> {
+    let if_match = header_value(&headers, header::IF_MATCH);
+    let group =
+        services::scim::patch_group(&state, &client, &id, if_match, json_body(body)?).await?;
+    Ok(scim_response(StatusCode::OK, &group, group.meta.as_ref()))
+}
+
+async fn delete_group(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    client: ClientInfo,
+) -> Result<Response, ScimError> {
+    let if_match = header_value(&headers, header::IF_MATCH);
+    services::scim::delete_group(&state, &client, &id, if_match).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    let protected_routes = Router::new()
+        .route("/Users", get(list_users).post(create_user))
+        .route(
+            "/Users/{id}",
+            get(get_user)
+                .put(replace_user)
+                .patch(patch_user)
+                .delete(delete_user),
+        )
+        .route("/Groups", get(list_groups).post(create_group))
+        .route(
+            "/Groups/{id}",
+            get(get_group)
+                .put(replace_group)
+                .patch(patch_group)
+                .delete(delete_group),
+        )
+        .layer(axum::middleware::from_fn(services::scim::scim_guard))
+        .layer(Extension(Arc::clone(&state)));
+
+    Router::new()
+        .route("/ServiceProviderConfig", get(service_provider_config))
+        .merge(protected_routes)
+        .with_state(state)
+}
@@ -1,30 +1,50 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::State,
+    extract::{rejection::QueryRejection, Query, State},
     http::StatusCode,
     routing::{delete, get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
+use serde::Deserialize;
 
 use crate::{
     error::ApiError,
-    models::user::{Role, UserDto},
+    models::{
+        audit_log::{event_type, AuditLog, ClientInfo},
+        user::{Role, UserDto, UserQuery},
+        webhook,
+    },
     routes::{
         auth::AuthPayload,
         extractors::{ApiVersion, VerIdParams},
     },
-    services, ApiState,
+    services::{self, auth::AuthError, authz::Resource, jwt::Claims},
+    ApiState,
 };
 
 use super::ApiResponse;
 
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RolesPayload {
+    pub roles: Vec<Role>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DisablePayload {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
 async fn create_user(
     State(state): State<Arc<ApiState>>,
     version: ApiVersion,
     Json(payload): Json<AuthPayload>,
 ) -> Result<ApiResponse, ApiError> {
-    let new_user = services::users::create_user(&state, payload, &[Role::User]).await?;
+    let (new_user, _) = services::invitations::register(&state, payload).await?;
+    services::webhooks::publish_user_event(&state, webhook::event_type::USER_REGISTERED, &new_user)
+        .await;
+
     let user_dto = UserDto::from(new_user);
 
     ApiResponse::builder()
@@ -39,28 +59,42 @@ async fn create_user(
 async fn get_all_users(
     State(state): State<Arc<ApiState>>,
     version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    query: Result<Query<UserQuery>, QueryRejection>,
 ) -> Result<ApiResponse, ApiError> {
-    let users: Vec<UserDto> = state
-        .db
-        .users()
-        .find_all()
-        .await?
-        .iter()
-        .map(UserDto::from)
-        .collect();
+    if !claims.has_role(Role::Admin) || claims.org_id.is_some() {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+    let page = state.db.users().find_page(&query).await?.map(UserDto::from);
 
     ApiResponse::builder()
         .with_api_version(version)
-        .with_message(&format!("found {} users", users.len()))
-        .with_payload(serde_json::json!({ "users": users }))
+        .with_message(&format!("found {} users", page.items.len()))
+        .with_payload(serde_json::json!({
+            "users": page.items,
+            "next_cursor": page.next_cursor,
+        }))
         .build()
         .as_ok()
 }
 
 async fn get_user_by_id(
     State(state): State<Arc<ApiState>>,
+    Extension(claims): Extension<Claims>,
     VerIdParams { version, id }: VerIdParams,
 ) -> Result<ApiResponse, ApiError> {
+    services::authz::enforce(
+        state.db.as_ref(),
+        &state.policies,
+        &claims,
+        "read",
+        &Resource::new("user", Some(id)),
+        serde_json::json!({ "id": id }),
+    )
+    .await?;
+
     let user = state.db.users().find_by_id(id).await?;
     if user.is_none() {
         return ApiResponse::builder()
@@ -84,8 +118,23 @@ async fn get_user_by_id(
 async fn delete_all_users(
     State(state): State<Arc<ApiState>>,
     version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
 ) -> Result<ApiResponse, ApiError> {
+    claims.ensure_interactive_user()?;
+    if !claims.has_role(Role::Admin) || claims.org_id.is_some() {
+        return Err(AuthError::Forbidden.into());
+    }
+
     let deleted_count = state.db.users().delete_all().await?;
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::USERS_DELETED, Some(claims.sub), None)
+            .with_client(&client)
+            .with_metadata(serde_json::json!({ "deleted_count": deleted_count })),
+    )
+    .await;
+
     ApiResponse::builder()
         .with_api_version(version)
         .with_message("deleted all users")
@@ -94,11 +143,205 @@ async fn delete_all_users(
         .as_ok()
 }
 
+async fn set_user_roles(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<RolesPayload>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let user = services::users::set_roles(&state, id, &payload.roles).await?;
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::ROLES_CHANGED, Some(claims.sub), Some(user.id))
+            .with_client(&client)
+            .with_metadata(serde_json::json!({ "roles": user.roles })),
+    )
+    .await;
+    services::webhooks::publish_user_event(&state, webhook::event_type::USER_ROLES_CHANGED, &user)
+        .await;
+
+    let user_dto = UserDto::from(user);
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("user roles updated")
+        .with_payload(serde_json::json!({ "user": user_dto }))
+        .build()
+        .as_ok()
+}
+
+async fn get_user_roles(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let user = state
+        .db
+        .users()
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("user ({}) not found", id)))?;
+    let groups = state.db.groups().find_by_member(id).await?;
+    let group_roles = services::groups::group_roles(state.db.as_ref(), id).await?;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("user roles")
+        .with_payload(serde_json::json!({
+            "roles": user.roles,
+            "groups": groups,
+            "effective_roles": user.effective_roles(&group_roles),
+        }))
+        .build()
+        .as_ok()
+}
+
+async fn force_logout(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let deleted_count = services::users::force_logout(&state, id).await?;
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::FORCE_LOGOUT, Some(claims.sub), Some(id))
+            .with_client(&client)
+            .with_metadata(serde_json::json!({ "deleted_count": deleted_count })),
+    )
+    .await;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("all user sessions revoked")
+        .with_payload(serde_json::json!({ "deleted_count": deleted_count }))
+        .build()
+        .as_ok()
+}
+
+async fn disable_user(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<DisablePayload>,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    if claims.sub == id {
+        return Err(ApiError::BadRequest(
+            "admins cannot disable their own account".to_owned(),
+        ));
+    }
+
+    let user = services::users::disable_user(&state, id, payload.reason.as_deref()).await?;
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::USER_DISABLED, Some(claims.sub), Some(user.id))
+            .with_client(&client)
+            .with_metadata(serde_json::json!({ "reason": user.disabled_reason })),
+    )
+    .await;
+    services::webhooks::publish_user_event(&state, webhook::event_type::USER_DISABLED, &user).await;
+
+    let user_dto = UserDto::from(user);
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("user disabled")
+        .with_payload(serde_json::json!({ "user": user_dto }))
+        .build()
+        .as_ok()
+}
+
+async fn enable_user(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let user = services::users::enable_user(&state, id).await?;
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::USER_ENABLED, Some(claims.sub), Some(user.id))
+            .with_client(&client),
+    )
+    .await;
+    services::webhooks::publish_user_event(&state, webhook::event_type::USER_ENABLED, &user).await;
+
+    let user_dto = UserDto::from(user);
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("user enabled")
+        .with_payload(serde_json::json!({ "user": user_dto }))
+        .build()
+        .as_ok()
+}
+
+async fn delete_user(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<ApiResponse, ApiError> {
+    if !claims.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let user = services::users::delete_user(&state, id).await?;
+    services::audit::record(
+        &state,
+        AuditLog::new(event_type::USER_DELETED, Some(claims.sub), Some(user.id))
+            .with_client(&client)
+            .with_metadata(serde_json::json!({ "username": user.username })),
+    )
+    .await;
+    services::webhooks::publish_user_event(&state, webhook::event_type::USER_DELETED, &user).await;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("user deleted")
+        .with_payload(serde_json::json!({ "id": user.id, "deleted_at": user.deleted_at }))
+        .build()
+        .as_ok()
+}
+
 pub fn create_routes(state: Arc<ApiState>) -> Router {
-    Router::new()
-        .route("/", get(get_all_users))
-        .route("/", post(create_user))
+    let public_routes = Router::new().route("/", post(create_user));
+
+    let protected_routes = Router::new()
+        .route("/", get(get_all_users).delete(delete_all_users))
         .route("/{id}", get(get_user_by_id))
-        .route("/", delete(delete_all_users))
+        .route("/{id}/roles", get(get_user_roles).put(set_user_roles))
+        .route("/{id}/logout", post(force_logout))
+        .route("/{id}/disable", post(disable_user))
+        .route("/{id}/enable", post(enable_user))
+        .route("/{id}", delete(delete_user))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)));
+
+    Router::new()
+        .merge(public_routes)
+        .merge(protected_routes)
         .with_state(state)
 }
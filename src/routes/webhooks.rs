@@ -0,0 +1,233 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    http::StatusCode,
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+
+use crate::{
+    error::ApiError,
+    models::{pagination, user::Role, webhook::WebhookSubscriptionDto},
+    routes::{
+        extractors::{ApiVersion, VerIdParams},
+        ApiResponse,
+    },
+    services::{self, auth::AuthError, jwt::Claims},
+    ApiState,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WebhookPayload {
+    pub url: String,
+    pub event_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WebhookUpdatePayload {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub event_types: Option<Vec<String>>,
+    #[serde(default)]
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeliveryQuery {
+    pub limit: Option<usize>,
+}
+
+/// Webhooks carry user data to other systems, so only admins can manage them.
+fn require_admin(claims: &Claims) -> Result<(), ApiError> {
+    if !claims.has_role(Role::Admin) || claims.is_delegated() {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    Ok(())
+}
+
+async fn create_webhook(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<WebhookPayload>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let (subscription, secret) =
+        services::webhooks::create_subscription(&state, &payload.url, &payload.event_types).await?;
+
+    ApiResponse::builder()
+        .with_code(StatusCode::CREATED)
+        .with_api_version(version)
+        .with_message("webhook created")
+        .with_payload(serde_json::json!({
+            "webhook": WebhookSubscriptionDto::from(subscription),
+            "secret": secret,
+        }))
+        .build()
+        .as_ok()
+}
+
+async fn get_all_webhooks(
+    State(state): State<Arc<ApiState>>,
+    version: ApiVersion,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let webhooks: Vec<WebhookSubscriptionDto> = state
+        .db
+        .webhooks()
+        .find_all()
+        .await?
+        .into_iter()
+        .map(WebhookSubscriptionDto::from)
+        .collect();
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message(&format!("found {} webhooks", webhooks.len()))
+        .with_payload(serde_json::json!({ "webhooks": webhooks }))
+        .build()
+        .as_ok()
+}
+
+async fn get_webhook_by_id(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let Some(subscription) = state.db.webhooks().find_by_id(id).await? else {
+        return ApiResponse::builder()
+            .with_success(false)
+            .with_api_version(version)
+            .with_message("webhook not found")
+            .with_code(StatusCode::NOT_FOUND)
+            .build()
+            .as_ok();
+    };
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("webhook found")
+        .with_payload(serde_json::json!({
+            "webhook": WebhookSubscriptionDto::from(subscription),
+        }))
+        .build()
+        .as_ok()
+}
+
+async fn update_webhook(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<WebhookUpdatePayload>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let subscription = services::webhooks::update_subscription(
+        &state,
+        id,
+        payload.url.as_deref(),
+        payload.event_types.as_deref(),
+        payload.active,
+    )
+    .await?;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("webhook updated")
+        .with_payload(serde_json::json!({
+            "webhook": WebhookSubscriptionDto::from(subscription),
+        }))
+        .build()
+        .as_ok()
+}
+
+async fn rotate_webhook_secret(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let secret = services::webhooks::rotate_secret(&state, id).await?;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("webhook secret rotated")
+        .with_payload(serde_json::json!({ "secret": secret }))
+        .build()
+        .as_ok()
+}
+
+async fn delete_webhook(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let Some(subscription) = state.db.webhooks().delete_by_id(id).await? else {
+        return ApiResponse::builder()
+            .with_success(false)
+            .with_api_version(version)
+            .with_message("webhook not found")
+            .with_code(StatusCode::NOT_FOUND)
+            .build()
+            .as_ok();
+    };
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message("webhook deleted")
+        .with_payload(serde_json::json!({
+            "webhook": WebhookSubscriptionDto::from(subscription),
+        }))
+        .build()
+        .as_ok()
+}
+
+async fn get_webhook_deliveries(
+    State(state): State<Arc<ApiState>>,
+    VerIdParams { version, id }: VerIdParams,
+    Extension(claims): Extension<Claims>,
+    query: Result<Query<DeliveryQuery>, QueryRejection>,
+) -> Result<ApiResponse, ApiError> {
+    require_admin(&claims)?;
+
+    let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+    let deliveries = state
+        .db
+        .webhook_outbox()
+        .find_deliveries(id, pagination::page_size(query.limit))
+        .await?;
+
+    ApiResponse::builder()
+        .with_api_version(version)
+        .with_message(&format!("found {} deliveries", deliveries.len()))
+        .with_payload(serde_json::json!({ "deliveries": deliveries }))
+        .build()
+        .as_ok()
+}
+
+pub fn create_routes(state: Arc<ApiState>) -> Router {
+    let protected_routes = Router::new()
+        .route("/", get(get_all_webhooks))
+        .route("/", post(create_webhook))
+        .route("/{id}", get(get_webhook_by_id))
+        .route("/{id}", put(update_webhook))
+        .route("/{id}", delete(delete_webhook))
+        .route("/{id}/rotate-secret", post(rotate_webhook_secret))
+        .route("/{id}/deliveries", get(get_webhook_deliveries))
+        .layer(axum::middleware::from_fn(services::auth::auth_guard))
+        .layer(Extension(Arc::clone(&state)));
+
+    Router::new().merge(protected_routes).with_state(state)
+}
@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    error::ApiError,
+    models::{
+        audit_log::{event_type, AuditLog, ClientInfo},
+        user::{Role, User},
+    },
+    services::{
+        self,
+        auth::AuthError,
+        jwt::{Actor, Claims, TokenType},
+    },
+    ApiState,
+};
+
+/// Finds the user the admin wants to impersonate along with the roles granted to them by
+/// groups. Admins cannot be impersonated, whether the role is their own or granted by a group.
+async fn impersonation_target(
+    db: &dyn Database,
+    admin: &Claims,
+    target_id: Uuid,
+) -> Result<(User, Vec<Role>), ApiError> {
+    admin.ensure_interactive_user()?;
+    if !admin.has_role(Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let target = db
+        .users()
+        .find_by_id(target_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("user ({}) not found", target_id)))?;
+
+    // Read from the database rather than the cache, which may lag behind group changes.
+    let group_roles = services::groups::group_roles(db, target.id).await?;
+    if target.has_role(Role::Admin) || group_roles.contains(&Role::Admin) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    if target.is_disabled() {
+        return Err(AuthError::AccountDisabled.into());
+    }
+
+    Ok((target, group_roles))
+}
+
+/// Issues a short-lived access token for the target user on behalf of the admin.
+/// The token carries the admin in the `act` claim and comes without a refresh token.
+/// Like tokens of the user, it includes the roles granted by their groups.
+pub async fn impersonate(
+    state: &Arc<ApiState>,
+    admin: &Claims,
+    target_id: Uuid,
+    client: &ClientInfo,
+) -> Result<(String, Claims), ApiError> {
+    let (target, group_roles) = impersonation_target(state.db.as_ref(), admin, target_id).await?;
+
+    let now = chrono::Utc::now().timestamp();
+    let claims = Claims {
+        exp: now + state.config.impersonation_token_expiration,
+        act: Some(Actor {
+            sub: admin.sub.to_string(),
+            act: None,
+        }),
+        roles: target.effective_roles(&group_roles),
+        ..Claims::from_user(&target, TokenType::Access, &state.config, now)
+    };
+    let access_token =
+        services::jwt::generate_access_token(&claims, &state.config, &state.signing_key)?;
+
+    state
+        .db
+        .audit_logs()
+        .create(
+            AuditLog::new(event_type::IMPERSONATION, Some(admin.sub), Some(target.id))
+                .with_client(client)
+                .with_metadata(serde_json::json!({
+                    "actor_username": admin.username,
+                    "target_username": target.username,
+                    "jti": claims.jti,
+                    "exp": claims.exp,
+                })),
+        )
+        .await?;
+
+    tracing::info!(
+        "admin ({}) started impersonating user ({})",
+        admin.sub,
+        target.id
+    );
+
+    Ok((access_token, claims))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, database::mock::MockDatabase, test_utils};
+
+    #[tokio::test]
+    async fn impersonation_target_checks() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let config = Config::default();
+        let users = db.users();
+        let admin = users
+            .create(User::new("admin", "hash", &[Role::Admin]))
+            .await
+            .unwrap();
+        let alice = users.create(User::new("alice", "hash", &[])).await.unwrap();
+        let bob = users
+            .create(User::new("bob", "hash", &[Role::User]))
+            .await
+            .unwrap();
+        let root = users
+            .create(User::new("root", "hash", &[Role::Admin]))
+            .await
+            .unwrap();
+        let dave = users
+            .create(User::new("dave", "hash", &[Role::User]).disable(None))
+            .await
+            .unwrap();
+
+        let staff = services::groups::create_group(db, "staff", None, &[Role::User])
+            .await
+            .unwrap();
+        services::groups::add_member(db, staff.id, alice.id)
+            .await
+            .unwrap();
+        let ops = services::groups::create_group(db, "ops", None, &[Role::Admin])
+            .await
+            .unwrap();
+        services::groups::add_member(db, ops.id, bob.id)
+            .await
+            .unwrap();
+
+        let admin = Claims::from_user(&admin, TokenType::Access, &config, 0);
+        let (target, group_roles) = impersonation_target(db, &admin, alice.id).await.unwrap();
+        assert_eq!(target.id, alice.id);
+        assert_eq!(target.effective_roles(&group_roles), vec![Role::User]);
+
+        for target_id in [root.id, bob.id] {
+            assert!(matches!(
+                impersonation_target(db, &admin, target_id).await,
+                Err(ApiError::Auth(AuthError::Forbidden))
+            ));
+        }
+        assert!(matches!(
+            impersonation_target(db, &admin, dave.id).await,
+            Err(ApiError::Auth(AuthError::AccountDisabled))
+        ));
+        assert!(matches!(
+            impersonation_target(db, &admin, Uuid::new_v4()).await,
+            Err(ApiError::NotFound(_))
+        ));
+
+        let delegated = Claims {
+            act: Some(Actor {
+                sub: Uuid::new_v4().to_string(),
+                act: None,
+            }),
+            ..admin.clone()
+        };
+        let client_token = Claims {
+            client_id: Some("console".to_owned()),
+            ..admin.clone()
+        };
+        let user = Claims::from_user(&alice, TokenType::Access, &config, 0);
+        for claims in [delegated, client_token, user] {
+            assert!(matches!(
+                impersonation_target(db, &claims, alice.id).await,
+                Err(ApiError::Auth(AuthError::Forbidden))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Redis"]
+    async fn admins_cannot_be_impersonated() {
+        let state = test_utils::state(Config::default()).await;
+        let users = state.db.users();
+        let admin = users
+            .create(User::new("admin", "hash", &[Role::Admin]))
+            .await
+            .unwrap();
+        let alice = users.create(User::new("alice", "hash", &[])).await.unwrap();
+        let bob = users
+            .create(User::new("bob", "hash", &[Role::User]))
+            .await
+            .unwrap();
+
+        let staff = services::groups::create_group(state.db.as_ref(), "staff", None, &[Role::User])
+            .await
+            .unwrap();
+        services::groups::add_member(state.db.as_ref(), staff.id, alice.id)
+            .await
+            .unwrap();
+        let ops = services::groups::create_group(state.db.as_ref(), "ops", None, &[Role::Admin])
+            .await
+            .unwrap();
+        services::groups::add_member(state.db.as_ref(), ops.id, bob.id)
+            .await
+            .unwrap();
+
+        let admin = Claims::from_user(&admin, TokenType::Access, &state.config, 0);
+        let client = ClientInfo::default();
+        let (_, claims) = impersonate(&state, &admin, alice.id, &client)
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, alice.id);
+        assert_eq!(claims.roles, vec![Role::User]);
+        assert!(claims.is_delegated());
+
+        assert!(matches!(
+            impersonate(&state, &admin, bob.id, &client).await,
+            Err(ApiError::Auth(AuthError::Forbidden))
+        ));
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{
+    error::ApiError,
+    models::audit_log::{event_type, AuditLog, AuditLogQuery, AuditOutcome, ClientInfo},
+    ApiState,
+};
+
+/// Appends the entry to the audit log. Failures are only logged, so that an unavailable
+/// audit log does not lock users out.
+pub async fn record(state: &Arc<ApiState>, audit_log: AuditLog) {
+    let event_type = audit_log.event_type.clone();
+    if let Err(e) = state.db.audit_logs().create(audit_log).await {
+        tracing::error!("failed to record audit event ({}): {}", event_type, e);
+    }
+}
+
+/// Error description stored along with failed events.
+pub fn failure_metadata(error: &ApiError) -> serde_json::Value {
+    serde_json::json!({ "error": error.to_string() })
+}
+
+/// Whether the user has already logged in successfully with the same user agent.
+/// Requests without a user agent are never reported as coming from a new device.
+pub async fn is_known_device(
+    state: &Arc<ApiState>,
+    user_id: Uuid,
+    client: &ClientInfo,
+) -> Result<bool, ApiError> {
+    let Some(user_agent) = client.user_agent.clone() else {
+        return Ok(true);
+    };
+
+    let query = AuditLogQuery {
+        event_type: Some(event_type::LOGIN.to_owned()),
+        actor_id: Some(user_id),
+        outcome: Some(AuditOutcome::Success),
+        user_agent: Some(user_agent),
+        limit: Some(1),
+        ..Default::default()
+    };
+    let page = state.db.audit_logs().find_page(&query).await?;
+
+    Ok(!page.items.is_empty())
+}
+
+/// Removes entries older than the configured retention period.
+pub async fn purge_expired(state: &Arc<ApiState>) -> Result<u64, ApiError> {
+    let before = chrono::Utc::now() - chrono::Duration::seconds(state.config.audit_log_retention);
+    let deleted_count = state.db.audit_logs().delete_older_than(before).await?;
+
+    Ok(deleted_count)
+}
@@ -1,29 +1,43 @@
 use std::sync::Arc;
 
 use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString},
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, SaltString,
+    },
     Argon2, PasswordVerifier,
 };
+use async_trait::async_trait;
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{OriginalUri, Request},
+    http::{header, HeaderMap, Method, StatusCode},
     middleware::Next,
     response::IntoResponse,
     Extension,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use uuid::Uuid;
 
 use crate::{
+    config::Config,
+    database::Database,
     error::ApiError,
-    models::refresh_token::RefreshToken,
+    models::{
+        audit_log::{event_type, AuditLog},
+        refresh_token::RefreshToken,
+        user::{AuthSource, User},
+        webhook,
+    },
     routes::auth::AuthPayload,
     services::{
         self,
-        jwt::{pairs_from_user, Claims},
+        jwt::{pairs_from_user, Claims, Confirmation, TokenContext, TokenType, TokenValidation},
     },
     ApiState,
 };
 
+pub mod ldap;
+
 #[derive(Debug, thiserror::Error)]
 pub enum AuthError {
     #[error("invalid credentials")]
@@ -40,6 +54,14 @@ pub enum AuthError {
     TokenRevoked,
     #[error("username already taken")]
     UsernameAlreadyTaken,
+    #[error("DPoP proof is invalid")]
+    InvalidDpopProof,
+    #[error("account is disabled")]
+    AccountDisabled,
+    #[error("registration is closed")]
+    RegistrationClosed,
+    #[error("invitation is missing, invalid or expired")]
+    InvitationInvalid,
 }
 
 impl AuthError {
@@ -52,6 +74,10 @@ impl AuthError {
             AuthError::TokenExpired => StatusCode::UNAUTHORIZED,
             AuthError::TokenRevoked => StatusCode::UNAUTHORIZED,
             AuthError::UsernameAlreadyTaken => StatusCode::CONFLICT,
+            AuthError::InvalidDpopProof => StatusCode::UNAUTHORIZED,
+            AuthError::AccountDisabled => StatusCode::FORBIDDEN,
+            AuthError::RegistrationClosed => StatusCode::FORBIDDEN,
+            AuthError::InvitationInvalid => StatusCode::FORBIDDEN,
         }
     }
 }
@@ -70,19 +96,128 @@ pub fn verify_hash(hash: &str, plain: &str) -> bool {
         .is_ok_and(|res| res.is_ok())
 }
 
-pub async fn login(
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Backend verifying the passwords of the users of a single `AuthSource`.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    fn source(&self) -> AuthSource;
+
+    /// Returns the user if the password is correct, or `None` if the provider does not know
+    /// the user or the password does not match.
+    async fn authenticate(
+        &self,
+        db: &dyn Database,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<User>, ApiError>;
+}
+
+/// Verifies passwords against the hashes stored in the database.
+pub struct LocalProvider;
+
+#[async_trait]
+impl AuthProvider for LocalProvider {
+    fn source(&self) -> AuthSource {
+        AuthSource::Local
+    }
+
+    async fn authenticate(
+        &self,
+        db: &dyn Database,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<User>, ApiError> {
+        let user = db
+            .users()
+            .find_by_username(username)
+            .await?
+            .filter(|user| user.auth_source == AuthSource::Local)
+            .filter(|user| verify_hash(&user.password_hash, password));
+
+        Ok(user)
+    }
+}
+
+/// Providers enabled in the config, in the order they are asked to authenticate a user.
+pub fn providers_from_config(config: &Config) -> anyhow::Result<Vec<Arc<dyn AuthProvider>>> {
+    let mut providers: Vec<Arc<dyn AuthProvider>> = vec![Arc::new(LocalProvider)];
+    if !config.ldap_url.is_empty() {
+        providers.push(Arc::new(ldap::LdapProvider::from_config(config)?));
+    }
+
+    Ok(providers)
+}
+
+pub async fn verify_credentials(
     state: &Arc<ApiState>,
-    auth_payload: AuthPayload,
-) -> Result<(String, String, Option<RefreshToken>), ApiError> {
-    let user = state
-        .db
-        .users()
-        .find_by_username(&auth_payload.username)
-        .await?
-        .ok_or(AuthError::InvalidCredentials)?;
+    username: &str,
+    password: &str,
+) -> Result<User, ApiError> {
+    // Providers may sync the roles from an external source, e.g. the groups of an LDAP user.
+    let previous = state.db.users().find_by_username(username).await?;
+
+    let mut user = None;
+    for provider in &state.auth_providers {
+        user = provider
+            .authenticate(state.db.as_ref(), username, password)
+            .await?;
+        if user.is_some() {
+            break;
+        }
+    }
+    let mut user = user.ok_or(AuthError::InvalidCredentials)?;
 
-    if !verify_hash(&user.password_hash, &auth_payload.password) {
-        return Err(AuthError::InvalidCredentials.into());
+    // Tokens carry the roles of the user, so the outdated ones have to be revoked.
+    if previous.is_some_and(|previous| previous.id == user.id && previous.roles != user.roles) {
+        user.token_version = services::users::revoke_tokens(state, user.id).await?;
+        services::audit::record(
+            state,
+            AuditLog::new(event_type::ROLES_CHANGED, None, Some(user.id)).with_metadata(
+                serde_json::json!({ "roles": user.roles, "source": user.auth_source.to_string() }),
+            ),
+        )
+        .await;
+        services::webhooks::publish_user_event(
+            state,
+            webhook::event_type::USER_ROLES_CHANGED,
+            &user,
+        )
+        .await;
+    }
+
+    // Checked only after the password, so that the account state is not disclosed to anyone else.
+    if user.is_disabled() {
+        return Err(AuthError::AccountDisabled.into());
+    }
+
+    Ok(user)
+}
+
+/// Rejects tokens issued before the token version of the user was last bumped.
+pub async fn verify_token_version(state: &Arc<ApiState>, claims: &Claims) -> Result<(), ApiError> {
+    let Some(ver) = claims.ver else {
+        return Ok(());
+    };
+
+    if services::users::token_version(state, claims.sub).await? != Some(ver) {
+        return Err(AuthError::TokenRevoked.into());
+    }
+
+    Ok(())
+}
+
+pub async fn create_session(
+    state: &Arc<ApiState>,
+    user: &User,
+    ctx: &TokenContext,
+) -> Result<(String, String, Option<RefreshToken>), ApiError> {
+    if user.is_disabled() {
+        return Err(AuthError::AccountDisabled.into());
     }
 
     // This is for session limiting that prevents the user from the 'login spam'.
@@ -91,21 +226,75 @@ pub async fn login(
     // This will log out the device that uses the oldest token - 'user_session_limit' value
     // probably should be at least 5.
     let deleted_token = services::jwt::revoke_oldest_token(state, user.id).await?;
+    if let Some(token) = &deleted_token {
+        services::audit::record(
+            state,
+            AuditLog::new(event_type::SESSION_REVOKED, Some(user.id), Some(user.id)).with_metadata(
+                serde_json::json!({ "session_id": token.jti, "reason": "session_limit" }),
+            ),
+        )
+        .await;
+    }
 
-    let (access_token, refresh_token, token_model) = pairs_from_user(
-        &user,
-        state.config.jwt_access_expiration,
-        state.config.jwt_refresh_expiration,
-        &state.config.jwt_access_secret,
-        &state.config.jwt_refresh_secret,
-    )?;
+    let ctx = ctx
+        .clone()
+        .with_group_roles(services::groups::inherited_roles(state, user.id).await?);
+    let (access_token, refresh_token, token_model) =
+        pairs_from_user(user, &ctx, &state.config, &state.signing_key)?;
 
     let _ = state.db.refresh_tokens().create(token_model).await?;
     Ok((access_token, refresh_token, deleted_token))
 }
 
-pub async fn refresh(state: &Arc<ApiState>, refresh_token: &str) -> Result<String, ApiError> {
-    let claims = services::jwt::decode_token(refresh_token, &state.config.jwt_refresh_secret)?;
+pub async fn login(
+    state: &Arc<ApiState>,
+    auth_payload: AuthPayload,
+    jkt: Option<&str>,
+) -> Result<(User, String, String, Option<RefreshToken>), ApiError> {
+    let (user, org_id) = match auth_payload.organization.as_deref() {
+        Some(slug) => {
+            let (user, organization) = services::organizations::verify_credentials(
+                state,
+                slug,
+                &auth_payload.username,
+                &auth_payload.password,
+            )
+            .await?;
+            (user, Some(organization.id))
+        }
+        None => (
+            verify_credentials(state, &auth_payload.username, &auth_payload.password).await?,
+            None,
+        ),
+    };
+    let (access_token, refresh_token, deleted_token) = create_session(
+        state,
+        &user,
+        &TokenContext::default().with_jkt(jkt).with_org(org_id),
+    )
+    .await?;
+
+    Ok((user, access_token, refresh_token, deleted_token))
+}
+
+pub async fn refresh(
+    state: &Arc<ApiState>,
+    refresh_token: &str,
+    jkt: Option<&str>,
+) -> Result<(String, Claims), ApiError> {
+    let claims = services::jwt::decode_token(
+        refresh_token,
+        &TokenValidation::new(TokenType::Refresh, &state.config),
+    )?;
+
+    // Refresh tokens bound to a DPoP key can only be used with a proof signed by the same key.
+    if claims
+        .cnf
+        .as_ref()
+        .is_some_and(|cnf| Some(cnf.jkt.as_str()) != jkt)
+    {
+        return Err(AuthError::InvalidDpopProof.into());
+    }
 
     if state
         .db
@@ -117,24 +306,56 @@ pub async fn refresh(state: &Arc<ApiState>, refresh_token: &str) -> Result<Strin
         return Err(AuthError::TokenInvalid.into());
     }
 
-    let now = chrono::Utc::now();
-    let access_claims = Claims::new(
-        claims.sub,
-        now.checked_add_signed(chrono::Duration::seconds(
-            state.config.jwt_refresh_expiration,
-        ))
-        .unwrap()
-        .timestamp(),
-        now.timestamp(),
-        Uuid::new_v4(),
-        claims.username,
-        claims.roles,
-        claims.admin,
-    );
+    let user = state
+        .db
+        .users()
+        .find_by_id(claims.sub)
+        .await?
+        .ok_or(AuthError::TokenInvalid)?;
+
+    if user.is_disabled() {
+        return Err(AuthError::AccountDisabled.into());
+    }
+
+    if claims.ver != Some(user.token_version) {
+        return Err(AuthError::TokenRevoked.into());
+    }
+
+    // Sessions of an organization end with the membership.
+    if let Some(org_id) = claims.org_id {
+        if state
+            .db
+            .memberships()
+            .find(org_id, user.id)
+            .await?
+            .is_none()
+        {
+            return Err(AuthError::TokenRevoked.into());
+        }
+    }
+
+    // Roles are resolved again, so that changes of the groups reach the refreshed tokens.
+    let group_roles = services::groups::inherited_roles(state, user.id).await?;
+
+    let now = chrono::Utc::now().timestamp();
+    let access_claims = Claims {
+        roles: user.effective_roles(&group_roles),
+        exp: now + state.config.jwt_access_expiration,
+        nbf: now,
+        iat: now,
+        jti: Uuid::new_v4(),
+        typ: TokenType::Access,
+        cnf: claims.cnf.clone().or_else(|| {
+            jkt.map(|jkt| Confirmation {
+                jkt: jkt.to_owned(),
+            })
+        }),
+        ..claims
+    };
     let access_token =
-        services::jwt::generate_token(&access_claims, &state.config.jwt_access_secret)?;
+        services::jwt::generate_access_token(&access_claims, &state.config, &state.signing_key)?;
 
-    Ok(access_token)
+    Ok((access_token, access_claims))
 }
 
 pub async fn logout(
@@ -142,7 +363,10 @@ pub async fn logout(
     refresh_token: &str,
     access_jti: Uuid,
 ) -> Result<Option<Uuid>, ApiError> {
-    let claims = services::jwt::decode_token(refresh_token, &state.config.jwt_refresh_secret)?;
+    let claims = services::jwt::decode_token(
+        refresh_token,
+        &TokenValidation::new(TokenType::Refresh, &state.config),
+    )?;
     let deleted_token = state.db.refresh_tokens().delete_by_jti(claims.jti).await?;
 
     if let Some(token) = deleted_token {
@@ -158,23 +382,87 @@ pub async fn logout(
     Ok(None)
 }
 
+/// Authenticates a request with the access token presented with the scheme, checking the
+/// DPoP proof against the method and path of the request for DPoP bound tokens.
+pub async fn authenticate(
+    state: &Arc<ApiState>,
+    scheme: &str,
+    access_token: &str,
+    headers: &HeaderMap,
+    method: &Method,
+    path: &str,
+) -> Result<Claims, ApiError> {
+    // Tokens exchanged for another audience are meant for downstream services only,
+    // so only tokens issued for the configured audience are accepted.
+    let claims = services::jwt::decode_token(
+        access_token,
+        &TokenValidation::for_state(TokenType::Access, state),
+    )?;
+
+    // DPoP bound tokens must be presented with a proof of possession of the bound key,
+    // while bearer tokens must not be presented with the DPoP scheme.
+    match (&claims.cnf, scheme) {
+        (Some(cnf), "DPoP") => {
+            let jkt =
+                services::dpop::verify_request(state, headers, method, path, Some(access_token))
+                    .await?;
+
+            if jkt.as_deref() != Some(cnf.jkt.as_str()) {
+                return Err(AuthError::InvalidDpopProof.into());
+            }
+        }
+        (None, "Bearer") => {}
+        _ => return Err(AuthError::TokenInvalid.into()),
+    }
+
+    if state.redis.tokens().is_blacklisted(claims.jti).await? {
+        return Err(AuthError::TokenRevoked.into());
+    }
+
+    verify_token_version(state, &claims).await?;
+
+    Ok(claims)
+}
+
+/// Splits the authorization header into the scheme and the token.
+pub fn authorization(headers: &HeaderMap) -> Option<(&str, &str)> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split_once(' '))
+}
+
+/// Value of the named cookie, if the request has it.
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find_map(|(key, value)| (key == name).then_some(value))
+}
+
 pub async fn auth_guard(
     Extension(state): Extension<Arc<ApiState>>,
     mut req: Request,
     next: Next,
 ) -> Result<impl IntoResponse, ApiError> {
-    let access_token = req
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "))
-        .ok_or(AuthError::Unauthorized)?;
+    let (scheme, access_token) = authorization(req.headers()).ok_or(AuthError::Unauthorized)?;
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| req.uri().path(), |uri| uri.path());
 
-    let claims = services::jwt::decode_token(access_token, &state.config.jwt_access_secret)?;
-
-    if state.redis.tokens().is_blacklisted(claims.jti).await? {
-        return Err(AuthError::TokenRevoked.into());
-    }
+    let claims = authenticate(
+        &state,
+        scheme,
+        access_token,
+        req.headers(),
+        req.method(),
+        path,
+    )
+    .await?;
 
     req.extensions_mut().insert(claims);
 
@@ -184,6 +472,7 @@ pub async fn auth_guard(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{database::mock::MockDatabase, models::user::Role};
 
     #[test]
     fn verify_hash_correct() {
@@ -200,4 +489,52 @@ mod tests {
 
         assert!(!verify_hash(&hash, "wrong_password"));
     }
+
+    #[tokio::test]
+    async fn local_provider_authenticate() {
+        let db = MockDatabase::new();
+        let local = User::new("local_user", &hash_string("test_password"), &[Role::User]);
+        let external = User::new("ldap_user", &hash_string("test_password"), &[Role::User])
+            .with_auth_source(AuthSource::Ldap);
+        db.users().create(local.clone()).await.unwrap();
+        db.users().create(external).await.unwrap();
+
+        let user = LocalProvider
+            .authenticate(db.as_ref(), "local_user", "test_password")
+            .await
+            .unwrap();
+        assert_eq!(user.map(|u| u.id), Some(local.id));
+
+        for (username, password) in [
+            ("local_user", "wrong_password"),
+            ("ldap_user", "test_password"),
+            ("unknown_user", "test_password"),
+        ] {
+            let user = LocalProvider
+                .authenticate(db.as_ref(), username, password)
+                .await
+                .unwrap();
+            assert!(user.is_none());
+        }
+    }
+
+    #[test]
+    fn generate_secret_unique() {
+        let first = generate_secret();
+        let second = generate_secret();
+
+        assert_eq!(first.len(), 43);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn read_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::COOKIE, "theme=dark; session=abc".parse().unwrap());
+        headers.append(header::COOKIE, "lang=en".parse().unwrap());
+
+        assert_eq!(cookie(&headers, "session"), Some("abc"));
+        assert_eq!(cookie(&headers, "lang"), Some("en"));
+        assert_eq!(cookie(&headers, "sess"), None);
+    }
 }
@@ -0,0 +1,703 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use ldap3::{
+    dn_escape, ldap_escape, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry,
+};
+
+use crate::{
+    config::Config,
+    database::Database,
+    error::ApiError,
+    models::user::{AuthSource, Role, User},
+    services::auth::{generate_secret, hash_string, AuthError, AuthProvider},
+};
+
+/// Result code of a bind with a wrong password or an unknown DN.
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// Entry of an authenticated user in the directory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LdapUser {
+    pub dn: String,
+    pub email: Option<String>,
+    /// Common names of the groups the user is a member of.
+    pub groups: Vec<String>,
+}
+
+/// Authenticates users by binding to an LDAP directory as them. Users are created in
+/// the database on their first successful login.
+#[derive(Clone, Debug)]
+pub struct LdapProvider {
+    url: String,
+    user_dn: String,
+    group_base_dn: Option<String>,
+    group_roles: Vec<(String, Role)>,
+    timeout: Duration,
+    allow_registration: bool,
+}
+
+/// Parses a comma separated list of `<group cn>:<role>` pairs.
+pub fn parse_group_roles(group_roles: &str) -> anyhow::Result<Vec<(String, Role)>> {
+    group_roles
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|pair| {
+            let (group, role) = pair
+                .split_once(':')
+                .with_context(|| format!("group role mapping ({}) is invalid", pair))?;
+            let role = Role::from_str(role.trim())
+                .map_err(|_| anyhow::anyhow!("role ({}) not recognized", role.trim()))?;
+
+            Ok((group.trim().to_owned(), role))
+        })
+        .collect()
+}
+
+fn ldap_error(err: LdapError) -> ApiError {
+    ApiError::Internal(anyhow::Error::new(err).context("LDAP request failed"))
+}
+
+impl LdapProvider {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        if !config.ldap_user_dn.contains("{username}") {
+            anyhow::bail!("LDAP user DN should contain the {{username}} placeholder");
+        }
+
+        Ok(Self {
+            url: config.ldap_url.clone(),
+            user_dn: config.ldap_user_dn.clone(),
+            group_base_dn: Some(config.ldap_group_base_dn.clone()).filter(|dn| !dn.is_empty()),
+            group_roles: parse_group_roles(&config.ldap_group_roles)?,
+            timeout: Duration::from_secs(config.ldap_timeout),
+            allow_registration: config.allows_external_registration(),
+        })
+    }
+
+    pub fn user_dn(&self, username: &str) -> String {
+        self.user_dn.replace("{username}", &dn_escape(username))
+    }
+
+    /// Roles of the members of the groups. Users without a mapped group get the user role.
+    pub fn roles(&self, groups: &[String]) -> Vec<Role> {
+        let mut roles = Vec::new();
+        for role in [Role::User, Role::Admin] {
+            let is_member = self.group_roles.iter().any(|(group, mapped)| {
+                *mapped == role && groups.iter().any(|g| g.eq_ignore_ascii_case(group))
+            });
+            if is_member {
+                roles.push(role);
+            }
+        }
+
+        if roles.is_empty() {
+            roles.push(Role::User);
+        }
+
+        roles
+    }
+
+    /// Binds as the user and reads their entry. Returns `None` if the credentials are rejected.
+    pub async fn bind(&self, username: &str, password: &str) -> Result<Option<LdapUser>, ApiError> {
+        // A simple bind without a password is an anonymous bind, which always succeeds.
+        if username.is_empty() || password.is_empty() {
+            return Ok(None);
+        }
+
+        let settings = LdapConnSettings::new().set_conn_timeout(self.timeout);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.url)
+            .await
+            .map_err(ldap_error)?;
+        tokio::spawn(async move {
+            if let Err(e) = conn.drive().await {
+                tracing::warn!("LDAP connection failed: {}", e);
+            }
+        });
+        ldap.with_timeout(self.timeout);
+
+        let dn = self.user_dn(username);
+        let res = ldap.simple_bind(&dn, password).await.map_err(ldap_error)?;
+        if res.rc == INVALID_CREDENTIALS {
+            let _ = ldap.unbind().await;
+            return Ok(None);
+        }
+        res.success().map_err(ldap_error)?;
+
+        let (entries, _) = ldap
+            .with_timeout(self.timeout)
+            .search(&dn, Scope::Base, "(objectClass=*)", vec!["mail"])
+            .await
+            .and_then(|res| res.success())
+            .map_err(ldap_error)?;
+        let email = entries
+            .into_iter()
+            .next()
+            .map(SearchEntry::construct)
+            .and_then(|entry| entry.attrs.get("mail").and_then(|v| v.first().cloned()))
+            .filter(|email| email.contains('@'));
+
+        let mut groups = Vec::new();
+        if let Some(base_dn) = self.group_base_dn.as_deref() {
+            let filter = format!(
+                "(|(member={dn})(uniqueMember={dn})(memberUid={uid}))",
+                dn = ldap_escape(&dn),
+                uid = ldap_escape(username),
+            );
+            let (entries, _) = ldap
+                .with_timeout(self.timeout)
+                .search(base_dn, Scope::Subtree, &filter, vec!["cn"])
+                .await
+                .and_then(|res| res.success())
+                .map_err(ldap_error)?;
+            groups = entries
+                .into_iter()
+                .map(SearchEntry::construct)
+                .filter_map(|entry| entry.attrs.get("cn").and_then(|v| v.first().cloned()))
+                .collect();
+        }
+
+        let _ = ldap.unbind().await;
+
+        Ok(Some(LdapUser { dn, email, groups }))
+    }
+}
+
+#[async_trait]
+impl AuthProvider for LdapProvider {
+    fn source(&self) -> AuthSource {
+        AuthSource::Ldap
+    }
+
+    async fn authenticate(
+        &self,
+        db: &dyn Database,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<User>, ApiError> {
+        let existing = db.users().find_by_username(username).await?;
+
+        // Accounts of other sources are never taken over by a directory user with the same name.
+        if existing
+            .as_ref()
+            .is_some_and(|user| user.auth_source != AuthSource::Ldap)
+        {
+            return Ok(None);
+        }
+
+        let Some(entry) = self.bind(username, password).await? else {
+            return Ok(None);
+        };
+
+        // Without a group lookup the roles are managed in flatline, like the roles of local users.
+        let roles = match (&existing, &self.group_base_dn) {
+            (Some(user), None) => user.roles.clone(),
+            _ => self.roles(&entry.groups),
+        };
+
+        let user = match existing {
+            Some(user) if user.roles == roles && user.email == entry.email => user,
+            Some(user) => {
+                let email_verified = user.email_verified && user.email == entry.email;
+                db.users()
+                    .update(User {
+                        roles,
+                        email: entry.email,
+                        email_verified,
+                        ..user
+                    })
+                    .await?
+                    .context("LDAP user was deleted during login")?
+            }
+            None if !self.allow_registration => return Err(AuthError::RegistrationClosed.into()),
+            None => {
+                // The password is never checked locally, the hash only fills the column.
+                let mut user = User::new(username, &hash_string(&generate_secret()), &roles)
+                    .with_auth_source(AuthSource::Ldap);
+                user.email = entry.email;
+                tracing::info!("Creating user {} from LDAP entry {}", username, entry.dn);
+                db.users().create(user).await?
+            }
+        };
+
+        Ok(Some(user))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use ldap3::asn1::{parse_tag, StructureTag, TagClass, PL};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::{
+        config::RegistrationMode,
+        database::mock::MockDatabase,
+        services::{
+            self,
+            auth::{create_session, verify_credentials, verify_token_version},
+            jwt::{TokenContext, TokenType, TokenValidation},
+        },
+        test_utils,
+    };
+
+    #[derive(Clone)]
+    struct Entry {
+        dn: String,
+        password: Option<String>,
+        attrs: Vec<(String, Vec<String>)>,
+    }
+
+    impl Entry {
+        fn new(dn: &str, password: Option<&str>, attrs: &[(&str, &[&str])]) -> Self {
+            Self {
+                dn: dn.to_owned(),
+                password: password.map(str::to_owned),
+                attrs: attrs
+                    .iter()
+                    .map(|(name, values)| {
+                        let values = values.iter().map(|v| v.to_string()).collect();
+                        (name.to_string(), values)
+                    })
+                    .collect(),
+            }
+        }
+
+        fn values(&self, attr: &str) -> Vec<&str> {
+            self.attrs
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(attr))
+                .flat_map(|(_, values)| values.iter().map(String::as_str))
+                .collect()
+        }
+    }
+
+    type Directory = Arc<RwLock<Vec<Entry>>>;
+
+    fn encode(tag: StructureTag, out: &mut Vec<u8>) {
+        let (constructed, payload) = match tag.payload {
+            PL::P(bytes) => (0, bytes),
+            PL::C(inner) => {
+                let mut bytes = Vec::new();
+                inner.into_iter().for_each(|t| encode(t, &mut bytes));
+                (0x20, bytes)
+            }
+        };
+        let class = match tag.class {
+            TagClass::Universal => 0x00,
+            TagClass::Application => 0x40,
+            TagClass::Context => 0x80,
+            TagClass::Private => 0xc0,
+        };
+
+        out.push(class | constructed | tag.id as u8);
+        if payload.len() < 0x80 {
+            out.push(payload.len() as u8);
+        } else {
+            let len = (payload.len() as u32).to_be_bytes();
+            let skip = len.iter().take_while(|b| **b == 0).count();
+            out.push(0x80 | (4 - skip) as u8);
+            out.extend_from_slice(&len[skip..]);
+        }
+        out.extend(payload);
+    }
+
+    fn tag(class: TagClass, id: u64, payload: PL) -> StructureTag {
+        StructureTag { class, id, payload }
+    }
+
+    fn octets(value: &str) -> StructureTag {
+        tag(TagClass::Universal, 4, PL::P(value.as_bytes().to_vec()))
+    }
+
+    fn primitive(tag: &StructureTag) -> Vec<u8> {
+        tag.clone().expect_primitive().unwrap_or_default()
+    }
+
+    fn string(tag: &StructureTag) -> String {
+        String::from_utf8(primitive(tag)).unwrap()
+    }
+
+    fn ldap_result(op: u64, rc: u8) -> StructureTag {
+        let result = vec![
+            tag(TagClass::Universal, 10, PL::P(vec![rc])),
+            octets(""),
+            octets(""),
+        ];
+        tag(TagClass::Application, op, PL::C(result))
+    }
+
+    fn filter_matches(filter: &StructureTag, entry: &Entry) -> bool {
+        let children = || filter.clone().expect_constructed().unwrap_or_default();
+        match filter.id {
+            0 => children().iter().all(|f| filter_matches(f, entry)),
+            1 => children().iter().any(|f| filter_matches(f, entry)),
+            2 => !filter_matches(&children()[0], entry),
+            3 => {
+                let (attr, value) = (string(&children()[0]), string(&children()[1]));
+                let is_dn = entry.dn.eq_ignore_ascii_case(&value);
+                entry
+                    .values(&attr)
+                    .iter()
+                    .any(|v| v.eq_ignore_ascii_case(&value))
+                    || (attr.eq_ignore_ascii_case("entryDN") && is_dn)
+            }
+            7 => {
+                let attr = string(filter);
+                attr.eq_ignore_ascii_case("objectClass") || !entry.values(&attr).is_empty()
+            }
+            _ => false,
+        }
+    }
+
+    /// Responds to a single request, returning `None` when the client unbinds.
+    fn respond(directory: &Directory, message: StructureTag) -> Option<Vec<StructureTag>> {
+        let mut parts = message.expect_constructed()?.into_iter();
+        let id = parts.next()?;
+        let op = parts.next()?;
+        let fields = op.clone().expect_constructed().unwrap_or_default();
+        let entries = directory.read().unwrap();
+
+        let ops = match op.id {
+            0 => {
+                let (dn, password) = (string(&fields[1]), string(&fields[2]));
+                let valid = entries.iter().any(|e| {
+                    e.dn.eq_ignore_ascii_case(&dn) && e.password.as_deref() == Some(&password)
+                });
+                vec![ldap_result(1, if valid { 0 } else { 49 })]
+            }
+            3 => {
+                let base = string(&fields[0]).to_ascii_lowercase();
+                let scope = primitive(&fields[1]).first().copied().unwrap_or_default();
+                let attrs: Vec<String> = fields[7]
+                    .clone()
+                    .expect_constructed()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(string)
+                    .collect();
+
+                let mut ops: Vec<StructureTag> = entries
+                    .iter()
+                    .filter(|e| match scope {
+                        0 => e.dn.to_ascii_lowercase() == base,
+                        _ => e.dn.to_ascii_lowercase().ends_with(&base),
+                    })
+                    .filter(|e| filter_matches(&fields[6], e))
+                    .map(|e| {
+                        let attributes = attrs
+                            .iter()
+                            .map(|attr| {
+                                let values = e.values(attr).into_iter().map(octets).collect();
+                                let values = tag(TagClass::Universal, 17, PL::C(values));
+                                tag(TagClass::Universal, 16, PL::C(vec![octets(attr), values]))
+                            })
+                            .collect();
+                        let attributes = tag(TagClass::Universal, 16, PL::C(attributes));
+                        tag(
+                            TagClass::Application,
+                            4,
+                            PL::C(vec![octets(&e.dn), attributes]),
+                        )
+                    })
+                    .collect();
+                ops.push(ldap_result(5, 0));
+                ops
+            }
+            _ => return None,
+        };
+
+        let messages = ops
+            .into_iter()
+            .map(|op| tag(TagClass::Universal, 16, PL::C(vec![id.clone(), op])))
+            .collect();
+        Some(messages)
+    }
+
+    /// Minimal in-process LDAP server, supporting simple binds and searches.
+    async fn spawn_directory(directory: Directory) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let directory = Arc::clone(&directory);
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0; 4096];
+                    loop {
+                        let n = stream.read(&mut chunk).await.unwrap_or_default();
+                        if n == 0 {
+                            return;
+                        }
+                        buf.extend_from_slice(&chunk[..n]);
+
+                        while let Ok((rest, message)) = parse_tag(&buf) {
+                            buf = rest.to_vec();
+                            let Some(responses) = respond(&directory, message) else {
+                                return;
+                            };
+                            let mut out = Vec::new();
+                            responses.into_iter().for_each(|r| encode(r, &mut out));
+                            if stream.write_all(&out).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        format!("ldap://{}", addr)
+    }
+
+    fn test_directory() -> Directory {
+        Arc::new(RwLock::new(vec![
+            Entry::new(
+                "uid=alice,ou=people,dc=example,dc=org",
+                Some("alice_password"),
+                &[("uid", &["alice"]), ("mail", &["alice@example.org"])],
+            ),
+            Entry::new(
+                "uid=bob,ou=people,dc=example,dc=org",
+                Some("bob_password"),
+                &[("uid", &["bob"])],
+            ),
+            Entry::new(
+                "cn=admins,ou=groups,dc=example,dc=org",
+                None,
+                &[
+                    ("cn", &["admins"]),
+                    ("member", &["uid=alice,ou=people,dc=example,dc=org"]),
+                ],
+            ),
+            Entry::new(
+                "cn=developers,ou=groups,dc=example,dc=org",
+                None,
+                &[("cn", &["developers"]), ("memberUid", &["alice", "bob"])],
+            ),
+        ]))
+    }
+
+    fn test_config(url: &str) -> Config {
+        Config {
+            ldap_url: url.to_owned(),
+            ldap_group_base_dn: "ou=groups,dc=example,dc=org".to_owned(),
+            ldap_group_roles: "admins:admin, developers:user".to_owned(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn group_roles_from_config() {
+        let roles = parse_group_roles(" admins:admin,,staff: user ").unwrap();
+        assert_eq!(
+            roles,
+            vec![
+                ("admins".to_owned(), Role::Admin),
+                ("staff".to_owned(), Role::User)
+            ]
+        );
+
+        assert!(parse_group_roles("admins").is_err());
+        assert!(parse_group_roles("admins:root").is_err());
+
+        let config = Config {
+            ldap_user_dn: "ou=people,dc=example,dc=org".to_owned(),
+            ..test_config("ldap://127.0.0.1")
+        };
+        assert!(LdapProvider::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn map_groups_to_roles() {
+        let provider = LdapProvider::from_config(&test_config("ldap://127.0.0.1")).unwrap();
+
+        assert_eq!(
+            provider.user_dn("a,b=c"),
+            "uid=a\\2cb\\3dc,ou=people,dc=example,dc=org"
+        );
+        assert_eq!(provider.roles(&["ADMINS".to_owned()]), vec![Role::Admin]);
+        assert_eq!(
+            provider.roles(&["developers".to_owned(), "admins".to_owned()]),
+            vec![Role::User, Role::Admin]
+        );
+        assert_eq!(provider.roles(&["others".to_owned()]), vec![Role::User]);
+    }
+
+    #[tokio::test]
+    async fn bind_as_user() {
+        let url = spawn_directory(test_directory()).await;
+        let provider = LdapProvider::from_config(&test_config(&url)).unwrap();
+
+        let alice = provider.bind("alice", "alice_password").await.unwrap();
+        assert_eq!(
+            alice,
+            Some(LdapUser {
+                dn: "uid=alice,ou=people,dc=example,dc=org".to_owned(),
+                email: Some("alice@example.org".to_owned()),
+                groups: vec!["admins".to_owned(), "developers".to_owned()],
+            })
+        );
+
+        let bob = provider.bind("bob", "bob_password").await.unwrap().unwrap();
+        assert_eq!(bob.email, None);
+        assert_eq!(bob.groups, vec!["developers".to_owned()]);
+
+        for (username, password) in [("alice", "wrong_password"), ("carol", "x"), ("alice", "")] {
+            assert_eq!(provider.bind(username, password).await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn bind_to_unreachable_directory() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ldap://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let provider = LdapProvider::from_config(&test_config(&url)).unwrap();
+        let res = provider.bind("alice", "alice_password").await;
+        assert!(matches!(res, Err(ApiError::Internal(_))));
+    }
+
+    #[tokio::test]
+    async fn create_user_on_first_login() {
+        let directory = test_directory();
+        let url = spawn_directory(Arc::clone(&directory)).await;
+        let provider = LdapProvider::from_config(&test_config(&url)).unwrap();
+        let db = MockDatabase::new();
+
+        let user = provider
+            .authenticate(db.as_ref(), "alice", "alice_password")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.auth_source, AuthSource::Ldap);
+        assert_eq!(user.roles, vec![Role::User, Role::Admin]);
+        assert_eq!(user.email.as_deref(), Some("alice@example.org"));
+
+        // Group memberships are synchronized on every login.
+        directory
+            .write()
+            .unwrap()
+            .retain(|e| !e.dn.starts_with("cn=admins"));
+        let same_user = provider
+            .authenticate(db.as_ref(), "alice", "alice_password")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(same_user.id, user.id);
+        assert_eq!(same_user.roles, vec![Role::User]);
+        assert_eq!(db.users().find_all().await.unwrap().len(), 1);
+
+        let res = provider
+            .authenticate(db.as_ref(), "alice", "wrong_password")
+            .await
+            .unwrap();
+        assert!(res.is_none());
+    }
+
+    #[tokio::test]
+    async fn create_users_only_if_registration_allowed() {
+        let url = spawn_directory(test_directory()).await;
+        let closed = Config {
+            registration_mode: RegistrationMode::Closed,
+            ..test_config(&url)
+        };
+        let provider = LdapProvider::from_config(&closed).unwrap();
+        let db = MockDatabase::new();
+
+        assert!(matches!(
+            provider
+                .authenticate(db.as_ref(), "alice", "alice_password")
+                .await,
+            Err(ApiError::Auth(AuthError::RegistrationClosed))
+        ));
+
+        let provider = LdapProvider::from_config(&Config {
+            external_registration: true,
+            ..closed.clone()
+        })
+        .unwrap();
+        let user = provider
+            .authenticate(db.as_ref(), "alice", "alice_password")
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Users who already have an account can log in regardless.
+        let provider = LdapProvider::from_config(&closed).unwrap();
+        let same_user = provider
+            .authenticate(db.as_ref(), "alice", "alice_password")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(same_user.id, user.id);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Redis"]
+    async fn revoke_tokens_when_groups_change() {
+        let directory = test_directory();
+        let url = spawn_directory(Arc::clone(&directory)).await;
+        let state = test_utils::state(test_config(&url)).await;
+
+        let user = verify_credentials(&state, "alice", "alice_password")
+            .await
+            .unwrap();
+        assert!(user.has_role(Role::Admin));
+        let (token, _, _) = create_session(&state, &user, &TokenContext::default())
+            .await
+            .unwrap();
+        let claims = services::jwt::decode_token(
+            &token,
+            &TokenValidation::for_state(TokenType::Access, &state),
+        )
+        .unwrap();
+
+        directory
+            .write()
+            .unwrap()
+            .retain(|e| !e.dn.starts_with("cn=admins"));
+        let user = verify_credentials(&state, "alice", "alice_password")
+            .await
+            .unwrap();
+        assert_eq!(user.roles, vec![Role::User]);
+
+        // Admin tokens issued before the groups changed are rejected, the new ones are not.
+        assert!(matches!(
+            verify_token_version(&state, &claims).await,
+            Err(ApiError::Auth(AuthError::TokenRevoked))
+        ));
+        let (token, _, _) = create_session(&state, &user, &TokenContext::default())
+            .await
+            .unwrap();
+        let claims = services::jwt::decode_token(
+            &token,
+            &TokenValidation::for_state(TokenType::Access, &state),
+        )
+        .unwrap();
+        assert!(verify_token_version(&state, &claims).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn keep_local_users() {
+        let url = spawn_directory(test_directory()).await;
+        let provider = LdapProvider::from_config(&test_config(&url)).unwrap();
+        let db = MockDatabase::new();
+        let local = User::new("bob", &hash_string("local_password"), &[Role::User]);
+        db.users().create(local).await.unwrap();
+
+        let res = provider
+            .authenticate(db.as_ref(), "bob", "bob_password")
+            .await
+            .unwrap();
+        assert!(res.is_none());
+    }
+}
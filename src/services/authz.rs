@@ -0,0 +1,303 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    database::Database,
+    error::ApiError,
+    models::policy::{Decision, PolicySet},
+    services::{auth::AuthError, jwt::Claims},
+};
+
+/// Policies used when no policy file is configured.
+const DEFAULT_POLICIES: &str = include_str!("../../policies.example.json");
+
+/// Resource an authorization request is about. Attributes of known resource types are
+/// resolved from the database and take precedence over the attributes given by the caller.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Resource {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub attributes: Map<String, Value>,
+}
+
+impl Resource {
+    pub fn new(kind: &str, id: Option<Uuid>) -> Self {
+        Self {
+            kind: kind.to_owned(),
+            id: id.map(|id| id.to_string()),
+            attributes: Map::new(),
+        }
+    }
+}
+
+pub fn parse_policies(policies: &str) -> anyhow::Result<PolicySet> {
+    let policies: PolicySet = serde_json::from_str(policies)?;
+
+    let mut names = HashSet::new();
+    for rule in &policies.rules {
+        if rule.name.is_empty() || !names.insert(rule.name.as_str()) {
+            anyhow::bail!("policy rule name ({}) is empty or not unique", rule.name);
+        }
+        if rule.actions.is_empty() || rule.resources.is_empty() {
+            anyhow::bail!("policy rule ({}) has no actions or resources", rule.name);
+        }
+    }
+
+    Ok(policies)
+}
+
+/// Loads the policies from the configured file, or the built-in ones if there is none.
+pub fn policies_from_config(config: &Config) -> anyhow::Result<PolicySet> {
+    if config.policy_file.is_empty() {
+        return parse_policies(DEFAULT_POLICIES);
+    }
+
+    let policies = std::fs::read_to_string(&config.policy_file)
+        .with_context(|| format!("failed to read policy file ({})", config.policy_file))?;
+    parse_policies(&policies)
+        .with_context(|| format!("policy file ({}) is invalid", config.policy_file))
+}
+
+/// Organizations the user belongs to, including the one owning their namespace.
+async fn org_ids(
+    db: &dyn Database,
+    user_id: Uuid,
+    org_id: Option<Uuid>,
+) -> Result<Vec<Uuid>, ApiError> {
+    let mut ids: Vec<Uuid> = org_id.into_iter().collect();
+    for organization in db.organizations().find_by_member(user_id).await? {
+        if !ids.contains(&organization.id) {
+            ids.push(organization.id);
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Claims of the subject along with the organizations and groups they belong to.
+pub async fn subject_attributes(db: &dyn Database, claims: &Claims) -> Result<Value, ApiError> {
+    let mut subject = serde_json::to_value(claims).map_err(anyhow::Error::from)?;
+    let groups: Vec<String> = db
+        .groups()
+        .find_by_member(claims.sub)
+        .await?
+        .into_iter()
+        .map(|group| group.name)
+        .collect();
+
+    subject["org_ids"] = json!(org_ids(db, claims.sub, claims.org_id).await?);
+    subject["groups"] = json!(groups);
+
+    Ok(subject)
+}
+
+pub async fn resource_attributes(
+    db: &dyn Database,
+    resource: &Resource,
+) -> Result<Value, ApiError> {
+    let mut attributes = resource.attributes.clone();
+    attributes.insert("type".to_owned(), json!(resource.kind));
+    attributes.insert("id".to_owned(), json!(resource.id));
+
+    let user_id = resource.id.as_deref().and_then(|id| id.parse().ok());
+    if let ("user", Some(user_id)) = (resource.kind.as_str(), user_id) {
+        let user = db
+            .users()
+            .find_by_id(user_id)
+            .await?
+            .filter(|user| user.deleted_at.is_none());
+
+        if let Some(user) = user {
+            attributes.insert("username".to_owned(), json!(user.username));
+            attributes.insert("roles".to_owned(), json!(user.roles));
+            attributes.insert("org_id".to_owned(), json!(user.org_id));
+            attributes.insert(
+                "org_ids".to_owned(),
+                json!(org_ids(db, user.id, user.org_id).await?),
+            );
+            attributes.insert("disabled".to_owned(), json!(user.disabled_at.is_some()));
+        }
+    }
+
+    Ok(Value::Object(attributes))
+}
+
+/// Decides whether the subject may perform the action on the resource. The path parameters
+/// are taken from the route being authorized and must never come from the caller.
+pub async fn authorize(
+    db: &dyn Database,
+    policies: &PolicySet,
+    claims: &Claims,
+    action: &str,
+    resource: &Resource,
+    params: Value,
+) -> Result<Decision, ApiError> {
+    let attributes = json!({
+        "subject": subject_attributes(db, claims).await?,
+        "resource": resource_attributes(db, resource).await?,
+        "params": params,
+    });
+
+    Ok(policies.evaluate(action, &resource.kind, &attributes))
+}
+
+/// Like [`authorize`], but fails with forbidden if the action is denied.
+pub async fn enforce(
+    db: &dyn Database,
+    policies: &PolicySet,
+    claims: &Claims,
+    action: &str,
+    resource: &Resource,
+    params: Value,
+) -> Result<(), ApiError> {
+    if !authorize(db, policies, claims, action, resource, params)
+        .await?
+        .allowed
+    {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::mock::MockDatabase,
+        models::{
+            organization::{Membership, OrgRole, Organization},
+            user::{Role, User},
+        },
+        services::jwt::TokenType,
+    };
+
+    fn claims(user: &User) -> Claims {
+        Claims::from_user(user, TokenType::Access, &Config::default(), 0)
+    }
+
+    #[test]
+    fn parse_default_policies() {
+        assert!(!parse_policies(DEFAULT_POLICIES).unwrap().rules.is_empty());
+        assert!(parse_policies(
+            r#"{ "rules": [{ "name": "a", "actions": [], "resources": ["*"] }] }"#
+        )
+        .is_err());
+        assert!(parse_policies(
+            r#"{ "rules": [
+                { "name": "a", "actions": ["*"], "resources": ["*"] },
+                { "name": "a", "actions": ["*"], "resources": ["*"] }
+            ] }"#
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn read_own_or_same_org_user() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let policies = parse_policies(DEFAULT_POLICIES).unwrap();
+
+        let org = db
+            .organizations()
+            .create(Organization::new("acme", "Acme"))
+            .await
+            .unwrap();
+        let alice = db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]).with_org(org.id))
+            .await
+            .unwrap();
+        let bob = db
+            .users()
+            .create(User::new("bob", "hash", &[Role::User]))
+            .await
+            .unwrap();
+        let carol = db
+            .users()
+            .create(User::new("carol", "hash", &[Role::User]))
+            .await
+            .unwrap();
+        let admin = db
+            .users()
+            .create(User::new("admin", "hash", &[Role::Admin]))
+            .await
+            .unwrap();
+
+        let read = |claims: Claims, user: &User| {
+            let resource = Resource::new("user", Some(user.id));
+            let policies = &policies;
+            async move {
+                let params = json!({ "id": resource.id });
+                authorize(db, policies, &claims, "read", &resource, params)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert!(read(claims(&bob), &bob).await.allowed);
+        assert!(!read(claims(&bob), &alice).await.allowed);
+        assert!(!read(claims(&bob), &carol).await.allowed);
+        assert!(read(claims(&admin), &carol).await.allowed);
+
+        db.memberships()
+            .upsert(Membership::new(org.id, bob.id, OrgRole::Member))
+            .await
+            .unwrap();
+        let decision = read(claims(&bob), &alice).await;
+        assert!(decision.allowed);
+        assert_eq!(decision.rule.as_deref(), Some("read_same_org_user"));
+        assert!(!read(claims(&alice), &carol).await.allowed);
+
+        // Path parameters pointing at the subject don't grant access to another user.
+        let resource = Resource::new("user", Some(carol.id));
+        let params = json!({ "id": bob.id });
+        assert!(matches!(
+            enforce(
+                db,
+                &policies,
+                &claims(&bob),
+                "read",
+                &resource,
+                params.clone()
+            )
+            .await,
+            Err(ApiError::Auth(AuthError::Forbidden))
+        ));
+        assert!(matches!(
+            enforce(db, &policies, &claims(&bob), "delete", &resource, params).await,
+            Err(ApiError::Auth(AuthError::Forbidden))
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolved_attributes_override_given_ones() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let user = db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]))
+            .await
+            .unwrap();
+
+        let mut resource = Resource::new("user", Some(user.id));
+        resource
+            .attributes
+            .insert("username".to_owned(), json!("mallory"));
+        resource.attributes.insert("tier".to_owned(), json!("gold"));
+
+        let attributes = resource_attributes(db, &resource).await.unwrap();
+        assert_eq!(attributes["username"], "alice");
+        assert_eq!(attributes["tier"], "gold");
+        assert_eq!(attributes["org_ids"], json!([]));
+        assert_eq!(attributes["disabled"], false);
+    }
+}
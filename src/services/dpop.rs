@@ -0,0 +1,266 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::http::{HeaderMap, Method};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    error::ApiError,
+    services::{auth::AuthError, oidc},
+    ApiState,
+};
+
+pub const DPOP_HEADER: &str = "DPoP";
+pub const DPOP_PROOF_TYPE: &str = "dpop+jwt";
+
+/// Asymmetric algorithms accepted for DPoP proofs (RFC 9449, section 4.2).
+pub const SUPPORTED_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::EdDSA,
+];
+
+const MAX_JTI_LENGTH: usize = 256;
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum DpopError {
+    #[error("DPoP proof is malformed")]
+    Malformed,
+    #[error("DPoP proof signature is invalid")]
+    InvalidSignature,
+    #[error("DPoP proof does not match the request")]
+    RequestMismatch,
+    #[error("DPoP proof is expired or issued in the future")]
+    InvalidIssueTime,
+    #[error("DPoP proof is not bound to the access token")]
+    AccessTokenMismatch,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProofClaims {
+    jti: String,
+    htm: String,
+    htu: String,
+    iat: i64,
+    #[serde(default)]
+    ath: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DpopProof {
+    pub jkt: String,
+    pub jti: String,
+    pub iat: i64,
+}
+
+/// Hash of the access token carried in the `ath` claim of proofs sent to protected resources.
+pub fn access_token_hash(access_token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(access_token.as_bytes()))
+}
+
+/// Strips the query and fragment, which are not covered by the `htu` claim.
+fn target_uri(uri: &str) -> Option<Url> {
+    let mut url = Url::parse(uri).ok()?;
+    url.set_query(None);
+    url.set_fragment(None);
+    Some(url)
+}
+
+/// Verifies a DPoP proof JWT against the request it was sent with and returns the
+/// thumbprint of the public key embedded in it.
+pub fn verify_proof(
+    proof: &str,
+    htm: &str,
+    htu: &str,
+    access_token: Option<&str>,
+    now: i64,
+    lifetime: i64,
+) -> Result<DpopProof, DpopError> {
+    let header = jsonwebtoken::decode_header(proof).map_err(|_| DpopError::Malformed)?;
+
+    if header.typ.as_deref() != Some(DPOP_PROOF_TYPE) || !SUPPORTED_ALGORITHMS.contains(&header.alg)
+    {
+        return Err(DpopError::Malformed);
+    }
+
+    let jwk = header.jwk.ok_or(DpopError::Malformed)?;
+    let jkt = oidc::jwk_thumbprint(&jwk).ok_or(DpopError::Malformed)?;
+    let key = DecodingKey::from_jwk(&jwk).map_err(|_| DpopError::Malformed)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.required_spec_claims = HashSet::new();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+
+    let claims = jsonwebtoken::decode::<ProofClaims>(proof, &key, &validation)
+        .map_err(|err| match err.kind() {
+            jsonwebtoken::errors::ErrorKind::InvalidSignature => DpopError::InvalidSignature,
+            _ => DpopError::Malformed,
+        })?
+        .claims;
+
+    if claims.jti.is_empty() || claims.jti.len() > MAX_JTI_LENGTH {
+        return Err(DpopError::Malformed);
+    }
+
+    let expected_htu = target_uri(htu).ok_or(DpopError::RequestMismatch)?;
+    if claims.htm != htm || target_uri(&claims.htu) != Some(expected_htu) {
+        return Err(DpopError::RequestMismatch);
+    }
+
+    if (now - claims.iat).abs() > lifetime {
+        return Err(DpopError::InvalidIssueTime);
+    }
+
+    if let Some(access_token) = access_token {
+        if claims.ath != Some(access_token_hash(access_token)) {
+            return Err(DpopError::AccessTokenMismatch);
+        }
+    }
+
+    Ok(DpopProof {
+        jkt,
+        jti: claims.jti,
+        iat: claims.iat,
+    })
+}
+
+/// Verifies the DPoP proof sent with the request, if there is one, and returns the
+/// thumbprint of its key. Every proof is accepted only once within its lifetime.
+pub async fn verify_request(
+    state: &Arc<ApiState>,
+    headers: &HeaderMap,
+    method: &Method,
+    path: &str,
+    access_token: Option<&str>,
+) -> Result<Option<String>, ApiError> {
+    let mut proofs = headers.get_all(DPOP_HEADER).iter();
+    let Some(proof) = proofs.next() else {
+        return Ok(None);
+    };
+
+    if proofs.next().is_some() {
+        return Err(AuthError::InvalidDpopProof.into());
+    }
+
+    let proof = proof.to_str().map_err(|_| AuthError::InvalidDpopProof)?;
+    let htu = format!("{}{}", state.config.issuer(), path);
+    let proof = verify_proof(
+        proof,
+        method.as_str(),
+        &htu,
+        access_token,
+        chrono::Utc::now().timestamp(),
+        state.config.dpop_proof_lifetime,
+    )
+    .map_err(|e| {
+        tracing::debug!("rejected DPoP proof: {}", e);
+        AuthError::InvalidDpopProof
+    })?;
+
+    // Proofs are accepted within the lifetime on either side of the current time.
+    if !state
+        .redis
+        .dpop_proofs()
+        .insert(&proof.jti, 2 * state.config.dpop_proof_lifetime)
+        .await?
+    {
+        return Err(AuthError::InvalidDpopProof.into());
+    }
+
+    Ok(Some(proof.jkt))
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{EncodingKey, Header};
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    const HTU: &str = "https://auth.test/api/v1/auth/protected";
+
+    fn sign_proof(claims: serde_json::Value) -> (String, String) {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+                .unwrap();
+        let signing_key = oidc::SigningKey::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let jwk = signing_key.jwks().keys.remove(0);
+
+        let mut header = Header::new(Algorithm::ES256);
+        header.typ = Some(DPOP_PROOF_TYPE.to_owned());
+        header.jwk = Some(jwk);
+
+        let proof =
+            jsonwebtoken::encode(&header, &claims, &EncodingKey::from_ec_der(pkcs8.as_ref()))
+                .unwrap();
+
+        (proof, signing_key.kid().unwrap().to_owned())
+    }
+
+    #[test]
+    fn verify_proof_valid() {
+        let (proof, kid) = sign_proof(json!({
+            "jti": "proof-1",
+            "htm": "GET",
+            "htu": HTU,
+            "iat": 1000,
+            "ath": access_token_hash("access_token"),
+        }));
+
+        let res = verify_proof(
+            &proof,
+            "GET",
+            &format!("{}?page=2", HTU),
+            Some("access_token"),
+            1010,
+            60,
+        );
+        assert_eq!(res.unwrap().jkt, kid);
+    }
+
+    #[test]
+    fn verify_proof_request_mismatch() {
+        let (proof, _) = sign_proof(json!({
+            "jti": "proof-1",
+            "htm": "GET",
+            "htu": HTU,
+            "iat": 1000,
+        }));
+
+        let res = verify_proof(&proof, "POST", HTU, None, 1000, 60);
+        assert_eq!(res, Err(DpopError::RequestMismatch));
+
+        let res = verify_proof(&proof, "GET", "https://other.test/", None, 1000, 60);
+        assert_eq!(res, Err(DpopError::RequestMismatch));
+    }
+
+    #[test]
+    fn verify_proof_stale_or_unbound() {
+        let (proof, _) = sign_proof(json!({
+            "jti": "proof-1",
+            "htm": "GET",
+            "htu": HTU,
+            "iat": 1000,
+        }));
+
+        let res = verify_proof(&proof, "GET", HTU, None, 1100, 60);
+        assert_eq!(res, Err(DpopError::InvalidIssueTime));
+
+        let res = verify_proof(&proof, "GET", HTU, Some("access_token"), 1000, 60);
+        assert_eq!(res, Err(DpopError::AccessTokenMismatch));
+    }
+}
@@ -0,0 +1,990 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    config::{Config, UpstreamProvider},
+    database::Database,
+    error::ApiError,
+    models::{
+        refresh_token::RefreshToken,
+        user::{AuthSource, Role, User},
+        user_identity::{FederatedLogin, UserIdentity},
+    },
+    routes::extractors::ApiVersion,
+    services::{
+        self,
+        auth::{generate_secret, hash_string, AuthError},
+        jwt::TokenContext,
+    },
+    ApiState,
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Endpoints of an upstream provider, taken from its discovery document.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// Claims of an ID token issued by an upstream provider. `iss`, `aud` and `exp`
+/// are checked during decoding.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UpstreamClaims {
+    pub sub: String,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: Option<bool>,
+    #[serde(default)]
+    pub preferred_username: Option<String>,
+}
+
+/// User resolved from an upstream identity.
+#[derive(Clone, Debug)]
+pub struct FederatedUser {
+    pub user: User,
+    pub identity: UserIdentity,
+    /// Whether the user was created during this login.
+    pub created: bool,
+}
+
+#[derive(Debug)]
+pub enum FederatedOutcome {
+    Login {
+        federated: Box<FederatedUser>,
+        access_token: String,
+        refresh_token: String,
+        deleted_token: Option<RefreshToken>,
+    },
+    Linked(UserIdentity),
+}
+
+#[derive(Deserialize)]
+struct TokenEndpointResponse {
+    #[serde(default)]
+    id_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenEndpointError {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("federation http client should build")
+}
+
+pub fn find_provider<'a>(config: &'a Config, name: &str) -> Result<&'a UpstreamProvider, ApiError> {
+    config
+        .oidc_upstream_providers
+        .iter()
+        .find(|provider| provider.name == name)
+        .ok_or_else(|| ApiError::NotFound(format!("identity provider ({}) not found", name)))
+}
+
+/// URI the upstream provider redirects back to, it has to be registered at the provider.
+pub fn callback_uri(config: &Config, version: &ApiVersion, provider: &str) -> String {
+    format!(
+        "{}/api/{}/auth/federated/{}/callback",
+        config.issuer(),
+        version,
+        provider
+    )
+}
+
+async fn get_json<T: DeserializeOwned>(http: &reqwest::Client, url: &str) -> Result<T, ApiError> {
+    let res = http
+        .get(url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .with_context(|| format!("request to {} failed", url))?;
+    let body = res
+        .text()
+        .await
+        .with_context(|| format!("failed to read response of {}", url))?;
+
+    Ok(serde_json::from_str(&body).with_context(|| format!("invalid response of {}", url))?)
+}
+
+pub async fn discover(
+    http: &reqwest::Client,
+    provider: &UpstreamProvider,
+) -> Result<ProviderMetadata, ApiError> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        provider.issuer.trim_end_matches('/')
+    );
+    let metadata: ProviderMetadata = get_json(http, &url).await?;
+
+    // A mismatch means the document may belong to another provider, see OpenID Connect Discovery 4.3.
+    if metadata.issuer != provider.issuer {
+        return Err(anyhow::anyhow!(
+            "issuer of {} ({}) does not match the configured issuer ({})",
+            provider.name,
+            metadata.issuer,
+            provider.issuer
+        )
+        .into());
+    }
+
+    Ok(metadata)
+}
+
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+pub fn authorization_url(
+    metadata: &ProviderMetadata,
+    provider: &UpstreamProvider,
+    state: &str,
+    login: &FederatedLogin,
+) -> Result<String, ApiError> {
+    let challenge = pkce_challenge(&login.code_verifier);
+    services::oauth::authorization_redirect(
+        &metadata.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", &provider.client_id),
+            ("redirect_uri", &login.redirect_uri),
+            ("scope", &provider.scope),
+            ("state", state),
+            ("nonce", &login.nonce),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+        ],
+    )
+}
+
+/// Redeems the authorization code at the token endpoint and returns the ID token.
+pub async fn exchange_code(
+    http: &reqwest::Client,
+    metadata: &ProviderMetadata,
+    provider: &UpstreamProvider,
+    code: &str,
+    login: &FederatedLogin,
+) -> Result<String, ApiError> {
+    let res = http
+        .post(&metadata.token_endpoint)
+        .basic_auth(&provider.client_id, Some(&provider.client_secret))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &login.redirect_uri),
+            ("code_verifier", &login.code_verifier),
+        ])
+        .send()
+        .await
+        .with_context(|| format!("token request to {} failed", provider.name))?;
+
+    let status = res.status();
+    let body = res
+        .text()
+        .await
+        .with_context(|| format!("failed to read token response of {}", provider.name))?;
+
+    if !status.is_success() {
+        let error = serde_json::from_str::<TokenEndpointError>(&body)
+            .map(|e| match e.error_description {
+                Some(description) => format!("{} ({})", e.error, description),
+                None => e.error,
+            })
+            .unwrap_or_else(|_| format!("unexpected status code ({})", status));
+        return Err(ApiError::BadRequest(format!(
+            "code exchange with {} failed: {}",
+            provider.name, error
+        )));
+    }
+
+    serde_json::from_str::<TokenEndpointResponse>(&body)
+        .with_context(|| format!("invalid token response of {}", provider.name))?
+        .id_token
+        .ok_or_else(|| {
+            anyhow::anyhow!("token response of {} has no id_token", provider.name).into()
+        })
+}
+
+/// Verifies the ID token against the keys published by the provider.
+pub fn decode_id_token(
+    id_token: &str,
+    jwks: &JwkSet,
+    provider: &UpstreamProvider,
+    nonce: &str,
+    leeway: u64,
+) -> Result<UpstreamClaims, ApiError> {
+    let header = jsonwebtoken::decode_header(id_token).map_err(|_| AuthError::TokenInvalid)?;
+
+    // Symmetric algorithms would let the token be verified with the public key as the secret.
+    if !matches!(
+        header.alg,
+        Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512
+            | Algorithm::ES256
+            | Algorithm::ES384
+            | Algorithm::EdDSA
+    ) {
+        return Err(AuthError::TokenInvalid.into());
+    }
+
+    let jwk = match header.kid.as_deref() {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or(AuthError::TokenInvalid)?;
+    let key = DecodingKey::from_jwk(jwk).map_err(|_| AuthError::TokenInvalid)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.leeway = leeway;
+    validation.set_issuer(&[&provider.issuer]);
+    validation.set_audience(&[&provider.client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = jsonwebtoken::decode::<UpstreamClaims>(id_token, &key, &validation)
+        .map_err(|e| match e.kind() {
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+            _ => AuthError::TokenInvalid,
+        })?
+        .claims;
+
+    // Binds the token to the authorization request, so that it cannot be replayed.
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(AuthError::TokenInvalid.into());
+    }
+
+    Ok(claims)
+}
+
+pub async fn verify_id_token(
+    http: &reqwest::Client,
+    metadata: &ProviderMetadata,
+    provider: &UpstreamProvider,
+    id_token: &str,
+    nonce: &str,
+    leeway: u64,
+) -> Result<UpstreamClaims, ApiError> {
+    let jwks: JwkSet = get_json(http, &metadata.jwks_uri).await?;
+    decode_id_token(id_token, &jwks, provider, nonce, leeway)
+}
+
+fn base_username(provider: &str, claims: &UpstreamClaims) -> String {
+    let email_name = claims
+        .email
+        .as_deref()
+        .and_then(|email| email.split_once('@'))
+        .map(|(name, _)| name);
+
+    claims
+        .preferred_username
+        .as_deref()
+        .or(email_name)
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| name.trim().to_owned())
+        .unwrap_or_else(|| format!("{}-{}", provider, claims.sub))
+}
+
+async fn free_username(db: &dyn Database, base: &str) -> Result<String, ApiError> {
+    let mut username = base.to_owned();
+    while db.users().find_by_username(&username).await?.is_some() {
+        let suffix = Uuid::new_v4().simple().to_string();
+        username = format!("{}-{}", base, &suffix[..8]);
+    }
+
+    Ok(username)
+}
+
+async fn create_user(
+    db: &dyn Database,
+    provider: &UpstreamProvider,
+    claims: &UpstreamClaims,
+    email: Option<&str>,
+) -> Result<User, ApiError> {
+    let username = free_username(db, &base_username(&provider.name, claims)).await?;
+
+    // The password is never checked locally, the hash only fills the column.
+    let mut user = User::new(&username, &hash_string(&generate_secret()), &[Role::User])
+        .with_auth_source(AuthSource::Oidc);
+    if let Some(email) = email {
+        user = user.with_email(email);
+        user.email_verified = claims.email_verified == Some(true);
+    }
+
+    tracing::info!(
+        "Creating user {} from {} subject {}",
+        username,
+        provider.name,
+        claims.sub
+    );
+    db.users().create(user).await
+}
+
+/// Finds the user linked to the upstream identity, creating one on the first login if
+/// `allow_registration` is set. With `link_user_id` the identity is linked to that user instead.
+///
+/// Identities are never linked to existing users by their email, since the email
+/// is controlled by the upstream provider and could be used to take over the account.
+pub async fn resolve_user(
+    db: &dyn Database,
+    provider: &UpstreamProvider,
+    claims: &UpstreamClaims,
+    link_user_id: Option<Uuid>,
+    allow_registration: bool,
+) -> Result<FederatedUser, ApiError> {
+    let email = claims.email.as_deref().filter(|email| email.contains('@'));
+
+    if let Some(identity) = db
+        .user_identities()
+        .find_by_subject(&provider.name, &claims.sub)
+        .await?
+    {
+        if link_user_id.is_some_and(|id| id != identity.user_id) {
+            return Err(ApiError::BadRequest(format!(
+                "{} identity is already linked to another user",
+                provider.name
+            )));
+        }
+
+        match db.users().find_by_id(identity.user_id).await? {
+            Some(user) => {
+                let identity = db
+                    .user_identities()
+                    .update(UserIdentity {
+                        email: email.map(str::to_owned),
+                        last_login_at: Utc::now(),
+                        ..identity
+                    })
+                    .await?
+                    .context("identity was deleted during login")?;
+
+                return Ok(FederatedUser {
+                    user,
+                    identity,
+                    created: false,
+                });
+            }
+            // The user was deleted, the identity starts over with a new one.
+            None => {
+                db.user_identities().delete_by_id(identity.id).await?;
+            }
+        }
+    }
+
+    let (user, created) = match link_user_id {
+        Some(id) => (
+            db.users()
+                .find_by_id(id)
+                .await?
+                .ok_or(AuthError::Unauthorized)?,
+            false,
+        ),
+        None if !allow_registration => return Err(AuthError::RegistrationClosed.into()),
+        None => (create_user(db, provider, claims, email).await?, true),
+    };
+
+    let identity = db
+        .user_identities()
+        .create(UserIdentity::new(
+            user.id,
+            &provider.name,
+            &claims.sub,
+            email,
+        ))
+        .await?;
+
+    Ok(FederatedUser {
+        user,
+        identity,
+        created,
+    })
+}
+
+/// Unlinks the identity from the user. The last identity of a user created by an upstream
+/// provider is kept, since the user would have no way to log in without it.
+pub async fn unlink_identity(
+    db: &dyn Database,
+    user: &User,
+    identity_id: Uuid,
+) -> Result<UserIdentity, ApiError> {
+    let identities = db.user_identities().find_by_user_id(user.id).await?;
+    if !identities.iter().any(|identity| identity.id == identity_id) {
+        return Err(ApiError::NotFound(format!(
+            "identity ({}) not found",
+            identity_id
+        )));
+    }
+
+    if user.auth_source == AuthSource::Oidc && identities.len() == 1 {
+        return Err(ApiError::BadRequest(
+            "the last identity of a federated user cannot be unlinked".to_string(),
+        ));
+    }
+
+    db.user_identities()
+        .delete_by_id(identity_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("identity ({}) not found", identity_id)))
+}
+
+/// Starts the authorization code flow and returns the URL of the upstream provider.
+pub async fn start(
+    state: &Arc<ApiState>,
+    version: &ApiVersion,
+    provider_name: &str,
+    link_user_id: Option<Uuid>,
+) -> Result<String, ApiError> {
+    let provider = find_provider(&state.config, provider_name)?;
+    let metadata = discover(&http_client(), provider).await?;
+
+    let login = FederatedLogin {
+        provider: provider.name.clone(),
+        redirect_uri: callback_uri(&state.config, version, &provider.name),
+        nonce: generate_secret(),
+        code_verifier: generate_secret(),
+        link_user_id,
+    };
+    let login_state = generate_secret();
+    state
+        .redis
+        .federated_logins()
+        .store(
+            &login_state,
+            &login,
+            state.config.federated_login_expiration,
+        )
+        .await?;
+
+    authorization_url(&metadata, provider, &login_state, &login)
+}
+
+/// Completes the authorization code flow started by [`start`].
+pub async fn complete(
+    state: &Arc<ApiState>,
+    provider_name: &str,
+    code: &str,
+    login_state: &str,
+) -> Result<FederatedOutcome, ApiError> {
+    let login = state
+        .redis
+        .federated_logins()
+        .take(login_state)
+        .await?
+        .filter(|login| login.provider == provider_name)
+        .ok_or_else(|| {
+            ApiError::BadRequest("federated login state is invalid or expired".to_string())
+        })?;
+
+    let provider = find_provider(&state.config, provider_name)?;
+    let http = http_client();
+    let metadata = discover(&http, provider).await?;
+    let id_token = exchange_code(&http, &metadata, provider, code, &login).await?;
+    let claims = verify_id_token(
+        &http,
+        &metadata,
+        provider,
+        &id_token,
+        &login.nonce,
+        state.config.jwt_leeway,
+    )
+    .await?;
+
+    let federated = resolve_user(
+        state.db.as_ref(),
+        provider,
+        &claims,
+        login.link_user_id,
+        state.config.allows_external_registration(),
+    )
+    .await?;
+    if login.link_user_id.is_some() {
+        return Ok(FederatedOutcome::Linked(federated.identity));
+    }
+
+    let (access_token, refresh_token, deleted_token) =
+        services::auth::create_session(state, &federated.user, &TokenContext::default()).await?;
+
+    Ok(FederatedOutcome::Login {
+        federated: Box::new(federated),
+        access_token,
+        refresh_token,
+        deleted_token,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use axum::{
+        extract::{Query, State},
+        http::{HeaderMap, StatusCode},
+        response::{IntoResponse, Redirect, Response},
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        database::mock::MockDatabase,
+        services::{oauth::ClientCredentials, oidc::SigningKey},
+    };
+
+    const CLIENT_ID: &str = "flatline";
+    const CLIENT_SECRET: &str = "upstream-secret";
+
+    struct PendingCode {
+        challenge: String,
+        nonce: String,
+        redirect_uri: String,
+    }
+
+    /// Upstream provider issuing ES256 ID tokens for the subject `alice`.
+    struct MockIdp {
+        issuer: String,
+        key: SigningKey,
+        codes: Mutex<HashMap<String, PendingCode>>,
+        /// Claims merged into the ID token, to issue tokens which have to be rejected.
+        overrides: Mutex<serde_json::Value>,
+    }
+
+    async fn idp_discovery(State(idp): State<Arc<MockIdp>>) -> Json<serde_json::Value> {
+        Json(json!({
+            "issuer": idp.issuer,
+            "authorization_endpoint": format!("{}/authorize", idp.issuer),
+            "token_endpoint": format!("{}/token", idp.issuer),
+            "jwks_uri": format!("{}/jwks", idp.issuer),
+        }))
+    }
+
+    async fn idp_jwks(State(idp): State<Arc<MockIdp>>) -> Json<JwkSet> {
+        Json(idp.key.jwks())
+    }
+
+    async fn idp_authorize(
+        State(idp): State<Arc<MockIdp>>,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> Response {
+        if params.get("client_id").map(String::as_str) != Some(CLIENT_ID)
+            || params.get("code_challenge_method").map(String::as_str) != Some("S256")
+        {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+
+        let code = generate_secret();
+        idp.codes.lock().unwrap().insert(
+            code.clone(),
+            PendingCode {
+                challenge: params["code_challenge"].clone(),
+                nonce: params["nonce"].clone(),
+                redirect_uri: params["redirect_uri"].clone(),
+            },
+        );
+        let location = services::oauth::authorization_redirect(
+            &params["redirect_uri"],
+            &[("code", &code), ("state", &params["state"])],
+        )
+        .unwrap();
+
+        Redirect::to(&location).into_response()
+    }
+
+    async fn idp_token(
+        State(idp): State<Arc<MockIdp>>,
+        headers: HeaderMap,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Response {
+        let error = |error: &str| (StatusCode::BAD_REQUEST, Json(json!({ "error": error })));
+
+        let authenticated = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Basic "))
+            .and_then(|v| ClientCredentials::from_basic(v).ok())
+            .is_some_and(|c| {
+                c.client_id == CLIENT_ID && c.client_secret.as_deref() == Some(CLIENT_SECRET)
+            });
+        if !authenticated {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "invalid_client" })),
+            )
+                .into_response();
+        }
+
+        let Some(pending) = idp.codes.lock().unwrap().remove(&form["code"]) else {
+            return error("invalid_grant").into_response();
+        };
+        if form["redirect_uri"] != pending.redirect_uri
+            || !services::oauth::verify_pkce(&form["code_verifier"], &pending.challenge)
+        {
+            return error("invalid_grant").into_response();
+        }
+
+        let now = Utc::now().timestamp();
+        let mut claims = json!({
+            "iss": idp.issuer,
+            "aud": CLIENT_ID,
+            "sub": "alice",
+            "iat": now,
+            "exp": now + 300,
+            "nonce": pending.nonce,
+            "email": "alice@example.org",
+            "email_verified": true,
+            "preferred_username": "alice",
+        });
+        for (k, v) in idp.overrides.lock().unwrap().as_object().unwrap() {
+            claims[k] = v.clone();
+        }
+
+        Json(json!({
+            "access_token": "upstream-access-token",
+            "token_type": "Bearer",
+            "id_token": idp.key.sign(&claims).unwrap(),
+        }))
+        .into_response()
+    }
+
+    async fn spawn_idp() -> (Arc<MockIdp>, UpstreamProvider) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let idp = Arc::new(MockIdp {
+            issuer: issuer.clone(),
+            key: SigningKey::generate().unwrap().0,
+            codes: Mutex::new(HashMap::new()),
+            overrides: Mutex::new(json!({})),
+        });
+
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(idp_discovery))
+            .route("/jwks", get(idp_jwks))
+            .route("/authorize", get(idp_authorize))
+            .route("/token", post(idp_token))
+            .with_state(Arc::clone(&idp));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let provider = UpstreamProvider {
+            name: "mock".to_string(),
+            issuer,
+            client_id: CLIENT_ID.to_string(),
+            client_secret: CLIENT_SECRET.to_string(),
+            scope: "openid email profile".to_string(),
+        };
+
+        (idp, provider)
+    }
+
+    fn pending_login() -> FederatedLogin {
+        FederatedLogin {
+            provider: "mock".to_string(),
+            redirect_uri: "http://localhost:8000/api/v1/auth/federated/mock/callback".to_string(),
+            nonce: generate_secret(),
+            code_verifier: generate_secret(),
+            link_user_id: None,
+        }
+    }
+
+    /// Runs the flow up to the verified ID token, like `start` and `complete` without Redis.
+    async fn run_flow(
+        provider: &UpstreamProvider,
+        login: &FederatedLogin,
+    ) -> Result<UpstreamClaims, ApiError> {
+        let http = http_client();
+        let metadata = discover(&http, provider).await?;
+        let url = authorization_url(&metadata, provider, "login-state", login)?;
+
+        let res = http.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::SEE_OTHER);
+        let location = url::Url::parse(
+            res.headers()
+                .get(reqwest::header::LOCATION)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+        )
+        .unwrap();
+        let params: HashMap<_, _> = location.query_pairs().into_owned().collect();
+        assert_eq!(params["state"], "login-state");
+
+        let id_token = exchange_code(&http, &metadata, provider, &params["code"], login).await?;
+        verify_id_token(&http, &metadata, provider, &id_token, &login.nonce, 0).await
+    }
+
+    #[tokio::test]
+    async fn federated_flow_with_mock_idp() {
+        let (_idp, provider) = spawn_idp().await;
+
+        let claims = run_flow(&provider, &pending_login()).await.unwrap();
+        assert_eq!(claims.sub, "alice");
+        assert_eq!(claims.email.as_deref(), Some("alice@example.org"));
+        assert_eq!(claims.email_verified, Some(true));
+    }
+
+    #[tokio::test]
+    async fn federated_flow_rejects_invalid_tokens() {
+        let (idp, provider) = spawn_idp().await;
+        let overrides = [
+            json!({ "nonce": "other-nonce" }),
+            json!({ "aud": "other-client" }),
+            json!({ "iss": "https://other.example.org" }),
+            json!({ "exp": Utc::now().timestamp() - 60 }),
+        ];
+
+        for claims in overrides {
+            *idp.overrides.lock().unwrap() = claims.clone();
+            assert!(
+                matches!(
+                    run_flow(&provider, &pending_login()).await,
+                    Err(ApiError::Auth(
+                        AuthError::TokenInvalid | AuthError::TokenExpired
+                    ))
+                ),
+                "accepted {}",
+                claims
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn federated_flow_rejects_invalid_exchange() {
+        let (_idp, provider) = spawn_idp().await;
+
+        let mut login = pending_login();
+        let http = http_client();
+        let metadata = discover(&http, &provider).await.unwrap();
+        assert!(matches!(
+            exchange_code(&http, &metadata, &provider, "unknown-code", &login).await,
+            Err(ApiError::BadRequest(_))
+        ));
+
+        // The verifier does not match the challenge sent with the authorization request.
+        let url = authorization_url(&metadata, &provider, "login-state", &login).unwrap();
+        let res = http.get(&url).send().await.unwrap();
+        let location = url::Url::parse(
+            res.headers()
+                .get(reqwest::header::LOCATION)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+        )
+        .unwrap();
+        let code = location
+            .query_pairs()
+            .find(|(k, _)| k == "code")
+            .unwrap()
+            .1
+            .into_owned();
+        login.code_verifier = generate_secret();
+        assert!(matches!(
+            exchange_code(&http, &metadata, &provider, &code, &login).await,
+            Err(ApiError::BadRequest(_))
+        ));
+
+        let wrong_secret = UpstreamProvider {
+            client_secret: "wrong".to_string(),
+            ..provider.clone()
+        };
+        assert!(matches!(
+            exchange_code(&http, &metadata, &wrong_secret, &code, &login).await,
+            Err(ApiError::BadRequest(_))
+        ));
+
+        let wrong_issuer = UpstreamProvider {
+            issuer: format!("{}/", provider.issuer),
+            ..provider
+        };
+        assert!(matches!(
+            discover(&http, &wrong_issuer).await,
+            Err(ApiError::Internal(_))
+        ));
+    }
+
+    #[test]
+    fn decode_id_token_rejects_symmetric_algorithms() {
+        let provider = UpstreamProvider {
+            name: "mock".to_string(),
+            issuer: "https://idp.example.org".to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: CLIENT_SECRET.to_string(),
+            scope: "openid".to_string(),
+        };
+        let key = SigningKey::generate().unwrap().0;
+        let now = Utc::now().timestamp();
+        let claims = json!({
+            "iss": provider.issuer,
+            "aud": CLIENT_ID,
+            "sub": "alice",
+            "exp": now + 300,
+            "nonce": "nonce",
+        });
+
+        let token = key.sign(&claims).unwrap();
+        assert!(decode_id_token(&token, &key.jwks(), &provider, "nonce", 0).is_ok());
+
+        let hs_token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(Algorithm::HS256),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(CLIENT_SECRET.as_bytes()),
+        )
+        .unwrap();
+        assert!(matches!(
+            decode_id_token(&hs_token, &key.jwks(), &provider, "nonce", 0),
+            Err(ApiError::Auth(AuthError::TokenInvalid))
+        ));
+
+        // Keys of another provider do not verify the token.
+        let other = SigningKey::generate().unwrap().0;
+        assert!(decode_id_token(&token, &other.jwks(), &provider, "nonce", 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_user_creates_and_links_users() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let provider = UpstreamProvider {
+            name: "mock".to_string(),
+            issuer: "https://idp.example.org".to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: CLIENT_SECRET.to_string(),
+            scope: "openid".to_string(),
+        };
+        let claims = UpstreamClaims {
+            sub: "alice".to_string(),
+            nonce: None,
+            email: Some("alice@example.org".to_string()),
+            email_verified: Some(true),
+            preferred_username: Some("alice".to_string()),
+        };
+
+        // A local user with the same name and email is never taken over.
+        let local = db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]).with_email("alice@example.org"))
+            .await
+            .unwrap();
+
+        let first = resolve_user(db, &provider, &claims, None, true)
+            .await
+            .unwrap();
+        assert!(first.created);
+        assert_ne!(first.user.id, local.id);
+        assert!(first.user.username.starts_with("alice-"));
+        assert_eq!(first.user.auth_source, AuthSource::Oidc);
+        assert!(first.user.email_verified);
+        assert_eq!(first.identity.user_id, first.user.id);
+
+        let second = resolve_user(db, &provider, &claims, None, true)
+            .await
+            .unwrap();
+        assert!(!second.created);
+        assert_eq!(second.user.id, first.user.id);
+        assert_eq!(second.identity.id, first.identity.id);
+
+        // Without registration, only users who already have an account can log in.
+        let second = resolve_user(db, &provider, &claims, None, false)
+            .await
+            .unwrap();
+        assert_eq!(second.user.id, first.user.id);
+        let carol = UpstreamClaims {
+            sub: "carol".to_string(),
+            ..claims.clone()
+        };
+        assert!(matches!(
+            resolve_user(db, &provider, &carol, None, false).await,
+            Err(ApiError::Auth(AuthError::RegistrationClosed))
+        ));
+
+        // The identity already belongs to another user.
+        assert!(matches!(
+            resolve_user(db, &provider, &claims, Some(local.id), true).await,
+            Err(ApiError::BadRequest(_))
+        ));
+
+        let bob = UpstreamClaims {
+            sub: "bob".to_string(),
+            email: None,
+            preferred_username: None,
+            ..claims.clone()
+        };
+        let linked = resolve_user(db, &provider, &bob, Some(local.id), true)
+            .await
+            .unwrap();
+        assert!(!linked.created);
+        assert_eq!(linked.user.id, local.id);
+        assert_eq!(
+            db.user_identities()
+                .find_by_user_id(local.id)
+                .await
+                .unwrap(),
+            vec![linked.identity.clone()]
+        );
+
+        // The last identity of a federated user cannot be removed, others can.
+        assert!(matches!(
+            unlink_identity(db, &first.user, first.identity.id).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(matches!(
+            unlink_identity(db, &local, first.identity.id).await,
+            Err(ApiError::NotFound(_))
+        ));
+        unlink_identity(db, &local, linked.identity.id)
+            .await
+            .unwrap();
+        assert!(db
+            .user_identities()
+            .find_by_user_id(local.id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Deleted users are replaced with a new one on the next login.
+        db.users().soft_delete(first.user.id).await.unwrap();
+        let third = resolve_user(db, &provider, &claims, None, true)
+            .await
+            .unwrap();
+        assert!(third.created);
+        assert_ne!(third.user.id, first.user.id);
+    }
+
+    #[test]
+    fn base_username_fallbacks() {
+        let claims = UpstreamClaims {
+            sub: "1234".to_string(),
+            nonce: None,
+            email: Some("jane@example.org".to_string()),
+            email_verified: None,
+            preferred_username: None,
+        };
+        assert_eq!(base_username("mock", &claims), "jane");
+
+        let claims = UpstreamClaims {
+            email: None,
+            ..claims.clone()
+        };
+        assert_eq!(base_username("mock", &claims), "mock-1234");
+    }
+}
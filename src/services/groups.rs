@@ -0,0 +1,325 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    error::ApiError,
+    models::{
+        group::{Group, GroupMember},
+        user::{Role, User},
+    },
+    services, ApiState,
+};
+
+const NAME_MAX_LEN: usize = 64;
+
+fn group_not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("group ({}) not found", id))
+}
+
+fn user_not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("user ({}) not found", id))
+}
+
+pub fn validate_name(name: &str) -> Result<(), ApiError> {
+    let valid = !name.is_empty()
+        && name.len() <= NAME_MAX_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+
+    if !valid {
+        return Err(ApiError::BadRequest(format!(
+            "group name ({}) is invalid",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Whether any of the roles granted by the old group is no longer granted by the new one.
+pub fn drops_roles(old: &Group, new: &Group) -> bool {
+    old.roles.iter().any(|role| !new.roles.contains(role))
+}
+
+pub async fn find_group(db: &dyn Database, id: Uuid) -> Result<Group, ApiError> {
+    db.groups()
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| group_not_found(id))
+}
+
+pub async fn create_group(
+    db: &dyn Database,
+    name: &str,
+    description: Option<&str>,
+    roles: &[Role],
+) -> Result<Group, ApiError> {
+    validate_name(name)?;
+    if db.groups().find_by_name(name).await?.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "group ({}) already exists",
+            name
+        )));
+    }
+
+    db.groups()
+        .create(Group::new(name, description, roles))
+        .await
+}
+
+/// Replaces the name, description and roles of the group, returning it before and after the update.
+pub async fn update_group(
+    db: &dyn Database,
+    id: Uuid,
+    name: &str,
+    description: Option<&str>,
+    roles: &[Role],
+) -> Result<(Group, Group), ApiError> {
+    validate_name(name)?;
+    let group = find_group(db, id).await?;
+    if name != group.name && db.groups().find_by_name(name).await?.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "group ({}) already exists",
+            name
+        )));
+    }
+
+    let updated = db
+        .groups()
+        .update(Group {
+            name: name.to_owned(),
+            description: description.map(str::to_owned),
+            roles: roles.to_vec(),
+            ..group.clone()
+        })
+        .await?
+        .ok_or_else(|| group_not_found(id))?;
+
+    Ok((group, updated))
+}
+
+/// Deletes the group, returning it along with the ids of its former members.
+pub async fn delete_group(db: &dyn Database, id: Uuid) -> Result<(Group, Vec<Uuid>), ApiError> {
+    let member_ids = member_ids(db, id).await?;
+    let group = db
+        .groups()
+        .delete_by_id(id)
+        .await?
+        .ok_or_else(|| group_not_found(id))?;
+
+    Ok((group, member_ids))
+}
+
+pub async fn member_ids(db: &dyn Database, group_id: Uuid) -> Result<Vec<Uuid>, ApiError> {
+    Ok(db
+        .group_members()
+        .find_by_group_id(group_id)
+        .await?
+        .into_iter()
+        .map(|member| member.user_id)
+        .collect())
+}
+
+pub async fn list_members(db: &dyn Database, group_id: Uuid) -> Result<Vec<User>, ApiError> {
+    find_group(db, group_id).await?;
+
+    let mut users = Vec::new();
+    for user_id in member_ids(db, group_id).await? {
+        if let Some(user) = db.users().find_by_id(user_id).await? {
+            users.push(user);
+        }
+    }
+
+    Ok(users)
+}
+
+/// Groups grant global roles, so only users of the global namespace can join them.
+pub async fn add_member(
+    db: &dyn Database,
+    group_id: Uuid,
+    user_id: Uuid,
+) -> Result<(Group, GroupMember), ApiError> {
+    let group = find_group(db, group_id).await?;
+    let user = db
+        .users()
+        .find_by_id(user_id)
+        .await?
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| user_not_found(user_id))?;
+    if user.org_id.is_some() {
+        return Err(ApiError::BadRequest(
+            "users of an organization cannot join groups".to_string(),
+        ));
+    }
+
+    let member = db
+        .group_members()
+        .create(GroupMember::new(group_id, user_id))
+        .await?
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "user ({}) already is a member of group ({})",
+                user_id, group.name
+            ))
+        })?;
+
+    Ok((group, member))
+}
+
+pub async fn remove_member(
+    db: &dyn Database,
+    group_id: Uuid,
+    user_id: Uuid,
+) -> Result<(Group, GroupMember), ApiError> {
+    let group = find_group(db, group_id).await?;
+    let member = db
+        .group_members()
+        .delete(group_id, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("member ({}) not found", user_id)))?;
+
+    Ok((group, member))
+}
+
+/// Union of the roles granted by all groups of the user.
+pub async fn group_roles(db: &dyn Database, user_id: Uuid) -> Result<Vec<Role>, ApiError> {
+    let mut roles = Vec::new();
+    for group in db.groups().find_by_member(user_id).await? {
+        for role in group.roles {
+            if !roles.contains(&role) {
+                roles.push(role);
+            }
+        }
+    }
+
+    Ok(roles)
+}
+
+/// Roles the user inherits from groups, served from the Redis cache when possible.
+pub async fn inherited_roles(state: &Arc<ApiState>, user_id: Uuid) -> Result<Vec<Role>, ApiError> {
+    if let Some(roles) = state.redis.group_roles().find(user_id).await? {
+        return Ok(roles);
+    }
+
+    let roles = group_roles(state.db.as_ref(), user_id).await?;
+    state
+        .redis
+        .group_roles()
+        .store(user_id, &roles, state.config.group_roles_cache_ttl)
+        .await?;
+
+    Ok(roles)
+}
+
+/// Propagates a change of the groups to their members. Roles gained take effect with the
+/// next token refresh, while the tokens of members who may have lost roles are revoked.
+pub async fn sync_members(
+    state: &Arc<ApiState>,
+    user_ids: &[Uuid],
+    roles_lost: bool,
+) -> Result<(), ApiError> {
+    state.redis.group_roles().invalidate(user_ids).await?;
+
+    if roles_lost {
+        for user_id in user_ids {
+            services::users::revoke_tokens(state, *user_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::mock::MockDatabase;
+
+    #[test]
+    fn validate_group_name() {
+        assert!(validate_name("ops").is_ok());
+        assert!(validate_name("release-managers_2.0").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("two words").is_err());
+        assert!(validate_name(&"a".repeat(NAME_MAX_LEN + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn inherit_roles_from_groups() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let user = db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]))
+            .await
+            .unwrap();
+
+        let ops = create_group(db, "ops", Some("Operations"), &[Role::Admin])
+            .await
+            .unwrap();
+        let staff = create_group(db, "staff", None, &[Role::User])
+            .await
+            .unwrap();
+        assert!(create_group(db, "ops", None, &[]).await.is_err());
+        assert!(group_roles(db, user.id).await.unwrap().is_empty());
+
+        add_member(db, ops.id, user.id).await.unwrap();
+        add_member(db, staff.id, user.id).await.unwrap();
+        assert!(add_member(db, ops.id, user.id).await.is_err());
+        assert!(add_member(db, ops.id, Uuid::new_v4()).await.is_err());
+
+        let roles = group_roles(db, user.id).await.unwrap();
+        assert_eq!(roles, vec![Role::Admin, Role::User]);
+        assert_eq!(user.effective_roles(&roles), vec![Role::User, Role::Admin]);
+
+        let members = list_members(db, ops.id).await.unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].id, user.id);
+
+        remove_member(db, ops.id, user.id).await.unwrap();
+        assert!(remove_member(db, ops.id, user.id).await.is_err());
+        assert_eq!(group_roles(db, user.id).await.unwrap(), vec![Role::User]);
+
+        let (_, ids) = delete_group(db, staff.id).await.unwrap();
+        assert_eq!(ids, vec![user.id]);
+        assert!(group_roles(db, user.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_group_roles() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let ops = create_group(db, "ops", None, &[Role::User, Role::Admin])
+            .await
+            .unwrap();
+        create_group(db, "dev", None, &[]).await.unwrap();
+
+        assert!(update_group(db, ops.id, "dev", None, &[]).await.is_err());
+
+        let (old, new) = update_group(db, ops.id, "ops", Some("On call"), &[Role::User])
+            .await
+            .unwrap();
+        assert_eq!(new.description.as_deref(), Some("On call"));
+        assert!(drops_roles(&old, &new));
+        assert!(!drops_roles(&new, &old));
+    }
+
+    #[tokio::test]
+    async fn org_users_cannot_join_groups() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let user = db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]).with_org(Uuid::new_v4()))
+            .await
+            .unwrap();
+        let ops = create_group(db, "ops", None, &[Role::Admin]).await.unwrap();
+
+        assert!(matches!(
+            add_member(db, ops.id, user.id).await,
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+}
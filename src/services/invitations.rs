@@ -0,0 +1,271 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{
+    config::RegistrationMode,
+    database::Database,
+    error::ApiError,
+    models::{
+        invitation::Invitation,
+        user::{Role, User},
+    },
+    routes::auth::AuthPayload,
+    services::{
+        self,
+        auth::{generate_secret, AuthError},
+        organizations::hash_invitation_token,
+    },
+    ApiState,
+};
+
+/// Creates an invitation, returning it along with its token. Accounts registered with the
+/// invitation get the given roles, or the user role if there are none.
+pub async fn create_invitation(
+    db: &dyn Database,
+    created_by: Uuid,
+    email: Option<&str>,
+    roles: &[Role],
+    expires_in: i64,
+) -> Result<(Invitation, String), ApiError> {
+    if expires_in <= 0 {
+        return Err(ApiError::BadRequest(
+            "expires_in has to be positive".to_string(),
+        ));
+    }
+
+    if email.is_some_and(|email| !email.contains('@')) {
+        return Err(ApiError::BadRequest(format!(
+            "email ({}) is invalid",
+            email.unwrap_or_default()
+        )));
+    }
+
+    let roles = if roles.is_empty() {
+        &[Role::User][..]
+    } else {
+        roles
+    };
+
+    let token = generate_secret();
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(expires_in);
+    let invitation = db
+        .invitations()
+        .create(Invitation::new(
+            &hash_invitation_token(&token),
+            email,
+            roles,
+            Some(created_by),
+            expires_at,
+        ))
+        .await?;
+
+    Ok((invitation, token))
+}
+
+pub async fn revoke_invitation(db: &dyn Database, id: Uuid) -> Result<Invitation, ApiError> {
+    db.invitations()
+        .delete_by_id(id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("invitation ({}) not found", id)))
+}
+
+/// Registers the user with the invitation. The invitation can be used only once, its email
+/// takes precedence over the one in the payload.
+pub async fn redeem(
+    db: &dyn Database,
+    token: &str,
+    payload: &AuthPayload,
+) -> Result<(User, Invitation), ApiError> {
+    let invitation = db
+        .invitations()
+        .find_by_token_hash(&hash_invitation_token(token))
+        .await?
+        .filter(|invitation| !invitation.is_used() && !invitation.is_expired())
+        .ok_or(AuthError::InvitationInvalid)?;
+
+    let mut payload = payload.clone();
+    if let Some(email) = invitation.email.as_deref() {
+        if payload
+            .email
+            .as_deref()
+            .is_some_and(|e| !e.eq_ignore_ascii_case(email))
+        {
+            return Err(ApiError::BadRequest(
+                "email does not match the invitation".to_string(),
+            ));
+        }
+        payload.email = Some(email.to_owned());
+    }
+
+    let user = services::users::new_user(db, &payload, &invitation.roles).await?;
+    let user_id = user.id;
+
+    // The invitation is claimed before the user is created, so that concurrent requests
+    // cannot both use it. It is released again if the user cannot be created.
+    let invitation = db
+        .invitations()
+        .mark_used(invitation.id, user_id)
+        .await?
+        .ok_or(AuthError::InvitationInvalid)?;
+    let user = match db.users().create(user).await {
+        Ok(user) => user,
+        Err(e) => {
+            if let Err(release_err) = db.invitations().release(invitation.id, user_id).await {
+                tracing::error!(
+                    "Failed to release invitation ({}): {}",
+                    invitation.id,
+                    release_err
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    Ok((user, invitation))
+}
+
+/// Registers the user according to the configured registration mode, returning the
+/// invitation used, if any.
+pub async fn register(
+    state: &Arc<ApiState>,
+    payload: AuthPayload,
+) -> Result<(User, Option<Invitation>), ApiError> {
+    match (
+        state.config.registration_mode,
+        payload.invitation.as_deref(),
+    ) {
+        (RegistrationMode::Closed, _) => Err(AuthError::RegistrationClosed.into()),
+        (RegistrationMode::InviteOnly, None) => Err(AuthError::InvitationInvalid.into()),
+        (_, Some(token)) => {
+            let (user, invitation) = redeem(state.db.as_ref(), token, &payload).await?;
+            Ok((user, Some(invitation)))
+        }
+        (RegistrationMode::Open, None) => {
+            let user = services::users::create_user(state, payload, &[Role::User]).await?;
+            Ok((user, None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::mock::MockDatabase;
+
+    fn payload(username: &str, email: Option<&str>) -> AuthPayload {
+        AuthPayload {
+            username: username.to_owned(),
+            password: "password".to_owned(),
+            email: email.map(str::to_owned),
+            organization: None,
+            invitation: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn register_with_invitation() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let admin = Uuid::new_v4();
+
+        let (invitation, token) = create_invitation(db, admin, None, &[Role::Admin], 60)
+            .await
+            .unwrap();
+        assert_eq!(invitation.roles, vec![Role::Admin]);
+        assert_ne!(invitation.token_hash, token);
+
+        assert!(matches!(
+            redeem(db, "unknown", &payload("alice", None)).await,
+            Err(ApiError::Auth(AuthError::InvitationInvalid))
+        ));
+
+        let (user, used) = redeem(db, &token, &payload("alice", None)).await.unwrap();
+        assert_eq!(user.roles, vec![Role::Admin]);
+        assert_eq!(used.used_by, Some(user.id));
+        assert!(used.is_used());
+
+        assert!(matches!(
+            redeem(db, &token, &payload("bob", None)).await,
+            Err(ApiError::Auth(AuthError::InvitationInvalid))
+        ));
+    }
+
+    #[tokio::test]
+    async fn invitation_email_and_expiry() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        let admin = Uuid::new_v4();
+
+        assert!(create_invitation(db, admin, None, &[], 0).await.is_err());
+        assert!(create_invitation(db, admin, Some("bob"), &[], 60)
+            .await
+            .is_err());
+
+        let (invitation, token) = create_invitation(db, admin, Some("bob@example.com"), &[], 60)
+            .await
+            .unwrap();
+        assert_eq!(invitation.roles, vec![Role::User]);
+
+        assert!(matches!(
+            redeem(db, &token, &payload("bob", Some("eve@example.com"))).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        let (user, _) = redeem(db, &token, &payload("bob", None)).await.unwrap();
+        assert_eq!(user.email.as_deref(), Some("bob@example.com"));
+
+        let expired = Invitation::new(
+            &hash_invitation_token("expired"),
+            None,
+            &[Role::User],
+            Some(admin),
+            chrono::Utc::now() - chrono::Duration::seconds(1),
+        );
+        db.invitations().create(expired).await.unwrap();
+        assert!(matches!(
+            redeem(db, "expired", &payload("carol", None)).await,
+            Err(ApiError::Auth(AuthError::InvitationInvalid))
+        ));
+    }
+
+    #[tokio::test]
+    async fn taken_username_keeps_invitation() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+        db.users()
+            .create(User::new("alice", "hash", &[Role::User]))
+            .await
+            .unwrap();
+        let (invitation, token) = create_invitation(db, Uuid::new_v4(), None, &[], 60)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            redeem(db, &token, &payload("alice", None)).await,
+            Err(ApiError::Auth(AuthError::UsernameAlreadyTaken))
+        ));
+
+        // Claims of users who could not be created are released, but only by the claiming user.
+        let claimed_by = Uuid::new_v4();
+        db.invitations()
+            .mark_used(invitation.id, claimed_by)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(db
+            .invitations()
+            .release(invitation.id, Uuid::new_v4())
+            .await
+            .unwrap()
+            .is_none());
+        let released = db
+            .invitations()
+            .release(invitation.id, claimed_by)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!released.is_used());
+
+        assert!(redeem(db, &token, &payload("bob", None)).await.is_ok());
+    }
+}
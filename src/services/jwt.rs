@@ -1,62 +1,323 @@
 use std::sync::Arc;
 
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    config::Config,
     error::ApiError,
     models::{
+        client::Client,
         refresh_token::RefreshToken,
         user::{Role, User},
     },
-    services::{self, auth::AuthError},
+    services::{self, auth::AuthError, oidc::SigningKey},
     ApiState,
 };
 
+/// Party acting on behalf of the subject of an exchanged token (RFC 8693, section 4.1).
+/// Nested actors describe the chain of earlier delegations.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Actor {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
+}
+
+/// Confirmation claim binding the token to the key of a DPoP proof (RFC 9449, section 6).
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Confirmation {
+    pub jkt: String,
+}
+
+/// Distinguishes access and refresh tokens, so that one can never be used as the other,
+/// even if both are signed with the same secret.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    Access,
+    Refresh,
+}
+
+impl TokenType {
+    pub fn secret(self, config: &Config) -> &str {
+        match self {
+            TokenType::Access => &config.jwt_access_secret,
+            TokenType::Refresh => &config.jwt_refresh_secret,
+        }
+    }
+
+    pub fn lifetime(self, config: &Config) -> i64 {
+        match self {
+            TokenType::Access => config.jwt_access_expiration,
+            TokenType::Refresh => config.jwt_refresh_expiration,
+        }
+    }
+}
+
+/// Serde helpers for the `roles` claim. Tokens issued before roles were typed carry
+/// them as a comma separated string, which is still accepted when decoding.
+mod roles {
+    use serde::{Deserialize, Deserializer};
+
+    use crate::models::user::Role;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RolesClaim {
+        List(Vec<Role>),
+        Legacy(String),
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Role>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match RolesClaim::deserialize(deserializer)? {
+            RolesClaim::List(roles) => Ok(roles),
+            RolesClaim::Legacy(roles) => Role::to_vec(&roles).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Serde helpers for the `scope` claim, which is a space delimited string on the wire
+/// (RFC 8693, section 4.2). Arrays are accepted as well when decoding.
+mod scope {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ScopeClaim {
+        List(Vec<String>),
+        Delimited(String),
+    }
+
+    pub fn serialize<S>(scope: &Option<Vec<String>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match scope {
+            Some(scope) => serializer.serialize_str(&scope.join(" ")),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(
+            Option::<ScopeClaim>::deserialize(deserializer)?.map(|scope| match scope {
+                ScopeClaim::List(scope) => scope,
+                ScopeClaim::Delimited(scope) => {
+                    scope.split_whitespace().map(str::to_owned).collect()
+                }
+            }),
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Claims {
+    pub iss: String,
     pub sub: Uuid,
+    pub aud: String,
     pub exp: i64,
+    pub nbf: i64,
     pub iat: i64,
     pub jti: Uuid,
+    pub typ: TokenType,
     pub username: String,
-    pub roles: String,
-    pub admin: bool,
+    #[serde(deserialize_with = "roles::deserialize")]
+    pub roles: Vec<Role>,
+    /// Token version of the user at the time of issuing. Tokens with an outdated version
+    /// are rejected, which revokes all tokens of the user at once. Absent in client tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ver: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "scope")]
+    pub scope: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+    /// Organization the user logged in to, absent for logins to the global namespace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<Uuid>,
 }
 
 impl Claims {
-    pub fn new(
-        sub: Uuid,
-        exp: i64,
-        iat: i64,
-        jti: Uuid,
-        username: String,
-        roles: String,
-        admin: bool,
-    ) -> Self {
+    pub fn from_user(user: &User, typ: TokenType, config: &Config, now: i64) -> Self {
         Self {
-            sub,
-            exp,
-            iat,
-            jti,
-            username,
-            roles,
-            admin,
+            iss: config.issuer(),
+            sub: user.id,
+            aud: config.jwt_audience.to_owned(),
+            exp: now + typ.lifetime(config),
+            nbf: now,
+            iat: now,
+            jti: Uuid::new_v4(),
+            typ,
+            username: user.username.to_owned(),
+            roles: user.roles.clone(),
+            ver: Some(user.token_version),
+            client_id: None,
+            scope: None,
+            act: None,
+            cnf: None,
+            org_id: None,
         }
     }
 
-    pub fn from_user(user: &User, exp: i64, iat: i64) -> Self {
+    pub fn from_client(client: &Client, scope: &[String], config: &Config, now: i64) -> Self {
         Self {
-            sub: user.id,
-            exp,
-            iat,
+            iss: config.issuer(),
+            sub: client.id,
+            aud: config.jwt_audience.to_owned(),
+            exp: now + TokenType::Access.lifetime(config),
+            nbf: now,
+            iat: now,
             jti: Uuid::new_v4(),
-            username: user.username.to_owned(),
-            roles: user.roles.to_owned(),
-            admin: user.has_role(Role::Admin),
+            typ: TokenType::Access,
+            username: client.client_id.to_owned(),
+            roles: Vec::new(),
+            ver: None,
+            client_id: Some(client.client_id.to_owned()),
+            scope: Some(scope.to_vec()),
+            act: None,
+            cnf: None,
+            org_id: None,
+        }
+    }
+
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.contains(&role)
+    }
+
+    /// Whether the token is used by another party on behalf of the subject, e.g. by an admin
+    /// impersonating the user.
+    pub fn is_delegated(&self) -> bool {
+        self.act.is_some()
+    }
+
+    /// Fails unless the user uses the token themselves, i.e. it was issued neither to an
+    /// OAuth client nor to an admin impersonating the user. Required to change the account.
+    pub fn ensure_interactive_user(&self) -> Result<(), AuthError> {
+        if self.client_id.is_some() || self.is_delegated() {
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(())
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_ref()
+            .is_some_and(|scopes| scopes.iter().any(|s| s == scope))
+    }
+
+    /// Granted scopes in the space delimited form used in OAuth responses.
+    pub fn scope_string(&self) -> Option<String> {
+        self.scope.as_ref().map(|scope| scope.join(" "))
+    }
+}
+
+/// Additional claims carried by both tokens of a pair, e.g. when the pair is
+/// issued to an OAuth client on behalf of the user.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TokenContext {
+    pub client_id: Option<String>,
+    pub scope: Option<Vec<String>>,
+    pub jkt: Option<String>,
+    pub org_id: Option<Uuid>,
+    /// Roles the user inherits from groups, added to the own roles of the user.
+    pub group_roles: Vec<Role>,
+}
+
+impl TokenContext {
+    pub fn for_client(client_id: &str, scope: &str) -> Self {
+        Self {
+            client_id: Some(client_id.to_owned()),
+            scope: Some(scope.split_whitespace().map(str::to_owned).collect()),
+            jkt: None,
+            org_id: None,
+            group_roles: Vec::new(),
+        }
+    }
+
+    /// Binds the tokens to the thumbprint of the DPoP proof key, if any.
+    pub fn with_jkt(mut self, jkt: Option<&str>) -> Self {
+        self.jkt = jkt.map(str::to_owned);
+        self
+    }
+
+    /// Scopes the tokens to the organization the user logged in to.
+    pub fn with_org(mut self, org_id: Option<Uuid>) -> Self {
+        self.org_id = org_id;
+        self
+    }
+
+    pub fn with_group_roles(mut self, group_roles: Vec<Role>) -> Self {
+        self.group_roles = group_roles;
+        self
+    }
+
+    fn apply(&self, claims: Claims) -> Claims {
+        Claims {
+            client_id: self.client_id.clone(),
+            scope: self.scope.clone(),
+            cnf: self.jkt.clone().map(|jkt| Confirmation { jkt }),
+            org_id: self.org_id,
+            ..claims
+        }
+    }
+}
+
+/// Expected values of the registered claims, checked when a token is decoded.
+#[derive(Clone)]
+pub struct TokenValidation<'a> {
+    pub typ: TokenType,
+    pub secret: &'a str,
+    /// Verifies the ES256 signature with the OIDC signing key instead of the secret.
+    pub signing_key: Option<&'a SigningKey>,
+    pub issuer: String,
+    pub audience: Option<&'a str>,
+    pub leeway: u64,
+    pub legacy_until: i64,
+}
+
+impl<'a> TokenValidation<'a> {
+    pub fn new(typ: TokenType, config: &'a Config) -> Self {
+        Self {
+            typ,
+            secret: typ.secret(config),
+            signing_key: None,
+            issuer: config.issuer(),
+            audience: Some(&config.jwt_audience),
+            leeway: config.jwt_leeway,
+            legacy_until: config.jwt_legacy_until,
+        }
+    }
+
+    /// Validates tokens the way they are issued by the server, i.e. access tokens are
+    /// verified with the OIDC signing key if `jwt_access_es256` is set.
+    pub fn for_state(typ: TokenType, state: &'a ApiState) -> Self {
+        let validation = Self::new(typ, &state.config);
+        match typ {
+            TokenType::Access if state.config.jwt_access_es256 => Self {
+                signing_key: Some(&state.signing_key),
+                ..validation
+            },
+            _ => validation,
         }
     }
+
+    /// Accepts tokens issued for any audience, e.g. tokens exchanged for downstream services.
+    pub fn any_audience(mut self) -> Self {
+        self.audience = None;
+        self
+    }
 }
 
 pub fn generate_token(claims: &Claims, secret: &str) -> Result<String, ApiError> {
@@ -67,47 +328,145 @@ pub fn generate_token(claims: &Claims, secret: &str) -> Result<String, ApiError>
     )?)
 }
 
-pub fn decode_token(token: &str, secret: &str) -> Result<Claims, ApiError> {
-    let token_data = jsonwebtoken::decode(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
-    )
-    .map_err(|err| match err.kind() {
-        jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired,
-        _ => AuthError::TokenInvalid,
-    })?;
+/// Signs the access token with the OIDC signing key if `jwt_access_es256` is set, so it can
+/// be verified with the published public key, and with the access token secret otherwise.
+pub fn generate_access_token(
+    claims: &Claims,
+    config: &Config,
+    signing_key: &SigningKey,
+) -> Result<String, ApiError> {
+    if config.jwt_access_es256 {
+        return signing_key.sign(claims);
+    }
+
+    generate_token(claims, &config.jwt_access_secret)
+}
+
+/// Claims of tokens issued before the registered claims and the token type were introduced.
+/// Unknown fields are denied, so that current tokens are never decoded as legacy ones.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LegacyClaims {
+    sub: Uuid,
+    exp: i64,
+    iat: i64,
+    jti: Uuid,
+    username: String,
+    #[serde(deserialize_with = "roles::deserialize")]
+    roles: Vec<Role>,
+    #[serde(rename = "admin")]
+    _admin: bool,
+}
+
+impl LegacyClaims {
+    /// Fills in the claims legacy tokens lack with the expected ones. Token versions start
+    /// at 0, so revoking the tokens of the user revokes their legacy tokens as well.
+    fn upgrade(self, expected: &TokenValidation) -> Claims {
+        Claims {
+            iss: expected.issuer.clone(),
+            sub: self.sub,
+            aud: expected.audience.unwrap_or_default().to_owned(),
+            exp: self.exp,
+            nbf: self.iat,
+            iat: self.iat,
+            jti: self.jti,
+            typ: expected.typ,
+            username: self.username,
+            roles: self.roles,
+            ver: Some(0),
+            client_id: None,
+            scope: None,
+            act: None,
+            cnf: None,
+            org_id: None,
+        }
+    }
+}
+
+fn token_error(err: jsonwebtoken::errors::Error) -> ApiError {
+    match err.kind() {
+        jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired.into(),
+        _ => AuthError::TokenInvalid.into(),
+    }
+}
+
+/// Decodes a token issued by an older version, which is signed with the secret of its type
+/// but carries none of the registered claims except `exp`.
+fn decode_legacy_token(
+    token: &str,
+    key: &DecodingKey,
+    expected: &TokenValidation,
+) -> Result<Claims, ApiError> {
+    let mut validation = Validation::default();
+    validation.leeway = expected.leeway;
+    validation.validate_aud = false;
+
+    let token_data =
+        jsonwebtoken::decode::<LegacyClaims>(token, key, &validation).map_err(token_error)?;
+    Ok(token_data.claims.upgrade(expected))
+}
+
+pub fn decode_token(token: &str, expected: &TokenValidation) -> Result<Claims, ApiError> {
+    let (key, algorithm) = match expected.signing_key {
+        Some(signing_key) => (signing_key.decoding_key()?, Algorithm::ES256),
+        None => (
+            DecodingKey::from_secret(expected.secret.as_bytes()),
+            Algorithm::HS256,
+        ),
+    };
+
+    let mut validation = Validation::new(algorithm);
+    validation.leeway = expected.leeway;
+    validation.validate_nbf = true;
+    validation.set_issuer(&[&expected.issuer]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    match expected.audience {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+
+    let claims = match jsonwebtoken::decode::<Claims>(token, &key, &validation) {
+        Ok(token_data) => token_data.claims,
+        // Legacy tokens lack claims, which fails deserializing them before validation.
+        Err(err)
+            if matches!(
+                err.kind(),
+                jsonwebtoken::errors::ErrorKind::Json(_)
+                    | jsonwebtoken::errors::ErrorKind::MissingRequiredClaim(_)
+            ) && chrono::Utc::now().timestamp() < expected.legacy_until =>
+        {
+            return decode_legacy_token(token, &key, expected);
+        }
+        Err(err) => return Err(token_error(err)),
+    };
+
+    if claims.typ != expected.typ {
+        return Err(AuthError::TokenInvalid.into());
+    }
 
-    Ok(token_data.claims)
+    Ok(claims)
 }
 
 pub fn pairs_from_user(
     user: &User,
-    aexp: i64,
-    rexp: i64,
-    asecret: &str,
-    rsecret: &str,
+    ctx: &TokenContext,
+    config: &Config,
+    signing_key: &SigningKey,
 ) -> Result<(String, String, RefreshToken), ApiError> {
-    let now = chrono::Utc::now();
+    let now = chrono::Utc::now().timestamp();
+    let roles = user.effective_roles(&ctx.group_roles);
 
-    let access_claims = Claims::from_user(
-        user,
-        now.checked_add_signed(chrono::Duration::seconds(aexp))
-            .unwrap()
-            .timestamp(),
-        now.timestamp(),
-    );
+    let access_claims = ctx.apply(Claims {
+        roles: roles.clone(),
+        ..Claims::from_user(user, TokenType::Access, config, now)
+    });
+    let refresh_claims = ctx.apply(Claims {
+        roles,
+        ..Claims::from_user(user, TokenType::Refresh, config, now)
+    });
 
-    let refresh_claims = Claims::from_user(
-        user,
-        now.checked_add_signed(chrono::Duration::seconds(rexp))
-            .unwrap()
-            .timestamp(),
-        now.timestamp(),
-    );
-
-    let access_token = generate_token(&access_claims, asecret)?;
-    let refresh_token = generate_token(&refresh_claims, rsecret)?;
+    let access_token = generate_access_token(&access_claims, config, signing_key)?;
+    let refresh_token = generate_token(&refresh_claims, &config.jwt_refresh_secret)?;
 
     let token_model = RefreshToken::new(
         refresh_claims.jti,
@@ -151,18 +510,37 @@ mod tests {
     const ACCES_EXP_MIN: chrono::TimeDelta = chrono::Duration::minutes(15);
     const REFRE_EXP_MIN: chrono::TimeDelta = chrono::Duration::days(30);
 
-    #[test]
-    fn generate_jwt_token() {
-        let now = chrono::Utc::now();
-        let claims = Claims {
+    fn test_claims(config: &Config, typ: TokenType, exp: i64, iat: i64) -> Claims {
+        Claims {
+            iss: config.issuer(),
             sub: Uuid::new_v4(),
-            exp: now.checked_add_signed(ACCES_EXP_MIN).unwrap().timestamp(),
-            iat: now.timestamp(),
+            aud: config.jwt_audience.to_owned(),
+            exp,
+            nbf: iat,
+            iat,
             jti: Uuid::new_v4(),
+            typ,
             username: "test_user".to_owned(),
-            roles: "user,admin".to_owned(),
-            admin: true,
-        };
+            roles: vec![Role::User, Role::Admin],
+            ver: Some(0),
+            client_id: None,
+            scope: None,
+            act: None,
+            cnf: None,
+            org_id: None,
+        }
+    }
+
+    #[test]
+    fn generate_jwt_token() {
+        let config = Config::default();
+        let now = chrono::Utc::now();
+        let claims = test_claims(
+            &config,
+            TokenType::Access,
+            now.checked_add_signed(ACCES_EXP_MIN).unwrap().timestamp(),
+            now.timestamp(),
+        );
 
         let token = generate_token(&claims, "test_secret");
         assert!(token.is_ok());
@@ -170,29 +548,32 @@ mod tests {
 
     #[test]
     fn decode_jwt_token_correct() {
+        let config = Config::default();
         let now = chrono::Utc::now();
-        let claims = Claims {
-            sub: Uuid::new_v4(),
-            exp: now.checked_add_signed(REFRE_EXP_MIN).unwrap().timestamp(),
-            iat: now.timestamp(),
-            jti: Uuid::new_v4(),
-            username: "test_user".to_owned(),
-            roles: "user,admin".to_owned(),
-            admin: true,
-        };
+        let claims = test_claims(
+            &config,
+            TokenType::Refresh,
+            now.checked_add_signed(REFRE_EXP_MIN).unwrap().timestamp(),
+            now.timestamp(),
+        );
 
         let token = generate_token(&claims, "another_test_secret");
         assert!(token.is_ok());
 
-        let result = decode_token(&token.unwrap(), "another_test_secret");
+        let validation = TokenValidation {
+            secret: "another_test_secret",
+            ..TokenValidation::new(TokenType::Refresh, &config)
+        };
+        let result = decode_token(&token.unwrap(), &validation);
         assert!(result.is_ok());
         assert_eq!(claims, result.unwrap());
     }
 
     #[test]
     fn decode_invalid_jwt_token_failed() {
+        let config = Config::default();
         let token = "invalid_token";
-        let result = decode_token(&token, "bad_jwt_secret");
+        let result = decode_token(token, &TokenValidation::new(TokenType::Access, &config));
         assert!(result.is_err());
         assert!(matches!(
             result,
@@ -202,25 +583,269 @@ mod tests {
 
     #[test]
     fn decode_expired_jwt_token_failed() {
+        let config = Config::default();
         let now = chrono::Utc::now();
-        let claims = Claims {
-            sub: Uuid::new_v4(),
-            exp: now.checked_sub_signed(REFRE_EXP_MIN).unwrap().timestamp(),
-            iat: now.timestamp(),
-            jti: Uuid::new_v4(),
-            username: "test_user".to_owned(),
-            roles: "user,admin".to_owned(),
-            admin: true,
-        };
+        let claims = test_claims(
+            &config,
+            TokenType::Refresh,
+            now.checked_sub_signed(REFRE_EXP_MIN).unwrap().timestamp(),
+            now.timestamp(),
+        );
 
-        let token = generate_token(&claims, "test_secret");
+        let token = generate_token(&claims, &config.jwt_refresh_secret);
         assert!(token.is_ok());
 
-        let result = decode_token(&token.unwrap(), "test_secret");
+        let result = decode_token(
+            &token.unwrap(),
+            &TokenValidation::new(TokenType::Refresh, &config),
+        );
         assert!(result.is_err());
         assert!(matches!(
             result,
             Err(ApiError::Auth(AuthError::TokenExpired))
         ));
     }
+
+    #[test]
+    fn decode_access_token_as_refresh_failed() {
+        let config = Config {
+            jwt_refresh_secret: "shared_secret".to_owned(),
+            jwt_access_secret: "shared_secret".to_owned(),
+            ..Config::default()
+        };
+        let user = User::new("test_user", "test_hash", &[Role::User]);
+        let (signing_key, _) = SigningKey::generate().unwrap();
+        let (access_token, _, _) =
+            pairs_from_user(&user, &TokenContext::default(), &config, &signing_key).unwrap();
+
+        let result = decode_token(
+            &access_token,
+            &TokenValidation::new(TokenType::Refresh, &config),
+        );
+        assert!(matches!(
+            result,
+            Err(ApiError::Auth(AuthError::TokenInvalid))
+        ));
+    }
+
+    #[test]
+    fn decode_jwt_token_registered_claims() {
+        let config = Config::default();
+        let now = chrono::Utc::now().timestamp();
+        let validation = TokenValidation::new(TokenType::Access, &config);
+
+        let claims = Claims {
+            aud: "another_service".to_owned(),
+            ..test_claims(&config, TokenType::Access, now + 60, now)
+        };
+        let token = generate_token(&claims, &config.jwt_access_secret).unwrap();
+        assert!(decode_token(&token, &validation).is_err());
+        assert!(decode_token(&token, &validation.clone().any_audience()).is_ok());
+
+        let claims = Claims {
+            iss: "https://issuer.test".to_owned(),
+            ..test_claims(&config, TokenType::Access, now + 60, now)
+        };
+        let token = generate_token(&claims, &config.jwt_access_secret).unwrap();
+        assert!(decode_token(&token, &validation).is_err());
+
+        // Not yet valid, but within the configured leeway.
+        let skewed = now + config.jwt_leeway as i64 / 2;
+        let claims = test_claims(&config, TokenType::Access, now + 60, skewed);
+        let token = generate_token(&claims, &config.jwt_access_secret).unwrap();
+        assert!(decode_token(&token, &validation).is_ok());
+
+        let claims = test_claims(&config, TokenType::Access, now + 600, now + 300);
+        let token = generate_token(&claims, &config.jwt_access_secret).unwrap();
+        assert!(decode_token(&token, &validation).is_err());
+    }
+
+    #[test]
+    fn pairs_from_user_with_context() {
+        let config = Config::default();
+        let user = User::new("test_user", "test_hash", &[Role::User]);
+        let ctx = TokenContext::for_client("test_client", "read write");
+
+        let (signing_key, _) = SigningKey::generate().unwrap();
+
+        let pair = pairs_from_user(&user, &ctx, &config, &signing_key);
+        assert!(pair.is_ok());
+
+        let (access_token, refresh_token, token_model) = pair.unwrap();
+        let access_claims = decode_token(
+            &access_token,
+            &TokenValidation::new(TokenType::Access, &config),
+        )
+        .unwrap();
+        let refresh_claims = decode_token(
+            &refresh_token,
+            &TokenValidation::new(TokenType::Refresh, &config),
+        )
+        .unwrap();
+
+        assert_eq!(access_claims.client_id.as_deref(), Some("test_client"));
+        assert_eq!(access_claims.scope_string().as_deref(), Some("read write"));
+        assert!(access_claims.has_scope("write"));
+        assert_eq!(refresh_claims.client_id, access_claims.client_id);
+        assert_eq!(refresh_claims.jti, token_model.jti);
+    }
+
+    #[test]
+    fn pairs_from_user_with_group_roles() {
+        let config = Config::default();
+        let user = User::new("test_user", "test_hash", &[Role::User]);
+        let ctx = TokenContext::default().with_group_roles(vec![Role::Admin]);
+
+        let (signing_key, _) = SigningKey::generate().unwrap();
+        let (access_token, refresh_token, _) =
+            pairs_from_user(&user, &ctx, &config, &signing_key).unwrap();
+        let access_claims = decode_token(
+            &access_token,
+            &TokenValidation::new(TokenType::Access, &config),
+        )
+        .unwrap();
+        let refresh_claims = decode_token(
+            &refresh_token,
+            &TokenValidation::new(TokenType::Refresh, &config),
+        )
+        .unwrap();
+
+        assert_eq!(access_claims.roles, vec![Role::User, Role::Admin]);
+        assert_eq!(refresh_claims.roles, access_claims.roles);
+    }
+
+    #[test]
+    fn pairs_from_user_signed_with_es256() {
+        let config = Config {
+            jwt_access_es256: true,
+            ..Config::default()
+        };
+        let user = User::new("test_user", "test_hash", &[Role::User]);
+        let (signing_key, _) = SigningKey::generate().unwrap();
+        let (access_token, refresh_token, _) =
+            pairs_from_user(&user, &TokenContext::default(), &config, &signing_key).unwrap();
+
+        let header = jsonwebtoken::decode_header(&access_token).unwrap();
+        assert_eq!(header.alg, Algorithm::ES256);
+        assert_eq!(header.kid.as_deref(), signing_key.kid());
+
+        let validation = TokenValidation {
+            signing_key: Some(&signing_key),
+            ..TokenValidation::new(TokenType::Access, &config)
+        };
+        assert_eq!(
+            decode_token(&access_token, &validation).unwrap().sub,
+            user.id
+        );
+        assert!(decode_token(
+            &access_token,
+            &TokenValidation::new(TokenType::Access, &config)
+        )
+        .is_err());
+
+        // Tokens signed with the secret are not accepted in place of signed ones.
+        let claims = Claims::from_user(
+            &user,
+            TokenType::Access,
+            &config,
+            chrono::Utc::now().timestamp(),
+        );
+        let hmac_token = generate_token(&claims, &config.jwt_access_secret).unwrap();
+        assert!(decode_token(&hmac_token, &validation).is_err());
+        let (other_key, _) = SigningKey::generate().unwrap();
+        assert!(decode_token(&other_key.sign(&claims).unwrap(), &validation).is_err());
+
+        // Refresh tokens are never handed to services, they are still signed with the secret.
+        let refresh_validation = TokenValidation::new(TokenType::Refresh, &config);
+        assert!(decode_token(&refresh_token, &refresh_validation).is_ok());
+    }
+
+    #[test]
+    fn decode_exchanged_jwt_token() {
+        let config = Config::default();
+        let user = User::new("test_user", "test_hash", &[Role::User]);
+        let now = chrono::Utc::now();
+        let claims = Claims {
+            aud: "billing".to_owned(),
+            act: Some(Actor {
+                sub: "gateway".to_owned(),
+                act: None,
+            }),
+            ..Claims::from_user(&user, TokenType::Access, &config, now.timestamp())
+        };
+
+        let token = generate_token(&claims, &config.jwt_access_secret).unwrap();
+        let validation = TokenValidation::new(TokenType::Access, &config).any_audience();
+        let result = decode_token(&token, &validation);
+        assert_eq!(result.unwrap(), claims);
+    }
+
+    #[test]
+    fn decode_legacy_jwt_token() {
+        let now = chrono::Utc::now().timestamp();
+        let config = Config {
+            jwt_legacy_until: now + 3600,
+            ..Config::default()
+        };
+        let validation = TokenValidation::new(TokenType::Access, &config);
+        let encode = |claims: &serde_json::Value| {
+            jsonwebtoken::encode(
+                &Header::default(),
+                claims,
+                &EncodingKey::from_secret(config.jwt_access_secret.as_bytes()),
+            )
+            .unwrap()
+        };
+
+        // Claims of tokens issued before the registered claims were introduced.
+        let sub = Uuid::new_v4();
+        let mut claims = serde_json::json!({
+            "sub": sub,
+            "exp": now + 60,
+            "iat": now,
+            "jti": Uuid::new_v4(),
+            "username": "test_user",
+            "roles": "user,admin",
+            "admin": true,
+        });
+
+        let decoded = decode_token(&encode(&claims), &validation).unwrap();
+        assert_eq!(decoded.sub, sub);
+        assert_eq!(decoded.typ, TokenType::Access);
+        assert_eq!(decoded.iss, config.issuer());
+        assert_eq!(decoded.roles, vec![Role::User, Role::Admin]);
+        assert_eq!(decoded.ver, Some(0));
+
+        let after_cutoff = TokenValidation {
+            legacy_until: now,
+            ..validation.clone()
+        };
+        assert!(matches!(
+            decode_token(&encode(&claims), &after_cutoff),
+            Err(ApiError::Auth(AuthError::TokenInvalid))
+        ));
+
+        claims["roles"] = serde_json::json!("user,superuser");
+        assert!(matches!(
+            decode_token(&encode(&claims), &validation),
+            Err(ApiError::Auth(AuthError::TokenInvalid))
+        ));
+
+        claims["roles"] = serde_json::json!("user");
+        claims["exp"] = serde_json::json!(now - 120);
+        assert!(matches!(
+            decode_token(&encode(&claims), &validation),
+            Err(ApiError::Auth(AuthError::TokenExpired))
+        ));
+
+        // Current tokens lacking a registered claim are not mistaken for legacy ones.
+        let mut claims =
+            serde_json::to_value(test_claims(&config, TokenType::Refresh, now + 60, now)).unwrap();
+        claims.as_object_mut().unwrap().remove("nbf");
+        claims["admin"] = serde_json::json!(true);
+        assert!(matches!(
+            decode_token(&encode(&claims), &validation),
+            Err(ApiError::Auth(AuthError::TokenInvalid))
+        ));
+    }
 }
@@ -1,3 +1,15 @@
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod authz;
+pub mod dpop;
+pub mod federation;
+pub mod groups;
+pub mod invitations;
 pub mod jwt;
+pub mod oauth;
+pub mod oidc;
+pub mod organizations;
+pub mod scim;
 pub mod users;
+pub mod webhooks;
@@ -0,0 +1,1701 @@
+use std::{str::FromStr, sync::Arc};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::http::{header, HeaderMap, StatusCode};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    error::ApiError,
+    models::{
+        authorization_code::AuthorizationCode,
+        client::Client,
+        device_authorization::{DeviceAuthorization, DeviceAuthorizationStatus, DevicePolling},
+        user::User,
+    },
+    services::{
+        self,
+        auth::{generate_secret, hash_string, verify_hash, AuthError},
+        jwt::{Actor, Claims, Confirmation, TokenContext, TokenType, TokenValidation},
+        oidc,
+    },
+    ApiState,
+};
+
+pub mod grant_type {
+    pub const AUTHORIZATION_CODE: &str = "authorization_code";
+    pub const CLIENT_CREDENTIALS: &str = "client_credentials";
+    pub const DEVICE_CODE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+    pub const REFRESH_TOKEN: &str = "refresh_token";
+    pub const TOKEN_EXCHANGE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+    pub const SUPPORTED: &[&str] = &[
+        AUTHORIZATION_CODE,
+        CLIENT_CREDENTIALS,
+        DEVICE_CODE,
+        REFRESH_TOKEN,
+        TOKEN_EXCHANGE,
+    ];
+}
+
+/// Token type identifiers used by the token exchange grant (RFC 8693, section 3).
+pub mod token_type {
+    pub const ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
+}
+
+pub const PKCE_METHOD_S256: &str = "S256";
+
+/// Characters used in user codes, without vowels and easily confused characters (RFC 8628, section 6.1).
+const USER_CODE_CHARSET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OAuthError {
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("client authentication failed")]
+    InvalidClient,
+    #[error("{0}")]
+    InvalidGrant(String),
+    #[error("client is not authorized to use this grant type")]
+    UnauthorizedClient,
+    #[error("grant type ({0}) not supported")]
+    UnsupportedGrantType(String),
+    #[error("requested scope ({0}) is invalid")]
+    InvalidScope(String),
+    #[error("resource owner denied the request")]
+    AccessDenied,
+    #[error("response type ({0}) not supported")]
+    UnsupportedResponseType(String),
+    #[error("token type ({0}) not supported")]
+    UnsupportedTokenType(String),
+    #[error("authorization request is still pending")]
+    AuthorizationPending,
+    #[error("polling too frequently, increase the interval by 5 seconds")]
+    SlowDown,
+    #[error("device code has expired")]
+    ExpiredToken,
+    #[error("audience ({0}) is not allowed for this client")]
+    InvalidTarget(String),
+    #[error("DPoP proof is invalid")]
+    InvalidDpopProof,
+}
+
+impl OAuthError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            OAuthError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::InvalidGrant(_) => StatusCode::BAD_REQUEST,
+            OAuthError::UnauthorizedClient => StatusCode::BAD_REQUEST,
+            OAuthError::UnsupportedGrantType(_) => StatusCode::BAD_REQUEST,
+            OAuthError::InvalidScope(_) => StatusCode::BAD_REQUEST,
+            OAuthError::AccessDenied => StatusCode::BAD_REQUEST,
+            OAuthError::UnsupportedResponseType(_) => StatusCode::BAD_REQUEST,
+            OAuthError::UnsupportedTokenType(_) => StatusCode::BAD_REQUEST,
+            OAuthError::AuthorizationPending => StatusCode::BAD_REQUEST,
+            OAuthError::SlowDown => StatusCode::BAD_REQUEST,
+            OAuthError::ExpiredToken => StatusCode::BAD_REQUEST,
+            OAuthError::InvalidTarget(_) => StatusCode::BAD_REQUEST,
+            OAuthError::InvalidDpopProof => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant(_) => "invalid_grant",
+            OAuthError::UnauthorizedClient => "unauthorized_client",
+            OAuthError::UnsupportedGrantType(_) => "unsupported_grant_type",
+            OAuthError::InvalidScope(_) => "invalid_scope",
+            OAuthError::AccessDenied => "access_denied",
+            OAuthError::UnsupportedResponseType(_) => "unsupported_response_type",
+            OAuthError::UnsupportedTokenType(_) => "unsupported_token_type",
+            OAuthError::AuthorizationPending => "authorization_pending",
+            OAuthError::SlowDown => "slow_down",
+            OAuthError::ExpiredToken => "expired_token",
+            OAuthError::InvalidTarget(_) => "invalid_target",
+            OAuthError::InvalidDpopProof => "invalid_dpop_proof",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+}
+
+impl ClientCredentials {
+    /// Reads client credentials from the `Authorization: Basic` header, falling back
+    /// to the `client_id` and `client_secret` request body parameters.
+    pub fn from_request(
+        headers: &HeaderMap,
+        client_id: Option<String>,
+        client_secret: Option<String>,
+    ) -> Result<Self, OAuthError> {
+        let basic = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Basic "));
+
+        match (basic, client_id) {
+            (Some(_), Some(_)) => Err(OAuthError::InvalidRequest(
+                "multiple client authentication methods used".to_owned(),
+            )),
+            (Some(encoded), None) => Self::from_basic(encoded),
+            (None, Some(client_id)) => Ok(Self {
+                client_id,
+                client_secret,
+            }),
+            (None, None) => Err(OAuthError::InvalidClient),
+        }
+    }
+
+    pub fn from_basic(encoded: &str) -> Result<Self, OAuthError> {
+        let decoded = STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(OAuthError::InvalidClient)?;
+
+        let (client_id, client_secret) =
+            decoded.split_once(':').ok_or(OAuthError::InvalidClient)?;
+
+        Ok(Self {
+            client_id: client_id.to_owned(),
+            client_secret: Some(client_secret.to_owned()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issued_token_type: Option<&'static str>,
+}
+
+/// Parameters of the token exchange grant (RFC 8693, section 2.1).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenExchangeRequest<'a> {
+    pub subject_token: Option<&'a str>,
+    pub subject_token_type: Option<&'a str>,
+    pub audience: Option<&'a str>,
+    pub scope: Option<&'a str>,
+    pub requested_token_type: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceAuthorizationResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    pub expires_in: i64,
+    pub interval: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenTypeHint {
+    AccessToken,
+    RefreshToken,
+}
+
+impl FromStr for TokenTypeHint {
+    type Err = OAuthError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "access_token" => Ok(Self::AccessToken),
+            "refresh_token" => Ok(Self::RefreshToken),
+            val => Err(OAuthError::UnsupportedTokenType(val.to_owned())),
+        }
+    }
+}
+
+impl From<TokenTypeHint> for TokenType {
+    fn from(value: TokenTypeHint) -> Self {
+        match value {
+            TokenTypeHint::AccessToken => TokenType::Access,
+            TokenTypeHint::RefreshToken => TokenType::Refresh,
+        }
+    }
+}
+
+impl std::fmt::Display for TokenTypeHint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenTypeHint::AccessToken => write!(f, "access_token"),
+            TokenTypeHint::RefreshToken => write!(f, "refresh_token"),
+        }
+    }
+}
+
+/// RFC 7662 introspection response. Inactive tokens carry no other members.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+}
+
+impl IntrospectionResponse {
+    pub fn inactive() -> Self {
+        Self::default()
+    }
+
+    pub fn active(claims: Claims, token_type: TokenTypeHint) -> Self {
+        Self {
+            active: true,
+            scope: claims.scope_string(),
+            client_id: claims.client_id,
+            username: Some(claims.username),
+            token_type: Some(token_type.to_string()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            sub: Some(claims.sub),
+            iss: Some(claims.iss),
+            jti: Some(claims.jti),
+            aud: Some(claims.aud),
+            act: claims.act,
+            cnf: claims.cnf,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub nonce: Option<String>,
+}
+
+/// Authorization request that passed validation and can be presented to the user.
+#[derive(Debug, Clone)]
+pub struct ValidatedAuthorization {
+    pub client: Client,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+}
+
+pub async fn authenticate_client(
+    state: &Arc<ApiState>,
+    credentials: &ClientCredentials,
+) -> Result<Client, ApiError> {
+    let client = state
+        .db
+        .clients()
+        .find_by_client_id(&credentials.client_id)
+        .await?
+        .ok_or(OAuthError::InvalidClient)?;
+
+    // Public clients have no secret and must not send one.
+    match (&client.secret_hash, credentials.client_secret.as_deref()) {
+        (Some(hash), Some(secret)) if verify_hash(hash, secret) => Ok(client),
+        (None, None) => Ok(client),
+        _ => Err(OAuthError::InvalidClient.into()),
+    }
+}
+
+/// Resolves the space-delimited `scope` request parameter against the scopes the
+/// client is allowed to request. When no scope is requested, all allowed scopes are granted.
+pub fn resolve_scopes(
+    requested: Option<&str>,
+    allowed: &[String],
+) -> Result<Vec<String>, OAuthError> {
+    let Some(requested) = requested.filter(|s| !s.trim().is_empty()) else {
+        return Ok(allowed.to_vec());
+    };
+
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in requested.split_whitespace() {
+        if !allowed.iter().any(|s| s == scope) {
+            return Err(OAuthError::InvalidScope(scope.to_owned()));
+        }
+
+        if !scopes.iter().any(|s| s == scope) {
+            scopes.push(scope.to_owned());
+        }
+    }
+
+    Ok(scopes)
+}
+
+/// Token type of issued access tokens, which depends on whether they are bound to a DPoP key.
+pub fn access_token_type(jkt: Option<&str>) -> &'static str {
+    if jkt.is_some() {
+        "DPoP"
+    } else {
+        "Bearer"
+    }
+}
+
+pub async fn client_credentials_grant(
+    state: &Arc<ApiState>,
+    client: &Client,
+    scope: Option<&str>,
+    jkt: Option<&str>,
+) -> Result<TokenResponse, ApiError> {
+    if !client.is_confidential() || !client.allows_grant(grant_type::CLIENT_CREDENTIALS) {
+        return Err(OAuthError::UnauthorizedClient.into());
+    }
+
+    let scopes = resolve_scopes(scope, &client.scopes)?;
+
+    let claims = Claims {
+        cnf: jkt.map(|jkt| Confirmation {
+            jkt: jkt.to_owned(),
+        }),
+        ..Claims::from_client(
+            client,
+            &scopes,
+            &state.config,
+            chrono::Utc::now().timestamp(),
+        )
+    };
+    let access_token =
+        services::jwt::generate_access_token(&claims, &state.config, &state.signing_key)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: access_token_type(jkt),
+        expires_in: state.config.jwt_access_expiration,
+        refresh_token: None,
+        scope: Some(scopes.join(" ")),
+        id_token: None,
+        issued_token_type: None,
+    })
+}
+
+pub fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    let valid_verifier = (43..=128).contains(&code_verifier.len())
+        && code_verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._~".contains(&b));
+
+    valid_verifier
+        && URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == code_challenge
+}
+
+/// Appends the query parameters to the client redirect URI.
+pub fn authorization_redirect(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+) -> Result<String, ApiError> {
+    let mut url = Url::parse(redirect_uri)
+        .map_err(|e| ApiError::BadRequest(format!("invalid redirect_uri: {}", e)))?;
+    url.query_pairs_mut().extend_pairs(params);
+    Ok(url.to_string())
+}
+
+/// Identifies the client and the redirect URI of an authorization request.
+/// Errors returned here must be shown to the user instead of being redirected to the client.
+pub async fn resolve_authorization_client(
+    state: &Arc<ApiState>,
+    request: &AuthorizationRequest,
+) -> Result<(Client, String), ApiError> {
+    let client_id = request
+        .client_id
+        .as_deref()
+        .ok_or_else(|| ApiError::BadRequest("client_id missing".to_owned()))?;
+
+    let client = state
+        .db
+        .clients()
+        .find_by_client_id(client_id)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest(format!("client ({}) not found", client_id)))?;
+
+    let redirect_uri = match request.redirect_uri.as_deref() {
+        Some(uri) if client.allows_redirect_uri(uri) => uri.to_owned(),
+        Some(uri) => {
+            return Err(ApiError::BadRequest(format!(
+                "redirect_uri ({}) not registered",
+                uri
+            )))
+        }
+        None if client.redirect_uris.len() == 1 => client.redirect_uris[0].clone(),
+        None => return Err(ApiError::BadRequest("redirect_uri missing".to_owned())),
+    };
+
+    Ok((client, redirect_uri))
+}
+
+pub fn validate_authorization(
+    client: Client,
+    redirect_uri: String,
+    request: &AuthorizationRequest,
+) -> Result<ValidatedAuthorization, OAuthError> {
+    match request.response_type.as_deref() {
+        Some("code") => {}
+        Some(response_type) => {
+            return Err(OAuthError::UnsupportedResponseType(
+                response_type.to_owned(),
+            ))
+        }
+        None => {
+            return Err(OAuthError::InvalidRequest(
+                "response_type missing".to_owned(),
+            ))
+        }
+    }
+
+    if !client.allows_grant(grant_type::AUTHORIZATION_CODE) {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+
+    let code_challenge = request
+        .code_challenge
+        .clone()
+        .ok_or_else(|| OAuthError::InvalidRequest("code_challenge required".to_owned()))?;
+
+    if request.code_challenge_method.as_deref() != Some(PKCE_METHOD_S256) {
+        return Err(OAuthError::InvalidRequest(
+            "code_challenge_method must be S256".to_owned(),
+        ));
+    }
+
+    let scope = resolve_scopes(request.scope.as_deref(), &client.scopes)?.join(" ");
+
+    Ok(ValidatedAuthorization {
+        client,
+        redirect_uri,
+        scope,
+        code_challenge,
+        nonce: request.nonce.clone(),
+    })
+}
+
+pub async fn issue_authorization_code(
+    state: &Arc<ApiState>,
+    authorization: &ValidatedAuthorization,
+    user: &User,
+) -> Result<String, ApiError> {
+    let code = generate_secret();
+    let authorization_code = AuthorizationCode::new(
+        &authorization.client.client_id,
+        user.id,
+        &authorization.redirect_uri,
+        &authorization.scope,
+        &authorization.code_challenge,
+        authorization.nonce.as_deref(),
+        chrono::Utc::now().timestamp(),
+    );
+
+    state
+        .redis
+        .authorization_codes()
+        .store(
+            &code,
+            &authorization_code,
+            state.config.oauth_code_expiration,
+        )
+        .await?;
+
+    Ok(code)
+}
+
+pub async fn authorization_code_grant(
+    state: &Arc<ApiState>,
+    client: &Client,
+    code: Option<&str>,
+    redirect_uri: Option<&str>,
+    code_verifier: Option<&str>,
+    jkt: Option<&str>,
+) -> Result<TokenResponse, ApiError> {
+    if !client.allows_grant(grant_type::AUTHORIZATION_CODE) {
+        return Err(OAuthError::UnauthorizedClient.into());
+    }
+
+    let code = code.ok_or_else(|| OAuthError::InvalidRequest("code missing".to_owned()))?;
+    let code_verifier = code_verifier
+        .ok_or_else(|| OAuthError::InvalidRequest("code_verifier missing".to_owned()))?;
+
+    let authorization_code = state
+        .redis
+        .authorization_codes()
+        .take(code)
+        .await?
+        .ok_or_else(|| {
+            OAuthError::InvalidGrant("authorization code is invalid or expired".to_owned())
+        })?;
+
+    if authorization_code.client_id != client.client_id
+        || redirect_uri != Some(authorization_code.redirect_uri.as_str())
+    {
+        return Err(OAuthError::InvalidGrant(
+            "authorization code was issued to another client or redirect_uri".to_owned(),
+        )
+        .into());
+    }
+
+    if !verify_pkce(code_verifier, &authorization_code.code_challenge) {
+        return Err(OAuthError::InvalidGrant("PKCE verification failed".to_owned()).into());
+    }
+
+    let user = state
+        .db
+        .users()
+        .find_by_id(authorization_code.user_id)
+        .await?
+        .ok_or_else(|| OAuthError::InvalidGrant("resource owner not found".to_owned()))?;
+
+    if user.is_disabled() {
+        return Err(OAuthError::InvalidGrant("resource owner is disabled".to_owned()).into());
+    }
+
+    let ctx = TokenContext::for_client(&client.client_id, &authorization_code.scope).with_jkt(jkt);
+    let (access_token, refresh_token, _) =
+        services::auth::create_session(state, &user, &ctx).await?;
+
+    let id_token = if oidc::has_scope(&authorization_code.scope, oidc::SCOPE_OPENID) {
+        Some(oidc::issue_id_token(
+            state,
+            user.id,
+            &client.client_id,
+            authorization_code.auth_time,
+            authorization_code.nonce,
+        )?)
+    } else {
+        None
+    };
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: access_token_type(jkt),
+        expires_in: state.config.jwt_access_expiration,
+        refresh_token: Some(refresh_token),
+        scope: Some(authorization_code.scope),
+        id_token,
+        issued_token_type: None,
+    })
+}
+
+pub async fn refresh_token_grant(
+    state: &Arc<ApiState>,
+    client: &Client,
+    refresh_token: Option<&str>,
+    jkt: Option<&str>,
+) -> Result<TokenResponse, ApiError> {
+    if !client.allows_grant(grant_type::REFRESH_TOKEN) {
+        return Err(OAuthError::UnauthorizedClient.into());
+    }
+
+    let refresh_token = refresh_token
+        .ok_or_else(|| OAuthError::InvalidRequest("refresh_token missing".to_owned()))?;
+
+    let invalid_grant = || OAuthError::InvalidGrant("refresh token is invalid".to_owned());
+    let claims = services::jwt::decode_token(
+        refresh_token,
+        &TokenValidation::new(TokenType::Refresh, &state.config),
+    )
+    .map_err(|_| invalid_grant())?;
+
+    if claims.client_id.as_deref() != Some(client.client_id.as_str()) {
+        return Err(invalid_grant().into());
+    }
+
+    let (access_token, _) = services::auth::refresh(state, refresh_token, jkt)
+        .await
+        .map_err(|e| match e {
+            ApiError::Auth(AuthError::InvalidDpopProof) => OAuthError::InvalidDpopProof.into(),
+            ApiError::Auth(_) => invalid_grant().into(),
+            e => e,
+        })?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: access_token_type(jkt),
+        expires_in: state.config.jwt_access_expiration,
+        refresh_token: None,
+        scope: claims.scope_string(),
+        id_token: None,
+        issued_token_type: None,
+    })
+}
+
+/// Generates a user code in the `XXXX-XXXX` format.
+pub fn generate_user_code() -> String {
+    let mut code = String::with_capacity(USER_CODE_LENGTH + 1);
+    while code.len() < USER_CODE_LENGTH + 1 {
+        let byte = (OsRng.next_u32() & 0xff) as u8;
+        // Rejection sampling keeps the distribution over the charset uniform.
+        if usize::from(byte) >= 256 - 256 % USER_CODE_CHARSET.len() {
+            continue;
+        }
+
+        if code.len() == USER_CODE_LENGTH / 2 {
+            code.push('-');
+        }
+        code.push(USER_CODE_CHARSET[usize::from(byte) % USER_CODE_CHARSET.len()] as char);
+    }
+    code
+}
+
+/// Normalizes user input of the user code, so that case, whitespace and dashes do not matter.
+pub fn normalize_user_code(user_code: &str) -> Option<String> {
+    let code: String = user_code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if code.len() != USER_CODE_LENGTH || !code.bytes().all(|b| USER_CODE_CHARSET.contains(&b)) {
+        return None;
+    }
+
+    Some(format!(
+        "{}-{}",
+        &code[..USER_CODE_LENGTH / 2],
+        &code[USER_CODE_LENGTH / 2..]
+    ))
+}
+
+pub async fn device_authorization(
+    state: &Arc<ApiState>,
+    client: &Client,
+    scope: Option<&str>,
+) -> Result<DeviceAuthorizationResponse, ApiError> {
+    if !client.allows_grant(grant_type::DEVICE_CODE) {
+        return Err(OAuthError::UnauthorizedClient.into());
+    }
+
+    let scope = resolve_scopes(scope, &client.scopes)?.join(" ");
+    let device_code = generate_secret();
+    let user_code = generate_user_code();
+    let interval = state.config.oauth_device_poll_interval;
+
+    state
+        .redis
+        .device_codes()
+        .store(
+            &device_code,
+            &DeviceAuthorization::new(&client.client_id, &user_code, &scope, interval),
+            state.config.oauth_device_code_expiration,
+        )
+        .await?;
+
+    let verification_uri = format!("{}/oauth/device", state.config.issuer());
+    let verification_uri_complete =
+        authorization_redirect(&verification_uri, &[("user_code", user_code.as_str())])?;
+
+    Ok(DeviceAuthorizationResponse {
+        device_code,
+        user_code,
+        verification_uri,
+        verification_uri_complete,
+        expires_in: state.config.oauth_device_code_expiration,
+        interval,
+    })
+}
+
+/// Looks up a pending device authorization by the user code entered by the resource owner.
+pub async fn find_device_authorization(
+    state: &Arc<ApiState>,
+    user_code: &str,
+) -> Result<(String, DeviceAuthorization), ApiError> {
+    let invalid_code = || ApiError::BadRequest("user code is invalid or expired".to_owned());
+    let user_code = normalize_user_code(user_code).ok_or_else(invalid_code)?;
+
+    let store = state.redis.device_codes();
+    let Some(device_code) = store.find_device_code(&user_code).await? else {
+        return Err(invalid_code());
+    };
+
+    match store.find(&device_code).await? {
+        Some(authorization) if authorization.status == DeviceAuthorizationStatus::Pending => {
+            Ok((device_code, authorization))
+        }
+        _ => Err(invalid_code()),
+    }
+}
+
+/// Records the decision of the resource owner for the device authorization with the given user code.
+pub async fn decide_device_authorization(
+    state: &Arc<ApiState>,
+    user_code: &str,
+    user: &User,
+    approve: bool,
+) -> Result<DeviceAuthorization, ApiError> {
+    let (device_code, mut authorization) = find_device_authorization(state, user_code).await?;
+
+    if approve {
+        authorization.approve(user.id, chrono::Utc::now().timestamp());
+    } else {
+        authorization.deny();
+    }
+
+    state
+        .redis
+        .device_codes()
+        .update(&device_code, &authorization)
+        .await?;
+
+    Ok(authorization)
+}
+
+pub async fn device_code_grant(
+    state: &Arc<ApiState>,
+    client: &Client,
+    device_code: Option<&str>,
+    jkt: Option<&str>,
+) -> Result<TokenResponse, ApiError> {
+    if !client.allows_grant(grant_type::DEVICE_CODE) {
+        return Err(OAuthError::UnauthorizedClient.into());
+    }
+
+    let device_code =
+        device_code.ok_or_else(|| OAuthError::InvalidRequest("device_code missing".to_owned()))?;
+
+    let store = state.redis.device_codes();
+    let authorization = store
+        .find(device_code)
+        .await?
+        .ok_or(OAuthError::ExpiredToken)?;
+
+    if authorization.client_id != client.client_id {
+        return Err(OAuthError::InvalidGrant(
+            "device code was issued to another client".to_owned(),
+        )
+        .into());
+    }
+
+    match authorization.status {
+        DeviceAuthorizationStatus::Pending => {
+            let now = chrono::Utc::now().timestamp();
+            let previous = store.find_polling(device_code).await?;
+            let too_fast = previous.is_some_and(|p| now - p.last_polled_at < p.interval);
+
+            let interval = previous.map_or(authorization.interval, |p| p.interval);
+            let polling = DevicePolling {
+                interval: if too_fast { interval + 5 } else { interval },
+                last_polled_at: now,
+            };
+            store
+                .store_polling(
+                    device_code,
+                    &polling,
+                    state.config.oauth_device_code_expiration,
+                )
+                .await?;
+
+            if too_fast {
+                Err(OAuthError::SlowDown.into())
+            } else {
+                Err(OAuthError::AuthorizationPending.into())
+            }
+        }
+        DeviceAuthorizationStatus::Denied => {
+            store.take(device_code).await?;
+            Err(OAuthError::AccessDenied.into())
+        }
+        DeviceAuthorizationStatus::Approved => {
+            // Concurrent polls may all have seen the approval, only the one removing the
+            // device code gets the tokens.
+            let authorization = store
+                .take(device_code)
+                .await?
+                .filter(|a| a.status == DeviceAuthorizationStatus::Approved)
+                .ok_or(OAuthError::ExpiredToken)?;
+
+            let user = match authorization.user_id {
+                Some(user_id) => state.db.users().find_by_id(user_id).await?,
+                None => None,
+            }
+            .ok_or_else(|| OAuthError::InvalidGrant("resource owner not found".to_owned()))?;
+
+            if user.is_disabled() {
+                return Err(
+                    OAuthError::InvalidGrant("resource owner is disabled".to_owned()).into(),
+                );
+            }
+
+            let ctx =
+                TokenContext::for_client(&client.client_id, &authorization.scope).with_jkt(jkt);
+            let (access_token, refresh_token, _) =
+                services::auth::create_session(state, &user, &ctx).await?;
+
+            let id_token = if oidc::has_scope(&authorization.scope, oidc::SCOPE_OPENID) {
+                Some(oidc::issue_id_token(
+                    state,
+                    user.id,
+                    &client.client_id,
+                    authorization.auth_time.unwrap_or_default(),
+                    None,
+                )?)
+            } else {
+                None
+            };
+
+            Ok(TokenResponse {
+                access_token,
+                token_type: access_token_type(jkt),
+                expires_in: state.config.jwt_access_expiration,
+                refresh_token: Some(refresh_token),
+                scope: Some(authorization.scope),
+                id_token,
+                issued_token_type: None,
+            })
+        }
+    }
+}
+
+/// Exchanges an access token of a user for a down-scoped token for another audience, with the
+/// exchanging client recorded as the actor (RFC 8693). Audiences are identified by the client
+/// id of the downstream service, so a service can in turn exchange tokens issued for itself.
+pub async fn token_exchange_grant(
+    state: &Arc<ApiState>,
+    client: &Client,
+    request: TokenExchangeRequest<'_>,
+    jkt: Option<&str>,
+) -> Result<TokenResponse, ApiError> {
+    if !client.is_confidential() || !client.allows_grant(grant_type::TOKEN_EXCHANGE) {
+        return Err(OAuthError::UnauthorizedClient.into());
+    }
+
+    let subject_token = request
+        .subject_token
+        .ok_or_else(|| OAuthError::InvalidRequest("subject_token missing".to_owned()))?;
+
+    match request.subject_token_type {
+        Some(token_type::ACCESS_TOKEN) => {}
+        Some(other) => return Err(OAuthError::UnsupportedTokenType(other.to_owned()).into()),
+        None => {
+            return Err(OAuthError::InvalidRequest("subject_token_type missing".to_owned()).into())
+        }
+    }
+
+    if let Some(requested) = request
+        .requested_token_type
+        .filter(|t| *t != token_type::ACCESS_TOKEN)
+    {
+        return Err(OAuthError::UnsupportedTokenType(requested.to_owned()).into());
+    }
+
+    let audience = request
+        .audience
+        .ok_or_else(|| OAuthError::InvalidRequest("audience missing".to_owned()))?;
+    if !client.allows_audience(audience) {
+        return Err(OAuthError::InvalidTarget(audience.to_owned()).into());
+    }
+
+    let subject = find_active_claims(state, subject_token, TokenTypeHint::AccessToken)
+        .await?
+        .ok_or_else(|| OAuthError::InvalidGrant("subject token is invalid".to_owned()))?;
+
+    // Tokens issued for another audience can only be exchanged further by that audience.
+    if subject.aud != state.config.jwt_audience && subject.aud != client.client_id {
+        return Err(OAuthError::InvalidGrant(
+            "subject token was issued for another audience".to_owned(),
+        )
+        .into());
+    }
+
+    // The exchanged token can never carry more scopes than the subject token.
+    let allowed = subject.scope.as_ref().unwrap_or(&client.scopes);
+    let scopes = resolve_scopes(request.scope, allowed)?;
+
+    let now = chrono::Utc::now().timestamp();
+    let exp = subject.exp.min(now + state.config.jwt_access_expiration);
+
+    let actor = Actor {
+        sub: client.client_id.clone(),
+        act: subject.act.clone().map(Box::new),
+    };
+    let claims = Claims {
+        aud: audience.to_owned(),
+        exp,
+        nbf: now,
+        iat: now,
+        jti: Uuid::new_v4(),
+        client_id: Some(client.client_id.clone()),
+        scope: Some(scopes.clone()),
+        act: Some(actor),
+        cnf: jkt.map(|jkt| Confirmation {
+            jkt: jkt.to_owned(),
+        }),
+        ..subject
+    };
+    let access_token =
+        services::jwt::generate_access_token(&claims, &state.config, &state.signing_key)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: access_token_type(jkt),
+        expires_in: exp - now,
+        refresh_token: None,
+        scope: Some(scopes.join(" ")),
+        id_token: None,
+        issued_token_type: Some(token_type::ACCESS_TOKEN),
+    })
+}
+
+/// Validates the token against the signing secret of the given type and its revocation state,
+/// i.e. the Redis blacklist for access tokens and the session store for refresh tokens.
+pub async fn find_active_claims(
+    state: &Arc<ApiState>,
+    token: &str,
+    token_type: TokenTypeHint,
+) -> Result<Option<Claims>, ApiError> {
+    // Tokens exchanged for downstream services are introspected by these services.
+    let validation = TokenValidation::for_state(token_type.into(), state).any_audience();
+    let Ok(claims) = services::jwt::decode_token(token, &validation) else {
+        return Ok(None);
+    };
+
+    let active = match token_type {
+        TokenTypeHint::AccessToken => !state.redis.tokens().is_blacklisted(claims.jti).await?,
+        TokenTypeHint::RefreshToken => state
+            .db
+            .refresh_tokens()
+            .find_by_jti(claims.jti)
+            .await?
+            .is_some(),
+    };
+
+    if !active {
+        return Ok(None);
+    }
+
+    match services::auth::verify_token_version(state, &claims).await {
+        Ok(()) => Ok(Some(claims)),
+        Err(ApiError::Auth(AuthError::TokenRevoked)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Looks the token up as the hinted type first, falling back to the other type.
+async fn find_token(
+    state: &Arc<ApiState>,
+    token: &str,
+    hint: Option<&str>,
+) -> Result<Option<(Claims, TokenTypeHint)>, ApiError> {
+    let hint = hint.map(TokenTypeHint::from_str).transpose()?;
+    let order = match hint {
+        Some(TokenTypeHint::RefreshToken) => {
+            [TokenTypeHint::RefreshToken, TokenTypeHint::AccessToken]
+        }
+        _ => [TokenTypeHint::AccessToken, TokenTypeHint::RefreshToken],
+    };
+
+    for token_type in order {
+        if let Some(claims) = find_active_claims(state, token, token_type).await? {
+            return Ok(Some((claims, token_type)));
+        }
+    }
+
+    Ok(None)
+}
+
+pub async fn introspect(
+    state: &Arc<ApiState>,
+    client: &Client,
+    token: Option<&str>,
+    hint: Option<&str>,
+) -> Result<IntrospectionResponse, ApiError> {
+    if !client.is_confidential() {
+        return Err(OAuthError::UnauthorizedClient.into());
+    }
+
+    let token = token.ok_or_else(|| OAuthError::InvalidRequest("token missing".to_owned()))?;
+
+    Ok(match find_token(state, token, hint).await? {
+        Some((claims, token_type)) => IntrospectionResponse::active(claims, token_type),
+        None => IntrospectionResponse::inactive(),
+    })
+}
+
+/// Revokes an access or refresh token issued to the client. Invalid, expired or already
+/// revoked tokens are ignored, as required by RFC 7009.
+pub async fn revoke(
+    state: &Arc<ApiState>,
+    client: &Client,
+    token: Option<&str>,
+    hint: Option<&str>,
+) -> Result<(), ApiError> {
+    let token = token.ok_or_else(|| OAuthError::InvalidRequest("token missing".to_owned()))?;
+
+    let Some((claims, token_type)) = find_token(state, token, hint).await? else {
+        return Ok(());
+    };
+
+    if claims.client_id.as_deref() != Some(client.client_id.as_str()) {
+        return Err(
+            OAuthError::InvalidRequest("token was not issued to this client".to_owned()).into(),
+        );
+    }
+
+    match token_type {
+        TokenTypeHint::AccessToken => {
+            let remaining = claims.exp - chrono::Utc::now().timestamp();
+            state
+                .redis
+                .tokens()
+                .blacklist(claims.jti, remaining.max(1))
+                .await?;
+        }
+        TokenTypeHint::RefreshToken => {
+            state.db.refresh_tokens().delete_by_jti(claims.jti).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the client registration is consistent before the client is created.
+/// Whether the scope is a valid scope token (RFC 6749 section 3.3).
+fn is_scope_token(scope: &str) -> bool {
+    !scope.is_empty()
+        && scope
+            .bytes()
+            .all(|b| matches!(b, 0x21 | 0x23..=0x5B | 0x5D..=0x7E))
+}
+
+pub fn validate_client_registration(
+    confidential: bool,
+    scopes: &[String],
+    redirect_uris: &[String],
+    grant_types: &[String],
+    exchange_audiences: &[String],
+) -> Result<(), ApiError> {
+    if let Some(scope) = scopes.iter().find(|s| !is_scope_token(s)) {
+        return Err(ApiError::BadRequest(format!("invalid scope ({})", scope)));
+    }
+
+    if let Some(grant) = grant_types
+        .iter()
+        .find(|g| !grant_type::SUPPORTED.contains(&g.as_str()))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "grant type ({}) not supported",
+            grant
+        )));
+    }
+
+    if !confidential
+        && grant_types
+            .iter()
+            .any(|g| g == grant_type::CLIENT_CREDENTIALS)
+    {
+        return Err(ApiError::BadRequest(
+            "public clients cannot use the client_credentials grant".to_owned(),
+        ));
+    }
+
+    if grant_types
+        .iter()
+        .any(|g| g == grant_type::AUTHORIZATION_CODE)
+        && redirect_uris.is_empty()
+    {
+        return Err(ApiError::BadRequest(
+            "authorization_code grant requires at least one redirect_uri".to_owned(),
+        ));
+    }
+
+    if grant_types.iter().any(|g| g == grant_type::TOKEN_EXCHANGE) {
+        if !confidential {
+            return Err(ApiError::BadRequest(
+                "public clients cannot use the token exchange grant".to_owned(),
+            ));
+        }
+
+        if exchange_audiences.is_empty() {
+            return Err(ApiError::BadRequest(
+                "token exchange grant requires at least one exchange audience".to_owned(),
+            ));
+        }
+    }
+
+    for uri in redirect_uris {
+        let url = Url::parse(uri)
+            .map_err(|e| ApiError::BadRequest(format!("invalid redirect_uri ({}): {}", uri, e)))?;
+        if url.fragment().is_some() {
+            return Err(ApiError::BadRequest(format!(
+                "redirect_uri ({}) must not contain a fragment",
+                uri
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn create_client(
+    db: &dyn Database,
+    client_id: &str,
+    confidential: bool,
+    scopes: &[String],
+    redirect_uris: &[String],
+    grant_types: &[String],
+    exchange_audiences: &[String],
+) -> Result<(Client, Option<String>), ApiError> {
+    validate_client_registration(
+        confidential,
+        scopes,
+        redirect_uris,
+        grant_types,
+        exchange_audiences,
+    )?;
+
+    if db.clients().find_by_client_id(client_id).await?.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "client_id ({}) already taken",
+            client_id
+        )));
+    }
+
+    let client_secret = confidential.then(generate_secret);
+    let new_client = Client::new(
+        client_id,
+        client_secret.as_deref().map(hash_string).as_deref(),
+        scopes,
+        redirect_uris,
+        grant_types,
+    )
+    .with_exchange_audiences(exchange_audiences);
+    let created_client = db.clients().create(new_client).await?;
+
+    Ok((created_client, client_secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, database::mock::MockDatabase, models::user::Role, test_utils};
+
+    fn allowed() -> Vec<String> {
+        vec!["read".to_owned(), "write".to_owned()]
+    }
+
+    #[test]
+    fn resolve_scopes_defaults_to_allowed() {
+        let scopes = resolve_scopes(None, &allowed());
+        assert_eq!(scopes.unwrap(), allowed());
+    }
+
+    #[test]
+    fn resolve_scopes_subset() {
+        let scopes = resolve_scopes(Some("write write"), &allowed());
+        assert_eq!(scopes.unwrap(), vec!["write".to_owned()]);
+    }
+
+    #[test]
+    fn resolve_scopes_not_allowed() {
+        let scopes = resolve_scopes(Some("read admin"), &allowed());
+        assert!(matches!(scopes, Err(OAuthError::InvalidScope(s)) if s == "admin"));
+    }
+
+    #[test]
+    fn verify_pkce_s256() {
+        // Test vector from RFC 7636, appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+        assert!(verify_pkce(verifier, challenge));
+        assert!(!verify_pkce(&verifier.replace('d', "e"), challenge));
+        assert!(!verify_pkce("too_short", challenge));
+    }
+
+    #[test]
+    fn authorization_redirect_keeps_query() {
+        let redirect = authorization_redirect(
+            "https://app.test/cb?tenant=1",
+            &[("code", "a b"), ("state", "xyz")],
+        );
+        assert_eq!(
+            redirect.unwrap(),
+            "https://app.test/cb?tenant=1&code=a+b&state=xyz"
+        );
+    }
+
+    #[test]
+    fn validate_authorization_requires_pkce() {
+        let client = Client::new(
+            "spa",
+            None,
+            &allowed(),
+            &["https://app.test/cb".to_owned()],
+            &[grant_type::AUTHORIZATION_CODE.to_owned()],
+        );
+        let mut request = AuthorizationRequest {
+            response_type: Some("code".to_owned()),
+            client_id: Some("spa".to_owned()),
+            code_challenge: Some("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_owned()),
+            code_challenge_method: Some("plain".to_owned()),
+            ..Default::default()
+        };
+
+        let res =
+            validate_authorization(client.clone(), "https://app.test/cb".to_owned(), &request);
+        assert!(matches!(res, Err(OAuthError::InvalidRequest(_))));
+
+        request.code_challenge_method = Some(PKCE_METHOD_S256.to_owned());
+        let res = validate_authorization(client, "https://app.test/cb".to_owned(), &request);
+        assert_eq!(res.unwrap().scope, "read write");
+    }
+
+    #[test]
+    fn validate_client_registration_public_client_credentials() {
+        let res = validate_client_registration(
+            false,
+            &[],
+            &[],
+            &[grant_type::CLIENT_CREDENTIALS.to_owned()],
+            &[],
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn validate_client_registration_token_exchange_audiences() {
+        let grant_types = [grant_type::TOKEN_EXCHANGE.to_owned()];
+
+        let res = validate_client_registration(true, &[], &[], &grant_types, &[]);
+        assert!(res.is_err());
+
+        let res =
+            validate_client_registration(true, &[], &[], &grant_types, &["billing".to_owned()]);
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn create_client_validates_registration() {
+        let db = MockDatabase::new();
+        let grant_types = [grant_type::CLIENT_CREDENTIALS.to_owned()];
+        let create = |client_id: &'static str, scopes: Vec<String>| {
+            let db = db.clone();
+            let grant_types = grant_types.clone();
+            async move {
+                create_client(
+                    db.as_ref(),
+                    client_id,
+                    true,
+                    &scopes,
+                    &[],
+                    &grant_types,
+                    &[],
+                )
+                .await
+            }
+        };
+
+        let (client, secret) = create("reports", allowed()).await.unwrap();
+        assert!(verify_hash(
+            client.secret_hash.as_deref().unwrap(),
+            &secret.unwrap()
+        ));
+
+        assert!(matches!(
+            create("reports", allowed()).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        for scope in ["", "read write", "say\"hi\""] {
+            assert!(matches!(
+                create("billing", vec![scope.to_owned()]).await,
+                Err(ApiError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn token_type_hint_unsupported() {
+        assert_eq!(
+            TokenTypeHint::from_str("refresh_token").unwrap(),
+            TokenTypeHint::RefreshToken
+        );
+        assert!(matches!(
+            TokenTypeHint::from_str("id_token"),
+            Err(OAuthError::UnsupportedTokenType(_))
+        ));
+    }
+
+    #[test]
+    fn introspection_inactive_has_no_members() {
+        let response = serde_json::to_value(IntrospectionResponse::inactive()).unwrap();
+        assert_eq!(response, serde_json::json!({ "active": false }));
+    }
+
+    #[test]
+    fn client_credentials_from_basic() {
+        let encoded = STANDARD.encode("service:s3cr3t:with:colons");
+        let credentials = ClientCredentials::from_basic(&encoded).unwrap();
+
+        assert_eq!(credentials.client_id, "service");
+        assert_eq!(
+            credentials.client_secret.as_deref(),
+            Some("s3cr3t:with:colons")
+        );
+    }
+
+    #[test]
+    fn client_credentials_multiple_methods() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Basic {}", STANDARD.encode("service:secret"))
+                .parse()
+                .unwrap(),
+        );
+
+        let credentials =
+            ClientCredentials::from_request(&headers, Some("service".to_owned()), None);
+        assert!(matches!(credentials, Err(OAuthError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn user_code_format() {
+        let code = generate_user_code();
+
+        assert_eq!(code.len(), USER_CODE_LENGTH + 1);
+        assert_eq!(code.chars().nth(USER_CODE_LENGTH / 2), Some('-'));
+        assert_eq!(normalize_user_code(&code), Some(code));
+    }
+
+    #[test]
+    fn normalize_user_code_input() {
+        assert_eq!(
+            normalize_user_code(" wdjb mjht "),
+            Some("WDJB-MJHT".to_owned())
+        );
+        assert_eq!(normalize_user_code("wdjb-mjh"), None);
+        assert_eq!(normalize_user_code("WDJB-MJHA"), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Redis"]
+    async fn device_code_redeemed_once() {
+        let state = test_utils::state(Config::default()).await;
+        let client = Client::new(
+            "tv",
+            None,
+            &["read".to_owned()],
+            &[],
+            &[grant_type::DEVICE_CODE.to_owned()],
+        );
+        let user = state
+            .db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]))
+            .await
+            .unwrap();
+
+        let authorization = device_authorization(&state, &client, None).await.unwrap();
+        let pending = state
+            .redis
+            .device_codes()
+            .find(&authorization.device_code)
+            .await
+            .unwrap();
+        assert!(matches!(
+            device_code_grant(&state, &client, Some(&authorization.device_code), None).await,
+            Err(ApiError::OAuth(OAuthError::AuthorizationPending))
+        ));
+        assert!(matches!(
+            device_code_grant(&state, &client, Some(&authorization.device_code), None).await,
+            Err(ApiError::OAuth(OAuthError::SlowDown))
+        ));
+        let store = state.redis.device_codes();
+        assert_eq!(
+            store.find(&authorization.device_code).await.unwrap(),
+            pending
+        );
+        assert_eq!(
+            store
+                .find_polling(&authorization.device_code)
+                .await
+                .unwrap()
+                .map(|p| p.interval),
+            Some(state.config.oauth_device_poll_interval + 5)
+        );
+
+        decide_device_authorization(&state, &authorization.user_code, &user, true)
+            .await
+            .unwrap();
+
+        // Polls never write the authorization, so they can't overwrite the decision.
+        let approved = state
+            .redis
+            .device_codes()
+            .find(&authorization.device_code)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(approved.status, DeviceAuthorizationStatus::Approved);
+
+        let (first, second) = tokio::join!(
+            device_code_grant(&state, &client, Some(&authorization.device_code), None),
+            device_code_grant(&state, &client, Some(&authorization.device_code), None),
+        );
+        assert!(first.is_ok() != second.is_ok());
+        assert!(matches!(
+            first.and(second),
+            Err(ApiError::OAuth(OAuthError::ExpiredToken))
+        ));
+    }
+
+    fn confidential_client(client_id: &str, grant_types: &[&str], audiences: &[&str]) -> Client {
+        let grant_types: Vec<String> = grant_types.iter().map(|g| g.to_string()).collect();
+        Client {
+            exchange_audiences: audiences.iter().map(|a| a.to_string()).collect(),
+            ..Client::new(client_id, Some("hash"), &allowed(), &[], &grant_types)
+        }
+    }
+
+    fn exchange<'a>(
+        subject_token: &'a str,
+        audience: &'a str,
+        scope: Option<&'a str>,
+    ) -> TokenExchangeRequest<'a> {
+        TokenExchangeRequest {
+            subject_token: Some(subject_token),
+            subject_token_type: Some(token_type::ACCESS_TOKEN),
+            audience: Some(audience),
+            scope,
+            requested_token_type: None,
+        }
+    }
+
+    /// Access token of the user with the given lifetime and scopes.
+    fn subject_token(user: &User, config: &Config, lifetime: i64, scope: &[&str]) -> String {
+        let now = chrono::Utc::now().timestamp();
+        let claims = Claims {
+            exp: now + lifetime,
+            scope: Some(scope.iter().map(|s| s.to_string()).collect()),
+            ..Claims::from_user(user, TokenType::Access, config, now)
+        };
+        services::jwt::generate_token(&claims, &config.jwt_access_secret).unwrap()
+    }
+
+    fn decode(token: &str, config: &Config) -> Claims {
+        let validation = TokenValidation::new(TokenType::Access, config).any_audience();
+        services::jwt::decode_token(token, &validation).unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Redis"]
+    async fn token_exchange_policy() {
+        let config = Config {
+            jwt_access_expiration: 300,
+            ..Config::default()
+        };
+        let state = test_utils::state(config.clone()).await;
+        let user = state
+            .db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]))
+            .await
+            .unwrap();
+        let gateway = confidential_client("gateway", &[grant_type::TOKEN_EXCHANGE], &["orders"]);
+        let orders = confidential_client("orders", &[grant_type::TOKEN_EXCHANGE], &["billing"]);
+        let subject = subject_token(&user, &config, 3600, &["read"]);
+
+        let res = token_exchange_grant(&state, &gateway, exchange(&subject, "billing", None), None);
+        assert!(matches!(
+            res.await,
+            Err(ApiError::OAuth(OAuthError::InvalidTarget(aud))) if aud == "billing"
+        ));
+
+        let public = Client {
+            secret_hash: None,
+            ..gateway.clone()
+        };
+        let res = token_exchange_grant(&state, &public, exchange(&subject, "orders", None), None);
+        assert!(matches!(
+            res.await,
+            Err(ApiError::OAuth(OAuthError::UnauthorizedClient))
+        ));
+
+        // Scopes are narrowed to the subject token, not widened to those of the client.
+        let res = token_exchange_grant(
+            &state,
+            &gateway,
+            exchange(&subject, "orders", Some("read write")),
+            None,
+        );
+        assert!(matches!(
+            res.await,
+            Err(ApiError::OAuth(OAuthError::InvalidScope(s))) if s == "write"
+        ));
+
+        let res = token_exchange_grant(&state, &gateway, exchange(&subject, "orders", None), None)
+            .await
+            .unwrap();
+        assert_eq!(res.scope.as_deref(), Some("read"));
+        assert_eq!(res.issued_token_type, Some(token_type::ACCESS_TOKEN));
+        // The lifetime is capped at the access token expiration.
+        assert!(res.expires_in <= 300);
+
+        let orders_token = res.access_token;
+        let claims = decode(&orders_token, &config);
+        assert_eq!(claims.sub, user.id);
+        assert_eq!(claims.aud, "orders");
+        assert_eq!(claims.client_id.as_deref(), Some("gateway"));
+        assert_eq!(
+            claims.act,
+            Some(Actor {
+                sub: "gateway".to_owned(),
+                act: None,
+            })
+        );
+
+        // Only the audience of a token can exchange it further, chaining the actors.
+        let res = token_exchange_grant(
+            &state,
+            &gateway,
+            exchange(&orders_token, "orders", None),
+            None,
+        );
+        assert!(matches!(
+            res.await,
+            Err(ApiError::OAuth(OAuthError::InvalidGrant(_)))
+        ));
+
+        let chained = token_exchange_grant(
+            &state,
+            &orders,
+            exchange(&orders_token, "billing", None),
+            None,
+        )
+        .await
+        .unwrap();
+        let claims = decode(&chained.access_token, &config);
+        assert_eq!(claims.aud, "billing");
+        assert_eq!(
+            claims.act,
+            Some(Actor {
+                sub: "orders".to_owned(),
+                act: Some(Box::new(Actor {
+                    sub: "gateway".to_owned(),
+                    act: None,
+                })),
+            })
+        );
+
+        // The lifetime is also capped at the expiration of the subject token.
+        let short = subject_token(&user, &config, 60, &["read"]);
+        let res = token_exchange_grant(&state, &gateway, exchange(&short, "orders", None), None)
+            .await
+            .unwrap();
+        assert!(res.expires_in <= 60);
+        assert_eq!(
+            decode(&res.access_token, &config).exp,
+            decode(&short, &config).exp
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires Redis"]
+    async fn introspect_and_revoke() {
+        let state = test_utils::state(Config::default()).await;
+        let user = state
+            .db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]))
+            .await
+            .unwrap();
+        let gateway = confidential_client("gateway", &[], &[]);
+        let other = confidential_client("other", &[], &[]);
+        let public = Client::new("spa", None, &allowed(), &[], &[]);
+        let (access_token, refresh_token, _) = services::auth::create_session(
+            &state,
+            &user,
+            &TokenContext::for_client("gateway", "read"),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            introspect(&state, &public, Some(&access_token), None).await,
+            Err(ApiError::OAuth(OAuthError::UnauthorizedClient))
+        ));
+
+        let res = introspect(&state, &gateway, Some(&access_token), None)
+            .await
+            .unwrap();
+        assert!(res.active);
+        assert_eq!(res.token_type.as_deref(), Some("access_token"));
+        assert_eq!(res.client_id.as_deref(), Some("gateway"));
+        assert_eq!(res.username.as_deref(), Some("alice"));
+        assert_eq!(res.scope.as_deref(), Some("read"));
+
+        let res = introspect(&state, &other, Some(&refresh_token), Some("refresh_token"))
+            .await
+            .unwrap();
+        assert!(res.active);
+        assert_eq!(res.token_type.as_deref(), Some("refresh_token"));
+
+        assert!(
+            !introspect(&state, &gateway, Some("invalid"), None)
+                .await
+                .unwrap()
+                .active
+        );
+
+        // Tokens can only be revoked by the client they were issued to.
+        assert!(matches!(
+            revoke(&state, &other, Some(&access_token), None).await,
+            Err(ApiError::OAuth(OAuthError::InvalidRequest(_)))
+        ));
+        revoke(&state, &gateway, Some("invalid"), None)
+            .await
+            .unwrap();
+
+        for token in [&access_token, &refresh_token] {
+            revoke(&state, &gateway, Some(token), None).await.unwrap();
+            let res = introspect(&state, &gateway, Some(token), None)
+                .await
+                .unwrap();
+            assert!(!res.active);
+            assert_eq!(res.client_id, None);
+        }
+    }
+}
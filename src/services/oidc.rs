@@ -0,0 +1,332 @@
+use std::sync::Arc;
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header,
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    error::ApiError,
+    models::user::User,
+    services::{auth::AuthError, dpop, jwt::Claims, oauth::grant_type},
+    ApiState,
+};
+
+pub const SCOPE_OPENID: &str = "openid";
+pub const SCOPE_PROFILE: &str = "profile";
+pub const SCOPE_EMAIL: &str = "email";
+
+/// ES256 key used to sign ID tokens, and access tokens if `jwt_access_es256` is set. The public
+/// part is published in the JWKS document, so relying parties can verify the tokens without
+/// sharing a secret with flatline.
+pub struct SigningKey {
+    encoding_key: EncodingKey,
+    jwk: Jwk,
+}
+
+impl SigningKey {
+    pub fn from_pkcs8(pkcs8: &[u8]) -> anyhow::Result<Self> {
+        let key_pair = EcdsaKeyPair::from_pkcs8(
+            &ECDSA_P256_SHA256_FIXED_SIGNING,
+            pkcs8,
+            &SystemRandom::new(),
+        )
+        .map_err(|e| anyhow::anyhow!("invalid P-256 signing key: {}", e))?;
+
+        // Uncompressed SEC1 point: 0x04 || x || y
+        let public_key = key_pair.public_key().as_ref();
+        let mut jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::ES256),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+                y: URL_SAFE_NO_PAD.encode(&public_key[33..65]),
+            }),
+        };
+        jwk.common.key_id = jwk_thumbprint(&jwk);
+
+        Ok(Self {
+            encoding_key: EncodingKey::from_ec_der(pkcs8),
+            jwk,
+        })
+    }
+
+    /// Generates a new key and returns it together with its base64 encoded PKCS#8 form.
+    pub fn generate() -> anyhow::Result<(Self, String)> {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+                .map_err(|_| anyhow::anyhow!("failed to generate P-256 signing key"))?;
+
+        Ok((
+            Self::from_pkcs8(pkcs8.as_ref())?,
+            STANDARD.encode(pkcs8.as_ref()),
+        ))
+    }
+
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        if config.oidc_signing_key.is_empty() {
+            tracing::warn!(
+                "oidc_signing_key not configured, using an ephemeral key - ID tokens will not verify after restart"
+            );
+            return Ok(Self::generate()?.0);
+        }
+
+        let pkcs8 = STANDARD.decode(config.oidc_signing_key.trim())?;
+        Self::from_pkcs8(&pkcs8)
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.jwk.common.key_id.as_deref()
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: vec![self.jwk.clone()],
+        }
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, ApiError> {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = self.kid().map(str::to_owned);
+
+        Ok(jsonwebtoken::encode(&header, claims, &self.encoding_key)?)
+    }
+
+    pub fn decoding_key(&self) -> Result<DecodingKey, ApiError> {
+        Ok(DecodingKey::from_jwk(&self.jwk)?)
+    }
+}
+
+/// Computes the RFC 7638 SHA-256 thumbprint of a public JWK.
+pub fn jwk_thumbprint(jwk: &Jwk) -> Option<String> {
+    // Required members only, in lexicographic order and without whitespace.
+    let canonical = match &jwk.algorithm {
+        AlgorithmParameters::EllipticCurve(params) => format!(
+            r#"{{"crv":{},"kty":"EC","x":"{}","y":"{}"}}"#,
+            serde_json::to_string(&params.curve).ok()?,
+            params.x,
+            params.y
+        ),
+        AlgorithmParameters::RSA(params) => {
+            format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, params.e, params.n)
+        }
+        AlgorithmParameters::OctetKeyPair(params) => format!(
+            r#"{{"crv":{},"kty":"OKP","x":"{}"}}"#,
+            serde_json::to_string(&params.curve).ok()?,
+            params.x
+        ),
+        AlgorithmParameters::OctetKey(_) => return None,
+    };
+
+    Some(URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes())))
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: Uuid,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub auth_time: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+pub fn has_scope(scope: &str, expected: &str) -> bool {
+    scope.split_whitespace().any(|s| s == expected)
+}
+
+pub fn issue_id_token(
+    state: &Arc<ApiState>,
+    user_id: Uuid,
+    client_id: &str,
+    auth_time: i64,
+    nonce: Option<String>,
+) -> Result<String, ApiError> {
+    let now = chrono::Utc::now();
+    let claims = IdTokenClaims {
+        iss: state.config.issuer(),
+        sub: user_id,
+        aud: client_id.to_owned(),
+        exp: now
+            .checked_add_signed(chrono::Duration::seconds(
+                state.config.jwt_access_expiration,
+            ))
+            .unwrap()
+            .timestamp(),
+        iat: now.timestamp(),
+        auth_time,
+        nonce,
+    };
+
+    state.signing_key.sign(&claims)
+}
+
+pub fn discovery_document(config: &Config) -> serde_json::Value {
+    let issuer = config.issuer();
+
+    serde_json::json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/oauth/authorize", issuer),
+        "token_endpoint": format!("{}/oauth/token", issuer),
+        "device_authorization_endpoint": format!("{}/oauth/device_authorization", issuer),
+        "introspection_endpoint": format!("{}/oauth/introspect", issuer),
+        "revocation_endpoint": format!("{}/oauth/revoke", issuer),
+        "userinfo_endpoint": format!("{}/userinfo", issuer),
+        "jwks_uri": format!("{}/.well-known/jwks.json", issuer),
+        "scopes_supported": [SCOPE_OPENID, SCOPE_PROFILE, SCOPE_EMAIL],
+        "response_types_supported": ["code"],
+        "response_modes_supported": ["query"],
+        "grant_types_supported": grant_type::SUPPORTED,
+        "dpop_signing_alg_values_supported": dpop::SUPPORTED_ALGORITHMS,
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["ES256"],
+        "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+        "code_challenge_methods_supported": ["S256"],
+        "claims_supported": [
+            "iss", "sub", "aud", "exp", "iat", "auth_time", "nonce",
+            "preferred_username", "updated_at", "email", "email_verified",
+        ],
+    })
+}
+
+/// Builds the UserInfo response, releasing only the claims covered by the granted scopes.
+pub fn user_info(user: &User, scope: &str) -> serde_json::Value {
+    let mut info = serde_json::Map::new();
+    info.insert("sub".to_owned(), serde_json::json!(user.id));
+
+    if has_scope(scope, SCOPE_PROFILE) {
+        info.insert(
+            "preferred_username".to_owned(),
+            serde_json::json!(user.username),
+        );
+        info.insert(
+            "updated_at".to_owned(),
+            serde_json::json!(user.updated_at.timestamp()),
+        );
+    }
+
+    if has_scope(scope, SCOPE_EMAIL) {
+        if let Some(email) = &user.email {
+            info.insert("email".to_owned(), serde_json::json!(email));
+            info.insert(
+                "email_verified".to_owned(),
+                serde_json::json!(user.email_verified),
+            );
+        }
+    }
+
+    serde_json::Value::Object(info)
+}
+
+pub async fn find_user_info(
+    state: &Arc<ApiState>,
+    claims: &Claims,
+) -> Result<serde_json::Value, ApiError> {
+    if !claims.has_scope(SCOPE_OPENID) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let user = state
+        .db
+        .users()
+        .find_by_id(claims.sub)
+        .await?
+        .ok_or(AuthError::TokenInvalid)?;
+
+    Ok(user_info(&user, &claims.scope_string().unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{DecodingKey, Validation};
+
+    use super::*;
+    use crate::models::user::Role;
+
+    #[test]
+    fn jwk_thumbprint_rfc7638() {
+        // Example from RFC 7638, section 3.1
+        let jwk: Jwk = serde_json::from_value(serde_json::json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            jwk_thumbprint(&jwk).as_deref(),
+            Some("NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs")
+        );
+    }
+
+    #[test]
+    fn signed_id_token_verifies_with_jwks() {
+        let (key, encoded) = SigningKey::generate().unwrap();
+        let claims = IdTokenClaims {
+            iss: "https://flatline.test".to_owned(),
+            sub: Uuid::new_v4(),
+            aud: "test_client".to_owned(),
+            exp: chrono::Utc::now().timestamp() + 60,
+            iat: chrono::Utc::now().timestamp(),
+            auth_time: chrono::Utc::now().timestamp(),
+            nonce: Some("n-0S6_WzA2Mj".to_owned()),
+        };
+
+        let token = key.sign(&claims).unwrap();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        let jwks = key.jwks();
+        let jwk = jwks.find(header.kid.as_deref().unwrap()).unwrap();
+
+        let mut validation = Validation::new(Algorithm::ES256);
+        validation.set_audience(&["test_client"]);
+        validation.set_issuer(&["https://flatline.test"]);
+        let decoded = jsonwebtoken::decode::<IdTokenClaims>(
+            &token,
+            &DecodingKey::from_jwk(jwk).unwrap(),
+            &validation,
+        )
+        .unwrap();
+        assert_eq!(decoded.claims, claims);
+
+        let restored = SigningKey::from_pkcs8(&STANDARD.decode(encoded).unwrap()).unwrap();
+        assert_eq!(restored.kid(), key.kid());
+    }
+
+    #[test]
+    fn user_info_respects_scopes() {
+        let user = User::new("test_user", "test_hash", &[Role::User])
+            .with_email("test_user@flatline.test");
+
+        let info = user_info(&user, "openid");
+        assert_eq!(info.as_object().unwrap().len(), 1);
+
+        let info = user_info(&user, "openid profile email");
+        assert_eq!(info["preferred_username"], "test_user");
+        assert_eq!(info["email"], "test_user@flatline.test");
+        assert_eq!(info["email_verified"], false);
+    }
+}
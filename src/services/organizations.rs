@@ -0,0 +1,731 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    error::ApiError,
+    models::{
+        organization::{MemberDto, Membership, OrgInvitation, OrgRole, Organization},
+        user::{AuthSource, Role, User},
+    },
+    services::{
+        self,
+        auth::{generate_secret, hash_string, verify_hash, AuthError},
+        jwt::Claims,
+    },
+    ApiState,
+};
+
+const SLUG_MAX_LEN: usize = 64;
+
+/// Account created within an organization, by an org admin or by accepting an invitation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewMember {
+    pub username: String,
+    pub password: String,
+    pub email: Option<String>,
+    pub role: OrgRole,
+}
+
+fn org_not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("organization ({}) not found", id))
+}
+
+fn member_not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("member ({}) not found", id))
+}
+
+pub fn hash_invitation_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Slugs are used when logging in, so they are kept to lowercase letters, digits and dashes.
+pub fn validate_slug(slug: &str) -> Result<(), ApiError> {
+    let valid = !slug.is_empty()
+        && slug.len() <= SLUG_MAX_LEN
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && slug
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+
+    if !valid {
+        return Err(ApiError::BadRequest(format!(
+            "organization slug ({}) is invalid",
+            slug
+        )));
+    }
+
+    Ok(())
+}
+
+/// Platform admins manage every organization. Everyone else has to be logged in to
+/// the organization and hold at least the required role in it.
+pub async fn authorize(
+    db: &dyn Database,
+    claims: &Claims,
+    org_id: Uuid,
+    required: OrgRole,
+) -> Result<(), ApiError> {
+    if claims.has_role(Role::Admin) && claims.org_id.is_none() {
+        return Ok(());
+    }
+
+    if claims.org_id != Some(org_id) {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    let membership = db
+        .memberships()
+        .find(org_id, claims.sub)
+        .await?
+        .ok_or(AuthError::Forbidden)?;
+
+    if required == OrgRole::Admin && membership.role != OrgRole::Admin {
+        return Err(AuthError::Forbidden.into());
+    }
+
+    Ok(())
+}
+
+pub async fn create_organization(
+    db: &dyn Database,
+    slug: &str,
+    name: &str,
+    owner_id: Option<Uuid>,
+) -> Result<Organization, ApiError> {
+    validate_slug(slug)?;
+    if db.organizations().find_by_slug(slug).await?.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "organization slug ({}) already taken",
+            slug
+        )));
+    }
+
+    let owner = match owner_id {
+        Some(id) => Some(
+            db.users()
+                .find_by_id(id)
+                .await?
+                .ok_or_else(|| ApiError::NotFound(format!("user ({}) not found", id)))?,
+        ),
+        None => None,
+    };
+    if owner.as_ref().is_some_and(|owner| owner.org_id.is_some()) {
+        return Err(ApiError::BadRequest(
+            "owner has to be a user of the global namespace".to_string(),
+        ));
+    }
+
+    let organization = db
+        .organizations()
+        .create(Organization::new(slug, name.trim()))
+        .await?;
+    if let Some(owner) = owner {
+        db.memberships()
+            .upsert(Membership::new(organization.id, owner.id, OrgRole::Admin))
+            .await?;
+    }
+
+    Ok(organization)
+}
+
+/// Organizations the caller is a member of, or all of them for platform admins.
+pub async fn list_organizations(
+    db: &dyn Database,
+    claims: &Claims,
+) -> Result<Vec<Organization>, ApiError> {
+    if claims.has_role(Role::Admin) && claims.org_id.is_none() {
+        return db.organizations().find_all().await;
+    }
+
+    db.organizations().find_by_member(claims.sub).await
+}
+
+pub async fn find_organization(db: &dyn Database, id: Uuid) -> Result<Organization, ApiError> {
+    db.organizations()
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| org_not_found(id))
+}
+
+pub async fn list_members(db: &dyn Database, org_id: Uuid) -> Result<Vec<MemberDto>, ApiError> {
+    let mut members = Vec::new();
+    for membership in db.memberships().find_by_org_id(org_id).await? {
+        // Memberships of soft deleted users are kept until the users are purged.
+        if let Some(user) = db.users().find_by_id(membership.user_id).await? {
+            members.push(MemberDto::new(&user, &membership));
+        }
+    }
+
+    Ok(members)
+}
+
+pub async fn find_member(
+    db: &dyn Database,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<(User, Membership), ApiError> {
+    let membership = db
+        .memberships()
+        .find(org_id, user_id)
+        .await?
+        .ok_or_else(|| member_not_found(user_id))?;
+    let user = db
+        .users()
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| member_not_found(user_id))?;
+
+    Ok((user, membership))
+}
+
+/// Creates a user in the namespace of the organization. Organization users never get
+/// global roles, their permissions come from the role within the organization.
+pub async fn create_member(
+    db: &dyn Database,
+    org_id: Uuid,
+    member: NewMember,
+) -> Result<(User, Membership), ApiError> {
+    if db
+        .users()
+        .find_by_org_username(org_id, &member.username)
+        .await?
+        .is_some()
+    {
+        return Err(AuthError::UsernameAlreadyTaken.into());
+    }
+
+    let mut user = User::new(
+        &member.username,
+        &hash_string(&member.password),
+        &[Role::User],
+    )
+    .with_org(org_id);
+    if let Some(email) = member.email.as_deref() {
+        if !email.contains('@') {
+            return Err(ApiError::BadRequest(format!(
+                "email ({}) is invalid",
+                email
+            )));
+        }
+        user = user.with_email(email);
+    }
+
+    let user = db.users().create(user).await?;
+    let membership = db
+        .memberships()
+        .upsert(Membership::new(org_id, user.id, member.role))
+        .await?;
+
+    Ok((user, membership))
+}
+
+async fn ensure_other_admin(
+    db: &dyn Database,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), ApiError> {
+    let has_other_admin = db
+        .memberships()
+        .find_by_org_id(org_id)
+        .await?
+        .iter()
+        .any(|m| m.role == OrgRole::Admin && m.user_id != user_id);
+
+    if !has_other_admin {
+        return Err(ApiError::BadRequest(
+            "organization needs at least one admin".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Changes the role of a member. Only platform admins can add existing users of the global
+/// namespace as new members, org admins add members through invitations.
+pub async fn set_member_role(
+    db: &dyn Database,
+    claims: &Claims,
+    org_id: Uuid,
+    user_id: Uuid,
+    role: OrgRole,
+) -> Result<(User, Membership), ApiError> {
+    let user = db
+        .users()
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| member_not_found(user_id))?;
+
+    let membership = match db.memberships().find(org_id, user_id).await? {
+        Some(membership) => {
+            if membership.role == OrgRole::Admin && role != OrgRole::Admin {
+                ensure_other_admin(db, org_id, user_id).await?;
+            }
+            Membership { role, ..membership }
+        }
+        None if claims.has_role(Role::Admin) && claims.org_id.is_none() => {
+            if user.org_id.is_some_and(|id| id != org_id) {
+                return Err(ApiError::BadRequest(
+                    "users of another organization cannot become members".to_string(),
+                ));
+            }
+            Membership::new(org_id, user_id, role)
+        }
+        None => return Err(member_not_found(user_id)),
+    };
+
+    let membership = db.memberships().upsert(membership).await?;
+    Ok((user, membership))
+}
+
+/// Removes the member. Users created within the organization are deleted along with
+/// their membership, since they cannot log in anywhere else.
+pub async fn remove_member(
+    state: &Arc<ApiState>,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<Membership, ApiError> {
+    let (user, membership) = find_member(state.db.as_ref(), org_id, user_id).await?;
+    if membership.role == OrgRole::Admin {
+        ensure_other_admin(state.db.as_ref(), org_id, user_id).await?;
+    }
+
+    state
+        .db
+        .memberships()
+        .delete(org_id, user_id)
+        .await?
+        .ok_or_else(|| member_not_found(user_id))?;
+
+    if user.org_id == Some(org_id) {
+        services::users::delete_user(state, user.id).await?;
+    }
+
+    Ok(membership)
+}
+
+/// Creates an invitation and returns it together with the token, which is not stored.
+pub async fn create_invitation(
+    db: &dyn Database,
+    org_id: Uuid,
+    invited_by: Uuid,
+    email: Option<&str>,
+    role: OrgRole,
+    expires_in: i64,
+) -> Result<(OrgInvitation, String), ApiError> {
+    if email.is_some_and(|email| !email.contains('@')) {
+        return Err(ApiError::BadRequest(format!(
+            "email ({}) is invalid",
+            email.unwrap_or_default()
+        )));
+    }
+
+    let token = generate_secret();
+    let now = chrono::Utc::now();
+    let invitation = db
+        .org_invitations()
+        .create(OrgInvitation {
+            id: Uuid::new_v4(),
+            org_id,
+            email: email.map(str::to_owned),
+            role,
+            token_hash: hash_invitation_token(&token),
+            invited_by: Some(invited_by),
+            expires_at: now + chrono::Duration::seconds(expires_in),
+            created_at: now,
+        })
+        .await?;
+
+    Ok((invitation, token))
+}
+
+pub async fn revoke_invitation(
+    db: &dyn Database,
+    org_id: Uuid,
+    id: Uuid,
+) -> Result<OrgInvitation, ApiError> {
+    let not_found = || ApiError::NotFound(format!("invitation ({}) not found", id));
+
+    let invitation = db
+        .org_invitations()
+        .find_by_org_id(org_id)
+        .await?
+        .into_iter()
+        .find(|invitation| invitation.id == id)
+        .ok_or_else(not_found)?;
+
+    db.org_invitations()
+        .delete_by_id(invitation.id)
+        .await?
+        .ok_or_else(not_found)
+}
+
+/// Creates the invited user in the namespace of the organization. Every invitation
+/// can be accepted once, the email of the invitation takes precedence over the given one.
+pub async fn accept_invitation(
+    db: &dyn Database,
+    token: &str,
+    username: &str,
+    password: &str,
+    email: Option<&str>,
+) -> Result<(Organization, User, Membership), ApiError> {
+    let invalid = || ApiError::BadRequest("invitation is invalid or expired".to_string());
+
+    let invitation = db
+        .org_invitations()
+        .find_by_token_hash(&hash_invitation_token(token))
+        .await?
+        .ok_or_else(invalid)?;
+    if invitation.is_expired() {
+        db.org_invitations().delete_by_id(invitation.id).await?;
+        return Err(invalid());
+    }
+
+    let organization = find_organization(db, invitation.org_id).await?;
+    if db
+        .users()
+        .find_by_org_username(organization.id, username)
+        .await?
+        .is_some()
+    {
+        return Err(AuthError::UsernameAlreadyTaken.into());
+    }
+
+    // Deleting first makes sure that concurrent requests cannot use the same invitation.
+    db.org_invitations()
+        .delete_by_id(invitation.id)
+        .await?
+        .ok_or_else(invalid)?;
+
+    let (user, membership) = create_member(
+        db,
+        organization.id,
+        NewMember {
+            username: username.to_owned(),
+            password: password.to_owned(),
+            email: invitation.email.clone().or(email.map(str::to_owned)),
+            role: invitation.role,
+        },
+    )
+    .await?;
+
+    Ok((organization, user, membership))
+}
+
+/// Verifies the credentials of a login to the organization. Users of the organization
+/// take precedence over members from the global namespace with the same username.
+pub async fn verify_credentials(
+    state: &Arc<ApiState>,
+    slug: &str,
+    username: &str,
+    password: &str,
+) -> Result<(User, Organization), ApiError> {
+    let organization = state
+        .db
+        .organizations()
+        .find_by_slug(slug)
+        .await?
+        .ok_or(AuthError::InvalidCredentials)?;
+
+    let user = match verify_org_user(state.db.as_ref(), &organization, username, password).await? {
+        Some(user) => user,
+        None => {
+            let user = services::auth::verify_credentials(state, username, password).await?;
+            state
+                .db
+                .memberships()
+                .find(organization.id, user.id)
+                .await?
+                .ok_or(AuthError::Forbidden)?;
+            user
+        }
+    };
+
+    if user.is_disabled() {
+        return Err(AuthError::AccountDisabled.into());
+    }
+
+    Ok((user, organization))
+}
+
+/// Finds the user in the namespace of the organization and checks the password.
+/// `None` if there is no such user, so that members of the global namespace are tried next.
+pub async fn verify_org_user(
+    db: &dyn Database,
+    organization: &Organization,
+    username: &str,
+    password: &str,
+) -> Result<Option<User>, ApiError> {
+    let Some(user) = db
+        .users()
+        .find_by_org_username(organization.id, username)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    if user.auth_source != AuthSource::Local || !verify_hash(&user.password_hash, password) {
+        return Err(AuthError::InvalidCredentials.into());
+    }
+
+    Ok(Some(user))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, database::mock::MockDatabase, services::jwt::TokenType};
+
+    fn claims_for(user: &User, org_id: Option<Uuid>) -> Claims {
+        Claims {
+            org_id,
+            ..Claims::from_user(user, TokenType::Access, &Config::default(), 0)
+        }
+    }
+
+    #[test]
+    fn validate_org_slug() {
+        assert!(validate_slug("acme").is_ok());
+        assert!(validate_slug("acme-2").is_ok());
+        assert!(validate_slug("").is_err());
+        assert!(validate_slug("-acme").is_err());
+        assert!(validate_slug("Acme").is_err());
+        assert!(validate_slug("acme corp").is_err());
+        assert!(validate_slug(&"a".repeat(SLUG_MAX_LEN + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn usernames_are_unique_per_organization() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+
+        let global = db
+            .users()
+            .create(User::new("alice", "hash", &[Role::User]))
+            .await
+            .unwrap();
+        let acme = create_organization(db, "acme", "Acme", None).await.unwrap();
+        let globex = create_organization(db, "globex", "Globex", None)
+            .await
+            .unwrap();
+        assert!(create_organization(db, "acme", "Acme 2", None)
+            .await
+            .is_err());
+
+        let new_member = |username: &str| NewMember {
+            username: username.to_string(),
+            password: "password".to_string(),
+            email: None,
+            role: OrgRole::Member,
+        };
+        let (acme_alice, _) = create_member(db, acme.id, new_member("alice"))
+            .await
+            .unwrap();
+        let (globex_alice, _) = create_member(db, globex.id, new_member("alice"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            create_member(db, acme.id, new_member("alice")).await,
+            Err(ApiError::Auth(AuthError::UsernameAlreadyTaken))
+        ));
+
+        assert_ne!(acme_alice.id, globex_alice.id);
+        assert_eq!(acme_alice.roles, vec![Role::User]);
+        assert_eq!(
+            db.users()
+                .find_by_username("alice")
+                .await
+                .unwrap()
+                .unwrap()
+                .id,
+            global.id
+        );
+        assert_eq!(
+            db.users()
+                .find_by_org_username(globex.id, "alice")
+                .await
+                .unwrap()
+                .unwrap()
+                .id,
+            globex_alice.id
+        );
+
+        assert!(verify_org_user(db, &acme, "alice", "password")
+            .await
+            .unwrap()
+            .is_some_and(|user| user.id == acme_alice.id));
+        assert!(matches!(
+            verify_org_user(db, &acme, "alice", "wrong").await,
+            Err(ApiError::Auth(AuthError::InvalidCredentials))
+        ));
+        assert!(verify_org_user(db, &acme, "bob", "password")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn organization_access() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+
+        let admin = db
+            .users()
+            .create(User::new("root", "hash", &[Role::Admin]))
+            .await
+            .unwrap();
+        let owner = db
+            .users()
+            .create(User::new("owner", "hash", &[Role::User]))
+            .await
+            .unwrap();
+        let acme = create_organization(db, "acme", "Acme", Some(owner.id))
+            .await
+            .unwrap();
+        let globex = create_organization(db, "globex", "Globex", None)
+            .await
+            .unwrap();
+        let (member, _) = create_member(
+            db,
+            acme.id,
+            NewMember {
+                username: "member".to_string(),
+                password: "password".to_string(),
+                email: None,
+                role: OrgRole::Member,
+            },
+        )
+        .await
+        .unwrap();
+
+        let admin_claims = claims_for(&admin, None);
+        assert!(authorize(db, &admin_claims, globex.id, OrgRole::Admin)
+            .await
+            .is_ok());
+        assert_eq!(
+            list_organizations(db, &admin_claims).await.unwrap().len(),
+            2
+        );
+
+        // Org admins have to be logged in to the organization they manage.
+        assert!(authorize(
+            db,
+            &claims_for(&owner, Some(acme.id)),
+            acme.id,
+            OrgRole::Admin
+        )
+        .await
+        .is_ok());
+        assert!(
+            authorize(db, &claims_for(&owner, None), acme.id, OrgRole::Admin)
+                .await
+                .is_err()
+        );
+        assert!(authorize(
+            db,
+            &claims_for(&owner, Some(acme.id)),
+            globex.id,
+            OrgRole::Member
+        )
+        .await
+        .is_err());
+
+        let member_claims = claims_for(&member, Some(acme.id));
+        assert!(authorize(db, &member_claims, acme.id, OrgRole::Member)
+            .await
+            .is_ok());
+        assert!(authorize(db, &member_claims, acme.id, OrgRole::Admin)
+            .await
+            .is_err());
+        assert_eq!(
+            list_organizations(db, &member_claims).await.unwrap(),
+            vec![acme.clone()]
+        );
+        assert_eq!(list_members(db, acme.id).await.unwrap().len(), 2);
+
+        // Org admins change roles of members, but do not add other users.
+        let owner_claims = claims_for(&owner, Some(acme.id));
+        let (_, membership) =
+            set_member_role(db, &owner_claims, acme.id, member.id, OrgRole::Admin)
+                .await
+                .unwrap();
+        assert_eq!(membership.role, OrgRole::Admin);
+        assert!(matches!(
+            set_member_role(db, &owner_claims, acme.id, admin.id, OrgRole::Member).await,
+            Err(ApiError::NotFound(_))
+        ));
+        assert!(matches!(
+            set_member_role(db, &admin_claims, globex.id, member.id, OrgRole::Member).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        set_member_role(db, &admin_claims, globex.id, owner.id, OrgRole::Admin)
+            .await
+            .unwrap();
+
+        // The last admin keeps their role.
+        set_member_role(db, &owner_claims, acme.id, member.id, OrgRole::Member)
+            .await
+            .unwrap();
+        assert!(matches!(
+            set_member_role(db, &owner_claims, acme.id, owner.id, OrgRole::Member).await,
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn accept_org_invitation() {
+        let mock = MockDatabase::new();
+        let db = mock.as_ref();
+
+        let acme = create_organization(db, "acme", "Acme", None).await.unwrap();
+        let inviter = Uuid::new_v4();
+        let (invitation, token) = create_invitation(
+            db,
+            acme.id,
+            inviter,
+            Some("new@example.org"),
+            OrgRole::Admin,
+            3600,
+        )
+        .await
+        .unwrap();
+        assert_ne!(invitation.token_hash, token);
+
+        assert!(accept_invitation(db, "unknown", "new", "password", None)
+            .await
+            .is_err());
+
+        let (organization, user, membership) =
+            accept_invitation(db, &token, "new", "password", Some("other@example.org"))
+                .await
+                .unwrap();
+        assert_eq!(organization.id, acme.id);
+        assert_eq!(user.org_id, Some(acme.id));
+        assert_eq!(user.email.as_deref(), Some("new@example.org"));
+        assert_eq!(membership.role, OrgRole::Admin);
+
+        // Invitations are single use.
+        assert!(matches!(
+            accept_invitation(db, &token, "other", "password", None).await,
+            Err(ApiError::BadRequest(_))
+        ));
+
+        let (_, expired) = create_invitation(db, acme.id, inviter, None, OrgRole::Member, -1)
+            .await
+            .unwrap();
+        assert!(matches!(
+            accept_invitation(db, &expired, "expired", "password", None).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(db
+            .org_invitations()
+            .find_by_org_id(acme.id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
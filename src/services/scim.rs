@@ -0,0 +1,1209 @@
+use std::{collections::BTreeSet, str::FromStr, sync::Arc};
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    error::ApiError,
+    models::{
+        audit_log::{self, AuditLog, ClientInfo},
+        pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+        scim::{
+            schema, ScimEmail, ScimGroup, ScimListQuery, ScimListResponse, ScimMeta,
+            ScimPatchRequest, ScimReference, ScimUser, CONTENT_TYPE,
+        },
+        user::{Role, User, UserQuery},
+        webhook,
+    },
+    routes::auth::AuthPayload,
+    services::{
+        self,
+        auth::{generate_secret, hash_string, AuthError},
+    },
+    ApiState,
+};
+
+pub mod filter;
+
+use filter::{Filter, PatchPath};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScimError {
+    #[error("{0}")]
+    InvalidFilter(String),
+    #[error("{0}")]
+    InvalidPath(String),
+    #[error("{0}")]
+    InvalidValue(String),
+    #[error("{0}")]
+    InvalidSyntax(String),
+    #[error("{0}")]
+    Mutability(String),
+    #[error("{0}")]
+    Uniqueness(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("resource version does not match")]
+    PreconditionFailed,
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error(transparent)]
+    Api(#[from] ApiError),
+}
+
+impl ScimError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ScimError::InvalidFilter(_)
+            | ScimError::InvalidPath(_)
+            | ScimError::InvalidValue(_)
+            | ScimError::InvalidSyntax(_)
+            | ScimError::Mutability(_) => StatusCode::BAD_REQUEST,
+            ScimError::Uniqueness(_) => StatusCode::CONFLICT,
+            ScimError::NotFound(_) => StatusCode::NOT_FOUND,
+            ScimError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ScimError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ScimError::Api(ApiError::BadRequest(_)) => StatusCode::BAD_REQUEST,
+            ScimError::Api(ApiError::NotFound(_)) => StatusCode::NOT_FOUND,
+            ScimError::Api(ApiError::Auth(auth_err)) => auth_err.status_code(),
+            ScimError::Api(ApiError::OAuth(_)) => StatusCode::BAD_REQUEST,
+            ScimError::Api(ApiError::Internal(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Error type defined in RFC 7644 (section 3.12).
+    pub fn scim_type(&self) -> Option<&'static str> {
+        match self {
+            ScimError::InvalidFilter(_) => Some("invalidFilter"),
+            ScimError::InvalidPath(_) => Some("invalidPath"),
+            ScimError::InvalidValue(_) | ScimError::Api(ApiError::BadRequest(_)) => {
+                Some("invalidValue")
+            }
+            ScimError::InvalidSyntax(_) => Some("invalidSyntax"),
+            ScimError::Mutability(_) => Some("mutability"),
+            ScimError::Uniqueness(_)
+            | ScimError::Api(ApiError::Auth(AuthError::UsernameAlreadyTaken)) => Some("uniqueness"),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for ScimError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status_code();
+        let detail = match self {
+            ScimError::Api(ApiError::Internal(ref error)) => {
+                tracing::error!("{}", error);
+                "something went wrong".to_owned()
+            }
+            ScimError::Api(ApiError::Auth(ref auth_err)) => auth_err.to_string(),
+            ScimError::Api(ApiError::BadRequest(ref msg) | ApiError::NotFound(ref msg)) => {
+                msg.clone()
+            }
+            ref error => error.to_string(),
+        };
+
+        let mut body = serde_json::json!({
+            "schemas": [schema::ERROR],
+            "status": status.as_u16().to_string(),
+            "detail": detail,
+        });
+        if let Some(scim_type) = self.scim_type() {
+            body["scimType"] = Value::from(scim_type);
+        }
+
+        let mut res = (status, Json(body)).into_response();
+        res.headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+        if status == StatusCode::UNAUTHORIZED {
+            res.headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+
+        res
+    }
+}
+
+/// Authenticates the provisioning client with the configured SCIM bearer token.
+pub async fn scim_guard(
+    Extension(state): Extension<Arc<ApiState>>,
+    req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, ScimError> {
+    if state.config.scim_token.is_empty() {
+        return Err(ScimError::Unauthorized(
+            "SCIM provisioning is disabled".to_owned(),
+        ));
+    }
+
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| ScimError::Unauthorized("bearer token is required".to_owned()))?;
+
+    // Comparing the digests keeps the comparison time independent of the configured token.
+    if Sha256::digest(token.as_bytes()) != Sha256::digest(state.config.scim_token.as_bytes()) {
+        return Err(ScimError::Unauthorized(
+            "bearer token is invalid".to_owned(),
+        ));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Checks the `If-Match` precondition against the current version of the resource.
+pub fn check_version(if_match: Option<&str>, version: &str) -> Result<(), ScimError> {
+    let strip_weak = |etag: &str| etag.trim().trim_start_matches("W/").to_owned();
+    match if_match {
+        Some(expected)
+            if !expected
+                .split(',')
+                .map(strip_weak)
+                .any(|etag| etag == "*" || etag == strip_weak(version)) =>
+        {
+            Err(ScimError::PreconditionFailed)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn location(base_url: &str, resource_type: &str, id: &str) -> String {
+    format!("{}/scim/v2/{}/{}", base_url, resource_type, id)
+}
+
+pub fn user_version(user: &User) -> String {
+    format!("W/\"{}\"", user.updated_at.timestamp_micros())
+}
+
+/// Groups have no timestamps of their own, so the version is derived from the members.
+pub fn group_version(members: &[User]) -> String {
+    let mut ids: Vec<(Uuid, &str)> = members
+        .iter()
+        .map(|m| (m.id, m.username.as_str()))
+        .collect();
+    ids.sort();
+
+    let mut hasher = Sha256::new();
+    for (id, username) in ids {
+        hasher.update(format!("{}:{}\n", id, username));
+    }
+    let digest = hasher.finalize();
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+
+    format!("W/\"{}\"", hex)
+}
+
+pub fn user_resource(base_url: &str, user: &User) -> ScimUser {
+    let id = user.id.to_string();
+    ScimUser {
+        schemas: vec![schema::USER.to_owned()],
+        id: Some(id.clone()),
+        user_name: user.username.clone(),
+        password: None,
+        active: !user.is_disabled(),
+        emails: user
+            .email
+            .iter()
+            .map(|email| ScimEmail {
+                value: email.clone(),
+                primary: true,
+                kind: None,
+            })
+            .collect(),
+        groups: user
+            .roles
+            .iter()
+            .map(|role| ScimReference {
+                value: role.to_string(),
+                display: Some(role.to_string()),
+                reference: Some(location(base_url, "Groups", &role.to_string())),
+            })
+            .collect(),
+        meta: Some(ScimMeta {
+            resource_type: "User".to_owned(),
+            created: Some(user.created_at),
+            last_modified: Some(user.updated_at),
+            location: location(base_url, "Users", &id),
+            version: user_version(user),
+        }),
+    }
+}
+
+pub fn group_resource(base_url: &str, role: Role, members: &[User]) -> ScimGroup {
+    ScimGroup {
+        schemas: vec![schema::GROUP.to_owned()],
+        id: Some(role.to_string()),
+        display_name: role.to_string(),
+        members: members
+            .iter()
+            .map(|member| ScimReference {
+                value: member.id.to_string(),
+                display: Some(member.username.clone()),
+                reference: Some(location(base_url, "Users", &member.id.to_string())),
+            })
+            .collect(),
+        meta: Some(ScimMeta {
+            resource_type: "Group".to_owned(),
+            created: None,
+            last_modified: None,
+            location: location(base_url, "Groups", &role.to_string()),
+            version: group_version(members),
+        }),
+    }
+}
+
+/// Requested page of a list request, filled by pushing the resources in order. Only the
+/// matching resources within the page are kept, the others are just counted.
+struct ListWindow {
+    filter: Option<Filter>,
+    start_index: usize,
+    count: usize,
+    total_results: usize,
+    resources: Vec<Value>,
+}
+
+impl ListWindow {
+    fn new(query: &ScimListQuery) -> Result<Self, ScimError> {
+        Ok(Self {
+            filter: query.filter.as_deref().map(Filter::parse).transpose()?,
+            start_index: query.start_index.unwrap_or(1).max(1),
+            count: query.count.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE),
+            total_results: 0,
+            resources: Vec::new(),
+        })
+    }
+
+    fn push<T: Serialize>(&mut self, resource: &T) {
+        let resource = serde_json::to_value(resource).expect("resource should serialize");
+        if self.filter.as_ref().is_some_and(|f| !f.matches(&resource)) {
+            return;
+        }
+
+        self.total_results += 1;
+        if self.total_results >= self.start_index && !self.is_full() {
+            self.resources.push(resource);
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.resources.len() >= self.count
+    }
+
+    fn into_response(self) -> ScimListResponse<Value> {
+        ScimListResponse::new(self.resources, self.total_results, self.start_index)
+    }
+}
+
+/// Filters the resources and returns the requested page of them.
+pub fn list_resources<T: Serialize>(
+    resources: &[T],
+    query: &ScimListQuery,
+) -> Result<ScimListResponse<Value>, ScimError> {
+    let mut window = ListWindow::new(query)?;
+    for resource in resources {
+        window.push(resource);
+    }
+
+    Ok(window.into_response())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PatchOp {
+    Add,
+    Replace,
+    Remove,
+}
+
+impl PatchOp {
+    fn parse(op: &str) -> Result<Self, ScimError> {
+        match op.to_ascii_lowercase().as_str() {
+            "add" => Ok(Self::Add),
+            "replace" => Ok(Self::Replace),
+            "remove" => Ok(Self::Remove),
+            _ => Err(ScimError::InvalidSyntax(format!(
+                "patch operation ({}) is not supported",
+                op
+            ))),
+        }
+    }
+}
+
+/// Splits the operations into attribute paths and values. Operations without a path
+/// carry an object whose members are the attributes to add or replace.
+fn patch_targets(
+    patch: &ScimPatchRequest,
+) -> Result<Vec<(PatchOp, PatchPath, Option<Value>)>, ScimError> {
+    let mut targets = Vec::new();
+    for operation in &patch.operations {
+        let op = PatchOp::parse(&operation.op)?;
+        match (operation.path.as_deref(), &operation.value) {
+            (Some(path), value) => targets.push((op, PatchPath::parse(path)?, value.clone())),
+            (None, _) if op == PatchOp::Remove => {
+                return Err(ScimError::InvalidPath(
+                    "remove operations require a path".to_owned(),
+                ))
+            }
+            (None, Some(Value::Object(attrs))) => {
+                for (attr, value) in attrs {
+                    targets.push((op, PatchPath::parse(attr)?, Some(value.clone())));
+                }
+            }
+            (None, _) => {
+                return Err(ScimError::InvalidValue(
+                    "operations without a path require an object value".to_owned(),
+                ))
+            }
+        }
+    }
+
+    Ok(targets)
+}
+
+fn string_value(attr: &str, value: Option<&Value>) -> Result<String, ScimError> {
+    match value {
+        Some(Value::String(s)) => Ok(s.clone()),
+        _ => Err(ScimError::InvalidValue(format!(
+            "{} must be a string",
+            attr
+        ))),
+    }
+}
+
+/// Some identity providers send booleans as strings, e.g. `"False"`.
+fn bool_value(attr: &str, value: Option<&Value>) -> Result<bool, ScimError> {
+    match value {
+        Some(Value::Bool(b)) => Ok(*b),
+        Some(Value::String(s)) if s.eq_ignore_ascii_case("true") => Ok(true),
+        Some(Value::String(s)) if s.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(ScimError::InvalidValue(format!(
+            "{} must be a boolean",
+            attr
+        ))),
+    }
+}
+
+fn email_value(value: Option<&Value>) -> Result<Option<String>, ScimError> {
+    let invalid = || ScimError::InvalidValue("emails value is invalid".to_owned());
+    match value {
+        Some(Value::String(email)) => Ok(Some(email.clone())),
+        Some(Value::Object(_)) => {
+            let email: ScimEmail = serde_json::from_value(value.cloned().unwrap_or_default())
+                .map_err(|_| invalid())?;
+            Ok(Some(email.value))
+        }
+        Some(Value::Array(_)) => {
+            let emails: Vec<ScimEmail> = serde_json::from_value(value.cloned().unwrap_or_default())
+                .map_err(|_| invalid())?;
+            Ok(emails
+                .iter()
+                .find(|e| e.primary)
+                .or_else(|| emails.first())
+                .map(|e| e.value.clone()))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Changes of the attributes which are mapped onto the user model.
+/// `None` leaves the attribute unchanged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserChanges {
+    pub user_name: Option<String>,
+    pub password: Option<String>,
+    pub email: Option<Option<String>>,
+    pub active: Option<bool>,
+}
+
+impl UserChanges {
+    pub fn from_resource(resource: &ScimUser) -> Self {
+        Self {
+            user_name: Some(resource.user_name.clone()),
+            password: resource.password.clone(),
+            email: Some(resource.primary_email().map(str::to_owned)),
+            active: Some(resource.active),
+        }
+    }
+
+    /// Attributes without a counterpart in the user model are ignored.
+    pub fn from_patch(patch: &ScimPatchRequest) -> Result<Self, ScimError> {
+        let mut changes = Self::default();
+        for (op, path, value) in patch_targets(patch)? {
+            let value = value.as_ref();
+            if path.is("userName") || path.is("password") || path.is("active") {
+                if op == PatchOp::Remove {
+                    return Err(ScimError::Mutability(format!(
+                        "{} cannot be removed",
+                        path.attr
+                    )));
+                }
+
+                if path.is("userName") {
+                    changes.user_name = Some(string_value("userName", value)?);
+                } else if path.is("password") {
+                    changes.password = Some(string_value("password", value)?);
+                } else {
+                    changes.active = Some(bool_value("active", value)?);
+                }
+            } else if path.is("emails") {
+                changes.email = match op {
+                    PatchOp::Remove => Some(None),
+                    _ => Some(email_value(value)?),
+                };
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MemberChange {
+    Add(Vec<Uuid>),
+    Remove(Vec<Uuid>),
+    Replace(Vec<Uuid>),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupChanges {
+    pub display_name: Option<String>,
+    pub members: Vec<MemberChange>,
+}
+
+fn parse_member_id(id: &str) -> Result<Uuid, ScimError> {
+    Uuid::parse_str(id)
+        .map_err(|_| ScimError::InvalidValue(format!("member ({}) is not a valid user id", id)))
+}
+
+fn member_ids(value: Option<&Value>) -> Result<Vec<Uuid>, ScimError> {
+    let members: Vec<ScimReference> = match value {
+        Some(Value::Array(_)) => serde_json::from_value(value.cloned().unwrap_or_default()),
+        Some(Value::Object(_)) => {
+            serde_json::from_value(value.cloned().unwrap_or_default()).map(|m| vec![m])
+        }
+        _ => {
+            return Err(ScimError::InvalidValue(
+                "members value is invalid".to_owned(),
+            ))
+        }
+    }
+    .map_err(|_| ScimError::InvalidValue("members value is invalid".to_owned()))?;
+
+    members.iter().map(|m| parse_member_id(&m.value)).collect()
+}
+
+impl GroupChanges {
+    pub fn from_resource(resource: &ScimGroup) -> Result<Self, ScimError> {
+        let members = resource
+            .members
+            .iter()
+            .map(|m| parse_member_id(&m.value))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            display_name: Some(resource.display_name.clone()),
+            members: vec![MemberChange::Replace(members)],
+        })
+    }
+
+    pub fn from_patch(patch: &ScimPatchRequest) -> Result<Self, ScimError> {
+        let mut changes = Self::default();
+        for (op, path, value) in patch_targets(patch)? {
+            let value = value.as_ref();
+            if path.is("displayName") {
+                if op == PatchOp::Remove {
+                    return Err(ScimError::Mutability(
+                        "displayName cannot be removed".to_owned(),
+                    ));
+                }
+                changes.display_name = Some(string_value("displayName", value)?);
+            } else if path.is("members") {
+                let change = match (op, &path.filter, value) {
+                    (PatchOp::Remove, Some(filter), _) => {
+                        let ids = filter
+                            .eq_values("value")
+                            .ok_or_else(|| {
+                                ScimError::InvalidPath(
+                                    "members can only be filtered by value equality".to_owned(),
+                                )
+                            })?
+                            .into_iter()
+                            .map(|id| parse_member_id(id.as_str().unwrap_or_default()))
+                            .collect::<Result<_, _>>()?;
+                        MemberChange::Remove(ids)
+                    }
+                    (_, Some(_), _) => {
+                        return Err(ScimError::InvalidPath(
+                            "member filters are only supported in remove operations".to_owned(),
+                        ))
+                    }
+                    (PatchOp::Remove, None, None) => MemberChange::Replace(Vec::new()),
+                    (PatchOp::Remove, None, value) => MemberChange::Remove(member_ids(value)?),
+                    (PatchOp::Add, None, value) => MemberChange::Add(member_ids(value)?),
+                    (PatchOp::Replace, None, value) => MemberChange::Replace(member_ids(value)?),
+                };
+                changes.members.push(change);
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+fn user_not_found(id: &str) -> ScimError {
+    ScimError::NotFound(format!("user ({}) not found", id))
+}
+
+fn group_not_found(id: &str) -> ScimError {
+    ScimError::NotFound(format!("group ({}) not found", id))
+}
+
+async fn find_user(state: &Arc<ApiState>, id: &str) -> Result<User, ScimError> {
+    let uuid = Uuid::parse_str(id).map_err(|_| user_not_found(id))?;
+    state
+        .db
+        .users()
+        .find_by_id(uuid)
+        .await?
+        .ok_or_else(|| user_not_found(id))
+}
+
+/// Records changes made through SCIM in the audit log, and notifies the webhook subscribers.
+async fn record_user_event(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    audit_event: &str,
+    webhook_event: &str,
+    user: &User,
+    mut metadata: Value,
+) {
+    metadata["source"] = Value::from("scim");
+    services::audit::record(
+        state,
+        AuditLog::new(audit_event, None, Some(user.id))
+            .with_client(client)
+            .with_metadata(metadata),
+    )
+    .await;
+    services::webhooks::publish_user_event(state, webhook_event, user).await;
+}
+
+pub async fn list_users(
+    db: &dyn Database,
+    base_url: &str,
+    query: &ScimListQuery,
+) -> Result<ScimListResponse<Value>, ScimError> {
+    let mut window = ListWindow::new(query)?;
+
+    // Without a filter the database counts the users, so reading stops after the requested
+    // page. Filters are evaluated on all users, a keyset page at a time.
+    let total_results = match window.filter {
+        Some(_) => None,
+        None => Some(db.users().count().await? as usize),
+    };
+
+    let mut page_query = UserQuery {
+        limit: Some(MAX_PAGE_SIZE),
+        ..UserQuery::default()
+    };
+    loop {
+        let page = db.users().find_page(&page_query).await?;
+        for user in &page.items {
+            window.push(&user_resource(base_url, user));
+        }
+
+        match page.next_cursor {
+            Some(cursor) if total_results.is_none() || !window.is_full() => {
+                page_query.cursor = Some(cursor)
+            }
+            _ => break,
+        }
+    }
+
+    if let Some(total_results) = total_results {
+        window.total_results = total_results;
+    }
+    Ok(window.into_response())
+}
+
+pub async fn get_user(state: &Arc<ApiState>, id: &str) -> Result<ScimUser, ScimError> {
+    let user = find_user(state, id).await?;
+    Ok(user_resource(&state.config.issuer(), &user))
+}
+
+/// Users provisioned without a password get a random one, which nobody knows,
+/// so they cannot log in with a password until it is set.
+pub async fn create_user(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    resource: ScimUser,
+) -> Result<ScimUser, ScimError> {
+    let payload = AuthPayload {
+        username: resource.user_name.clone(),
+        password: resource.password.clone().unwrap_or_else(generate_secret),
+        email: resource.primary_email().map(str::to_owned),
+        organization: None,
+        invitation: None,
+    };
+    let mut user = services::users::create_user(state, payload, &[Role::User]).await?;
+
+    if !resource.active {
+        user = state
+            .db
+            .users()
+            .update(user.disable(None))
+            .await?
+            .ok_or_else(|| user_not_found(&resource.user_name))?;
+    }
+
+    record_user_event(
+        state,
+        client,
+        audit_log::event_type::REGISTER,
+        webhook::event_type::USER_REGISTERED,
+        &user,
+        serde_json::json!({}),
+    )
+    .await;
+
+    Ok(user_resource(&state.config.issuer(), &user))
+}
+
+async fn apply_user_changes(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    user: User,
+    changes: UserChanges,
+) -> Result<User, ScimError> {
+    let id = user.id;
+    let was_disabled = user.is_disabled();
+    let mut updated = user.clone();
+
+    if let Some(user_name) = changes.user_name {
+        if user_name.is_empty() {
+            return Err(ScimError::InvalidValue(
+                "userName cannot be empty".to_owned(),
+            ));
+        }
+        if user_name != user.username
+            && state
+                .db
+                .users()
+                .find_by_username(&user_name)
+                .await?
+                .is_some()
+        {
+            return Err(ScimError::Uniqueness(format!(
+                "username ({}) is already taken",
+                user_name
+            )));
+        }
+        updated.username = user_name;
+    }
+
+    if let Some(password) = changes.password.as_deref() {
+        updated.password_hash = hash_string(password);
+    }
+
+    if let Some(email) = changes.email {
+        if email.as_deref().is_some_and(|e| !e.contains('@')) {
+            return Err(ScimError::InvalidValue(format!(
+                "email ({}) is invalid",
+                email.unwrap_or_default()
+            )));
+        }
+        if email != updated.email {
+            updated.email = email;
+            updated.email_verified = false;
+        }
+    }
+
+    updated = match changes.active {
+        Some(false) if !was_disabled => updated.disable(None),
+        Some(true) if was_disabled => updated.enable(),
+        _ => updated,
+    };
+
+    let updated = state
+        .db
+        .users()
+        .update(updated)
+        .await?
+        .ok_or_else(|| user_not_found(&id.to_string()))?;
+
+    if changes.password.is_some() || (updated.is_disabled() && !was_disabled) {
+        services::users::force_logout(state, id).await?;
+    }
+
+    if updated.is_disabled() != was_disabled {
+        let (audit_event, webhook_event) = if updated.is_disabled() {
+            (
+                audit_log::event_type::USER_DISABLED,
+                webhook::event_type::USER_DISABLED,
+            )
+        } else {
+            (
+                audit_log::event_type::USER_ENABLED,
+                webhook::event_type::USER_ENABLED,
+            )
+        };
+        record_user_event(
+            state,
+            client,
+            audit_event,
+            webhook_event,
+            &updated,
+            serde_json::json!({}),
+        )
+        .await;
+    }
+
+    Ok(updated)
+}
+
+pub async fn replace_user(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    id: &str,
+    if_match: Option<&str>,
+    resource: ScimUser,
+) -> Result<ScimUser, ScimError> {
+    let user = find_user(state, id).await?;
+    check_version(if_match, &user_version(&user))?;
+
+    let user =
+        apply_user_changes(state, client, user, UserChanges::from_resource(&resource)).await?;
+    Ok(user_resource(&state.config.issuer(), &user))
+}
+
+pub async fn patch_user(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    id: &str,
+    if_match: Option<&str>,
+    patch: ScimPatchRequest,
+) -> Result<ScimUser, ScimError> {
+    let changes = UserChanges::from_patch(&patch)?;
+    let user = find_user(state, id).await?;
+    check_version(if_match, &user_version(&user))?;
+
+    let user = apply_user_changes(state, client, user, changes).await?;
+    Ok(user_resource(&state.config.issuer(), &user))
+}
+
+pub async fn delete_user(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    id: &str,
+    if_match: Option<&str>,
+) -> Result<(), ScimError> {
+    let user = find_user(state, id).await?;
+    check_version(if_match, &user_version(&user))?;
+
+    let user = services::users::delete_user(state, user.id).await?;
+    record_user_event(
+        state,
+        client,
+        audit_log::event_type::USER_DELETED,
+        webhook::event_type::USER_DELETED,
+        &user,
+        serde_json::json!({ "username": user.username }),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Groups are the fixed set of roles, identified by the role name.
+fn parse_group_id(id: &str) -> Result<Role, ScimError> {
+    Role::from_str(&id.to_ascii_lowercase()).map_err(|_| group_not_found(id))
+}
+
+async fn find_members(state: &Arc<ApiState>, role: Role) -> Result<Vec<User>, ScimError> {
+    let mut members = state.db.users().find_by_role(role).await?;
+    members.sort_by(|a, b| a.username.cmp(&b.username));
+
+    Ok(members)
+}
+
+pub async fn list_groups(
+    state: &Arc<ApiState>,
+    query: &ScimListQuery,
+) -> Result<ScimListResponse<Value>, ScimError> {
+    let base_url = state.config.issuer();
+    let mut resources = Vec::new();
+    for role in [Role::User, Role::Admin] {
+        let members = find_members(state, role).await?;
+        resources.push(group_resource(&base_url, role, &members));
+    }
+
+    list_resources(&resources, query)
+}
+
+pub async fn get_group(state: &Arc<ApiState>, id: &str) -> Result<ScimGroup, ScimError> {
+    let role = parse_group_id(id)?;
+    let members = find_members(state, role).await?;
+
+    Ok(group_resource(&state.config.issuer(), role, &members))
+}
+
+/// Updates the roles of the users joining or leaving the group. All changes are validated
+/// before any of them is applied, so a rejected request leaves the memberships untouched.
+async fn apply_group_changes(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    role: Role,
+    changes: GroupChanges,
+) -> Result<Vec<User>, ScimError> {
+    if let Some(display_name) = changes.display_name.as_deref() {
+        if !display_name.eq_ignore_ascii_case(&role.to_string()) {
+            return Err(ScimError::Mutability(
+                "groups are mapped onto roles and cannot be renamed".to_owned(),
+            ));
+        }
+    }
+
+    let members = find_members(state, role).await?;
+    let current: BTreeSet<Uuid> = members.iter().map(|m| m.id).collect();
+    let mut target = current.clone();
+    for change in changes.members {
+        match change {
+            MemberChange::Add(ids) => target.extend(ids),
+            MemberChange::Remove(ids) => ids.iter().for_each(|id| {
+                target.remove(id);
+            }),
+            MemberChange::Replace(ids) => target = ids.into_iter().collect(),
+        }
+    }
+
+    let mut updates = Vec::new();
+    for id in target.difference(&current) {
+        let user = find_user(state, &id.to_string())
+            .await
+            .map_err(|_| ScimError::InvalidValue(format!("member ({}) not found", id)))?;
+        let mut roles = user.roles.clone();
+        roles.push(role);
+        updates.push((user, roles));
+    }
+    for user in members.into_iter().filter(|m| !target.contains(&m.id)) {
+        if services::groups::group_roles(state.db.as_ref(), user.id)
+            .await?
+            .contains(&role)
+        {
+            return Err(ScimError::Mutability(format!(
+                "member ({}) holds the role through a group and cannot be removed",
+                user.id
+            )));
+        }
+
+        let roles: Vec<Role> = user.roles.iter().copied().filter(|r| *r != role).collect();
+        if roles.is_empty() {
+            return Err(ScimError::Mutability(format!(
+                "member ({}) cannot be removed from its only group",
+                user.id
+            )));
+        }
+        updates.push((user, roles));
+    }
+
+    for (user, roles) in updates {
+        let user = services::users::set_roles(state, user.id, &roles).await?;
+        record_user_event(
+            state,
+            client,
+            audit_log::event_type::ROLES_CHANGED,
+            webhook::event_type::USER_ROLES_CHANGED,
+            &user,
+            serde_json::json!({ "roles": user.roles }),
+        )
+        .await;
+    }
+
+    find_members(state, role).await
+}
+
+/// Groups cannot be created, as they are mapped onto the roles. Creating a group with
+/// the name of a role adds the given members to the role instead.
+pub async fn create_group(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    resource: ScimGroup,
+) -> Result<ScimGroup, ScimError> {
+    let role = Role::from_str(&resource.display_name.to_ascii_lowercase()).map_err(|_| {
+        ScimError::InvalidValue(format!(
+            "groups are mapped onto roles, ({}) is not a role",
+            resource.display_name
+        ))
+    })?;
+
+    let mut changes = GroupChanges::from_resource(&resource)?;
+    changes.members = match changes.members.pop() {
+        Some(MemberChange::Replace(ids)) => vec![MemberChange::Add(ids)],
+        _ => Vec::new(),
+    };
+
+    let members = apply_group_changes(state, client, role, changes).await?;
+    Ok(group_resource(&state.config.issuer(), role, &members))
+}
+
+pub async fn replace_group(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    id: &str,
+    if_match: Option<&str>,
+    resource: ScimGroup,
+) -> Result<ScimGroup, ScimError> {
+    let role = parse_group_id(id)?;
+    let changes = GroupChanges::from_resource(&resource)?;
+    check_version(if_match, &group_version(&find_members(state, role).await?))?;
+
+    let members = apply_group_changes(state, client, role, changes).await?;
+    Ok(group_resource(&state.config.issuer(), role, &members))
+}
+
+pub async fn patch_group(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    id: &str,
+    if_match: Option<&str>,
+    patch: ScimPatchRequest,
+) -> Result<ScimGroup, ScimError> {
+    let role = parse_group_id(id)?;
+    let changes = GroupChanges::from_patch(&patch)?;
+    check_version(if_match, &group_version(&find_members(state, role).await?))?;
+
+    let members = apply_group_changes(state, client, role, changes).await?;
+    Ok(group_resource(&state.config.issuer(), role, &members))
+}
+
+/// Deleting a group removes the role from all of its members.
+pub async fn delete_group(
+    state: &Arc<ApiState>,
+    client: &ClientInfo,
+    id: &str,
+    if_match: Option<&str>,
+) -> Result<(), ScimError> {
+    let role = parse_group_id(id)?;
+    check_version(if_match, &group_version(&find_members(state, role).await?))?;
+
+    let changes = GroupChanges {
+        display_name: None,
+        members: vec![MemberChange::Replace(Vec::new())],
+    };
+    apply_group_changes(state, client, role, changes).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{config::Config, database::mock::MockDatabase};
+
+    fn patch(operations: Value) -> ScimPatchRequest {
+        serde_json::from_value(json!({
+            "schemas": [schema::PATCH_OP],
+            "Operations": operations,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn user_resource_roundtrip() {
+        let user = User::new("test_user", "test_password", &[Role::User, Role::Admin])
+            .with_email("test@example.com");
+        let resource = user_resource("http://127.0.0.1:8080", &user);
+
+        let json = serde_json::to_value(&resource).unwrap();
+        assert_eq!(json["userName"], "test_user");
+        assert_eq!(json["active"], true);
+        assert_eq!(json["emails"][0]["value"], "test@example.com");
+        assert_eq!(json["groups"][1]["value"], "admin");
+        assert_eq!(
+            json["meta"]["location"],
+            format!("http://127.0.0.1:8080/scim/v2/Users/{}", user.id)
+        );
+        assert!(json.get("password").is_none());
+
+        let changes = UserChanges::from_resource(&serde_json::from_value(json).unwrap());
+        assert_eq!(changes.user_name.as_deref(), Some("test_user"));
+        assert_eq!(changes.email, Some(Some("test@example.com".to_owned())));
+        assert_eq!(changes.active, Some(true));
+    }
+
+    #[test]
+    fn list_with_filter_and_pagination() {
+        let users: Vec<ScimUser> = ["alice", "bob", "carol", "dave"]
+            .iter()
+            .map(|name| user_resource("", &User::new(name, "test_password", &[Role::User])))
+            .collect();
+
+        let query = ScimListQuery {
+            filter: Some(r#"userName eq "Carol""#.to_owned()),
+            ..Default::default()
+        };
+        let res = list_resources(&users, &query).unwrap();
+        assert_eq!(res.total_results, 1);
+        assert_eq!(res.resources[0]["userName"], "carol");
+
+        let query = ScimListQuery {
+            start_index: Some(2),
+            count: Some(2),
+            ..Default::default()
+        };
+        let res = list_resources(&users, &query).unwrap();
+        assert_eq!(res.total_results, 4);
+        assert_eq!(res.items_per_page, 2);
+        assert_eq!(res.resources[0]["userName"], "bob");
+
+        let query = ScimListQuery {
+            filter: Some("userName eq".to_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            list_resources(&users, &query),
+            Err(ScimError::InvalidFilter(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn list_users_in_pages() {
+        let db = MockDatabase::new();
+        let base_url = Config::default().issuer();
+        for i in 0..MAX_PAGE_SIZE + 20 {
+            let user = User::new(&format!("user-{:03}", i), "hash", &[Role::User]);
+            db.users().create(user).await.unwrap();
+        }
+
+        let query = ScimListQuery {
+            start_index: Some(MAX_PAGE_SIZE),
+            count: Some(5),
+            ..Default::default()
+        };
+        let res = list_users(db.as_ref(), &base_url, &query).await.unwrap();
+        assert_eq!(res.total_results, MAX_PAGE_SIZE + 20);
+        assert_eq!(res.items_per_page, 5);
+
+        let query = ScimListQuery {
+            filter: Some(r#"userName ew "7""#.to_owned()),
+            start_index: Some(10),
+            ..Default::default()
+        };
+        let res = list_users(db.as_ref(), &base_url, &query).await.unwrap();
+        assert_eq!(res.total_results, 12);
+        assert_eq!(res.items_per_page, 3);
+    }
+
+    #[test]
+    fn user_changes_from_patch() {
+        let changes = UserChanges::from_patch(&patch(json!([
+            { "op": "Replace", "path": "active", "value": "False" },
+            { "op": "replace", "path": "emails[type eq \"work\"].value", "value": "new@example.com" },
+            { "op": "add", "path": "name.givenName", "value": "Test" },
+        ])))
+        .unwrap();
+        assert_eq!(
+            changes,
+            UserChanges {
+                email: Some(Some("new@example.com".to_owned())),
+                active: Some(false),
+                ..Default::default()
+            }
+        );
+
+        let changes = UserChanges::from_patch(&patch(json!([
+            { "op": "replace", "value": { "userName": "renamed_user", "active": true } },
+            { "op": "remove", "path": "emails" },
+        ])))
+        .unwrap();
+        assert_eq!(changes.user_name.as_deref(), Some("renamed_user"));
+        assert_eq!(changes.active, Some(true));
+        assert_eq!(changes.email, Some(None));
+
+        assert!(matches!(
+            UserChanges::from_patch(&patch(json!([{ "op": "remove", "path": "userName" }]))),
+            Err(ScimError::Mutability(_))
+        ));
+        assert!(matches!(
+            UserChanges::from_patch(&patch(json!([{ "op": "move", "path": "active" }]))),
+            Err(ScimError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            UserChanges::from_patch(&patch(
+                json!([{ "op": "replace", "path": "active", "value": 1 }])
+            )),
+            Err(ScimError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn group_changes_from_patch() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let changes = GroupChanges::from_patch(&patch(json!([
+            { "op": "add", "path": "members", "value": [{ "value": a }, { "value": b }] },
+            { "op": "remove", "path": format!("members[value eq \"{}\"]", a) },
+            { "op": "remove", "path": "members", "value": [{ "value": b }] },
+            { "op": "replace", "value": { "displayName": "admin" } },
+            { "op": "remove", "path": "members" },
+        ])))
+        .unwrap();
+        assert_eq!(
+            changes,
+            GroupChanges {
+                display_name: Some("admin".to_owned()),
+                members: vec![
+                    MemberChange::Add(vec![a, b]),
+                    MemberChange::Remove(vec![a]),
+                    MemberChange::Remove(vec![b]),
+                    MemberChange::Replace(Vec::new()),
+                ],
+            }
+        );
+
+        assert!(matches!(
+            GroupChanges::from_patch(&patch(
+                json!([{ "op": "add", "path": "members", "value": [{ "value": "x" }] }])
+            )),
+            Err(ScimError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            GroupChanges::from_patch(&patch(
+                json!([{ "op": "add", "path": "members[value eq \"x\"]" }])
+            )),
+            Err(ScimError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn check_resource_version() {
+        let user = User::new("test_user", "test_password", &[Role::User]);
+        let version = user_version(&user);
+
+        assert!(check_version(None, &version).is_ok());
+        assert!(check_version(Some("*"), &version).is_ok());
+        assert!(check_version(Some(&version), &version).is_ok());
+        assert!(check_version(Some(version.trim_start_matches("W/")), &version).is_ok());
+        assert!(matches!(
+            check_version(Some("W/\"0\""), &version),
+            Err(ScimError::PreconditionFailed)
+        ));
+
+        assert_ne!(
+            group_version(std::slice::from_ref(&user)),
+            group_version(&[user, User::new("other", "test_password", &[Role::User])])
+        );
+    }
+}
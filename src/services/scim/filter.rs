@@ -0,0 +1,619 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use super::ScimError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Co,
+    Sw,
+    Ew,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl CompareOp {
+    fn parse(op: &str) -> Option<Self> {
+        let op = match op.to_ascii_lowercase().as_str() {
+            "eq" => Self::Eq,
+            "ne" => Self::Ne,
+            "co" => Self::Co,
+            "sw" => Self::Sw,
+            "ew" => Self::Ew,
+            "gt" => Self::Gt,
+            "ge" => Self::Ge,
+            "lt" => Self::Lt,
+            "le" => Self::Le,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    fn matches_ordering(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Ne => ordering.is_ne(),
+            Self::Gt => ordering.is_gt(),
+            Self::Ge => ordering.is_ge(),
+            Self::Lt => ordering.is_lt(),
+            Self::Le => ordering.is_le(),
+            Self::Co | Self::Sw | Self::Ew => false,
+        }
+    }
+}
+
+/// Attribute with an optional sub-attribute, e.g. `userName` or `emails.value`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttrPath {
+    pub attr: String,
+    pub sub_attr: Option<String>,
+}
+
+impl AttrPath {
+    /// Parses the path, dropping the schema URN prefix of fully qualified attributes.
+    fn parse(path: &str) -> Self {
+        let path = if path.to_ascii_lowercase().starts_with("urn:") {
+            path.rsplit(':').next().unwrap_or(path)
+        } else {
+            path
+        };
+
+        match path.split_once('.') {
+            Some((attr, sub_attr)) => Self {
+                attr: attr.to_owned(),
+                sub_attr: Some(sub_attr.to_owned()),
+            },
+            None => Self {
+                attr: path.to_owned(),
+                sub_attr: None,
+            },
+        }
+    }
+
+    /// Values of the attribute in the resource. Multi-valued attributes are flattened,
+    /// and without a sub-attribute their elements are compared by the `value` sub-attribute.
+    fn values<'a>(&self, resource: &'a Value) -> Vec<&'a Value> {
+        let Some(value) = get_attr(resource, &self.attr) else {
+            return Vec::new();
+        };
+
+        let items: Vec<&Value> = match value {
+            Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        };
+
+        match &self.sub_attr {
+            Some(sub_attr) => items
+                .into_iter()
+                .filter_map(|item| get_attr(item, sub_attr))
+                .collect(),
+            None => items
+                .into_iter()
+                .map(|item| get_attr(item, "value").unwrap_or(item))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Present(AttrPath),
+    Compare(AttrPath, CompareOp, Value),
+    /// Filter applied to the elements of a multi-valued attribute, e.g. `emails[type eq "work"]`.
+    ValuePath(String, Box<Filter>),
+}
+
+impl Filter {
+    pub fn parse(filter: &str) -> Result<Self, ScimError> {
+        let mut parser = Parser::new(filter).map_err(ScimError::InvalidFilter)?;
+        let parsed = parser.parse_or().map_err(ScimError::InvalidFilter)?;
+        parser.expect_end().map_err(ScimError::InvalidFilter)?;
+
+        Ok(parsed)
+    }
+
+    pub fn matches(&self, resource: &Value) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(resource) && right.matches(resource),
+            Filter::Or(left, right) => left.matches(resource) || right.matches(resource),
+            Filter::Not(filter) => !filter.matches(resource),
+            Filter::Present(path) => path.values(resource).into_iter().any(is_present),
+            Filter::Compare(path, op, expected) => {
+                let values = path.values(resource);
+                if values.is_empty() {
+                    // Missing attributes are treated as null.
+                    return match op {
+                        CompareOp::Eq => expected.is_null(),
+                        CompareOp::Ne => !expected.is_null(),
+                        _ => false,
+                    };
+                }
+
+                values
+                    .into_iter()
+                    .any(|actual| compare(actual, *op, expected))
+            }
+            Filter::ValuePath(attr, filter) => match get_attr(resource, attr) {
+                Some(Value::Array(items)) => items.iter().any(|item| filter.matches(item)),
+                Some(item) => filter.matches(item),
+                None => false,
+            },
+        }
+    }
+
+    /// Values compared for equality against the given attribute, if the filter consists of
+    /// nothing else, e.g. `value eq "a" or value eq "b"`.
+    pub fn eq_values(&self, attr: &str) -> Option<Vec<&Value>> {
+        match self {
+            Filter::Compare(path, CompareOp::Eq, value)
+                if path.attr.eq_ignore_ascii_case(attr) && path.sub_attr.is_none() =>
+            {
+                Some(vec![value])
+            }
+            Filter::Or(left, right) => {
+                let mut values = left.eq_values(attr)?;
+                values.extend(right.eq_values(attr)?);
+                Some(values)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Target of a PATCH operation, e.g. `active`, `emails[type eq "work"].value`
+/// or `members[value eq "2819c223-7f76-453a-919d-413861904646"]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatchPath {
+    pub attr: String,
+    pub filter: Option<Filter>,
+    pub sub_attr: Option<String>,
+}
+
+impl PatchPath {
+    pub fn parse(path: &str) -> Result<Self, ScimError> {
+        let mut parser = Parser::new(path).map_err(ScimError::InvalidPath)?;
+        let Some(Token::Word(word)) = parser.next() else {
+            return Err(ScimError::InvalidPath(format!(
+                "path ({}) is invalid",
+                path
+            )));
+        };
+
+        if parser.peek() != Some(&Token::LBracket) {
+            parser.expect_end().map_err(ScimError::InvalidPath)?;
+            let attr_path = AttrPath::parse(&word);
+            return Ok(Self {
+                attr: attr_path.attr,
+                filter: None,
+                sub_attr: attr_path.sub_attr,
+            });
+        }
+
+        parser.next();
+        let filter = parser.parse_or().map_err(ScimError::InvalidPath)?;
+        parser
+            .expect(&Token::RBracket)
+            .map_err(ScimError::InvalidPath)?;
+
+        let sub_attr = match parser.next() {
+            Some(Token::Word(sub_attr)) if sub_attr.len() > 1 && sub_attr.starts_with('.') => {
+                Some(sub_attr[1..].to_owned())
+            }
+            None => None,
+            Some(_) => {
+                return Err(ScimError::InvalidPath(format!(
+                    "path ({}) is invalid",
+                    path
+                )))
+            }
+        };
+        parser.expect_end().map_err(ScimError::InvalidPath)?;
+
+        Ok(Self {
+            attr: AttrPath::parse(&word).attr,
+            filter: Some(filter),
+            sub_attr,
+        })
+    }
+
+    pub fn is(&self, attr: &str) -> bool {
+        self.attr.eq_ignore_ascii_case(attr)
+    }
+}
+
+fn get_attr<'a>(resource: &'a Value, name: &str) -> Option<&'a Value> {
+    resource
+        .as_object()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+fn is_present(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => true,
+    }
+}
+
+/// String comparisons are case insensitive, timestamps are compared as points in time.
+fn compare(actual: &Value, op: CompareOp, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::String(actual), Value::String(expected)) => {
+            if let (Ok(actual), Ok(expected)) = (
+                actual.parse::<DateTime<Utc>>(),
+                expected.parse::<DateTime<Utc>>(),
+            ) {
+                if !matches!(op, CompareOp::Co | CompareOp::Sw | CompareOp::Ew) {
+                    return op.matches_ordering(actual.cmp(&expected));
+                }
+            }
+
+            let (actual, expected) = (actual.to_lowercase(), expected.to_lowercase());
+            match op {
+                CompareOp::Co => actual.contains(&expected),
+                CompareOp::Sw => actual.starts_with(&expected),
+                CompareOp::Ew => actual.ends_with(&expected),
+                op => op.matches_ordering(actual.cmp(&expected)),
+            }
+        }
+        (Value::Number(actual), Value::Number(expected)) => {
+            match actual.as_f64().partial_cmp(&expected.as_f64()) {
+                Some(ordering) => op.matches_ordering(ordering),
+                None => false,
+            }
+        }
+        (Value::Bool(actual), Value::Bool(expected)) => match op {
+            CompareOp::Eq => actual == expected,
+            CompareOp::Ne => actual != expected,
+            _ => false,
+        },
+        (actual, Value::Null) => match op {
+            CompareOp::Eq => actual.is_null(),
+            CompareOp::Ne => !actual.is_null(),
+            _ => false,
+        },
+        _ => op == CompareOp::Ne,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '[' => tokens.push(Token::LBracket),
+            ']' => tokens.push(Token::RBracket),
+            '"' => {
+                let mut escaped = false;
+                let mut end = None;
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        '"' => {
+                            end = Some(i);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+
+                let end = end.ok_or("string literal is not terminated")?;
+                let value = serde_json::from_str(&input[start..=end])
+                    .map_err(|_| format!("string literal ({}) is invalid", &input[start..=end]))?;
+                tokens.push(Token::Str(value));
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | '"') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Word(input[start..end].to_owned()));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Filters are parsed, matched and dropped recursively, so their size is limited to keep
+/// crafted filters from overflowing the stack.
+const MAX_DEPTH: usize = 16;
+const MAX_TERMS: usize = 64;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+    terms: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self, String> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            pos: 0,
+            depth: 0,
+            terms: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), String> {
+        match self.next() {
+            Some(ref t) if t == token => Ok(()),
+            Some(t) => Err(format!("expected {:?}, found {:?}", token, t)),
+            None => Err(format!("expected {:?}, found end of input", token)),
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(t) => Err(format!("unexpected {:?}", t)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.next();
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_unary()?;
+        while self.peek_keyword("and") {
+            self.next();
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+
+        Ok(filter)
+    }
+
+    /// Parses the filter up to the closing token, e.g. the contents of parentheses.
+    fn parse_nested(&mut self, close: &Token) -> Result<Filter, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("filter is nested deeper than {} levels", MAX_DEPTH));
+        }
+
+        self.depth += 1;
+        let filter = self.parse_or()?;
+        self.depth -= 1;
+        self.expect(close)?;
+        Ok(filter)
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, String> {
+        self.terms += 1;
+        if self.terms > MAX_TERMS {
+            return Err(format!("filter has more than {} terms", MAX_TERMS));
+        }
+
+        match self.next() {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("not") => {
+                self.expect(&Token::LParen)?;
+                let filter = self.parse_nested(&Token::RParen)?;
+                Ok(Filter::Not(Box::new(filter)))
+            }
+            Some(Token::LParen) => self.parse_nested(&Token::RParen),
+            Some(Token::Word(attr)) if self.peek() == Some(&Token::LBracket) => {
+                self.next();
+                let filter = self.parse_nested(&Token::RBracket)?;
+                Ok(Filter::ValuePath(
+                    AttrPath::parse(&attr).attr,
+                    Box::new(filter),
+                ))
+            }
+            Some(Token::Word(attr)) => {
+                let path = AttrPath::parse(&attr);
+                match self.next() {
+                    Some(Token::Word(op)) if op.eq_ignore_ascii_case("pr") => {
+                        Ok(Filter::Present(path))
+                    }
+                    Some(Token::Word(op)) => {
+                        let op = CompareOp::parse(&op)
+                            .ok_or_else(|| format!("operator ({}) is not supported", op))?;
+                        Ok(Filter::Compare(path, op, self.parse_value()?))
+                    }
+                    _ => Err(format!("expected an operator after ({})", attr)),
+                }
+            }
+            Some(t) => Err(format!("unexpected {:?}", t)),
+            None => Err("unexpected end of input".to_owned()),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(Value::String(s)),
+            Some(Token::Word(w)) => match w.to_ascii_lowercase().as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                "null" => Ok(Value::Null),
+                _ => serde_json::from_str::<serde_json::Number>(&w)
+                    .map(Value::Number)
+                    .map_err(|_| format!("value ({}) is invalid", w)),
+            },
+            Some(t) => Err(format!("expected a value, found {:?}", t)),
+            None => Err("expected a value, found end of input".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn user() -> Value {
+        json!({
+            "userName": "Test_User",
+            "active": true,
+            "emails": [
+                { "value": "test@example.com", "type": "work", "primary": true },
+                { "value": "test@home.example", "type": "home" },
+            ],
+            "meta": { "created": "2025-09-01T10:00:00Z" },
+        })
+    }
+
+    fn matches(filter: &str) -> bool {
+        Filter::parse(filter).unwrap().matches(&user())
+    }
+
+    #[test]
+    fn parse_comparison() {
+        assert_eq!(
+            Filter::parse(r#"userName eq "bjensen""#).unwrap(),
+            Filter::Compare(
+                AttrPath {
+                    attr: "userName".to_owned(),
+                    sub_attr: None,
+                },
+                CompareOp::Eq,
+                json!("bjensen"),
+            )
+        );
+        assert_eq!(
+            Filter::parse(r#"urn:ietf:params:scim:schemas:core:2.0:User:userName EQ "a""#).unwrap(),
+            Filter::parse(r#"userName eq "a""#).unwrap()
+        );
+    }
+
+    #[test]
+    fn reject_invalid_filters() {
+        for filter in [
+            "",
+            "userName",
+            r#"userName eq"#,
+            r#"userName xx "a""#,
+            r#"userName eq "a"#,
+            r#"(userName eq "a""#,
+            r#"userName eq "a" and"#,
+            r#"userName eq "a" extra"#,
+        ] {
+            assert!(
+                matches!(Filter::parse(filter), Err(ScimError::InvalidFilter(_))),
+                "{}",
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn reject_oversized_filters() {
+        let nested =
+            |depth: usize| format!("{}userName pr{}", "not (".repeat(depth), ")".repeat(depth));
+        assert!(Filter::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(matches!(
+            Filter::parse(&nested(100_000)),
+            Err(ScimError::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            Filter::parse(&"emails[".repeat(100_000)),
+            Err(ScimError::InvalidFilter(_))
+        ));
+
+        let terms = |count: usize| vec!["userName pr"; count].join(" and ");
+        assert!(Filter::parse(&terms(MAX_TERMS / 2)).is_ok());
+        assert!(matches!(
+            Filter::parse(&terms(100_000)),
+            Err(ScimError::InvalidFilter(_))
+        ));
+    }
+
+    #[test]
+    fn match_attributes() {
+        assert!(matches(r#"userName eq "test_user""#));
+        assert!(matches(r#"username sw "test""#));
+        assert!(!matches(r#"userName ne "test_user""#));
+        assert!(matches("active eq true"));
+        assert!(matches("emails pr"));
+        assert!(!matches("title pr"));
+        assert!(matches("title eq null"));
+        assert!(matches(r#"emails co "@home""#));
+        assert!(matches(r#"emails.value ew "example.com""#));
+        assert!(matches(r#"meta.created gt "2025-08-31T23:00:00+00:00""#));
+        assert!(!matches(r#"meta.created lt "2025-09-01T10:00:00Z""#));
+    }
+
+    #[test]
+    fn match_logical_expressions() {
+        assert!(matches(r#"userName eq "x" or active eq true"#));
+        assert!(!matches(r#"userName eq "x" and active eq true"#));
+        assert!(matches(r#"not (userName eq "x")"#));
+        assert!(matches(
+            r#"(userName eq "x" or userName eq "test_user") and active eq true"#
+        ));
+        assert!(matches(r#"emails[type eq "work" and value co "@example"]"#));
+        assert!(!matches(r#"emails[type eq "home" and primary eq true]"#));
+    }
+
+    #[test]
+    fn parse_patch_paths() {
+        let path = PatchPath::parse("active").unwrap();
+        assert!(path.is("active") && path.filter.is_none() && path.sub_attr.is_none());
+
+        let path = PatchPath::parse(r#"emails[type eq "work"].value"#).unwrap();
+        assert!(path.is("emails"));
+        assert_eq!(path.sub_attr.as_deref(), Some("value"));
+
+        let path = PatchPath::parse(r#"members[value eq "a" or value eq "b"]"#).unwrap();
+        assert_eq!(
+            path.filter.unwrap().eq_values("value"),
+            Some(vec![&json!("a"), &json!("b")])
+        );
+
+        assert!(PatchPath::parse(r#"members[value eq "a""#).is_err());
+        assert!(PatchPath::parse("").is_err());
+    }
+}
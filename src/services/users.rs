@@ -1,20 +1,29 @@
 use std::sync::Arc;
 
+use uuid::Uuid;
+
 use crate::{
+    database::Database,
     error::ApiError,
-    models::user::{Role, User},
+    models::user::{AuthSource, Role, User},
     routes::auth::AuthPayload,
-    services::auth::{hash_string, AuthError},
+    services::auth::{hash_string, verify_hash, AuthError},
     ApiState,
 };
 
-pub async fn create_user(
-    state: &Arc<ApiState>,
-    payload: AuthPayload,
+/// Validates the payload and builds the user, without storing it.
+pub async fn new_user(
+    db: &dyn Database,
+    payload: &AuthPayload,
     roles: &[Role],
 ) -> Result<User, ApiError> {
-    if state
-        .db
+    if payload.organization.is_some() {
+        return Err(ApiError::BadRequest(
+            "users of an organization are created by its admins or by invitation".to_string(),
+        ));
+    }
+
+    if db
         .users()
         .find_by_username(&payload.username)
         .await?
@@ -23,8 +32,212 @@ pub async fn create_user(
         return Err(AuthError::UsernameAlreadyTaken.into());
     }
 
-    let new_user = User::new(&payload.username, &hash_string(&payload.password), roles);
+    let mut new_user = User::new(&payload.username, &hash_string(&payload.password), roles);
+    if let Some(email) = payload.email.as_deref() {
+        if !email.contains('@') {
+            return Err(ApiError::BadRequest(format!(
+                "email ({}) is invalid",
+                email
+            )));
+        }
+        new_user = new_user.with_email(email);
+    }
+
+    Ok(new_user)
+}
+
+pub async fn create_user(
+    state: &Arc<ApiState>,
+    payload: AuthPayload,
+    roles: &[Role],
+) -> Result<User, ApiError> {
+    let new_user = new_user(state.db.as_ref(), &payload, roles).await?;
     let created_user = state.db.users().create(new_user).await?;
 
     Ok(created_user)
 }
+
+fn user_not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("user ({}) not found", id))
+}
+
+/// Current token version of the user, served from the Redis cache when possible.
+/// Returns `None` if the user does not exist.
+pub async fn token_version(state: &Arc<ApiState>, id: Uuid) -> Result<Option<i64>, ApiError> {
+    if let Some(version) = state.redis.token_versions().find(id).await? {
+        return Ok(Some(version));
+    }
+
+    let Some(user) = state.db.users().find_by_id(id).await? else {
+        return Ok(None);
+    };
+
+    state
+        .redis
+        .token_versions()
+        .fill(id, user.token_version, state.config.token_version_cache_ttl)
+        .await?;
+
+    Ok(Some(user.token_version))
+}
+
+/// Bumps the token version of the user, which invalidates all tokens issued to them so far.
+pub async fn revoke_tokens(state: &Arc<ApiState>, id: Uuid) -> Result<i64, ApiError> {
+    let version = state
+        .db
+        .users()
+        .increment_token_version(id)
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    state
+        .redis
+        .token_versions()
+        .store(id, version, state.config.token_version_cache_ttl)
+        .await?;
+
+    Ok(version)
+}
+
+/// Revokes all tokens of the user and removes their sessions.
+pub async fn force_logout(state: &Arc<ApiState>, id: Uuid) -> Result<u64, ApiError> {
+    revoke_tokens(state, id).await?;
+    let deleted_count = state.db.refresh_tokens().delete_by_sub(id).await?;
+
+    Ok(deleted_count)
+}
+
+pub async fn change_password(
+    state: &Arc<ApiState>,
+    id: Uuid,
+    current_password: &str,
+    new_password: &str,
+) -> Result<User, ApiError> {
+    let user = state
+        .db
+        .users()
+        .find_by_id(id)
+        .await?
+        .ok_or(AuthError::Unauthorized)?;
+
+    if user.auth_source != AuthSource::Local {
+        return Err(ApiError::BadRequest(format!(
+            "password of {} users is managed by an external provider",
+            user.auth_source
+        )));
+    }
+
+    if !verify_hash(&user.password_hash, current_password) {
+        return Err(AuthError::InvalidCredentials.into());
+    }
+
+    let user = state
+        .db
+        .users()
+        .update(User {
+            password_hash: hash_string(new_password),
+            ..user
+        })
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    force_logout(state, id).await?;
+
+    Ok(user)
+}
+
+pub async fn set_roles(state: &Arc<ApiState>, id: Uuid, roles: &[Role]) -> Result<User, ApiError> {
+    if roles.is_empty() {
+        return Err(ApiError::BadRequest(
+            "user must have at least one role".to_owned(),
+        ));
+    }
+
+    let user = state
+        .db
+        .users()
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    let user = state
+        .db
+        .users()
+        .update(User {
+            roles: roles.to_vec(),
+            ..user
+        })
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    // Tokens carry the roles of the user, so the outdated ones have to be revoked.
+    revoke_tokens(state, id).await?;
+
+    Ok(user)
+}
+
+/// Disables the account and revokes all of its tokens and sessions.
+pub async fn disable_user(
+    state: &Arc<ApiState>,
+    id: Uuid,
+    reason: Option<&str>,
+) -> Result<User, ApiError> {
+    let user = state
+        .db
+        .users()
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    let user = state
+        .db
+        .users()
+        .update(user.disable(reason))
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    force_logout(state, id).await?;
+
+    Ok(user)
+}
+
+pub async fn enable_user(state: &Arc<ApiState>, id: Uuid) -> Result<User, ApiError> {
+    let user = state
+        .db
+        .users()
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    let user = state
+        .db
+        .users()
+        .update(user.enable())
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    Ok(user)
+}
+
+/// Soft deletes the user. The row is kept until it is purged after the retention period.
+pub async fn delete_user(state: &Arc<ApiState>, id: Uuid) -> Result<User, ApiError> {
+    force_logout(state, id).await?;
+
+    let user = state
+        .db
+        .users()
+        .soft_delete(id)
+        .await?
+        .ok_or_else(|| user_not_found(id))?;
+
+    Ok(user)
+}
+
+/// Permanently removes users soft deleted longer than the configured retention period ago.
+pub async fn purge_deleted_users(state: &Arc<ApiState>) -> Result<u64, ApiError> {
+    let before =
+        chrono::Utc::now() - chrono::Duration::seconds(state.config.deleted_user_retention);
+    let purged_count = state.db.users().purge_deleted(before).await?;
+
+    Ok(purged_count)
+}
@@ -0,0 +1,515 @@
+use std::{fmt::Write, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use ring::hmac;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    database::Database,
+    error::ApiError,
+    models::{
+        user::{User, UserDto},
+        webhook::{
+            event_type, WebhookDelivery, WebhookMessage, WebhookStatus, WebhookSubscription,
+        },
+    },
+    services::auth::generate_secret,
+    ApiState,
+};
+
+pub const ID_HEADER: &str = "x-webhook-id";
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// Maximum number of messages taken from the outbox in a single pass.
+const BATCH_SIZE: usize = 20;
+/// Upper bound of the delay between retries, in seconds.
+const MAX_RETRY_DELAY: i64 = 86400;
+
+fn subscription_not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("webhook subscription ({}) not found", id))
+}
+
+fn validate_url(url: &str) -> Result<(), ApiError> {
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+        _ => Err(ApiError::BadRequest(format!(
+            "webhook url ({}) must be an absolute http(s) url",
+            url
+        ))),
+    }
+}
+
+fn validate_event_types(event_types: &[String]) -> Result<Vec<String>, ApiError> {
+    if event_types.is_empty() {
+        return Err(ApiError::BadRequest(
+            "at least one event type is required".to_owned(),
+        ));
+    }
+
+    let mut validated: Vec<String> = Vec::with_capacity(event_types.len());
+    for e in event_types {
+        if !event_type::is_known(e) {
+            return Err(ApiError::BadRequest(format!("unknown event type ({})", e)));
+        }
+        if !validated.contains(e) {
+            validated.push(e.clone());
+        }
+    }
+
+    Ok(validated)
+}
+
+/// Creates a subscription and returns it along with its signing secret.
+/// The secret is only returned here and when it is rotated.
+pub async fn create_subscription(
+    state: &Arc<ApiState>,
+    url: &str,
+    event_types: &[String],
+) -> Result<(WebhookSubscription, String), ApiError> {
+    validate_url(url)?;
+    let event_types = validate_event_types(event_types)?;
+
+    let secret = generate_secret();
+    let subscription = state
+        .db
+        .webhooks()
+        .create(WebhookSubscription::new(url, &secret, &event_types))
+        .await?;
+
+    Ok((subscription, secret))
+}
+
+pub async fn update_subscription(
+    state: &Arc<ApiState>,
+    id: Uuid,
+    url: Option<&str>,
+    event_types: Option<&[String]>,
+    active: Option<bool>,
+) -> Result<WebhookSubscription, ApiError> {
+    let mut subscription = state
+        .db
+        .webhooks()
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| subscription_not_found(id))?;
+
+    if let Some(url) = url {
+        validate_url(url)?;
+        subscription.url = url.to_owned();
+    }
+    if let Some(event_types) = event_types {
+        subscription.event_types = validate_event_types(event_types)?;
+    }
+    if let Some(active) = active {
+        subscription.active = active;
+    }
+
+    state
+        .db
+        .webhooks()
+        .update(subscription)
+        .await?
+        .ok_or_else(|| subscription_not_found(id))
+}
+
+pub async fn rotate_secret(state: &Arc<ApiState>, id: Uuid) -> Result<String, ApiError> {
+    let mut subscription = state
+        .db
+        .webhooks()
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| subscription_not_found(id))?;
+
+    let secret = generate_secret();
+    subscription.secret = secret.clone();
+    state
+        .db
+        .webhooks()
+        .update(subscription)
+        .await?
+        .ok_or_else(|| subscription_not_found(id))?;
+
+    Ok(secret)
+}
+
+/// Stores the event in the outbox, once for every active subscription interested in it.
+pub async fn enqueue(
+    db: &dyn Database,
+    event_type: &str,
+    data: serde_json::Value,
+) -> Result<u64, ApiError> {
+    let subscriptions = db.webhooks().find_by_event_type(event_type).await?;
+    if subscriptions.is_empty() {
+        return Ok(0);
+    }
+
+    // Every subscriber gets the same event id, which they can use to deduplicate deliveries.
+    let payload = serde_json::json!({
+        "id": Uuid::new_v4(),
+        "type": event_type,
+        "created_at": Utc::now(),
+        "data": data,
+    });
+    let messages = subscriptions
+        .iter()
+        .map(|s| WebhookMessage::new(s.id, event_type, payload.clone()))
+        .collect();
+
+    db.webhook_outbox().enqueue(messages).await
+}
+
+/// Publishes the event to the webhook subscribers. Failures are only logged, so that
+/// the request which triggered the event is not affected by them.
+pub async fn publish(state: &Arc<ApiState>, event_type: &str, data: serde_json::Value) {
+    if let Err(e) = enqueue(state.db.as_ref(), event_type, data).await {
+        tracing::error!("failed to enqueue webhook event ({}): {}", event_type, e);
+    }
+}
+
+pub async fn publish_user_event(state: &Arc<ApiState>, event_type: &str, user: &User) {
+    publish(
+        state,
+        event_type,
+        serde_json::json!({ "user": UserDto::from(user) }),
+    )
+    .await;
+}
+
+/// Signature of the payload, sent in the signature header as `sha256=<hex digest>`.
+/// The timestamp is part of the signed content, so that receivers can reject replayed requests.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let mut ctx = hmac::Context::with_key(&key);
+    ctx.update(timestamp.to_string().as_bytes());
+    ctx.update(b".");
+    ctx.update(body);
+
+    let tag = ctx.sign();
+    let mut signature = String::from("sha256=");
+    for byte in tag.as_ref() {
+        let _ = write!(signature, "{:02x}", byte);
+    }
+
+    signature
+}
+
+/// Delay before the next attempt, doubled with every failed attempt.
+pub fn retry_delay(base_delay: i64, attempts: i32) -> chrono::Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    let delay = base_delay.saturating_mul(2_i64.pow(exponent));
+
+    chrono::Duration::seconds(delay.min(MAX_RETRY_DELAY))
+}
+
+/// Sends the message to the subscriber. Any response other than 2xx counts as a failure.
+pub async fn deliver(
+    http: &reqwest::Client,
+    subscription: &WebhookSubscription,
+    message: &WebhookMessage,
+) -> WebhookDelivery {
+    let body = serde_json::to_vec(&message.payload).expect("payload should always serialize");
+    let timestamp = Utc::now().timestamp();
+    let signature = sign(&subscription.secret, timestamp, &body);
+
+    let started_at = std::time::Instant::now();
+    let res = http
+        .post(&subscription.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(ID_HEADER, message.id.to_string())
+        .header(EVENT_HEADER, &message.event_type)
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await;
+    let duration_ms = started_at.elapsed().as_millis() as i64;
+
+    let (status_code, error) = match res {
+        Ok(res) if res.status().is_success() => (Some(res.status().as_u16() as i32), None),
+        Ok(res) => (
+            Some(res.status().as_u16() as i32),
+            Some(format!("unexpected status code ({})", res.status())),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    WebhookDelivery {
+        id: Uuid::new_v4(),
+        message_id: message.id,
+        subscription_id: subscription.id,
+        attempt: message.attempts + 1,
+        status_code,
+        error,
+        duration_ms,
+        created_at: Utc::now(),
+    }
+}
+
+async fn process_message(
+    db: &dyn Database,
+    http: &reqwest::Client,
+    config: &Config,
+    mut message: WebhookMessage,
+) -> Result<(), ApiError> {
+    let subscription = db
+        .webhooks()
+        .find_by_id(message.subscription_id)
+        .await?
+        .filter(|s| s.active);
+
+    let Some(subscription) = subscription else {
+        // Events of deactivated subscriptions are dropped instead of piling up.
+        message.status = WebhookStatus::Failed;
+        db.webhook_outbox().update(message).await?;
+        return Ok(());
+    };
+
+    let delivery = deliver(http, &subscription, &message).await;
+    let delivered = delivery.is_success();
+    if let Some(error) = delivery.error.as_deref() {
+        tracing::warn!(
+            "webhook message ({}) delivery attempt ({}) failed: {}",
+            message.id,
+            delivery.attempt,
+            error
+        );
+    }
+
+    message.attempts = delivery.attempt;
+    db.webhook_outbox().create_delivery(delivery).await?;
+
+    if delivered {
+        message.status = WebhookStatus::Delivered;
+    } else if message.attempts >= config.webhook_max_attempts {
+        message.status = WebhookStatus::Failed;
+    } else {
+        message.next_attempt_at =
+            Utc::now() + retry_delay(config.webhook_retry_base_delay, message.attempts);
+    }
+
+    db.webhook_outbox().update(message).await?;
+    Ok(())
+}
+
+/// Delivers the messages which are due. Returns the number of processed messages.
+pub async fn process_outbox(
+    db: &Arc<dyn Database>,
+    http: &reqwest::Client,
+    config: &Config,
+) -> Result<usize, ApiError> {
+    // The lease outlives the delivery, so a message is only picked up again
+    // if the process died before recording the result.
+    let lease_until =
+        Utc::now() + chrono::Duration::seconds(2 * config.webhook_request_timeout as i64);
+    let messages = db
+        .webhook_outbox()
+        .claim_due(lease_until, BATCH_SIZE)
+        .await?;
+    let count = messages.len();
+
+    let mut tasks = JoinSet::new();
+    for message in messages {
+        let (db, http, config) = (Arc::clone(db), http.clone(), config.clone());
+        tasks.spawn(async move {
+            let message_id = message.id;
+            if let Err(e) = process_message(db.as_ref(), &http, &config, message).await {
+                tracing::error!("failed to process webhook message ({}): {}", message_id, e);
+            }
+        });
+    }
+    tasks.join_all().await;
+
+    Ok(count)
+}
+
+pub fn http_client(config: &Config) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.webhook_request_timeout))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("webhook http client should build")
+}
+
+/// Polls the outbox in the background until the returned handle is aborted.
+pub fn spawn_dispatcher(db: Arc<dyn Database>, config: Config) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let http = http_client(&config);
+        let mut interval = tokio::time::interval(Duration::from_secs(config.webhook_poll_interval));
+
+        loop {
+            interval.tick().await;
+            loop {
+                match process_outbox(&db, &http, &config).await {
+                    Ok(count) if count == BATCH_SIZE => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        tracing::error!("failed to process webhook outbox: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::database::mock::MockDatabase;
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    /// Starts a local receiver which answers every request with the given status code.
+    async fn start_receiver(status: StatusCode) -> (String, Received) {
+        let received: Received = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    move |State(received): State<Received>, headers: HeaderMap, body: Bytes| async move {
+                        received.lock().await.push((headers, body));
+                        status
+                    },
+                ),
+            )
+            .with_state(Arc::clone(&received));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{}/hook", addr), received)
+    }
+
+    async fn subscribe(db: &Arc<dyn Database>, url: &str) -> WebhookSubscription {
+        db.webhooks()
+            .create(WebhookSubscription::new(
+                url,
+                "test_secret",
+                &[event_type::USER_REGISTERED.to_owned()],
+            ))
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn retry_delay_doubles() {
+        assert_eq!(retry_delay(30, 1), chrono::Duration::seconds(30));
+        assert_eq!(retry_delay(30, 2), chrono::Duration::seconds(60));
+        assert_eq!(retry_delay(30, 4), chrono::Duration::seconds(240));
+        assert_eq!(
+            retry_delay(30, 100),
+            chrono::Duration::seconds(MAX_RETRY_DELAY)
+        );
+    }
+
+    #[test]
+    fn signature_depends_on_timestamp() {
+        let signature = sign("test_secret", 1700000000, b"{}");
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_eq!(signature, sign("test_secret", 1700000000, b"{}"));
+        assert_ne!(signature, sign("test_secret", 1700000001, b"{}"));
+        assert_ne!(signature, sign("other_secret", 1700000000, b"{}"));
+    }
+
+    #[tokio::test]
+    async fn deliver_signed_event() {
+        let db: Arc<dyn Database> = MockDatabase::new();
+        let config = Config::default();
+        let (url, received) = start_receiver(StatusCode::NO_CONTENT).await;
+        let subscription = subscribe(&db, &url).await;
+
+        let enqueued = enqueue(
+            db.as_ref(),
+            event_type::USER_REGISTERED,
+            serde_json::json!({ "username": "test_user" }),
+        )
+        .await;
+        assert_eq!(enqueued.unwrap(), 1);
+
+        let processed = process_outbox(&db, &http_client(&config), &config).await;
+        assert_eq!(processed.unwrap(), 1);
+
+        let received = received.lock().await;
+        assert_eq!(received.len(), 1);
+        let (headers, body) = &received[0];
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            sign("test_secret", timestamp, body)
+        );
+        assert_eq!(headers[EVENT_HEADER], event_type::USER_REGISTERED);
+
+        let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload["type"], event_type::USER_REGISTERED);
+        assert_eq!(payload["data"]["username"], "test_user");
+
+        let message_id: Uuid = headers[ID_HEADER].to_str().unwrap().parse().unwrap();
+        let message = db.webhook_outbox().find_by_id(message_id).await.unwrap();
+        assert_eq!(message.unwrap().status, WebhookStatus::Delivered);
+
+        let deliveries = db
+            .webhook_outbox()
+            .find_deliveries(subscription.id, 10)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status_code, Some(204));
+    }
+
+    #[tokio::test]
+    async fn retry_failed_delivery() {
+        let db: Arc<dyn Database> = MockDatabase::new();
+        let config = Config {
+            webhook_max_attempts: 2,
+            webhook_retry_base_delay: 0,
+            ..Config::default()
+        };
+        let (url, received) = start_receiver(StatusCode::INTERNAL_SERVER_ERROR).await;
+        let subscription = subscribe(&db, &url).await;
+
+        let _ = enqueue(
+            db.as_ref(),
+            event_type::USER_REGISTERED,
+            serde_json::json!({}),
+        )
+        .await;
+        let http = http_client(&config);
+
+        assert_eq!(process_outbox(&db, &http, &config).await.unwrap(), 1);
+        assert_eq!(process_outbox(&db, &http, &config).await.unwrap(), 1);
+        assert_eq!(process_outbox(&db, &http, &config).await.unwrap(), 0);
+        assert_eq!(received.lock().await.len(), 2);
+
+        let deliveries = db
+            .webhook_outbox()
+            .find_deliveries(subscription.id, 10)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert!(deliveries.iter().all(|d| !d.is_success()));
+
+        let message = db
+            .webhook_outbox()
+            .find_by_id(deliveries[0].message_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.status, WebhookStatus::Failed);
+        assert_eq!(message.attempts, 2);
+    }
+}